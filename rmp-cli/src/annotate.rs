@@ -0,0 +1,96 @@
+//! Annotated hex dump, which explains MessagePack data marker by marker.
+
+use std::io::Write;
+use std::str;
+
+use rmp::decode::{self, Token, TokenValue};
+
+/// The number of bytes shown per line.
+const BYTES_PER_LINE: usize = 8;
+
+/// The maximum number of characters of a string shown in its description.
+const MAX_STR_CHARS: usize = 40;
+
+/// The maximum nesting depth reflected by indentation. Deeper tokens are indented the same, so
+/// the output stays linear in the input size.
+const MAX_INDENT_DEPTH: usize = 32;
+
+/// Writes an annotated hex dump of all values in the buffer.
+///
+/// Malformed data is dumped up to and including the first malformed byte before failing.
+pub fn annotate<W: Write>(buf: &[u8], wr: &mut W) -> Result<(), String> {
+    for token in decode::annotate(buf) {
+        match token {
+            Ok(token) => {
+                write_lines(wr, token.offset, token.bytes, token.depth, &describe(&token))?
+            }
+            Err(err) => {
+                write_lines(wr, err.offset, err.bytes, err.depth, "malformed")?;
+                return Err(err.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe(token: &Token) -> String {
+    let name = token.marker.name();
+    match token.value {
+        TokenValue::Nil => name.to_owned(),
+        TokenValue::Boolean(..) => name.to_owned(),
+        TokenValue::Uint(val) => format!("{}: {}", name, val),
+        TokenValue::Sint(val) => format!("{}: {}", name, val),
+        TokenValue::F32(val) => format!("{}: {:?}", name, val),
+        TokenValue::F64(val) => format!("{}: {:?}", name, val),
+        TokenValue::Str(data) => {
+            match str::from_utf8(data) {
+                Ok(val) => format!("{}, {} bytes: {}", name, data.len(), quote(val)),
+                Err(..) => format!("{}, {} bytes: invalid UTF-8", name, data.len()),
+            }
+        }
+        TokenValue::Bin(data) => format!("{}, {} bytes", name, data.len()),
+        TokenValue::Array(len) => format!("{}, {} elements", name, len),
+        TokenValue::Map(len) => format!("{}, {} entries", name, len),
+        TokenValue::Ext(ty, data) => {
+            let ext = if ty == -1 { " (timestamp)" } else { "" };
+            format!("{}, type {}{}, {} bytes", name, ty, ext, data.len())
+        }
+    }
+}
+
+
+fn write_lines<W: Write>(wr: &mut W, pos: usize, data: &[u8], depth: usize, desc: &str)
+    -> Result<(), String>
+{
+    // Keep a line for empty data, e.g. when the input ends where a value is expected.
+    let mut chunks: Vec<&[u8]> = data.chunks(BYTES_PER_LINE).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+
+    for (idx, chunk) in chunks.into_iter().enumerate() {
+        let hex = chunk.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+        let res = if idx == 0 {
+            writeln!(wr, "{:08x}  {:<width$}  {:indent$}{}",
+                     pos,
+                     hex,
+                     "",
+                     desc,
+                     width = 3 * BYTES_PER_LINE - 1,
+                     indent = 2 * depth.min(MAX_INDENT_DEPTH))
+        } else {
+            writeln!(wr, "{:08x}  {}", pos + idx * BYTES_PER_LINE, hex)
+        };
+        res.map_err(|err| format!("failed to write the output: {}", err))?;
+    }
+    Ok(())
+}
+
+fn quote(val: &str) -> String {
+    if val.chars().count() > MAX_STR_CHARS {
+        let val: String = val.chars().take(MAX_STR_CHARS).collect();
+        format!("{:?}...", val)
+    } else {
+        format!("{:?}", val)
+    }
+}
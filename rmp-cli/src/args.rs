@@ -0,0 +1,92 @@
+//! Minimal command-line argument parsing.
+
+use std::str::FromStr;
+
+/// Parsed arguments of a subcommand: options with values, boolean flags and positional arguments.
+#[derive(Debug)]
+pub struct Args {
+    opts: Vec<(String, String)>,
+    flags: Vec<String>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    /// Parses the arguments, accepting only the given options, which take a value either as the
+    /// next argument or after `=`, and the given flags. Everything after `--` is positional.
+    pub fn parse(args: &[String], opts: &[&str], flags: &[&str]) -> Result<Args, String> {
+        let mut res = Args {
+            opts: Vec::new(),
+            flags: Vec::new(),
+            positional: Vec::new(),
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                res.positional.extend(iter.cloned());
+                break;
+            }
+
+            if !arg.starts_with("--") {
+                res.positional.push(arg.clone());
+                continue;
+            }
+
+            let (name, value) = match arg.find('=') {
+                Some(idx) => (&arg[2..idx], Some(arg[idx + 1..].to_owned())),
+                None => (&arg[2..], None),
+            };
+
+            if opts.contains(&name) {
+                let value = match value {
+                    Some(value) => value,
+                    None => {
+                        iter.next()
+                            .cloned()
+                            .ok_or_else(|| format!("option --{} requires a value", name))?
+                    }
+                };
+                res.opts.push((name.to_owned(), value));
+            } else if flags.contains(&name) && value.is_none() {
+                res.flags.push(name.to_owned());
+            } else {
+                return Err(format!("unexpected argument {}", arg));
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Returns the last value of the option.
+    pub fn opt(&self, name: &str) -> Option<&str> {
+        self.opts.iter().rev().find(|opt| opt.0 == name).map(|opt| &opt.1[..])
+    }
+
+    /// Parses the last value of the option.
+    pub fn parse_opt<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.opt(name) {
+            Some(value) => {
+                value.parse()
+                    .map(Some)
+                    .map_err(|_| format!("invalid value {:?} of option --{}", value, name))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns whether the flag is set.
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|n| n == name)
+    }
+
+    /// Returns the positional arguments, failing if there are less than `min` or more than `max`.
+    pub fn positional(&self, min: usize, max: usize) -> Result<&[String], String> {
+        if self.positional.len() < min {
+            Err("missing argument".to_owned())
+        } else if self.positional.len() > max {
+            Err(format!("unexpected argument {}", self.positional[max]))
+        } else {
+            Ok(&self.positional)
+        }
+    }
+}
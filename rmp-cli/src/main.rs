@@ -0,0 +1,511 @@
+//! Command-line tool for inspecting and converting MessagePack data.
+//!
+//! Every command reads a stream of concatenated MessagePack values (or JSON documents for
+//! `from-json`) from the given file or from the standard input if the file is omitted or `-`.
+
+extern crate rmp;
+extern crate rmpv;
+
+mod annotate;
+mod args;
+mod walk;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::process;
+
+use rmpv::Value;
+use rmpv::decode::{read_value, read_value_ref};
+use rmpv::decode::value::Error as DecodeError;
+use rmpv::json::{self, BinaryPolicy, ExtPolicy, FloatPolicy, IntPolicy, KeyPolicy, ToJson,
+                 TranscodeError};
+use rmpv::profile::Profiler;
+use rmpv::schema::{Inference, Schema};
+
+use args::Args;
+use walk::{Limits, Reader};
+
+const USAGE: &str = "\
+Usage: rmp-cli COMMAND [OPTIONS] [FILE]
+
+Reads concatenated MessagePack values from FILE or from the standard input if FILE is omitted
+or `-`.
+
+Commands:
+    dump                    Pretty prints each value in text notation.
+        --max-depth N       The maximum nesting depth, 512 by default.
+    to-json                 Converts each value into JSON, one document per line.
+        --binary POLICY     base64 (default), array or error
+        --ext POLICY        tagged (default) or error
+        --keys POLICY       stringify (default) or error
+        --floats POLICY     null (default), string or error
+        --ints POLICY       exact (default), string or error
+        --quiet             Does not report lossy conversions.
+        --max-depth N       The maximum nesting depth, 512 by default.
+    from-json               Converts concatenated JSON documents into MessagePack.
+        --output FILE       Writes into FILE instead of the standard output.
+    validate                Checks that values are well-formed without decoding them.
+        --max-depth N       The maximum nesting depth, 512 by default.
+        --max-len N         The maximum length of strings, binaries, arrays and maps.
+        --max-size N        The maximum size of an encoded value in bytes.
+        --schema FILE       Checks that values conform to the schema in FILE, written in text
+                            notation, e.g. as printed by `schema --value`, and prints all
+                            violations.
+    annotate                Prints a hex dump explaining each marker.
+    profile                 Prints encoded sizes, counts and markers of values by path.
+        --max-depth N       The maximum nesting depth, 512 by default.
+    schema                  Prints a schema inferred from the values by path.
+        --max-enum N        The maximum number of distinct strings reported as an enum.
+        --max-depth N       The maximum nesting depth, 512 by default.
+        --value             Prints the schema as a value in text notation.
+    query EXPR              Prints values matching a JSON pointer, if EXPR starts with `/`
+                            or is empty, or a query expression otherwise.
+        --max-depth N       The maximum nesting depth, 512 by default.
+    split                   Prints the offset and length of each value.
+        --max-depth N       The maximum nesting depth, 512 by default.
+        --prefix PREFIX     Writes each value into PREFIX000000.msgpack and so on instead.
+    help                    Prints this message.
+";
+
+// The default maximum nesting depth, which keeps recursive decoding from overflowing the stack.
+const DEFAULT_MAX_DEPTH: usize = 512;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(err) = run(&args) {
+        let _ = writeln!(io::stderr(), "rmp-cli: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (cmd, args) = match args.split_first() {
+        Some((cmd, args)) => (&cmd[..], args),
+        None => return Err(format!("missing command\n\n{}", USAGE)),
+    };
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    match cmd {
+        "dump" => {
+            let args = Args::parse(args, &["max-depth"], &[])?;
+            dump(&args, &mut open(&args, 0)?, &mut out)?;
+        }
+        "to-json" => {
+            let opts = ["binary", "ext", "keys", "floats", "ints", "max-depth"];
+            let args = Args::parse(args, &opts, &["quiet"])?;
+            to_json(&args, &mut open(&args, 0)?, &mut out)?;
+        }
+        "from-json" => {
+            let args = Args::parse(args, &["output"], &[])?;
+            from_json(&args, &mut open(&args, 0)?, &mut out)?;
+        }
+        "validate" => {
+            let args = Args::parse(args, &["max-depth", "max-len", "max-size", "schema"], &[])?;
+            validate(&args, &mut open(&args, 0)?, &mut out)?;
+        }
+        "annotate" => {
+            let args = Args::parse(args, &[], &[])?;
+            let mut buf = Vec::new();
+            open(&args, 0)?.read_to_end(&mut buf).map_err(|err| read_error(&err))?;
+            annotate::annotate(&buf, &mut out)?;
+        }
+        "profile" => {
+            let args = Args::parse(args, &["max-depth"], &[])?;
+            profile(&args, &mut open(&args, 0)?, &mut out)?;
+        }
+        "schema" => {
+            let args = Args::parse(args, &["max-enum", "max-depth"], &["value"])?;
+            schema(&args, &mut open(&args, 0)?, &mut out)?;
+        }
+        "query" => {
+            let args = Args::parse(args, &["max-depth"], &[])?;
+            let expr = args.positional(1, 2)?[0].clone();
+            query(&args, &expr, &mut open(&args, 1)?, &mut out)?;
+        }
+        "split" => {
+            let args = Args::parse(args, &["prefix", "max-depth"], &[])?;
+            split(&args, &mut open(&args, 0)?, &mut out)?;
+        }
+        "help" | "--help" | "-h" => {
+            out.write_all(USAGE.as_bytes()).map_err(|err| write_error(&err))?;
+        }
+        cmd => return Err(format!("unknown command {:?}\n\n{}", cmd, USAGE)),
+    }
+
+    out.flush().map_err(|err| write_error(&err))
+}
+
+/// Opens the input given as the positional argument after `skip` others.
+fn open(args: &Args, skip: usize) -> Result<Box<dyn BufRead>, String> {
+    let path = args.positional(skip, skip + 1)?.get(skip).map(|path| &path[..]);
+
+    match path {
+        None | Some("-") => Ok(Box::new(BufReader::new(io::stdin()))),
+        Some(path) => {
+            let file = File::open(path)
+                .map_err(|err| format!("failed to open {}: {}", path, err))?;
+            Ok(Box::new(BufReader::new(file)))
+        }
+    }
+}
+
+/// Returns the maximum nesting depth given by the `--max-depth` option.
+fn max_depth(args: &Args) -> Result<usize, String> {
+    Ok(args.parse_opt("max-depth")?.unwrap_or(DEFAULT_MAX_DEPTH))
+}
+
+/// Returns limits with the maximum nesting depth given by the `--max-depth` option.
+fn depth_limits(args: &Args) -> Result<Limits, String> {
+    Ok(Limits {
+        max_depth: max_depth(args)?,
+        max_len: None,
+        max_size: None,
+    })
+}
+
+/// Reads the next value after checking that it is well-formed and respects the limits, since
+/// decoding recurses into nested arrays and maps.
+fn read_checked<R: BufRead>(rd: &mut Reader<R>, limits: &Limits) -> Result<Value, String> {
+    let pos = rd.pos();
+    rd.start_copy();
+    walk::walk(rd, limits)?;
+    let buf = rd.take_copy();
+    read_value(&mut &buf[..]).map_err(|err| value_error(pos, &err))
+}
+
+fn dump<R: BufRead, W: Write>(args: &Args, rd: &mut R, wr: &mut W) -> Result<(), String> {
+    let limits = depth_limits(args)?;
+
+    let mut rd = Reader::new(rd);
+    while !rd.is_eof().map_err(|err| read_error(&err))? {
+        let val = read_checked(&mut rd, &limits)?;
+        writeln!(wr, "{:#}", val).map_err(|err| write_error(&err))?;
+    }
+    Ok(())
+}
+
+fn schema<R: BufRead, W: Write>(args: &Args, rd: &mut R, wr: &mut W) -> Result<(), String> {
+    let mut inference = Inference::new();
+    if let Some(max) = args.parse_opt("max-enum")? {
+        inference.set_max_enum_values(max);
+    }
+    let limits = depth_limits(args)?;
+
+    let mut rd = Reader::new(rd);
+    while !rd.is_eof().map_err(|err| read_error(&err))? {
+        let val = read_checked(&mut rd, &limits)?;
+        inference.add(&val);
+    }
+
+    let res = if args.flag("value") {
+        writeln!(wr, "{:#}", inference.to_value())
+    } else {
+        write!(wr, "{}", inference)
+    };
+    res.map_err(|err| write_error(&err))
+}
+
+fn to_json<R: BufRead, W: Write>(args: &Args, rd: &mut R, wr: &mut W) -> Result<(), String> {
+    let mut conv = ToJson::new();
+    if let Some(policy) = args.opt("binary") {
+        conv.set_binary_policy(match policy {
+            "base64" => BinaryPolicy::Base64,
+            "array" => BinaryPolicy::Array,
+            "error" => BinaryPolicy::Error,
+            _ => return Err(invalid_policy("binary", policy)),
+        });
+    }
+    if let Some(policy) = args.opt("ext") {
+        conv.set_ext_policy(match policy {
+            "tagged" => ExtPolicy::Tagged,
+            "error" => ExtPolicy::Error,
+            _ => return Err(invalid_policy("ext", policy)),
+        });
+    }
+    if let Some(policy) = args.opt("keys") {
+        conv.set_key_policy(match policy {
+            "stringify" => KeyPolicy::Stringify,
+            "error" => KeyPolicy::Error,
+            _ => return Err(invalid_policy("keys", policy)),
+        });
+    }
+    if let Some(policy) = args.opt("floats") {
+        conv.set_float_policy(match policy {
+            "null" => FloatPolicy::Null,
+            "string" => FloatPolicy::String,
+            "error" => FloatPolicy::Error,
+            _ => return Err(invalid_policy("floats", policy)),
+        });
+    }
+    if let Some(policy) = args.opt("ints") {
+        conv.set_int_policy(match policy {
+            "exact" => IntPolicy::Exact,
+            "string" => IntPolicy::String,
+            "error" => IntPolicy::Error,
+            _ => return Err(invalid_policy("ints", policy)),
+        });
+    }
+    let max_depth = max_depth(args)?;
+    conv.set_max_depth(max_depth);
+    let quiet = args.flag("quiet");
+
+    let mut rd = Reader::new(rd);
+    let mut index = 0;
+    while !rd.is_eof().map_err(|err| read_error(&err))? {
+        let pos = rd.pos();
+        conv.transcode_with(&mut rd, wr, |loss| {
+            if !quiet {
+                let _ = writeln!(io::stderr(),
+                                 "rmp-cli: lossy conversion of {} at {:?} in value {}",
+                                 loss.kind,
+                                 loss.path,
+                                 index);
+            }
+        }).map_err(|err| match err {
+            TranscodeError::DepthLimitExceeded => depth_error(pos, max_depth),
+            err => format!("value at offset {}: {}", pos, err),
+        })?;
+        writeln!(wr).map_err(|err| write_error(&err))?;
+        index += 1;
+    }
+    Ok(())
+}
+
+fn profile<R: BufRead, W: Write>(args: &Args, rd: &mut R, wr: &mut W) -> Result<(), String> {
+    let max_depth = max_depth(args)?;
+    let mut profiler = Profiler::new();
+    profiler.set_max_depth(max_depth);
+
+    profiler.profile_stream(rd).map_err(|err| match err {
+        DecodeError::DepthLimitExceeded => depth_error(profiler.bytes(), max_depth),
+        err => value_error(profiler.bytes(), &err),
+    })?;
+    write!(wr, "{}", profiler).map_err(|err| write_error(&err))
+}
+
+fn from_json<R: BufRead, W: Write>(args: &Args, rd: &mut R, wr: &mut W) -> Result<(), String> {
+    match args.opt("output") {
+        Some(path) => {
+            let file = File::create(path)
+                .map_err(|err| format!("failed to create {}: {}", path, err))?;
+            let mut file = BufWriter::new(file);
+            from_json_into(rd, &mut file)?;
+            file.flush().map_err(|err| write_error(&err))
+        }
+        None => {
+            // The standard output is not seekable, so buffer the result.
+            let mut buf = Cursor::new(Vec::new());
+            from_json_into(rd, &mut buf)?;
+            wr.write_all(buf.get_ref()).map_err(|err| write_error(&err))
+        }
+    }
+}
+
+fn from_json_into<R, W>(rd: &mut R, wr: &mut W) -> Result<(), String>
+    where R: BufRead,
+          W: Write + io::Seek
+{
+    let mut index = 0;
+    while !skip_whitespace(rd).map_err(|err| read_error(&err))? {
+        let mut doc = Vec::new();
+        read_json(rd, &mut doc).map_err(|err| read_error(&err))?;
+        json::transcode_from_json(&mut &doc[..], wr)
+            .map_err(|err| format!("JSON document {}: {}", index, err))?;
+        index += 1;
+    }
+    Ok(())
+}
+
+/// Skips whitespace, returning whether the input is exhausted.
+fn skip_whitespace<R: BufRead>(rd: &mut R) -> io::Result<bool> {
+    loop {
+        let (len, eof) = {
+            let buf = rd.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(true);
+            }
+            let len = buf.iter().take_while(|b| b" \t\r\n".contains(b)).count();
+            (len, len < buf.len())
+        };
+        rd.consume(len);
+        if eof {
+            return Ok(false);
+        }
+    }
+}
+
+/// Reads a single JSON document, tracking nesting and strings only to find where it ends.
+/// Malformed documents are left for the JSON parser to report.
+fn read_json<R: BufRead>(rd: &mut R, doc: &mut Vec<u8>) -> io::Result<()> {
+    let mut depth = 0usize;
+    let mut in_str = false;
+    let mut escaped = false;
+
+    for byte in rd.bytes() {
+        let byte = byte?;
+
+        if in_str {
+            doc.push(byte);
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_str = false;
+                if depth == 0 {
+                    return Ok(());
+                }
+            }
+            continue;
+        }
+
+        if b" \t\r\n".contains(&byte) {
+            if depth == 0 {
+                return Ok(());
+            }
+            doc.push(byte);
+            continue;
+        }
+
+        doc.push(byte);
+        match byte {
+            b'"' => in_str = true,
+            b'[' | b'{' => depth += 1,
+            b']' | b'}' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn validate<R: BufRead, W: Write>(args: &Args, rd: &mut R, wr: &mut W) -> Result<(), String> {
+    let limits = Limits {
+        max_depth: max_depth(args)?,
+        max_len: args.parse_opt("max-len")?,
+        max_size: args.parse_opt("max-size")?,
+    };
+
+    let schema = match args.opt("schema") {
+        Some(path) => Some(read_schema(path)?),
+        None => None,
+    };
+
+    let mut rd = Reader::new(rd);
+    let mut count = 0;
+    let mut invalid = 0;
+    while !rd.is_eof().map_err(|err| read_error(&err))? {
+        let pos = rd.pos();
+        rd.start_copy();
+        walk::walk(&mut rd, &limits)?;
+        let buf = rd.take_copy();
+
+        if let Some(ref schema) = schema {
+            let val = read_value_ref(&mut &buf[..])
+                .map_err(|err| format!("failed to decode the value at offset {}: {:?}", pos, err))?;
+            if let Err(violations) = schema.validate_ref(&val) {
+                invalid += 1;
+                for violation in violations {
+                    writeln!(wr, "value {} at offset {}: {}", count, pos, violation)
+                        .map_err(|err| write_error(&err))?;
+                }
+            }
+        }
+        count += 1;
+    }
+
+    if invalid > 0 {
+        return Err(format!("{} of {} values do not conform to the schema", invalid, count));
+    }
+    writeln!(wr, "valid: {} values, {} bytes", count, rd.pos()).map_err(|err| write_error(&err))
+}
+
+fn read_schema(path: &str) -> Result<Schema, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|err| format!("failed to read {}: {}", path, err))?;
+
+    let val: Value = text.parse().map_err(|err| format!("invalid schema in {}: {}", path, err))?;
+    Schema::from_value(&val).map_err(|err| format!("invalid schema in {}: {}", path, err))
+}
+
+fn query<R, W>(args: &Args, expr: &str, rd: &mut R, wr: &mut W) -> Result<(), String>
+    where R: BufRead,
+          W: Write
+{
+    let limits = depth_limits(args)?;
+
+    let mut rd = Reader::new(rd);
+    while !rd.is_eof().map_err(|err| read_error(&err))? {
+        let val = read_checked(&mut rd, &limits)?;
+
+        let matches = if expr.is_empty() || expr.starts_with('/') {
+            val.pointer(expr).into_iter().collect()
+        } else {
+            val.query(expr).map_err(|err| format!("invalid query {:?}: {}", expr, err))?
+        };
+
+        for val in matches {
+            writeln!(wr, "{:#}", val).map_err(|err| write_error(&err))?;
+        }
+    }
+    Ok(())
+}
+
+fn split<R: BufRead, W: Write>(args: &Args, rd: &mut R, wr: &mut W) -> Result<(), String> {
+    let prefix = args.opt("prefix");
+    let limits = depth_limits(args)?;
+
+    let mut rd = Reader::new(rd);
+    let mut index = 0;
+    while !rd.is_eof().map_err(|err| read_error(&err))? {
+        let pos = rd.pos();
+        match prefix {
+            Some(prefix) => {
+                rd.start_copy();
+                walk::walk(&mut rd, &limits)?;
+                let path = format!("{}{:06}.msgpack", prefix, index);
+                File::create(&path)
+                    .and_then(|mut file| file.write_all(&rd.take_copy()))
+                    .map_err(|err| format!("failed to write {}: {}", path, err))?;
+            }
+            None => {
+                walk::walk(&mut rd, &limits)?;
+                writeln!(wr, "{} {} {}", index, pos, rd.pos() - pos)
+                    .map_err(|err| write_error(&err))?;
+            }
+        }
+        index += 1;
+    }
+    Ok(())
+}
+
+fn invalid_policy(name: &str, policy: &str) -> String {
+    format!("invalid value {:?} of option --{}", policy, name)
+}
+
+fn value_error(pos: u64, err: &DecodeError) -> String {
+    format!("value at offset {}: {}", pos, err)
+}
+
+fn depth_error(pos: u64, max_depth: usize) -> String {
+    format!("value at offset {} exceeds the depth limit of {}", pos, max_depth)
+}
+
+fn read_error(err: &io::Error) -> String {
+    format!("failed to read the input: {}", err)
+}
+
+fn write_error(err: &io::Error) -> String {
+    format!("failed to write the output: {}", err)
+}
@@ -0,0 +1,185 @@
+//! Walks over MessagePack values without decoding them, checking that they are well-formed and
+//! respect the given limits.
+
+use std::io::{self, BufRead, Read};
+use std::str;
+
+use rmp::Marker;
+use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, ValueReadError};
+
+/// Limits applied to each value.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The maximum nesting depth of arrays and maps, where scalars have depth 0.
+    pub max_depth: usize,
+    /// The maximum number of elements of arrays and maps, and bytes of strings, binaries and
+    /// extensions.
+    pub max_len: Option<u64>,
+    /// The maximum size of an encoded value in bytes.
+    pub max_size: Option<u64>,
+}
+
+/// A reader, which counts bytes read and optionally keeps a copy of them.
+pub struct Reader<R> {
+    rd: R,
+    pos: u64,
+    copy: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(rd: R) -> Reader<R> {
+        Reader {
+            rd: rd,
+            pos: 0,
+            copy: None,
+        }
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn pos(&self) -> u64 {
+        self.pos
+    }
+
+    /// Returns whether the underlying reader is exhausted.
+    pub fn is_eof(&mut self) -> io::Result<bool> {
+        Ok(self.rd.fill_buf()?.is_empty())
+    }
+
+    /// Starts keeping a copy of bytes read.
+    pub fn start_copy(&mut self) {
+        self.copy = Some(Vec::new());
+    }
+
+    /// Returns the bytes read since `start_copy` was called.
+    pub fn take_copy(&mut self) -> Vec<u8> {
+        self.copy.take().unwrap_or_default()
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.rd.read(buf)?;
+        self.pos += len as u64;
+        if let Some(ref mut copy) = self.copy {
+            copy.extend_from_slice(&buf[..len]);
+        }
+        Ok(len)
+    }
+}
+
+/// Reads a single value, checking that it is well-formed and respects the limits.
+pub fn walk<R: BufRead>(rd: &mut Reader<R>, limits: &Limits) -> Result<(), String> {
+    let start = rd.pos();
+    walk_value(rd, limits, start, 0)
+}
+
+fn walk_value<R: BufRead>(rd: &mut Reader<R>, limits: &Limits, start: u64, depth: usize)
+    -> Result<(), String>
+{
+    let pos = rd.pos();
+    let marker = read_marker(rd).map_err(|err| read_error(pos, err.0))?;
+
+    let len = match marker {
+        Marker::FixStr(len) => Some(len as u32),
+        Marker::Str8 | Marker::Bin8 | Marker::Ext8 => {
+            Some(read_data_u8(rd).map_err(|err| data_error(pos, err))? as u32)
+        }
+        Marker::Str16 | Marker::Bin16 | Marker::Ext16 | Marker::Array16 | Marker::Map16 => {
+            Some(read_data_u16(rd).map_err(|err| data_error(pos, err))? as u32)
+        }
+        Marker::Str32 | Marker::Bin32 | Marker::Ext32 | Marker::Array32 | Marker::Map32 => {
+            Some(read_data_u32(rd).map_err(|err| data_error(pos, err))?)
+        }
+        Marker::FixArray(len) | Marker::FixMap(len) => Some(len as u32),
+        Marker::FixExt1 => Some(1),
+        Marker::FixExt2 => Some(2),
+        Marker::FixExt4 => Some(4),
+        Marker::FixExt8 => Some(8),
+        Marker::FixExt16 => Some(16),
+        Marker::Reserved => return Err(format!("reserved marker 0xc1 at offset {}", pos)),
+        _ => None,
+    };
+
+    if let (Some(len), Some(max)) = (len, limits.max_len) {
+        if len as u64 > max {
+            return Err(format!("length {} at offset {} exceeds the limit of {}", len, pos, max));
+        }
+    }
+
+    let skip = match marker {
+        Marker::U8 | Marker::I8 => 1,
+        Marker::U16 | Marker::I16 => 2,
+        Marker::U32 | Marker::I32 | Marker::F32 => 4,
+        Marker::U64 | Marker::I64 | Marker::F64 => 8,
+        Marker::Bin8 | Marker::Bin16 | Marker::Bin32 => len.unwrap() as u64,
+        Marker::FixExt1 | Marker::FixExt2 | Marker::FixExt4 | Marker::FixExt8 |
+        Marker::FixExt16 | Marker::Ext8 | Marker::Ext16 | Marker::Ext32 => len.unwrap() as u64 + 1,
+        _ => 0,
+    };
+    check_size(rd.pos() + skip, start, limits)?;
+    let skipped = io::copy(&mut rd.by_ref().take(skip), &mut io::sink())
+        .map_err(|err| read_error(pos, err))?;
+    if skipped < skip {
+        return Err(format!("unexpected end of input in value at offset {}", pos));
+    }
+
+    match marker {
+        Marker::FixStr(..) | Marker::Str8 | Marker::Str16 | Marker::Str32 => {
+            let len = len.unwrap() as u64;
+            check_size(rd.pos() + len, start, limits)?;
+            // The buffer grows with the bytes actually read, since the length is not trusted.
+            let mut buf = Vec::new();
+            rd.by_ref().take(len).read_to_end(&mut buf).map_err(|err| read_error(pos, err))?;
+            if (buf.len() as u64) < len {
+                return Err(format!("unexpected end of input in value at offset {}", pos));
+            }
+            str::from_utf8(&buf)
+                .map_err(|_| format!("string at offset {} is not valid UTF-8", pos))?;
+        }
+        Marker::FixArray(..) | Marker::Array16 | Marker::Array32 |
+        Marker::FixMap(..) | Marker::Map16 | Marker::Map32 => {
+            if depth >= limits.max_depth {
+                return Err(format!("nesting at offset {} exceeds the depth limit of {}",
+                                   pos,
+                                   limits.max_depth));
+            }
+
+            let count = match marker {
+                Marker::FixMap(..) | Marker::Map16 | Marker::Map32 => 2 * len.unwrap() as u64,
+                _ => len.unwrap() as u64,
+            };
+
+            for _ in 0..count {
+                walk_value(rd, limits, start, depth + 1)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn check_size(end: u64, start: u64, limits: &Limits) -> Result<(), String> {
+    match limits.max_size {
+        Some(max) if end - start > max => {
+            Err(format!("value at offset {} exceeds the size limit of {} bytes", start, max))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn read_error(pos: u64, err: io::Error) -> String {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        format!("unexpected end of input in value at offset {}", pos)
+    } else {
+        format!("failed to read value at offset {}: {}", pos, err)
+    }
+}
+
+fn data_error(pos: u64, err: ValueReadError) -> String {
+    match err {
+        ValueReadError::InvalidMarkerRead(err) |
+        ValueReadError::InvalidDataRead(err) => read_error(pos, err),
+        ValueReadError::TypeMismatch(..) => unreachable!("reading data never checks markers"),
+    }
+}
@@ -0,0 +1,217 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{self, Command, Output, Stdio};
+
+fn run(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rmp-cli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The tool may exit without reading the input, for example on invalid arguments, so the write
+    // may fail with a broken pipe.
+    let _ = child.stdin.take().unwrap().write_all(input);
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str], input: &[u8]) -> String {
+    let out = run(args, input);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+fn stderr(args: &[&str], input: &[u8]) -> String {
+    let out = run(args, input);
+    assert!(!out.status.success());
+    String::from_utf8(out.stderr).unwrap()
+}
+
+// {"a": [1, "x"]} and 42.
+const INPUT: &[u8] = &[0x81, 0xa1, 0x61, 0x92, 0x01, 0xa1, 0x78, 0x2a];
+
+#[test]
+fn dump() {
+    assert_eq!("{\n  \"a\": [\n    1,\n    \"x\"\n  ]\n}\n42\n", stdout(&["dump"], INPUT));
+}
+
+#[test]
+fn to_json() {
+    assert_eq!("{\"a\":[1,\"x\"]}\n42\n", stdout(&["to-json"], INPUT));
+}
+
+#[test]
+fn to_json_reports_losses() {
+    let out = run(&["to-json", "--binary", "array"], &[0xc4, 0x02, 0x00, 0xff]);
+
+    assert!(out.status.success());
+    assert_eq!(b"[0,255]\n", &out.stdout[..]);
+    assert_eq!("rmp-cli: lossy conversion of binary at \"\" in value 0\n",
+               String::from_utf8(out.stderr).unwrap());
+}
+
+#[test]
+fn to_json_fails_by_policy() {
+    let err = stderr(&["to-json", "--binary=error", "--quiet"], &[0xc4, 0x00]);
+    assert!(err.contains("binary"), "{}", err);
+}
+
+#[test]
+fn from_json_roundtrip() {
+    let buf = run(&["from-json"], b" {\"a\": [1, \"x\"]}\n42 ").stdout;
+    assert_eq!("{\"a\":[1,\"x\"]}\n42\n", stdout(&["to-json"], &buf));
+}
+
+#[test]
+fn validate() {
+    assert_eq!("valid: 2 values, 8 bytes\n", stdout(&["validate"], INPUT));
+}
+
+#[test]
+fn validate_limits() {
+    assert!(stderr(&["validate", "--max-depth", "1"], INPUT).contains("depth limit of 1"));
+    assert!(stderr(&["validate", "--max-len", "1"], INPUT).contains("exceeds the limit of 1"));
+    assert!(stderr(&["validate", "--max-size", "4"], INPUT).contains("size limit of 4"));
+}
+
+#[test]
+fn validate_schema() {
+    let path = env::temp_dir().join(format!("rmp-cli-schema-{}.txt", process::id()));
+    fs::write(&path, r#"{"type": ["map", "int"], "properties": {"a": {"max_len": 1}}}"#)
+        .unwrap();
+    let path = path.to_str().unwrap();
+
+    let out = run(&["validate", "--schema", path], INPUT);
+    assert!(!out.status.success());
+    assert_eq!("value 0 at offset 0: $.a: longer than the maximum length 1\n",
+               String::from_utf8(out.stdout).unwrap());
+    assert_eq!("rmp-cli: 1 of 2 values do not conform to the schema\n",
+               String::from_utf8(out.stderr).unwrap());
+
+    fs::write(path, r#"{"min": 1}"#).unwrap();
+    assert_eq!("valid: 2 values, 8 bytes\n", stdout(&["validate", "--schema", path], INPUT));
+
+    fs::write(path, r#"{"min": "1"}"#).unwrap();
+    assert!(stderr(&["validate", "--schema", path], INPUT)
+                .contains("invalid schema value at $.min"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn validate_malformed() {
+    assert!(stderr(&["validate"], &[0x92, 0x01]).contains("unexpected end of input"));
+    assert!(stderr(&["validate"], &[0xc1]).contains("reserved marker"));
+    assert!(stderr(&["validate"], &[0xa1, 0xff]).contains("not valid UTF-8"));
+}
+
+#[test]
+fn annotate() {
+    let expected = "\
+00000000  81                       fixmap, 1 entries
+00000001  a1 61                      fixstr, 1 bytes: \"a\"
+00000003  92                         fixarray, 2 elements
+00000004  01                           positive fixint: 1
+00000005  a1 78                        fixstr, 1 bytes: \"x\"
+00000007  2a                       positive fixint: 42
+";
+    assert_eq!(expected, stdout(&["annotate"], INPUT));
+}
+
+#[test]
+fn annotate_wraps_long_data() {
+    let expected = "\
+00000000  cb 40 09 21 fb 54 44 2d  float64: 3.141592653589793
+00000008  18
+";
+    let input = [0xcb, 0x40, 0x09, 0x21, 0xfb, 0x54, 0x44, 0x2d, 0x18];
+    assert_eq!(expected, stdout(&["annotate"], &input));
+}
+
+#[test]
+fn annotate_caps_indentation() {
+    let mut input = vec![0x91; 200_000];
+    input.push(0xc0);
+
+    let out = stdout(&["annotate"], &input);
+    let last = out.lines().last().unwrap();
+    assert_eq!(format!("{:08x}  c0{:23}{:64}nil", 200_000, "", ""), last);
+}
+
+#[test]
+fn annotate_malformed() {
+    let out = run(&["annotate"], &[0x92, 0x01, 0xc1]);
+
+    assert!(!out.status.success());
+    assert_eq!("\
+00000000  92                       fixarray, 2 elements
+00000001  01                         positive fixint: 1
+00000002  c1                         malformed
+",
+               String::from_utf8(out.stdout).unwrap());
+    assert_eq!("rmp-cli: reserved marker at offset 2\n", String::from_utf8(out.stderr).unwrap());
+}
+
+#[test]
+fn profile() {
+    let out = stdout(&["profile"], INPUT);
+    let lines: Vec<_> = out.lines().collect();
+
+    assert_eq!(4, lines.len());
+    assert!(lines[1].starts_with("$    "), "{}", out);
+    assert!(lines[3].starts_with("$.a[*] "), "{}", out);
+    assert!(lines[3].ends_with("fixstr: 1, positive fixint: 1"), "{}", out);
+}
+
+#[test]
+fn schema() {
+    assert_eq!("\
+$: int 42..=42 | map len 1..=1 -> rmpv::Value
+$.a: array len 2..=2 -> Vec<rmpv::Value>
+$.a[*]: int 1..=1 | str len 1..=1 (mean 1.0) -> rmpv::Value
+",
+               stdout(&["schema"], INPUT));
+    assert!(stdout(&["schema", "--value"], INPUT).ends_with("\
+  \"required\": [
+    \"a\"
+  ]
+}
+"));
+}
+
+#[test]
+fn query() {
+    assert_eq!("\"x\"\n", stdout(&["query", "/a/1"], INPUT));
+    assert_eq!("1\n", stdout(&["query", "$.a[0]"], INPUT));
+}
+
+#[test]
+fn split() {
+    assert_eq!("0 0 7\n1 7 1\n", stdout(&["split"], INPUT));
+}
+
+#[test]
+fn depth_limits() {
+    // An array nested a million levels deep, which would overflow the stack when decoded.
+    let deep = vec![0x91; 1_000_000];
+
+    for cmd in &["dump", "to-json", "profile", "schema", "split"] {
+        let err = stderr(&[cmd], &deep);
+        assert!(err.contains("depth limit of 512"), "{}: {}", cmd, err);
+    }
+    assert!(stderr(&["query", "/0"], &deep).contains("depth limit of 512"));
+
+    assert!(stderr(&["dump", "--max-depth", "1"], INPUT).contains("depth limit of 1"));
+    assert!(stderr(&["query", "--max-depth=1", "/a"], INPUT).contains("depth limit of 1"));
+    assert!(stderr(&["to-json", "--max-depth", "1"], INPUT).contains("depth limit of 1"));
+    assert!(stderr(&["profile", "--max-depth", "1"], INPUT).contains("depth limit of 1"));
+    assert_eq!("0 0 4\n", stdout(&["split", "--max-depth", "3"], &[0x91, 0x91, 0x91, 0x01]));
+}
+
+#[test]
+fn unknown_command() {
+    assert!(stderr(&["bogus"], b"").starts_with("rmp-cli: unknown command \"bogus\""));
+}
@@ -308,3 +308,23 @@ fn pass_enum_with_one_arg() {
     assert_eq!(Enum::V1(vec![1, 2]), actual);
     assert_eq!(buf.len() as u64, de.get_ref().position())
 }
+
+#[test]
+fn pass_struct_duplicate_fields_keep_last() {
+    use rmp_serde::decode::DuplicateKeys;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Struct {
+        x: u32,
+        y: u32,
+    }
+
+    // Encoded as map: {"x": 1, "y": 2, "x": 3}.
+    let buf = [0x83, 0xa1, 0x78, 0x01, 0xa1, 0x79, 0x02, 0xa1, 0x78, 0x03];
+
+    let mut de = Deserializer::new(&buf[..]);
+    de.set_duplicate_keys(DuplicateKeys::KeepLast);
+    let actual: Struct = Deserialize::deserialize(&mut de).unwrap();
+
+    assert_eq!(Struct { x: 3, y: 2 }, actual);
+}
@@ -1,17 +1,21 @@
+use std::collections::HashSet;
 use std::error;
 use std::fmt::{self, Display, Formatter};
 use std::io::{self, Cursor};
 use std::str::{self, Utf8Error};
+use std::vec;
 
 use byteorder::{self, ReadBytesExt};
 
 use serde;
-use serde::de::{self, Deserialize, DeserializeSeed, Visitor};
+use serde::de::{self, Deserialize, DeserializeSeed, Unexpected, Visitor};
 
 use rmp;
 use rmp::Marker;
 use rmp::decode::{MarkerReadError, DecodeStringError, ValueReadError, NumValueReadError,
                   read_array_len};
+use rmp::encode::{write_uint, write_sint, write_str_len, write_bin_len, write_array_len,
+                  write_map_len, write_ext_meta};
 
 ///
 // TODO: Write docs.
@@ -23,12 +27,21 @@ pub enum Error {
     TypeMismatch(Marker),
     /// Numeric cast failed due to out of range error.
     OutOfRange,
+    /// The number does not fit in the requested type while coercing numbers.
+    ///
+    /// Contains the offending value and the name of the requested type.
+    NumberOutOfRange(String, &'static str),
     LengthMismatch(u32),
     /// Uncategorized error.
     Uncategorized(String),
     Syntax(String),
     Utf8Error(Utf8Error),
     DepthLimitExceeded,
+    /// The map contains a key equal to one of its previous keys, which is rejected by
+    /// `DuplicateKeys::Reject`.
+    ///
+    /// Contains the index of the duplicate entry within its map.
+    DuplicateKey(u32),
 }
 
 impl error::Error for Error {
@@ -43,10 +56,12 @@ impl error::Error for Error {
             Error::InvalidDataRead(ref err) => Some(err),
             Error::LengthMismatch(..) => None,
             Error::OutOfRange => None,
+            Error::NumberOutOfRange(..) => None,
             Error::Uncategorized(..) => None,
             Error::Syntax(..) => None,
             Error::Utf8Error(ref err) => Some(err),
             Error::DepthLimitExceeded => None,
+            Error::DuplicateKey(..) => None,
         }
     }
 }
@@ -59,7 +74,12 @@ impl de::Error for Error {
 
 impl Display for Error {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
-        error::Error::description(self).fmt(fmt)
+        match *self {
+            Error::NumberOutOfRange(ref val, ty) => {
+                write!(fmt, "value {} is out of range of {}", val, ty)
+            }
+            _ => error::Error::description(self).fmt(fmt),
+        }
     }
 }
 
@@ -108,6 +128,53 @@ impl<'a> From<DecodeStringError<'a>> for Error {
     }
 }
 
+/// Strategy used by the `Deserializer` when the encoded number type differs from the requested one.
+///
+/// The requested type is the one passed to `deserialize_u8`, `deserialize_f32` and others by the
+/// `Deserialize` implementation. Generic `deserialize` calls always receive the encoded type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberCoercion {
+    /// Numbers are passed to the visitor using their encoded type. This is the default.
+    Exact,
+    /// Integers are converted to the requested integer type if they fit in its range, otherwise
+    /// `Error::NumberOutOfRange` is returned. Integers are also converted to the requested float
+    /// type and `f32` values are widened when `f64` is requested.
+    Lenient,
+    /// Like `Lenient`, but additionally floats with an integral value are converted to the
+    /// requested integer type if they fit in its range. Other floats are passed as is.
+    Integral,
+}
+
+/// Policy applied by the `Deserializer` to map entries with duplicate keys.
+///
+/// Keys are compared as MessagePack values before any conversion to Rust types: integers are
+/// compared by their numeric value regardless of the marker used, while other values, including
+/// floats, are compared by their encoded form.
+///
+/// Policies other than `Allow` buffer each map in memory before passing its entries to the
+/// visitor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// All entries are passed to the visitor, which decides what to do with duplicates. For
+    /// example, `HashMap` keeps the last entry, while derived structs fail. This is the default.
+    Allow,
+    /// Only the first entry with each key is passed to the visitor.
+    KeepFirst,
+    /// Only the last entry with each key is passed to the visitor at its position.
+    KeepLast,
+    /// Decoding fails with `Error::DuplicateKey` pointing at the first duplicate entry.
+    Reject,
+}
+
+// A number read while coercing it to the requested type.
+#[derive(Clone, Copy, Debug)]
+enum Number {
+    U64(u64),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
 /// A Deserializer that reads bytes from a buffer.
 ///
 /// # Note
@@ -118,6 +185,9 @@ pub struct Deserializer<R> {
     rd: R,
     marker: Option<Marker>,
     depth: usize,
+    coercion: NumberCoercion,
+    key_coercion: bool,
+    duplicate_keys: DuplicateKeys,
 }
 
 impl<'a> Deserializer<SliceReader<'a>> {
@@ -126,6 +196,9 @@ impl<'a> Deserializer<SliceReader<'a>> {
             rd: SliceReader::new(slice),
             marker: None,
             depth: 1024,
+            coercion: NumberCoercion::Exact,
+            key_coercion: false,
+            duplicate_keys: DuplicateKeys::Allow,
         }
     }
 
@@ -142,6 +215,9 @@ impl<R: io::Read> Deserializer<ReadReader<R>> {
             // Cached marker in case of deserializing options.
             marker: None,
             depth: 1024,
+            coercion: NumberCoercion::Exact,
+            key_coercion: false,
+            duplicate_keys: DuplicateKeys::Allow,
         }
     }
 
@@ -179,6 +255,96 @@ impl<R: Read> Deserializer<R> {
         self.depth = depth;
     }
 
+    /// Changes the strategy used to convert numbers, which are encoded using a type other than the
+    /// requested one.
+    ///
+    /// This is useful when talking to producers, that do not preserve exact numeric types, for
+    /// example ones that encode every integer as signed or every number as a float.
+    pub fn set_number_coercion(&mut self, coercion: NumberCoercion) {
+        self.coercion = coercion;
+    }
+
+    /// Enables or disables map key coercion, which is disabled by default.
+    ///
+    /// When enabled, map keys encoded as strings are parsed if the key type requests a number or
+    /// a boolean, and keys encoded as numbers or booleans are formatted if the key type requests
+    /// a string. This allows to read data produced from JSON, where every map key is a string,
+    /// into maps like `HashMap<u32, T>`, and data written with `Serializer::set_string_keys`.
+    pub fn set_key_coercion(&mut self, enabled: bool) {
+        self.key_coercion = enabled;
+    }
+
+    /// Changes the policy applied to map entries with duplicate keys.
+    ///
+    /// By default every entry is passed to the visitor, so the outcome depends on the type being
+    /// deserialized. Setting an explicit policy makes it consistent across all types and with
+    /// `rmpv::decode::read_value_with_policy`.
+    pub fn set_duplicate_keys(&mut self, keys: DuplicateKeys) {
+        self.duplicate_keys = keys;
+    }
+
+    // Creates a deserializer with the same settings for a buffered value.
+    fn nested<'b>(&self, buf: &'b [u8]) -> Deserializer<SliceReader<'b>> {
+        Deserializer {
+            rd: SliceReader::new(buf),
+            marker: None,
+            depth: self.depth,
+            coercion: self.coercion,
+            key_coercion: self.key_coercion,
+            duplicate_keys: self.duplicate_keys,
+        }
+    }
+
+    // Reads the next value if it is a string, otherwise leaves its marker cached.
+    fn read_key_str(&mut self) -> Result<Option<&str>, Error> {
+        let marker = match self.marker.take() {
+            Some(marker) => marker,
+            None => rmp::decode::read_marker(&mut self.rd)?,
+        };
+
+        let len = match marker {
+            Marker::FixStr(len) => len as u32,
+            Marker::Str8 => read_u8(&mut self.rd)? as u32,
+            Marker::Str16 => read_u16(&mut self.rd)? as u32,
+            Marker::Str32 => read_u32(&mut self.rd)?,
+            marker => {
+                self.marker = Some(marker);
+                return Ok(None);
+            }
+        };
+
+        self.read_str_data(len).map(Some)
+    }
+
+    // Reads the next value if it is a number, otherwise leaves its marker cached.
+    fn read_number(&mut self) -> Result<Option<Number>, Error> {
+        let marker = match self.marker.take() {
+            Some(marker) => marker,
+            None => rmp::decode::read_marker(&mut self.rd)?,
+        };
+
+        let num = match marker {
+            Marker::FixPos(val) => Number::U64(val as u64),
+            Marker::FixNeg(val) => Number::I64(val as i64),
+            Marker::U8 => Number::U64(rmp::decode::read_data_u8(&mut self.rd)? as u64),
+            Marker::U16 => Number::U64(rmp::decode::read_data_u16(&mut self.rd)? as u64),
+            Marker::U32 => Number::U64(rmp::decode::read_data_u32(&mut self.rd)? as u64),
+            Marker::U64 => Number::U64(rmp::decode::read_data_u64(&mut self.rd)?),
+            Marker::I8 => Number::I64(rmp::decode::read_data_i8(&mut self.rd)? as i64),
+            Marker::I16 => Number::I64(rmp::decode::read_data_i16(&mut self.rd)? as i64),
+            Marker::I32 => Number::I64(rmp::decode::read_data_i32(&mut self.rd)? as i64),
+            Marker::I64 => Number::I64(rmp::decode::read_data_i64(&mut self.rd)?),
+            Marker::F32 => Number::F32(rmp::decode::read_data_f32(&mut self.rd)?),
+            Marker::F64 => Number::F64(rmp::decode::read_data_f64(&mut self.rd)?),
+            marker => {
+                self.marker = Some(marker);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(num))
+    }
+
     fn read_str_data(&mut self, len: u32) -> Result<&str, Error> {
         let slice = self.read_bin_data(len)?;
         str::from_utf8(slice).map_err(From::from)
@@ -197,7 +363,168 @@ impl<R: Read> Deserializer<R> {
     fn read_map<V>(&mut self, len: u32, visitor: V) -> Result<V::Value, Error>
         where V: Visitor
     {
-        visitor.visit_map(MapVisitor::new(self, len))
+        if self.duplicate_keys == DuplicateKeys::Allow {
+            return visitor.visit_map(MapVisitor::new(self, len));
+        }
+
+        let depth = self.depth;
+        let mut entries = Vec::new();
+        for _ in 0..len {
+            let mut key = Vec::new();
+            let mut norm = Vec::new();
+            let mut val = Vec::new();
+            self.read_raw(&mut key, Some(&mut norm), depth)?;
+            self.read_raw(&mut val, None, depth)?;
+            entries.push((norm, key, val));
+        }
+
+        if self.duplicate_keys == DuplicateKeys::KeepLast {
+            entries.reverse();
+        }
+
+        let mut seen = HashSet::with_capacity(entries.len());
+        let mut map = Vec::with_capacity(entries.len());
+        for (idx, (norm, key, val)) in entries.into_iter().enumerate() {
+            if seen.insert(norm) {
+                map.push((key, val));
+            } else if self.duplicate_keys == DuplicateKeys::Reject {
+                return Err(Error::DuplicateKey(idx as u32));
+            }
+        }
+
+        if self.duplicate_keys == DuplicateKeys::KeepLast {
+            map.reverse();
+        }
+
+        visitor.visit_map(BufferedMapVisitor::new(self, map))
+    }
+
+    // Copies the next value into `raw` as is and optionally into `norm` in the normalized form,
+    // where integers and lengths use the most compact encoding, so that equal keys are equal
+    // bytewise.
+    fn read_raw(&mut self, raw: &mut Vec<u8>, mut norm: Option<&mut Vec<u8>>, depth: usize) ->
+        Result<(), Error>
+    {
+        if depth == 0 {
+            return Err(Error::DepthLimitExceeded);
+        }
+
+        let marker = match self.marker.take() {
+            Some(marker) => marker,
+            None => rmp::decode::read_marker(&mut self.rd)?,
+        };
+
+        let start = raw.len();
+        raw.push(marker.to_u8());
+
+        // Writing into a `Vec` never fails, so the results of normalized writes are ignored.
+        match marker {
+            Marker::Null | Marker::True | Marker::False | Marker::FixPos(..) |
+            Marker::FixNeg(..) => {}
+            Marker::U8 | Marker::U16 | Marker::U32 | Marker::U64 => {
+                let size = 1 << (marker.to_u8() - Marker::U8.to_u8());
+                let val = self.read_raw_uint(raw, size)?;
+                if let Some(norm) = norm {
+                    let _ = write_uint(norm, val);
+                }
+                return Ok(());
+            }
+            Marker::I8 | Marker::I16 | Marker::I32 | Marker::I64 => {
+                let size = 1 << (marker.to_u8() - Marker::I8.to_u8());
+                let shift = 64 - 8 * size;
+                let val = ((self.read_raw_uint(raw, size)? << shift) as i64) >> shift;
+                if let Some(norm) = norm {
+                    if val < 0 {
+                        let _ = write_sint(norm, val);
+                    } else {
+                        let _ = write_uint(norm, val as u64);
+                    }
+                }
+                return Ok(());
+            }
+            Marker::F32 => self.read_raw_data(raw, 4)?,
+            Marker::F64 => self.read_raw_data(raw, 8)?,
+            Marker::FixExt1 => self.read_raw_data(raw, 1 + 1)?,
+            Marker::FixExt2 => self.read_raw_data(raw, 1 + 2)?,
+            Marker::FixExt4 => self.read_raw_data(raw, 1 + 4)?,
+            Marker::FixExt8 => self.read_raw_data(raw, 1 + 8)?,
+            Marker::FixExt16 => self.read_raw_data(raw, 1 + 16)?,
+            Marker::Reserved => return Err(Error::TypeMismatch(Marker::Reserved)),
+            marker => {
+                let len = match marker {
+                    Marker::FixStr(len) | Marker::FixArray(len) | Marker::FixMap(len) => len as u64,
+                    Marker::Str8 | Marker::Bin8 | Marker::Ext8 => self.read_raw_uint(raw, 1)?,
+                    Marker::Str16 | Marker::Bin16 | Marker::Ext16 | Marker::Array16 |
+                    Marker::Map16 => self.read_raw_uint(raw, 2)?,
+                    _ => self.read_raw_uint(raw, 4)?,
+                } as u32;
+
+                match marker {
+                    Marker::FixStr(..) | Marker::Str8 | Marker::Str16 | Marker::Str32 => {
+                        if let Some(ref mut norm) = norm {
+                            let _ = write_str_len(*norm, len);
+                        }
+                        self.read_raw_data(raw, len as usize)?;
+                    }
+                    Marker::Bin8 | Marker::Bin16 | Marker::Bin32 => {
+                        if let Some(ref mut norm) = norm {
+                            let _ = write_bin_len(*norm, len);
+                        }
+                        self.read_raw_data(raw, len as usize)?;
+                    }
+                    Marker::Ext8 | Marker::Ext16 | Marker::Ext32 => {
+                        self.read_raw_data(raw, 1 + len as usize)?;
+                        if let Some(ref mut norm) = norm {
+                            let ty = raw[raw.len() - len as usize - 1] as i8;
+                            let _ = write_ext_meta(*norm, len, ty);
+                        }
+                    }
+                    Marker::FixArray(..) | Marker::Array16 | Marker::Array32 => {
+                        if let Some(ref mut norm) = norm {
+                            let _ = write_array_len(*norm, len);
+                        }
+                        for _ in 0..len {
+                            self.read_raw(raw, norm.as_mut().map(|norm| &mut **norm), depth - 1)?;
+                        }
+                        return Ok(());
+                    }
+                    _ => {
+                        if let Some(ref mut norm) = norm {
+                            let _ = write_map_len(*norm, len);
+                        }
+                        for _ in 0..len * 2 {
+                            self.read_raw(raw, norm.as_mut().map(|norm| &mut **norm), depth - 1)?;
+                        }
+                        return Ok(());
+                    }
+                }
+
+                // Only the payload is left to be copied after the normalized header.
+                if let Some(norm) = norm {
+                    let payload = raw.len() - len as usize;
+                    norm.extend_from_slice(&raw[payload..]);
+                }
+                return Ok(());
+            }
+        }
+
+        if let Some(norm) = norm {
+            norm.extend_from_slice(&raw[start..]);
+        }
+
+        Ok(())
+    }
+
+    fn read_raw_data(&mut self, raw: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+        raw.extend_from_slice(self.rd.read_slice(len).map_err(Error::InvalidDataRead)?);
+        Ok(())
+    }
+
+    // Copies a big-endian unsigned integer of the given size, returning its value.
+    fn read_raw_uint(&mut self, raw: &mut Vec<u8>, size: usize) -> Result<u64, Error> {
+        let buf = self.rd.read_slice(size).map_err(Error::InvalidDataRead)?;
+        raw.extend_from_slice(buf);
+        Ok(buf.iter().fold(0, |acc, &byte| (acc << 8) | byte as u64))
     }
 }
 
@@ -213,6 +540,67 @@ fn read_u32<R: Read>(rd: &mut R) -> Result<u32, Error> {
     rd.read_u32::<byteorder::BigEndian>().map_err(Error::InvalidDataRead)
 }
 
+// Implements integer deserialization, which honors the `NumberCoercion` strategy.
+macro_rules! deserialize_int {
+    ($name:ident, $ty:ident, $visit:ident) => {
+        fn $name<V>(self, visitor: V) -> Result<V::Value, Error>
+            where V: Visitor
+        {
+            if self.coercion == NumberCoercion::Exact {
+                return self.deserialize(visitor);
+            }
+
+            let min = $ty::min_value() as i128;
+            let max = $ty::max_value() as i128;
+
+            match self.read_number()? {
+                Some(Number::U64(val)) if val as i128 <= max => visitor.$visit(val as $ty),
+                Some(Number::I64(val)) if min <= val as i128 && val as i128 <= max => {
+                    visitor.$visit(val as $ty)
+                }
+                Some(Number::U64(val)) => {
+                    Err(Error::NumberOutOfRange(val.to_string(), stringify!($ty)))
+                }
+                Some(Number::I64(val)) => {
+                    Err(Error::NumberOutOfRange(val.to_string(), stringify!($ty)))
+                }
+                Some(Number::F32(val)) => self.visit_integral_float(val as f64,
+                    (min, max, stringify!($ty)), visitor,
+                    |visitor| visitor.visit_f32(val), |visitor, val| visitor.$visit(val as $ty)),
+                Some(Number::F64(val)) => self.visit_integral_float(val,
+                    (min, max, stringify!($ty)), visitor,
+                    |visitor| visitor.visit_f64(val), |visitor, val| visitor.$visit(val as $ty)),
+                None => self.deserialize(visitor),
+            }
+        }
+    }
+}
+
+impl<R: Read> Deserializer<R> {
+    // Passes the float to the visitor either as an integer if it has an integral value and the
+    // coercion strategy allows it, or as is otherwise. The `range` contains the bounds and the
+    // name of the requested integer type.
+    fn visit_integral_float<V, F, I>(&self, val: f64, range: (i128, i128, &'static str),
+                                     visitor: V, float: F, int: I) -> Result<V::Value, Error>
+        where V: Visitor,
+              F: FnOnce(V) -> Result<V::Value, Error>,
+              I: FnOnce(V, f64) -> Result<V::Value, Error>
+    {
+        if self.coercion != NumberCoercion::Integral || val.fract() != 0.0 {
+            return float(visitor);
+        }
+
+        // Note, that `max as f64 + 1.0` is exact for all integer types up to 64 bits wide after
+        // rounding, which makes the check correct even for `u64::MAX` and `i64::MAX`.
+        let (min, max, ty) = range;
+        if min as f64 <= val && val < max as f64 + 1.0 {
+            int(visitor, val)
+        } else {
+            Err(Error::NumberOutOfRange(val.to_string(), ty))
+        }
+    }
+}
+
 impl<'a, R: Read> serde::Deserializer for &'a mut Deserializer<R> {
     type Error = Error;
 
@@ -324,8 +712,49 @@ impl<'a, R: Read> serde::Deserializer for &'a mut Deserializer<R> {
         }
     }
 
+    deserialize_int!(deserialize_u8, u8, visit_u8);
+    deserialize_int!(deserialize_u16, u16, visit_u16);
+    deserialize_int!(deserialize_u32, u32, visit_u32);
+    deserialize_int!(deserialize_u64, u64, visit_u64);
+    deserialize_int!(deserialize_i8, i8, visit_i8);
+    deserialize_int!(deserialize_i16, i16, visit_i16);
+    deserialize_int!(deserialize_i32, i32, visit_i32);
+    deserialize_int!(deserialize_i64, i64, visit_i64);
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor
+    {
+        if self.coercion == NumberCoercion::Exact {
+            return self.deserialize(visitor);
+        }
+
+        match self.read_number()? {
+            Some(Number::U64(val)) => visitor.visit_f32(val as f32),
+            Some(Number::I64(val)) => visitor.visit_f32(val as f32),
+            Some(Number::F32(val)) => visitor.visit_f32(val),
+            Some(Number::F64(val)) => visitor.visit_f64(val),
+            None => self.deserialize(visitor),
+        }
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor
+    {
+        if self.coercion == NumberCoercion::Exact {
+            return self.deserialize(visitor);
+        }
+
+        match self.read_number()? {
+            Some(Number::U64(val)) => visitor.visit_f64(val as f64),
+            Some(Number::I64(val)) => visitor.visit_f64(val as f64),
+            Some(Number::F32(val)) => visitor.visit_f64(val as f64),
+            Some(Number::F64(val)) => visitor.visit_f64(val),
+            None => self.deserialize(visitor),
+        }
+    }
+
     forward_to_deserialize! {
-        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char
+        bool char
         str string bytes byte_buf unit unit_struct seq seq_fixed_size map
         tuple_struct struct struct_field tuple
         ignored_any
@@ -391,7 +820,11 @@ impl<'a, R: Read + 'a> de::MapVisitor for MapVisitor<'a, R> {
     {
         if self.nleft > 0 {
             self.nleft -= 1;
-            Ok(Some(seed.deserialize(&mut *self.de)?))
+            if self.de.key_coercion {
+                Ok(Some(seed.deserialize(KeyDeserializer { de: &mut *self.de })?))
+            } else {
+                Ok(Some(seed.deserialize(&mut *self.de)?))
+            }
         } else {
             Ok(None)
         }
@@ -408,6 +841,173 @@ impl<'a, R: Read + 'a> de::MapVisitor for MapVisitor<'a, R> {
     }
 }
 
+// Deserializes map keys with coercion between strings and scalar types.
+struct KeyDeserializer<'a, R: Read + 'a> {
+    de: &'a mut Deserializer<R>,
+}
+
+// Implements parsing of scalar map keys encoded as strings.
+macro_rules! deserialize_parsed_key {
+    ($name:ident, $ty:ident, $visit:ident) => {
+        fn $name<V>(self, visitor: V) -> Result<V::Value, Error>
+            where V: Visitor
+        {
+            let val = match self.de.read_key_str()? {
+                Some(s) => match s.parse::<$ty>() {
+                    Ok(val) => val,
+                    Err(..) => return Err(de::Error::invalid_value(Unexpected::Str(s), &visitor)),
+                },
+                None => return serde::Deserializer::$name(self.de, visitor),
+            };
+
+            visitor.$visit(val)
+        }
+    }
+}
+
+impl<'a, R: Read + 'a> KeyDeserializer<'a, R> {
+    // Formats the next value if it is a number or a boolean, otherwise leaves its marker cached.
+    fn read_formatted(&mut self) -> Result<Option<String>, Error> {
+        let marker = match self.de.marker.take() {
+            Some(marker) => marker,
+            None => rmp::decode::read_marker(&mut self.de.rd)?,
+        };
+
+        match marker {
+            Marker::True => return Ok(Some("true".into())),
+            Marker::False => return Ok(Some("false".into())),
+            marker => self.de.marker = Some(marker),
+        }
+
+        let s = match self.de.read_number()? {
+            Some(Number::U64(val)) => format!("{}", val),
+            Some(Number::I64(val)) => format!("{}", val),
+            Some(Number::F32(val)) => format!("{}", val),
+            Some(Number::F64(val)) => format!("{}", val),
+            None => return Ok(None),
+        };
+
+        Ok(Some(s))
+    }
+}
+
+impl<'a, R: Read + 'a> serde::Deserializer for KeyDeserializer<'a, R> {
+    type Error = Error;
+
+    fn deserialize<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where V: Visitor
+    {
+        self.de.deserialize(visitor)
+    }
+
+    deserialize_parsed_key!(deserialize_bool, bool, visit_bool);
+    deserialize_parsed_key!(deserialize_u8, u8, visit_u8);
+    deserialize_parsed_key!(deserialize_u16, u16, visit_u16);
+    deserialize_parsed_key!(deserialize_u32, u32, visit_u32);
+    deserialize_parsed_key!(deserialize_u64, u64, visit_u64);
+    deserialize_parsed_key!(deserialize_i8, i8, visit_i8);
+    deserialize_parsed_key!(deserialize_i16, i16, visit_i16);
+    deserialize_parsed_key!(deserialize_i32, i32, visit_i32);
+    deserialize_parsed_key!(deserialize_i64, i64, visit_i64);
+    deserialize_parsed_key!(deserialize_f32, f32, visit_f32);
+    deserialize_parsed_key!(deserialize_f64, f64, visit_f64);
+
+    fn deserialize_str<V>(mut self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor
+    {
+        match self.read_formatted()? {
+            Some(s) => visitor.visit_string(s),
+            None => self.de.deserialize_str(visitor),
+        }
+    }
+
+    fn deserialize_string<V>(mut self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor
+    {
+        match self.read_formatted()? {
+            Some(s) => visitor.visit_string(s),
+            None => self.de.deserialize_string(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor
+    {
+        self.de.deserialize_option(visitor)
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V)
+        -> Result<V::Value, Error>
+        where V: Visitor
+    {
+        self.de.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor
+    {
+        self.de.deserialize_newtype_struct(name, visitor)
+    }
+
+    forward_to_deserialize! {
+        char bytes byte_buf unit unit_struct seq seq_fixed_size map tuple_struct struct
+        struct_field tuple ignored_any
+    }
+}
+
+// Passes map entries buffered to apply the duplicate keys policy.
+struct BufferedMapVisitor<'a, R: Read + 'a> {
+    de: &'a mut Deserializer<R>,
+    len: usize,
+    entries: vec::IntoIter<(Vec<u8>, Vec<u8>)>,
+    value: Option<Vec<u8>>,
+}
+
+impl<'a, R: Read + 'a> BufferedMapVisitor<'a, R> {
+    fn new(de: &'a mut Deserializer<R>, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        BufferedMapVisitor {
+            de: de,
+            len: entries.len(),
+            entries: entries.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'a, R: Read + 'a> de::MapVisitor for BufferedMapVisitor<'a, R> {
+    type Error = Error;
+
+    fn visit_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+        where K: DeserializeSeed
+    {
+        let (key, val) = match self.entries.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        self.value = Some(val);
+
+        let mut de = self.de.nested(&key);
+        if de.key_coercion {
+            Ok(Some(seed.deserialize(KeyDeserializer { de: &mut de })?))
+        } else {
+            Ok(Some(seed.deserialize(&mut de)?))
+        }
+    }
+
+    fn visit_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+        where V: DeserializeSeed
+    {
+        match self.value.take() {
+            Some(val) => seed.deserialize(&mut self.de.nested(&val)),
+            None => Err(Error::Uncategorized("map value requested before its key".into())),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
 /// Default variant visitor.
 ///
 /// # Note
@@ -472,7 +1072,7 @@ pub trait Read: io::Read {
     fn read_slice<'r>(&'r mut self, len: usize) -> io::Result<&'r [u8]>;
 }
 
-struct SliceReader<'a> {
+pub struct SliceReader<'a> {
     inner: &'a [u8],
 }
 
@@ -508,7 +1108,7 @@ impl<'r> io::Read for SliceReader<'r> {
     }
 }
 
-struct ReadReader<R: io::Read> {
+pub struct ReadReader<R: io::Read> {
     inner: R,
     buf: Vec<u8>
 }
@@ -1,16 +1,18 @@
 use std::error;
+use std::f32;
+use std::f64;
 use std::fmt::{self, Display};
-use std::io::Write;
+use std::io::{self, Write};
 
 use serde;
 use serde::Serialize;
-use serde::ser::{SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+use serde::ser::{Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
                  SerializeTuple, SerializeTupleStruct, SerializeTupleVariant};
 
 use rmp;
 use rmp::Marker;
-use rmp::encode::{write_nil, write_bool, write_uint, write_sint, write_f32, write_f64, write_str,
-                  write_array_len, write_map_len, write_bin_len, ValueWriteError};
+use rmp::encode::{write_nil, write_bool, write_uint, write_sint, write_f32, write_f64, write_float,
+                  write_str, write_array_len, write_map_len, write_bin_len, ValueWriteError};
 
 #[derive(Debug)]
 pub enum Error {
@@ -21,6 +23,8 @@ pub enum Error {
 
     /// Depth limit exceeded
     DepthLimitExceeded,
+    /// Failed to serialize map key in string keys mode, because it has no string representation.
+    KeyMustBeString,
     Syntax(String),
 }
 
@@ -32,6 +36,7 @@ impl error::Error for Error {
                 "attempt to serialize struct, sequence or map with unknown length"
             }
             Error::DepthLimitExceeded => "depth limit exceeded",
+            Error::KeyMustBeString => "map key cannot be represented as a string",
             Error::Syntax(..) => "syntax error",
         }
     }
@@ -41,6 +46,7 @@ impl error::Error for Error {
             Error::InvalidValueWrite(ref err) => Some(err),
             Error::UnknownLength => None,
             Error::DepthLimitExceeded => None,
+            Error::KeyMustBeString => None,
             Error::Syntax(..) => None,
         }
     }
@@ -92,6 +98,21 @@ impl VariantWriter for StructArrayWriter {
     }
 }
 
+/// Strategy used by the `Serializer` to encode floating point numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatEncoding {
+    /// Floats are written with the width of their Rust type. This is the default.
+    Exact,
+    /// `f64` values are written as `f32` if the conversion is lossless.
+    Compact,
+    /// Like `Compact`, but additionally floats with an integral value, that fits in either `i64`
+    /// or `u64`, are written as integers. Negative zero is kept as a float to preserve its sign.
+    ///
+    /// Note, that the type of the value is lost, but the `Deserializer` accepts integers where
+    /// floats are expected.
+    Integral,
+}
+
 /// Represents MessagePack serialization implementation.
 ///
 /// # Note
@@ -108,6 +129,11 @@ pub struct Serializer<W, V> {
     wr: W,
     vw: V,
     depth: usize,
+    canonical: bool,
+    floats: FloatEncoding,
+    string_keys: bool,
+    // Maps being collected in canonical mode, from the outermost to the innermost one.
+    maps: Vec<MapBuffer>,
 }
 
 impl<W, V> Serializer<W, V> {
@@ -115,6 +141,118 @@ impl<W, V> Serializer<W, V> {
     pub fn set_max_depth(&mut self, depth: usize) {
         self.depth = depth;
     }
+
+    /// Enables or disables the canonical encoding mode, which is disabled by default.
+    ///
+    /// In canonical mode the output depends only on the serialized data, which makes it suitable
+    /// for signing and content addressing:
+    ///
+    /// - map entries are sorted by their encoded keys in bytewise lexicographic order, so
+    ///   `HashMap` iteration order no longer matters;
+    /// - entries with duplicate encoded keys are collapsed, keeping the last one;
+    /// - non-negative integers are always written using the unsigned family;
+    /// - every NaN is written as the canonical quiet NaN of its width.
+    ///
+    /// Each map is buffered in memory until all of its entries are serialized.
+    ///
+    /// Note, that structs are written by the `VariantWriter` in their declaration order, which is
+    /// already deterministic, but is not sorted if the writer encodes them as maps.
+    ///
+    /// The output can be verified using `rmp::decode::check_canonical`.
+    pub fn set_canonical(&mut self, canonical: bool) {
+        self.canonical = canonical;
+    }
+
+    /// Changes the strategy used to encode floating point numbers.
+    ///
+    /// By default floats are written as is, always taking 9 bytes for `f64`. Using
+    /// `FloatEncoding::Compact` halves the size of values, that are exactly representable as `f32`.
+    pub fn set_float_encoding(&mut self, floats: FloatEncoding) {
+        self.floats = floats;
+    }
+
+    /// Enables or disables the string keys mode, which is disabled by default.
+    ///
+    /// In this mode map keys are always written as strings, which is required by consumers, that
+    /// convert MessagePack to JSON. Booleans, numbers and chars are formatted using their
+    /// `Display` implementation and unit variants are written as their names. Other keys cause
+    /// `Error::KeyMustBeString`.
+    ///
+    /// Such keys can be read back into numeric and boolean types by enabling
+    /// `Deserializer::set_key_coercion`.
+    pub fn set_string_keys(&mut self, string_keys: bool) {
+        self.string_keys = string_keys;
+    }
+}
+
+impl<W: Write, V> Serializer<W, V> {
+    fn output(&mut self) -> Output<W> {
+        Output {
+            wr: &mut self.wr,
+            maps: &mut self.maps,
+        }
+    }
+
+    // Writes the given float as an integer if allowed and if it has an integral value, that fits
+    // either in `u64` or in `i64`. Returns whether the value was written.
+    fn write_integral_float(&mut self, v: f64) -> Result<bool, Error> {
+        if self.floats != FloatEncoding::Integral || v.fract() != 0.0 ||
+           (v == 0.0 && v.is_sign_negative()) {
+            return Ok(false);
+        }
+
+        if 0.0 <= v && v < 18446744073709551616.0 {
+            write_uint(&mut self.output(), v as u64)?;
+        } else if -9223372036854775808.0 <= v && v < 0.0 {
+            write_sint(&mut self.output(), v as i64)?;
+        } else {
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+}
+
+// Encoded entries of a map, which are written sorted when the map is complete.
+struct MapBuffer {
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    // Whether the key of the last entry is being written, otherwise its value.
+    key: bool,
+}
+
+impl MapBuffer {
+    fn new(len: usize) -> Self {
+        MapBuffer {
+            entries: Vec::with_capacity(len),
+            key: false,
+        }
+    }
+}
+
+// A writer, which redirects the output into the innermost map being collected, if any.
+struct Output<'a, W: 'a> {
+    wr: &'a mut W,
+    maps: &'a mut Vec<MapBuffer>,
+}
+
+impl<'a, W: Write> Write for Output<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.maps.last_mut() {
+            Some(map) => {
+                let key = map.key;
+                match map.entries.last_mut() {
+                    Some(&mut (ref mut kbuf, _)) if key => kbuf.write(buf),
+                    Some(&mut (_, ref mut vbuf)) => vbuf.write(buf),
+                    None => Err(io::Error::new(io::ErrorKind::Other, "map entry is not started")),
+                }
+            }
+            None => self.wr.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.wr.flush()
+    }
 }
 
 impl<W: Write> Serializer<W, StructArrayWriter> {
@@ -124,6 +262,10 @@ impl<W: Write> Serializer<W, StructArrayWriter> {
             wr: wr,
             vw: StructArrayWriter,
             depth: 1024,
+            canonical: false,
+            floats: FloatEncoding::Exact,
+            string_keys: false,
+            maps: Vec::new(),
         }
     }
 }
@@ -141,6 +283,10 @@ impl<W: Write, V: VariantWriter> Serializer<W, V> {
             wr: wr,
             vw: vw,
             depth: 1024,
+            canonical: false,
+            floats: FloatEncoding::Exact,
+            string_keys: false,
+            maps: Vec::new(),
         }
     }
 }
@@ -207,14 +353,48 @@ impl<'a, W: Write + 'a, V: VariantWriter + 'a> SerializeMap for Compound<'a, W,
     type Error = Error;
 
     fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
-        key.serialize(&mut *self.se)
+        if let Some(map) = self.se.maps.last_mut() {
+            map.entries.push((Vec::new(), Vec::new()));
+            map.key = true;
+        }
+
+        if self.se.string_keys {
+            key.serialize(KeySerializer { se: &mut *self.se })
+        } else {
+            key.serialize(&mut *self.se)
+        }
     }
 
     fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        if let Some(map) = self.se.maps.last_mut() {
+            map.key = false;
+        }
         value.serialize(&mut *self.se)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if !self.se.canonical {
+            return Ok(());
+        }
+
+        let mut entries = match self.se.maps.pop() {
+            Some(map) => map.entries,
+            None => return Ok(()),
+        };
+
+        // Reverse first, so that the stable sort followed by dedup keeps the last duplicate.
+        entries.reverse();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.dedup_by(|a, b| a.0 == b.0);
+
+        let mut wr = self.se.output();
+        write_map_len(&mut wr, entries.len() as u32)?;
+        for &(ref key, ref val) in &entries {
+            wr.write_all(key)
+                .and_then(|()| wr.write_all(val))
+                .map_err(|err| Error::InvalidValueWrite(ValueWriteError::InvalidDataWrite(err)))?;
+        }
+
         Ok(())
     }
 }
@@ -226,7 +406,11 @@ impl<'a, W: Write + 'a, V: VariantWriter + 'a> SerializeStruct for Compound<'a,
     fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) ->
         Result<(), Self::Error>
     {
-        self.se.vw.write_field_name(&mut self.se.wr, key)?;
+        let mut wr = Output {
+            wr: &mut self.se.wr,
+            maps: &mut self.se.maps,
+        };
+        self.se.vw.write_field_name(&mut wr, key)?;
         value.serialize(&mut *self.se)
     }
 
@@ -263,7 +447,7 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
     type SerializeStructVariant = Compound<'a, W, V>;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
-        write_bool(&mut self.wr, v)
+        write_bool(&mut self.output(), v)
             .map_err(|err| Error::InvalidValueWrite(ValueWriteError::InvalidMarkerWrite(err)))
     }
 
@@ -280,7 +464,11 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        write_sint(&mut self.wr, v)?;
+        if self.canonical && v >= 0 {
+            write_uint(&mut self.output(), v as u64)?;
+        } else {
+            write_sint(&mut self.output(), v)?;
+        }
         Ok(())
     }
 
@@ -297,17 +485,29 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        write_uint(&mut self.wr, v)?;
+        write_uint(&mut self.output(), v)?;
         Ok(())
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-        write_f32(&mut self.wr, v)?;
+        let v = if self.canonical && v.is_nan() { f32::NAN } else { v };
+        if !self.write_integral_float(v as f64)? {
+            write_f32(&mut self.output(), v)?;
+        }
         Ok(())
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        write_f64(&mut self.wr, v)?;
+        let v = if self.canonical && v.is_nan() { f64::NAN } else { v };
+        if !self.write_integral_float(v)? {
+            match self.floats {
+                FloatEncoding::Exact => write_f64(&mut self.output(), v)?,
+                FloatEncoding::Compact |
+                FloatEncoding::Integral => {
+                    write_float(&mut self.output(), v)?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -319,13 +519,13 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        write_str(&mut self.wr, v)?;
+        write_str(&mut self.output(), v)?;
         Ok(())
     }
 
     fn serialize_bytes(self, value: &[u8]) -> Result<Self::Ok, Self::Error> {
-        write_bin_len(&mut self.wr, value.len() as u32)?;
-        self.wr
+        write_bin_len(&mut self.output(), value.len() as u32)?;
+        self.output()
             .write_all(value)
             .map_err(|err| Error::InvalidValueWrite(ValueWriteError::InvalidDataWrite(err)))
     }
@@ -339,21 +539,25 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        write_nil(&mut self.wr)
+        write_nil(&mut self.output())
             .map_err(|err| Error::InvalidValueWrite(ValueWriteError::InvalidMarkerWrite(err)))
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
-        self.vw.write_struct_len(&mut self.wr, 0)?;
+        let mut wr = Output {
+            wr: &mut self.wr,
+            maps: &mut self.maps,
+        };
+        self.vw.write_struct_len(&mut wr, 0)?;
         Ok(())
     }
 
     fn serialize_unit_variant(self, _name: &str, idx: usize, _variant: &str) ->
         Result<(), Self::Error>
     {
-        write_array_len(&mut self.wr, 2)?;
+        write_array_len(&mut self.output(), 2)?;
         self.serialize_u64(idx as u64)?;
-        write_array_len(&mut self.wr, 0)?;
+        write_array_len(&mut self.output(), 0)?;
         Ok(())
     }
 
@@ -373,7 +577,7 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
             None => return Err(Error::UnknownLength),
         };
 
-        write_array_len(&mut self.wr, len as u32)?;
+        write_array_len(&mut self.output(), len as u32)?;
 
         Ok(Compound { se: self })
     }
@@ -400,7 +604,7 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
                                len: usize)
                                -> Result<Self::SerializeTupleVariant, Error> {
         // We encode variant types as a tuple of id with array of args, like: [id, [args...]].
-        rmp::encode::write_array_len(&mut self.wr, 2)?;
+        rmp::encode::write_array_len(&mut self.output(), 2)?;
         self.serialize_u64(idx as u64)?;
         self.serialize_tuple_struct(name, len)
     }
@@ -408,7 +612,12 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
         match len {
             Some(len) => {
-                write_map_len(&mut self.wr, len as u32)?;
+                if self.canonical {
+                    // The length is written at the end, because duplicate keys may be collapsed.
+                    self.maps.push(MapBuffer::new(len));
+                } else {
+                    write_map_len(&mut self.output(), len as u32)?;
+                }
                 Ok(Compound { se: self })
             }
             None => Err(Error::UnknownLength),
@@ -418,7 +627,11 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
     fn serialize_struct(self, _name: &'static str, len: usize) ->
         Result<Self::SerializeStruct, Self::Error>
     {
-        self.vw.write_struct_len(&mut self.wr, len as u32)?;
+        let mut wr = Output {
+            wr: &mut self.wr,
+            maps: &mut self.maps,
+        };
+        self.vw.write_struct_len(&mut wr, len as u32)?;
         Ok(Compound { se: self })
     }
 
@@ -428,12 +641,162 @@ impl<'a, W: Write, V: VariantWriter> serde::Serializer for &'a mut Serializer<W,
                                 _variant: &'static str,
                                 len: usize)
                                 -> Result<Self::SerializeStructVariant, Error> {
-        write_array_len(&mut self.wr, 2)?;
+        write_array_len(&mut self.output(), 2)?;
         self.serialize_u64(id as u64)?;
         self.serialize_struct(name, len)
     }
 }
 
+// Serializes map keys in string keys mode, formatting scalars as strings.
+struct KeySerializer<'a, W: 'a, V: 'a> {
+    se: &'a mut Serializer<W, V>,
+}
+
+impl<'a, W: Write, V: VariantWriter> KeySerializer<'a, W, V> {
+    fn serialize_display<T: Display>(self, v: T) -> Result<(), Error> {
+        serde::Serializer::serialize_str(self.se, &format!("{}", v))
+    }
+}
+
+impl<'a, W: Write, V: VariantWriter> serde::Serializer for KeySerializer<'a, W, V> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_display(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        serde::Serializer::serialize_char(self.se, v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        serde::Serializer::serialize_str(self.se, v)
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_none(self) -> Result<(), Self::Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_some<T: ?Sized + serde::Serialize>(self, v: &T) -> Result<(), Self::Error> {
+        v.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_unit_variant(self, _name: &str, _idx: usize, variant: &str) ->
+        Result<Self::Ok, Self::Error>
+    {
+        serde::Serializer::serialize_str(self.se, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + serde::Serialize>(self, _name: &'static str, value: &T) -> Result<(), Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + serde::Serialize>(self, _name: &'static str, _variant_index: usize, _variant: &'static str, _value: &T) -> Result<(), Self::Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_seq_fixed_size(self, _size: usize) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) ->
+        Result<Self::SerializeTupleStruct, Self::Error>
+    {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _idx: usize, _variant: &'static str,
+                               _len: usize) -> Result<Self::SerializeTupleVariant, Error>
+    {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) ->
+        Result<Self::SerializeStruct, Self::Error>
+    {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _idx: usize, _variant: &'static str,
+                                _len: usize) -> Result<Self::SerializeStructVariant, Error>
+    {
+        Err(Error::KeyMustBeString)
+    }
+}
+
 /// Serialize the given data structure as MessagePack into the I/O stream.
 ///
 /// Serialization can fail if `T`'s implementation of `Serialize` decides to fail.
@@ -6,7 +6,7 @@ use serde::Deserialize;
 
 use rmp::Marker;
 use rmp_serde::Deserializer;
-use rmp_serde::decode::{self, Error};
+use rmp_serde::decode::{self, Error, NumberCoercion};
 
 #[test]
 fn pass_nil() {
@@ -445,3 +445,230 @@ fn pass_deserializer_cursor_position() {
 fn pass_from() {
     assert_eq!(2147483647, decode::from_read(&[0xd2, 0x7f, 0xff, 0xff, 0xff][..]).unwrap());
 }
+
+#[test]
+fn pass_f64_from_f32() {
+    let buf = [0xca, 0x3f, 0x00, 0x00, 0x00];
+    let cur = Cursor::new(&buf[..]);
+
+    let mut deserializer = Deserializer::new(cur);
+
+    assert_eq!(0.5f64, Deserialize::deserialize(&mut deserializer).unwrap());
+}
+
+#[test]
+fn pass_f64_from_int() {
+    let buf = [0x2a, 0xd1, 0xfe, 0xd4];
+    let cur = Cursor::new(&buf[..]);
+
+    let mut deserializer = Deserializer::new(cur);
+
+    assert_eq!(42f64, Deserialize::deserialize(&mut deserializer).unwrap());
+    assert_eq!(-300f64, Deserialize::deserialize(&mut deserializer).unwrap());
+}
+
+#[test]
+fn pass_coerce_int_lenient() {
+    let buf = [0xd1, 0x00, 0x2a, 0xcd, 0x01, 0x00, 0x2a];
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_number_coercion(NumberCoercion::Lenient);
+
+    assert_eq!(42u8, Deserialize::deserialize(&mut deserializer).unwrap());
+    assert_eq!(256i16, Deserialize::deserialize(&mut deserializer).unwrap());
+    assert_eq!(42f32, Deserialize::deserialize(&mut deserializer).unwrap());
+}
+
+#[test]
+fn fail_coerce_int_out_of_range() {
+    let buf = [0xcd, 0x01, 0x00];
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_number_coercion(NumberCoercion::Lenient);
+
+    let res: Result<u8, Error> = Deserialize::deserialize(&mut deserializer);
+    match res.err() {
+        Some(err @ Error::NumberOutOfRange(..)) => {
+            assert_eq!("value 256 is out of range of u8", err.to_string());
+        }
+        other => panic!("unexpected result: {:?}", other)
+    }
+}
+
+// Accepts only `f32` values, which makes it possible to observe numeric coercion.
+struct F32(f32);
+
+impl Deserialize for F32 {
+    fn deserialize<D: de::Deserializer>(de: D) -> Result<F32, D::Error> {
+        struct F32Visitor;
+
+        impl de::Visitor for F32Visitor {
+            type Value = F32;
+
+            fn expecting(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+                write!(fmt, "an f32")
+            }
+
+            fn visit_f32<E>(self, value: f32) -> Result<F32, E> {
+                Ok(F32(value))
+            }
+        }
+
+        de.deserialize_f32(F32Visitor)
+    }
+}
+
+#[test]
+fn pass_coerce_int_to_float_visitor() {
+    let buf = [0xd0, 0xd6];
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_number_coercion(NumberCoercion::Lenient);
+
+    let F32(val) = Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(-42f32, val);
+}
+
+#[test]
+fn pass_coerce_integral_float() {
+    let buf = [0xcb, 0x40, 0x45, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xca, 0xc0, 0x40, 0x00, 0x00];
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_number_coercion(NumberCoercion::Integral);
+
+    assert_eq!(42u64, Deserialize::deserialize(&mut deserializer).unwrap());
+    assert_eq!(-3i8, Deserialize::deserialize(&mut deserializer).unwrap());
+}
+
+#[test]
+fn fail_coerce_integral_float_out_of_range() {
+    let buf = [0xcb, 0x43, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_number_coercion(NumberCoercion::Integral);
+
+    let res: Result<u64, Error> = Deserialize::deserialize(&mut deserializer);
+    match res.err() {
+        Some(Error::NumberOutOfRange(ref val, "u64")) if val == "18446744073709552000" => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+}
+
+#[test]
+fn fail_coerce_int_to_float_visitor_exact() {
+    let buf = [0xd0, 0xd6];
+    let mut deserializer = Deserializer::new(&buf[..]);
+
+    let res: Result<F32, Error> = Deserialize::deserialize(&mut deserializer);
+    assert!(res.is_err());
+}
+
+#[test]
+fn pass_key_coercion_from_str() {
+    use std::collections::BTreeMap;
+
+    // {"-1": nil, "42": nil}
+    let buf = [0x82, 0xa2, 0x2d, 0x31, 0xc0, 0xa2, 0x34, 0x32, 0xc0];
+
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_key_coercion(true);
+
+    let val: BTreeMap<i32, ()> = Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(vec![-1, 42], val.keys().cloned().collect::<Vec<_>>());
+}
+
+#[test]
+fn pass_key_coercion_bool_from_str() {
+    use std::collections::BTreeMap;
+
+    // {"true": 1}
+    let buf = [0x81, 0xa4, 0x74, 0x72, 0x75, 0x65, 0x01];
+
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_key_coercion(true);
+
+    let val: BTreeMap<bool, u8> = Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(Some(&1), val.get(&true));
+}
+
+#[test]
+fn pass_key_coercion_to_str() {
+    use std::collections::BTreeMap;
+
+    // {-1: nil, false: nil}
+    let buf = [0x82, 0xff, 0xc0, 0xc2, 0xc0];
+
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_key_coercion(true);
+
+    let val: BTreeMap<String, ()> = Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(vec!["-1", "false"], val.keys().map(|k| k.as_str()).collect::<Vec<_>>());
+}
+
+#[test]
+fn fail_key_coercion_invalid_number() {
+    use std::collections::BTreeMap;
+
+    // {"x": nil}
+    let buf = [0x81, 0xa1, 0x78, 0xc0];
+
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_key_coercion(true);
+
+    let res: Result<BTreeMap<u32, ()>, Error> = Deserialize::deserialize(&mut deserializer);
+    assert!(res.is_err());
+}
+
+#[test]
+fn fail_key_coercion_disabled() {
+    use std::collections::BTreeMap;
+
+    // {42: nil}
+    let buf = [0x81, 0x2a, 0xc0];
+
+    let mut deserializer = Deserializer::new(&buf[..]);
+
+    let res: Result<BTreeMap<String, ()>, Error> = Deserialize::deserialize(&mut deserializer);
+    assert!(res.is_err());
+}
+
+#[test]
+fn pass_duplicate_keys_keep_first() {
+    use std::collections::BTreeMap;
+
+    // {1: 2, 4: 5, 1: 3}, where the last key is encoded using u16.
+    let buf = [0x83, 0x01, 0x02, 0x04, 0x05, 0xcd, 0x00, 0x01, 0x03];
+
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_duplicate_keys(decode::DuplicateKeys::KeepFirst);
+
+    let val: BTreeMap<u32, u32> = Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(vec![(1, 2), (4, 5)], val.into_iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn fail_duplicate_keys_reject_nested() {
+    use std::collections::BTreeMap;
+
+    // [{"a": [1], "b": [2], "a": [3]}]
+    let buf = [0x91, 0x83, 0xa1, 0x61, 0x91, 0x01, 0xa1, 0x62, 0x91, 0x02, 0xa1, 0x61, 0x91, 0x03];
+
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_duplicate_keys(decode::DuplicateKeys::Reject);
+
+    let res: Result<Vec<BTreeMap<String, Vec<u8>>>, Error> =
+        Deserialize::deserialize(&mut deserializer);
+    match res.err() {
+        Some(Error::DuplicateKey(2)) => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+}
+
+#[test]
+fn pass_duplicate_keys_reject_distinct() {
+    use std::collections::BTreeMap;
+
+    // {[1]: "a", [-1]: "b"}
+    let buf = [0x82, 0x91, 0x01, 0xa1, 0x61, 0x91, 0xff, 0xa1, 0x62];
+
+    let mut deserializer = Deserializer::new(&buf[..]);
+    deserializer.set_duplicate_keys(decode::DuplicateKeys::Reject);
+
+    let val: BTreeMap<Vec<i8>, String> = Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(2, val.len());
+}
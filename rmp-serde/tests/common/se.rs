@@ -304,3 +304,154 @@ fn pass_to_vec() {
     assert_eq!(vec![0xaa, 0x6c, 0x65, 0x20, 0x6d, 0x65, 0x73, 0x73, 0x61, 0x67, 0x65],
         encode::to_vec("le message").unwrap());
 }
+
+#[test]
+fn pass_canonical_hash_map() {
+    use std::collections::HashMap;
+
+    let mut val = HashMap::new();
+    for key in 0..64u32 {
+        val.insert(key * 7, key);
+    }
+
+    let mut buf = Vec::new();
+    {
+        let mut se = Serializer::new(&mut buf);
+        se.set_canonical(true);
+        val.serialize(&mut se).unwrap();
+    }
+
+    assert!(rmp::decode::check_canonical(&buf).is_ok());
+}
+
+#[test]
+fn pass_canonical_nested_map_sorted() {
+    use std::collections::BTreeMap;
+
+    let mut inner = BTreeMap::new();
+    inner.insert(-1i64, ());
+    inner.insert(1i64, ());
+
+    let mut val = BTreeMap::new();
+    val.insert("b", inner.clone());
+    val.insert("a", inner);
+
+    let mut buf = Vec::new();
+    {
+        let mut se = Serializer::new(&mut buf);
+        se.set_canonical(true);
+        val.serialize(&mut se).unwrap();
+    }
+
+    // The negative fixint 0xff sorts after the positive fixint 0x01.
+    assert_eq!(vec![0x82, 0xa1, 0x61, 0x82, 0x01, 0xc0, 0xff, 0xc0,
+                          0xa1, 0x62, 0x82, 0x01, 0xc0, 0xff, 0xc0], buf);
+}
+
+#[test]
+fn pass_canonical_duplicate_keys_keep_last() {
+    use serde::Serializer as SerdeSerializer;
+    use serde::ser::SerializeMap;
+
+    struct Duplicates;
+
+    impl Serialize for Duplicates {
+        fn serialize<S: SerdeSerializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            let mut state = s.serialize_map(Some(3))?;
+            state.serialize_entry(&1u8, &false)?;
+            state.serialize_entry(&2u8, &false)?;
+            state.serialize_entry(&1i64, &true)?;
+            state.end()
+        }
+    }
+
+    let mut buf = Vec::new();
+    {
+        let mut se = Serializer::new(&mut buf);
+        se.set_canonical(true);
+        Duplicates.serialize(&mut se).unwrap();
+    }
+
+    assert_eq!(vec![0x82, 0x01, 0xc3, 0x02, 0xc2], buf);
+}
+
+#[test]
+fn pass_canonical_nan_and_uint() {
+    let mut buf = Vec::new();
+    {
+        let mut se = Serializer::new(&mut buf);
+        se.set_canonical(true);
+        (-::std::f64::NAN, 4294967296i64).serialize(&mut se).unwrap();
+    }
+
+    assert_eq!(vec![0x92, 0xcb, 0x7f, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0xcf, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00], buf);
+}
+
+#[test]
+fn pass_f64_compact() {
+    use rmp_serde::encode::FloatEncoding;
+
+    let mut buf = Vec::new();
+    {
+        let mut se = Serializer::new(&mut buf);
+        se.set_float_encoding(FloatEncoding::Compact);
+        (0.5f64, 0.1f64, 42.0f64).serialize(&mut se).unwrap();
+    }
+
+    assert_eq!(vec![0x93, 0xca, 0x3f, 0x00, 0x00, 0x00,
+                          0xcb, 0x3f, 0xb9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a,
+                          0xca, 0x42, 0x28, 0x00, 0x00], buf);
+}
+
+#[test]
+fn pass_float_integral() {
+    use rmp_serde::encode::FloatEncoding;
+
+    let mut buf = Vec::new();
+    {
+        let mut se = Serializer::new(&mut buf);
+        se.set_float_encoding(FloatEncoding::Integral);
+        (42.0f64, -300.0f32, -0.0f64, 0.5f64, 1e20f64).serialize(&mut se).unwrap();
+    }
+
+    assert_eq!(vec![0x95, 0x2a,
+                          0xd1, 0xfe, 0xd4,
+                          0xca, 0x80, 0x00, 0x00, 0x00,
+                          0xca, 0x3f, 0x00, 0x00, 0x00,
+                          0xcb, 0x44, 0x15, 0xaf, 0x1d, 0x78, 0xb5, 0x8c, 0x40], buf);
+}
+
+#[test]
+fn pass_string_keys() {
+    use std::collections::BTreeMap;
+
+    let mut val = BTreeMap::new();
+    val.insert(-1i64, true);
+    val.insert(42i64, false);
+
+    let mut buf = Vec::new();
+    {
+        let mut se = Serializer::new(&mut buf);
+        se.set_string_keys(true);
+        val.serialize(&mut se).unwrap();
+    }
+
+    assert_eq!(vec![0x82, 0xa2, 0x2d, 0x31, 0xc3, 0xa2, 0x34, 0x32, 0xc2], buf);
+}
+
+#[test]
+fn fail_string_keys_compound_key() {
+    use std::collections::BTreeMap;
+
+    let mut val = BTreeMap::new();
+    val.insert(vec![1, 2], ());
+
+    let mut se = Serializer::new(Vec::new());
+    se.set_string_keys(true);
+
+    match val.serialize(&mut se) {
+        Err(Error::KeyMustBeString) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,294 @@
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::io::ErrorKind;
+
+use Marker;
+use super::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64, read_data_i8,
+            read_data_i16, read_data_i32, read_data_i64, read_data_f32, read_data_f64, Error,
+            ValueReadError};
+
+/// The decoded content of a `Token`.
+///
+/// Strings are kept as raw bytes, because they are not guaranteed to be valid UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenValue<'a> {
+    /// Nil.
+    Nil,
+    /// A boolean.
+    Boolean(bool),
+    /// An unsigned integer, including positive fixints.
+    Uint(u64),
+    /// A signed integer, including negative fixints.
+    Sint(i64),
+    /// A single precision float.
+    F32(f32),
+    /// A double precision float.
+    F64(f64),
+    /// A string with its raw bytes.
+    Str(&'a [u8]),
+    /// A binary.
+    Bin(&'a [u8]),
+    /// An array header with the number of elements, which follow as separate tokens.
+    Array(u32),
+    /// A map header with the number of entries, which follow as separate key and value tokens.
+    Map(u32),
+    /// An extension with its type and data.
+    Ext(i8, &'a [u8]),
+}
+
+/// A single MessagePack marker together with its header and payload.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Token<'a> {
+    /// The offset of the marker relative to the beginning of the buffer.
+    pub offset: usize,
+    /// The raw bytes of the token: the marker, the length or type if any and, except for arrays
+    /// and maps, the payload.
+    pub bytes: &'a [u8],
+    /// The nesting depth, where top-level values have depth 0.
+    pub depth: usize,
+    /// The marker.
+    pub marker: Marker,
+    /// The decoded value.
+    pub value: TokenValue<'a>,
+}
+
+/// The first malformed token of a buffer, which ends the annotation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Malformed<'a> {
+    /// The offset of the token relative to the beginning of the buffer.
+    pub offset: usize,
+    /// The bytes of the token up to and including the malformed one, i.e. the reserved marker
+    /// or the rest of the truncated buffer. Empty if the buffer ended where a marker was expected.
+    pub bytes: &'a [u8],
+    /// The nesting depth, where top-level values have depth 0.
+    pub depth: usize,
+    /// The marker, which is `Marker::Reserved` for the reserved marker and some other marker if
+    /// the buffer is truncated. `None` if the buffer ended where a marker was expected.
+    pub marker: Option<Marker>,
+}
+
+impl<'a> Malformed<'a> {
+    fn reason(&self) -> &'static str {
+        match self.marker {
+            Some(Marker::Reserved) => "reserved marker",
+            Some(..) | None => "unexpected end of buffer",
+        }
+    }
+}
+
+impl<'a> error::Error for Malformed<'a> {
+    fn description(&self) -> &str {
+        self.reason()
+    }
+}
+
+impl<'a> Display for Malformed<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} at offset {}", self.reason(), self.offset)
+    }
+}
+
+/// An iterator over tokens of a buffer created by `annotate`.
+#[derive(Clone, Debug)]
+pub struct Annotations<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    // The number of values remaining in each enclosing array or map and their depth.
+    remaining: Vec<(u64, usize)>,
+    done: bool,
+}
+
+impl<'a> Iterator for Annotations<'a> {
+    type Item = Result<Token<'a>, Malformed<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.pos == self.buf.len() && self.remaining.is_empty() {
+            return None;
+        }
+
+        let depth = self.remaining.last().map_or(0, |&(_, depth)| depth);
+        let token = match read_token(self.buf, self.pos, depth) {
+            Ok(token) => token,
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            }
+        };
+
+        self.pos += token.bytes.len();
+
+        if let Some(&mut (ref mut count, _)) = self.remaining.last_mut() {
+            *count -= 1;
+        }
+        while let Some(&(0, _)) = self.remaining.last() {
+            self.remaining.pop();
+        }
+
+        let children = match token.value {
+            TokenValue::Array(len) => len as u64,
+            TokenValue::Map(len) => 2 * len as u64,
+            _ => 0,
+        };
+        if children > 0 {
+            self.remaining.push((children, depth + 1));
+        }
+
+        Some(Ok(token))
+    }
+}
+
+/// Walks over all MessagePack values in the given buffer, producing a token for every marker in
+/// the order they are encoded.
+///
+/// Each token carries its offset, raw bytes, marker, decoded value and nesting depth, which makes
+/// it possible to explain the buffer byte by byte, e.g. when debugging a failed decoding. Elements
+/// of arrays and maps follow their header as separate tokens with greater depth.
+///
+/// The iteration is tolerant to errors: all well-formed tokens are produced before the first
+/// malformed one is reported as `Malformed` together with its bytes, after which the iteration
+/// ends. Strings are not checked for UTF-8 validity.
+///
+/// # Examples
+///
+/// ```
+/// use rmp::Marker;
+/// use rmp::decode::{annotate, TokenValue};
+///
+/// // [1, "a"] followed by a truncated uint16.
+/// let buf = [0x92, 0x01, 0xa1, 0x61, 0xcd, 0x01];
+///
+/// let tokens: Vec<_> = annotate(&buf).collect();
+///
+/// assert_eq!(4, tokens.len());
+///
+/// let token = tokens[2].unwrap();
+/// assert_eq!(2, token.offset);
+/// assert_eq!(&[0xa1, 0x61], token.bytes);
+/// assert_eq!(1, token.depth);
+/// assert_eq!(Marker::FixStr(1), token.marker);
+/// assert_eq!(TokenValue::Str(b"a"), token.value);
+///
+/// let err = tokens[3].unwrap_err();
+/// assert_eq!(4, err.offset);
+/// assert_eq!(&[0xcd, 0x01], err.bytes);
+/// assert_eq!(Some(Marker::U16), err.marker);
+/// ```
+pub fn annotate<'a>(buf: &'a [u8]) -> Annotations<'a> {
+    Annotations {
+        buf: buf,
+        pos: 0,
+        remaining: Vec::new(),
+        done: false,
+    }
+}
+
+fn read_token<'a>(buf: &'a [u8], pos: usize, depth: usize) -> Result<Token<'a>, Malformed<'a>> {
+    let mut rd = &buf[pos..];
+
+    let malformed = |marker| {
+        let bytes = match marker {
+            Some(Marker::Reserved) => &buf[pos..pos + 1],
+            Some(..) | None => &buf[pos..],
+        };
+        Malformed {
+            offset: pos,
+            bytes: bytes,
+            depth: depth,
+            marker: marker,
+        }
+    };
+
+    let marker = read_marker(&mut rd).map_err(|_| malformed(None))?;
+    let truncated = |_: ValueReadError| malformed(Some(marker));
+
+    let value = match marker {
+        Marker::Null => TokenValue::Nil,
+        Marker::True => TokenValue::Boolean(true),
+        Marker::False => TokenValue::Boolean(false),
+        Marker::FixPos(val) => TokenValue::Uint(val as u64),
+        Marker::FixNeg(val) => TokenValue::Sint(val as i64),
+        Marker::U8 => TokenValue::Uint(read_data_u8(&mut rd).map_err(truncated)? as u64),
+        Marker::U16 => TokenValue::Uint(read_data_u16(&mut rd).map_err(truncated)? as u64),
+        Marker::U32 => TokenValue::Uint(read_data_u32(&mut rd).map_err(truncated)? as u64),
+        Marker::U64 => TokenValue::Uint(read_data_u64(&mut rd).map_err(truncated)?),
+        Marker::I8 => TokenValue::Sint(read_data_i8(&mut rd).map_err(truncated)? as i64),
+        Marker::I16 => TokenValue::Sint(read_data_i16(&mut rd).map_err(truncated)? as i64),
+        Marker::I32 => TokenValue::Sint(read_data_i32(&mut rd).map_err(truncated)? as i64),
+        Marker::I64 => TokenValue::Sint(read_data_i64(&mut rd).map_err(truncated)?),
+        Marker::F32 => TokenValue::F32(read_data_f32(&mut rd).map_err(truncated)?),
+        Marker::F64 => TokenValue::F64(read_data_f64(&mut rd).map_err(truncated)?),
+        Marker::FixStr(len) => TokenValue::Str(take(&mut rd, len as u32).map_err(truncated)?),
+        Marker::Str8 => {
+            let len = read_data_u8(&mut rd).map_err(truncated)? as u32;
+            TokenValue::Str(take(&mut rd, len).map_err(truncated)?)
+        }
+        Marker::Str16 => {
+            let len = read_data_u16(&mut rd).map_err(truncated)? as u32;
+            TokenValue::Str(take(&mut rd, len).map_err(truncated)?)
+        }
+        Marker::Str32 => {
+            let len = read_data_u32(&mut rd).map_err(truncated)?;
+            TokenValue::Str(take(&mut rd, len).map_err(truncated)?)
+        }
+        Marker::Bin8 => {
+            let len = read_data_u8(&mut rd).map_err(truncated)? as u32;
+            TokenValue::Bin(take(&mut rd, len).map_err(truncated)?)
+        }
+        Marker::Bin16 => {
+            let len = read_data_u16(&mut rd).map_err(truncated)? as u32;
+            TokenValue::Bin(take(&mut rd, len).map_err(truncated)?)
+        }
+        Marker::Bin32 => {
+            let len = read_data_u32(&mut rd).map_err(truncated)?;
+            TokenValue::Bin(take(&mut rd, len).map_err(truncated)?)
+        }
+        Marker::FixArray(len) => TokenValue::Array(len as u32),
+        Marker::Array16 => TokenValue::Array(read_data_u16(&mut rd).map_err(truncated)? as u32),
+        Marker::Array32 => TokenValue::Array(read_data_u32(&mut rd).map_err(truncated)?),
+        Marker::FixMap(len) => TokenValue::Map(len as u32),
+        Marker::Map16 => TokenValue::Map(read_data_u16(&mut rd).map_err(truncated)? as u32),
+        Marker::Map32 => TokenValue::Map(read_data_u32(&mut rd).map_err(truncated)?),
+        Marker::FixExt1 => read_ext(&mut rd, 1).map_err(truncated)?,
+        Marker::FixExt2 => read_ext(&mut rd, 2).map_err(truncated)?,
+        Marker::FixExt4 => read_ext(&mut rd, 4).map_err(truncated)?,
+        Marker::FixExt8 => read_ext(&mut rd, 8).map_err(truncated)?,
+        Marker::FixExt16 => read_ext(&mut rd, 16).map_err(truncated)?,
+        Marker::Ext8 => {
+            let len = read_data_u8(&mut rd).map_err(truncated)? as u32;
+            read_ext(&mut rd, len).map_err(truncated)?
+        }
+        Marker::Ext16 => {
+            let len = read_data_u16(&mut rd).map_err(truncated)? as u32;
+            read_ext(&mut rd, len).map_err(truncated)?
+        }
+        Marker::Ext32 => {
+            let len = read_data_u32(&mut rd).map_err(truncated)?;
+            read_ext(&mut rd, len).map_err(truncated)?
+        }
+        Marker::Reserved => return Err(malformed(Some(marker))),
+    };
+
+    Ok(Token {
+        offset: pos,
+        bytes: &buf[pos..buf.len() - rd.len()],
+        depth: depth,
+        marker: marker,
+        value: value,
+    })
+}
+
+fn read_ext<'a>(rd: &mut &'a [u8], len: u32) -> Result<TokenValue<'a>, ValueReadError> {
+    let ty = read_data_i8(rd)?;
+    Ok(TokenValue::Ext(ty, take(rd, len)?))
+}
+
+fn take<'a>(rd: &mut &'a [u8], len: u32) -> Result<&'a [u8], ValueReadError> {
+    let len = len as usize;
+    if rd.len() < len {
+        let err = Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer");
+        return Err(ValueReadError::InvalidDataRead(err));
+    }
+    let (head, tail) = rd.split_at(len);
+    *rd = tail;
+    Ok(head)
+}
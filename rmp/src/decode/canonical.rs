@@ -0,0 +1,320 @@
+use std::cmp::Ordering;
+use std::error;
+use std::f32;
+use std::f64;
+use std::fmt::{self, Display, Formatter};
+use std::io::ErrorKind;
+
+use Marker;
+use super::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64, read_data_i8,
+            read_data_i16, read_data_i32, read_data_i64, read_data_f32, read_data_f64, Error,
+            ValueReadError};
+
+/// The maximum nesting depth of arrays and maps accepted by `check_canonical`.
+const MAX_DEPTH: usize = 512;
+
+/// An error describing the first reason why a buffer is not in canonical form.
+///
+/// Every offset points to the first byte of the offending value relative to the beginning of the
+/// checked buffer.
+#[derive(Debug)]
+pub enum CanonicalError {
+    /// Failed to read the value, either because the buffer is truncated or because it contains
+    /// the reserved marker.
+    InvalidValueRead(ValueReadError),
+    /// The value is encoded using a wider marker than its content requires.
+    NonMinimal(usize, Marker),
+    /// The map key is not greater than the previous key of the same map.
+    UnsortedKey(usize),
+    /// The map key is byte-identical to the previous key of the same map.
+    DuplicateKey(usize),
+    /// The float is a NaN with a bit pattern other than the canonical quiet NaN.
+    NonCanonicalNaN(usize),
+    /// The buffer contains extra bytes after the value.
+    TrailingBytes(usize),
+    /// The array or map is nested deeper than 512 levels.
+    DepthLimitExceeded(usize),
+}
+
+impl CanonicalError {
+    fn reason(&self) -> &'static str {
+        match *self {
+            CanonicalError::InvalidValueRead(..) => "failed to read MessagePack value",
+            CanonicalError::NonMinimal(..) => "value is not encoded using the most compact marker",
+            CanonicalError::UnsortedKey(..) => "map keys are not sorted by their encoded bytes",
+            CanonicalError::DuplicateKey(..) => "map contains duplicate keys",
+            CanonicalError::NonCanonicalNaN(..) => "NaN is not encoded as the canonical quiet NaN",
+            CanonicalError::TrailingBytes(..) => "unexpected trailing bytes after the value",
+            CanonicalError::DepthLimitExceeded(..) => "depth limit exceeded",
+        }
+    }
+}
+
+impl error::Error for CanonicalError {
+    fn description(&self) -> &str {
+        self.reason()
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            CanonicalError::InvalidValueRead(ref err) => Some(err),
+            CanonicalError::NonMinimal(..) |
+            CanonicalError::UnsortedKey(..) |
+            CanonicalError::DuplicateKey(..) |
+            CanonicalError::NonCanonicalNaN(..) |
+            CanonicalError::TrailingBytes(..) |
+            CanonicalError::DepthLimitExceeded(..) => None,
+        }
+    }
+}
+
+impl Display for CanonicalError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.reason())
+    }
+}
+
+impl From<ValueReadError> for CanonicalError {
+    fn from(err: ValueReadError) -> CanonicalError {
+        CanonicalError::InvalidValueRead(err)
+    }
+}
+
+/// Checks whether the given buffer contains exactly one MessagePack value encoded in canonical
+/// form.
+///
+/// A value is canonical when:
+///
+/// - every integer, string, binary, array, map and ext uses the most compact marker, and
+///   non-negative integers use the unsigned family;
+/// - every map has its keys sorted in bytewise lexicographic order of their encoded form, without
+///   duplicates;
+/// - every NaN is encoded as the canonical quiet NaN of its width.
+///
+/// Floats are allowed to be encoded either as `f32` or `f64`, because the width is considered to
+/// be a part of the value.
+///
+/// # Errors
+///
+/// Returns `CanonicalError` describing the first violation found in the buffer. Arrays and maps
+/// nested deeper than 512 levels are rejected with `CanonicalError::DepthLimitExceeded`.
+///
+/// # Examples
+///
+/// ```
+/// use rmp::decode::{check_canonical, CanonicalError};
+///
+/// assert!(check_canonical(&[0x82, 0x01, 0xc0, 0x02, 0xc0]).is_ok());
+///
+/// match check_canonical(&[0x82, 0x02, 0xc0, 0x01, 0xc0]) {
+///     Err(CanonicalError::UnsortedKey(3)) => {}
+///     other => panic!("unexpected result: {:?}", other),
+/// }
+/// ```
+pub fn check_canonical(buf: &[u8]) -> Result<(), CanonicalError> {
+    let mut rd = buf;
+    check_value(buf, &mut rd, MAX_DEPTH)?;
+
+    if rd.is_empty() {
+        Ok(())
+    } else {
+        Err(CanonicalError::TrailingBytes(buf.len() - rd.len()))
+    }
+}
+
+// Checks a value, allowing arrays and maps to be nested at most `depth` levels deep.
+fn check_value(buf: &[u8], rd: &mut &[u8], depth: usize) -> Result<(), CanonicalError> {
+    let offset = buf.len() - rd.len();
+    let marker = read_marker(rd).map_err(ValueReadError::from)?;
+
+    let minimal = match marker {
+        Marker::Null |
+        Marker::True |
+        Marker::False |
+        Marker::FixPos(..) |
+        Marker::FixNeg(..) => true,
+        Marker::U8 => read_data_u8(rd)? > 0x7f,
+        Marker::U16 => read_data_u16(rd)? > 0xff,
+        Marker::U32 => read_data_u32(rd)? > 0xffff,
+        Marker::U64 => read_data_u64(rd)? > 0xffffffff,
+        Marker::I8 => read_data_i8(rd)? < -32,
+        Marker::I16 => read_data_i16(rd)? < -128,
+        Marker::I32 => read_data_i32(rd)? < -32768,
+        Marker::I64 => read_data_i64(rd)? < -2147483648,
+        Marker::F32 => {
+            let val = read_data_f32(rd)?;
+            if val.is_nan() && val.to_bits() != f32::NAN.to_bits() {
+                return Err(CanonicalError::NonCanonicalNaN(offset));
+            }
+            true
+        }
+        Marker::F64 => {
+            let val = read_data_f64(rd)?;
+            if val.is_nan() && val.to_bits() != f64::NAN.to_bits() {
+                return Err(CanonicalError::NonCanonicalNaN(offset));
+            }
+            true
+        }
+        Marker::FixStr(len) => {
+            skip_data(rd, len as usize)?;
+            true
+        }
+        Marker::Str8 => {
+            let len = read_data_u8(rd)? as usize;
+            skip_data(rd, len)?;
+            len > 0x1f
+        }
+        Marker::Str16 => {
+            let len = read_data_u16(rd)? as usize;
+            skip_data(rd, len)?;
+            len > 0xff
+        }
+        Marker::Str32 => {
+            let len = read_data_u32(rd)? as usize;
+            skip_data(rd, len)?;
+            len > 0xffff
+        }
+        Marker::Bin8 => {
+            let len = read_data_u8(rd)? as usize;
+            skip_data(rd, len)?;
+            true
+        }
+        Marker::Bin16 => {
+            let len = read_data_u16(rd)? as usize;
+            skip_data(rd, len)?;
+            len > 0xff
+        }
+        Marker::Bin32 => {
+            let len = read_data_u32(rd)? as usize;
+            skip_data(rd, len)?;
+            len > 0xffff
+        }
+        Marker::FixExt1 => {
+            skip_data(rd, 1 + 1)?;
+            true
+        }
+        Marker::FixExt2 => {
+            skip_data(rd, 1 + 2)?;
+            true
+        }
+        Marker::FixExt4 => {
+            skip_data(rd, 1 + 4)?;
+            true
+        }
+        Marker::FixExt8 => {
+            skip_data(rd, 1 + 8)?;
+            true
+        }
+        Marker::FixExt16 => {
+            skip_data(rd, 1 + 16)?;
+            true
+        }
+        Marker::Ext8 => {
+            let len = read_data_u8(rd)? as usize;
+            skip_data(rd, 1 + len)?;
+            !matches!(len, 1 | 2 | 4 | 8 | 16)
+        }
+        Marker::Ext16 => {
+            let len = read_data_u16(rd)? as usize;
+            skip_data(rd, 1 + len)?;
+            len > 0xff
+        }
+        Marker::Ext32 => {
+            let len = read_data_u32(rd)? as usize;
+            skip_data(rd, 1 + len)?;
+            len > 0xffff
+        }
+        Marker::FixArray(len) => return check_array(buf, rd, len as u32, offset, depth),
+        Marker::Array16 => {
+            let len = read_data_u16(rd)? as u32;
+            if len <= 0x0f {
+                return Err(CanonicalError::NonMinimal(offset, marker));
+            }
+            return check_array(buf, rd, len, offset, depth);
+        }
+        Marker::Array32 => {
+            let len = read_data_u32(rd)?;
+            if len <= 0xffff {
+                return Err(CanonicalError::NonMinimal(offset, marker));
+            }
+            return check_array(buf, rd, len, offset, depth);
+        }
+        Marker::FixMap(len) => return check_map(buf, rd, len as u32, offset, depth),
+        Marker::Map16 => {
+            let len = read_data_u16(rd)? as u32;
+            if len <= 0x0f {
+                return Err(CanonicalError::NonMinimal(offset, marker));
+            }
+            return check_map(buf, rd, len, offset, depth);
+        }
+        Marker::Map32 => {
+            let len = read_data_u32(rd)?;
+            if len <= 0xffff {
+                return Err(CanonicalError::NonMinimal(offset, marker));
+            }
+            return check_map(buf, rd, len, offset, depth);
+        }
+        Marker::Reserved => {
+            return Err(CanonicalError::InvalidValueRead(ValueReadError::TypeMismatch(marker)));
+        }
+    };
+
+    if minimal {
+        Ok(())
+    } else {
+        Err(CanonicalError::NonMinimal(offset, marker))
+    }
+}
+
+fn check_array(buf: &[u8], rd: &mut &[u8], len: u32, offset: usize, depth: usize)
+    -> Result<(), CanonicalError>
+{
+    if depth == 0 {
+        return Err(CanonicalError::DepthLimitExceeded(offset));
+    }
+
+    for _ in 0..len {
+        check_value(buf, rd, depth - 1)?;
+    }
+
+    Ok(())
+}
+
+fn check_map(buf: &[u8], rd: &mut &[u8], len: u32, offset: usize, depth: usize)
+    -> Result<(), CanonicalError>
+{
+    if depth == 0 {
+        return Err(CanonicalError::DepthLimitExceeded(offset));
+    }
+
+    let mut prev: Option<&[u8]> = None;
+
+    for _ in 0..len {
+        let offset = buf.len() - rd.len();
+        check_value(buf, rd, depth - 1)?;
+        let key = &buf[offset..buf.len() - rd.len()];
+
+        if let Some(prev) = prev {
+            match prev.cmp(key) {
+                Ordering::Less => {}
+                Ordering::Equal => return Err(CanonicalError::DuplicateKey(offset)),
+                Ordering::Greater => return Err(CanonicalError::UnsortedKey(offset)),
+            }
+        }
+        prev = Some(key);
+
+        check_value(buf, rd, depth - 1)?;
+    }
+
+    Ok(())
+}
+
+fn skip_data(rd: &mut &[u8], len: usize) -> Result<(), ValueReadError> {
+    if len > rd.len() {
+        return Err(ValueReadError::InvalidDataRead(Error::new(ErrorKind::UnexpectedEof,
+                                                              "unexpected EOF")));
+    }
+
+    *rd = &rd[len..];
+    Ok(())
+}
@@ -14,6 +14,8 @@ mod uint;
 mod dec;
 mod str;
 mod ext;
+mod canonical;
+mod annotate;
 
 pub use self::sint::{read_nfix, read_i8, read_i16, read_i32, read_i64};
 pub use self::uint::{read_pfix, read_u8, read_u16, read_u32, read_u64};
@@ -21,6 +23,8 @@ pub use self::dec::{read_f32, read_f64};
 pub use self::str::{read_str_len, read_str, read_str_ref, DecodeStringError};
 pub use self::ext::{read_fixext1, read_fixext2, read_fixext4, read_fixext8, read_fixext16,
                     read_ext_meta, ExtMeta};
+pub use self::canonical::{check_canonical, CanonicalError};
+pub use self::annotate::{annotate, Annotations, Malformed, Token, TokenValue};
 
 use std::error;
 use std::fmt::{self, Display, Formatter};
@@ -31,3 +31,37 @@ pub fn write_f64<W: Write>(wr: &mut W, val: f64) -> Result<(), ValueWriteError>
     try!(write_data_f64(wr, val));
     Ok(())
 }
+
+/// Encodes and attempts to write an `f64` value into the given write using the smallest lossless
+/// representation, returning the marker used.
+///
+/// The value is written as a 5-byte `f32` if converting it to `f32` and back reproduces exactly
+/// the same bits, otherwise as a 9-byte `f64`. This holds for all values representable as `f32`,
+/// including infinities, both zeros and the canonical NaN.
+///
+/// # Errors
+///
+/// This function will return `ValueWriteError` on any I/O error occurred while writing either the
+/// marker or the data, except the EINTR, which is handled internally.
+///
+/// # Examples
+///
+/// ```
+/// use rmp::Marker;
+///
+/// let mut buf = Vec::new();
+///
+/// assert_eq!(Marker::F32, rmp::encode::write_float(&mut buf, 0.5).unwrap());
+/// assert_eq!(vec![0xca, 0x3f, 0x00, 0x00, 0x00], buf);
+///
+/// assert_eq!(Marker::F64, rmp::encode::write_float(&mut buf, 0.1).unwrap());
+/// ```
+pub fn write_float<W: Write>(wr: &mut W, val: f64) -> Result<Marker, ValueWriteError> {
+    let narrow = val as f32;
+
+    if (narrow as f64).to_bits() == val.to_bits() {
+        write_f32(wr, narrow).and(Ok(Marker::F32))
+    } else {
+        write_f64(wr, val).and(Ok(Marker::F64))
+    }
+}
@@ -140,6 +140,60 @@ impl Marker {
             Marker::Reserved      => 0xc1,
         }
     }
+
+    /// Returns the name of the marker's format as used in the MessagePack specification, e.g.
+    /// `"fixstr"` or `"uint16"`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmp::Marker;
+    ///
+    /// assert_eq!("positive fixint", Marker::FixPos(42).name());
+    /// assert_eq!("true", Marker::True.name());
+    /// assert_eq!("map16", Marker::from_u8(0xde).name());
+    /// ```
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Marker::FixPos(..)    => "positive fixint",
+            Marker::FixNeg(..)    => "negative fixint",
+            Marker::Null          => "nil",
+            Marker::True          => "true",
+            Marker::False         => "false",
+            Marker::U8            => "uint8",
+            Marker::U16           => "uint16",
+            Marker::U32           => "uint32",
+            Marker::U64           => "uint64",
+            Marker::I8            => "int8",
+            Marker::I16           => "int16",
+            Marker::I32           => "int32",
+            Marker::I64           => "int64",
+            Marker::F32           => "float32",
+            Marker::F64           => "float64",
+            Marker::FixStr(..)    => "fixstr",
+            Marker::Str8          => "str8",
+            Marker::Str16         => "str16",
+            Marker::Str32         => "str32",
+            Marker::Bin8          => "bin8",
+            Marker::Bin16         => "bin16",
+            Marker::Bin32         => "bin32",
+            Marker::FixArray(..)  => "fixarray",
+            Marker::Array16       => "array16",
+            Marker::Array32       => "array32",
+            Marker::FixMap(..)    => "fixmap",
+            Marker::Map16         => "map16",
+            Marker::Map32         => "map32",
+            Marker::FixExt1       => "fixext1",
+            Marker::FixExt2       => "fixext2",
+            Marker::FixExt4       => "fixext4",
+            Marker::FixExt8       => "fixext8",
+            Marker::FixExt16      => "fixext16",
+            Marker::Ext8          => "ext8",
+            Marker::Ext16         => "ext16",
+            Marker::Ext32         => "ext32",
+            Marker::Reserved      => "reserved",
+        }
+    }
 }
 
 impl From<u8> for Marker {
@@ -0,0 +1,116 @@
+use msgpack::Marker;
+use msgpack::decode::*;
+
+fn tokens<'a>(buf: &'a [u8]) -> Vec<Token<'a>> {
+    annotate(buf).map(|token| token.unwrap()).collect()
+}
+
+#[test]
+fn annotate_scalars() {
+    let buf = [0xc0, 0xc3, 0x2a, 0xe0, 0xcd, 0x01, 0x2c, 0xd1, 0xfe, 0xd4,
+               0xca, 0x3f, 0xc0, 0x00, 0x00];
+    let tokens = tokens(&buf);
+
+    let values: Vec<_> = tokens.iter().map(|token| token.value).collect();
+    assert_eq!(vec![TokenValue::Nil,
+                    TokenValue::Boolean(true),
+                    TokenValue::Uint(42),
+                    TokenValue::Sint(-32),
+                    TokenValue::Uint(300),
+                    TokenValue::Sint(-300),
+                    TokenValue::F32(1.5)],
+               values);
+
+    let offsets: Vec<_> = tokens.iter().map(|token| token.offset).collect();
+    assert_eq!(vec![0, 1, 2, 3, 4, 7, 10], offsets);
+    assert!(tokens.iter().all(|token| token.depth == 0));
+    assert_eq!(&[0xcd, 0x01, 0x2c], tokens[4].bytes);
+    assert_eq!(Marker::I16, tokens[5].marker);
+}
+
+#[test]
+fn annotate_payloads() {
+    let buf = [0xd9, 0x02, 0x68, 0x69, 0xc4, 0x01, 0xff, 0xd5, 0x05, 0x01, 0x02,
+               0xc7, 0x01, 0xfe, 0x00];
+    let tokens = tokens(&buf);
+
+    assert_eq!(4, tokens.len());
+    assert_eq!(TokenValue::Str(b"hi"), tokens[0].value);
+    assert_eq!(Marker::Str8, tokens[0].marker);
+    assert_eq!(TokenValue::Bin(&[0xff]), tokens[1].value);
+    assert_eq!(TokenValue::Ext(5, &[0x01, 0x02]), tokens[2].value);
+    assert_eq!(&[0xd5, 0x05, 0x01, 0x02], tokens[2].bytes);
+    assert_eq!(TokenValue::Ext(-2, &[0x00]), tokens[3].value);
+    assert_eq!(11, tokens[3].offset);
+}
+
+#[test]
+fn annotate_nesting() {
+    // [{"a": [1]}, 2] and 3.
+    let buf = [0x92, 0x81, 0xa1, 0x61, 0x91, 0x01, 0x02, 0x03];
+    let tokens = tokens(&buf);
+
+    let depths: Vec<_> = tokens.iter().map(|token| token.depth).collect();
+    assert_eq!(vec![0, 1, 2, 2, 3, 1, 0], depths);
+    assert_eq!(TokenValue::Array(2), tokens[0].value);
+    assert_eq!(TokenValue::Map(1), tokens[1].value);
+    assert_eq!(&[0x92], tokens[0].bytes);
+}
+
+#[test]
+fn annotate_empty_containers() {
+    let tokens = tokens(&[0x90, 0x80, 0x01]);
+
+    let depths: Vec<_> = tokens.iter().map(|token| token.depth).collect();
+    assert_eq!(vec![0, 0, 0], depths);
+}
+
+#[test]
+fn annotate_empty_buffer() {
+    assert_eq!(0, annotate(&[]).count());
+}
+
+#[test]
+fn annotate_reserved_marker() {
+    let items: Vec<_> = annotate(&[0x91, 0xc1, 0x01]).collect();
+
+    assert_eq!(2, items.len());
+    let err = items[1].unwrap_err();
+    assert_eq!(1, err.offset);
+    assert_eq!(&[0xc1], err.bytes);
+    assert_eq!(1, err.depth);
+    assert_eq!(Some(Marker::Reserved), err.marker);
+    assert_eq!("reserved marker at offset 1", format!("{}", err));
+}
+
+#[test]
+fn annotate_truncated_payload() {
+    let items: Vec<_> = annotate(&[0x01, 0xa3, 0x61, 0x62]).collect();
+
+    assert_eq!(2, items.len());
+    let err = items[1].unwrap_err();
+    assert_eq!(1, err.offset);
+    assert_eq!(&[0xa3, 0x61, 0x62], err.bytes);
+    assert_eq!(Some(Marker::FixStr(3)), err.marker);
+}
+
+#[test]
+fn annotate_truncated_ext_type() {
+    let err = annotate(&[0xd4]).next().unwrap().unwrap_err();
+
+    assert_eq!(&[0xd4], err.bytes);
+    assert_eq!(Some(Marker::FixExt1), err.marker);
+}
+
+#[test]
+fn annotate_missing_element() {
+    let items: Vec<_> = annotate(&[0x92, 0x01]).collect();
+
+    assert_eq!(3, items.len());
+    let err = items[2].unwrap_err();
+    assert_eq!(2, err.offset);
+    assert!(err.bytes.is_empty());
+    assert_eq!(1, err.depth);
+    assert_eq!(None, err.marker);
+    assert_eq!("unexpected end of buffer at offset 2", format!("{}", err));
+}
@@ -0,0 +1,101 @@
+use msgpack::Marker;
+use msgpack::decode::*;
+
+#[test]
+fn pass_canonical_scalars() {
+    assert!(check_canonical(&[0xc0]).is_ok());
+    assert!(check_canonical(&[0x7f]).is_ok());
+    assert!(check_canonical(&[0xcc, 0x80]).is_ok());
+    assert!(check_canonical(&[0xe0]).is_ok());
+    assert!(check_canonical(&[0xd0, 0xdf]).is_ok());
+    assert!(check_canonical(&[0xa2, 0x6c, 0x65]).is_ok());
+    assert!(check_canonical(&[0xc4, 0x00]).is_ok());
+    assert!(check_canonical(&[0xd4, 0x01, 0x2a]).is_ok());
+}
+
+#[test]
+fn fail_non_minimal_uint() {
+    match check_canonical(&[0xcd, 0x00, 0x2a]) {
+        Err(CanonicalError::NonMinimal(0, Marker::U16)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_non_negative_sint() {
+    match check_canonical(&[0xd0, 0x01]) {
+        Err(CanonicalError::NonMinimal(0, Marker::I8)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_non_minimal_str_len() {
+    match check_canonical(&[0x91, 0xd9, 0x01, 0x61]) {
+        Err(CanonicalError::NonMinimal(1, Marker::Str8)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_ext8_with_fixext_len() {
+    match check_canonical(&[0xc7, 0x01, 0x01, 0x2a]) {
+        Err(CanonicalError::NonMinimal(0, Marker::Ext8)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_non_canonical_nan() {
+    assert!(check_canonical(&[0xca, 0x7f, 0xc0, 0x00, 0x00]).is_ok());
+
+    match check_canonical(&[0xca, 0x7f, 0xc0, 0x00, 0x01]) {
+        Err(CanonicalError::NonCanonicalNaN(0)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_unsorted_map_keys() {
+    // {"b": 1, "a": 2}
+    match check_canonical(&[0x82, 0xa1, 0x62, 0x01, 0xa1, 0x61, 0x02]) {
+        Err(CanonicalError::UnsortedKey(4)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_duplicate_map_keys() {
+    match check_canonical(&[0x82, 0x01, 0xc0, 0x01, 0xc3]) {
+        Err(CanonicalError::DuplicateKey(3)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_trailing_bytes() {
+    match check_canonical(&[0xc0, 0xc0]) {
+        Err(CanonicalError::TrailingBytes(1)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_truncated() {
+    match check_canonical(&[0x92, 0xc0]) {
+        Err(CanonicalError::InvalidValueRead(ValueReadError::InvalidMarkerRead(..))) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn fail_too_deep() {
+    let mut buf = vec![0x91; 100_000];
+    buf.push(0xc0);
+
+    match check_canonical(&buf) {
+        Err(CanonicalError::DepthLimitExceeded(512)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(check_canonical(&buf[buf.len() - 513..]).is_ok());
+}
@@ -8,3 +8,5 @@ mod bin;
 mod array;
 mod map;
 mod ext;
+mod canonical;
+mod annotate;
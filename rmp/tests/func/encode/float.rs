@@ -1,3 +1,5 @@
+use msgpack::Marker;
+use msgpack::decode::read_f64;
 use msgpack::encode::*;
 
 #[test]
@@ -19,3 +21,29 @@ fn pass_pack_f64() {
 
     assert_eq!([0xcb, 0x7f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], buf);
 }
+
+#[test]
+fn pass_pack_float_lossless_as_f32() {
+    use std::f64;
+
+    let mut buf = Vec::new();
+
+    assert_eq!(Marker::F32, write_float(&mut buf, 1.5).unwrap());
+    assert_eq!(Marker::F32, write_float(&mut buf, -0.0).unwrap());
+    assert_eq!(Marker::F32, write_float(&mut buf, f64::NEG_INFINITY).unwrap());
+
+    assert_eq!(vec![0xca, 0x3f, 0xc0, 0x00, 0x00,
+                    0xca, 0x80, 0x00, 0x00, 0x00,
+                    0xca, 0xff, 0x80, 0x00, 0x00], buf);
+}
+
+#[test]
+fn pass_pack_float_lossy_as_f64() {
+    let mut buf = Vec::new();
+
+    assert_eq!(Marker::F64, write_float(&mut buf, 0.1).unwrap());
+    assert_eq!(Marker::F64, write_float(&mut buf, 1e300).unwrap());
+
+    assert_eq!(18, buf.len());
+    assert_eq!(0.1, read_f64(&mut &buf[..9]).unwrap());
+}
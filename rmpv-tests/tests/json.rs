@@ -0,0 +1,363 @@
+#[macro_use]
+extern crate rmpv;
+#[macro_use]
+extern crate serde_json;
+
+use std::io::Cursor;
+
+use rmpv::{Utf8String, Value};
+use rmpv::decode::read_value;
+use rmpv::encode::write_value;
+use rmpv::json::{transcode_from_json, BinaryPolicy, Error, ExtPolicy, FloatPolicy, IntPolicy,
+                 KeyPolicy, Loss, LossKind, ToJson, TranscodeError};
+
+fn loss(kind: LossKind, path: &str) -> Loss {
+    Loss { kind: kind, path: path.to_owned() }
+}
+
+fn encode(val: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_value(&mut buf, val).unwrap();
+    buf
+}
+
+fn transcode(conv: &ToJson, val: &Value) -> Result<(String, Vec<Loss>), TranscodeError> {
+    let mut json = Vec::new();
+    let mut losses = Vec::new();
+    conv.transcode_with(&mut &encode(val)[..], &mut json, |loss| losses.push(loss))?;
+    Ok((String::from_utf8(json).unwrap(), losses))
+}
+
+#[test]
+fn from_json() {
+    let json = json!({
+        "null": null,
+        "bool": true,
+        "int": -42,
+        "uint": 18446744073709551615u64,
+        "float": 0.5,
+        "array": ["a", 1],
+    });
+
+    assert_eq!(msgpack!({
+        "array": ["a", 1],
+        "bool": true,
+        "float": 0.5,
+        "int": -42,
+        "null": nil,
+        "uint": (u64::max_value()),
+    }), Value::from(json));
+}
+
+#[test]
+fn to_json_lossless() {
+    let val = msgpack!({"a": [nil, true, -1, 2, 0.25], "b": "le message"});
+
+    let (json, losses) = ToJson::new().convert(&val).unwrap();
+
+    assert_eq!(json!({"a": [null, true, -1, 2, 0.25], "b": "le message"}), json);
+    assert!(losses.is_empty());
+    assert_eq!(val, Value::from(json));
+}
+
+#[test]
+fn to_json_with_default_policies() {
+    let val = msgpack!({
+        "bin": bin([0, 1, 255]),
+        "ext": ext(5, [0xff]),
+        "nan": (::std::f64::NAN),
+        "big": (u64::max_value()),
+        42: "int key",
+        "42": "string key",
+    });
+
+    let (json, losses) = ToJson::new().convert(&val).unwrap();
+
+    assert_eq!(json!({
+        "bin": "AAH/",
+        "ext": {"$ext": {"type": 5, "data": "/w=="}},
+        "nan": null,
+        "big": 18446744073709551615u64,
+        "42": "string key",
+    }), json);
+    assert_eq!(vec![
+        loss(LossKind::Binary, "/bin"),
+        loss(LossKind::Ext, "/ext"),
+        loss(LossKind::NonFiniteFloat, "/nan"),
+        loss(LossKind::NonStringKey, "/42"),
+        loss(LossKind::DuplicateKey, "/42"),
+    ], losses);
+
+    assert_eq!(json, serde_json::Value::from(&val));
+}
+
+#[test]
+fn to_json_reports_stringified_keys() {
+    let val = Value::Map(vec![
+        (Value::I64(5), Value::Map(vec![(Value::Nil, Value::from(1))])),
+        (msgpack!([1, "a"]), Value::from(2)),
+    ]);
+
+    let expected_losses = vec![
+        loss(LossKind::NonStringKey, "/i64(5)"),
+        loss(LossKind::NonStringKey, "/i64(5)/nil"),
+        loss(LossKind::NonStringKey, "/[1, \"a\"]"),
+    ];
+
+    let (json, losses) = ToJson::new().convert(&val).unwrap();
+    assert_eq!(json!({"i64(5)": {"nil": 1}, "[1, \"a\"]": 2}), json);
+    assert_eq!(expected_losses, losses);
+
+    // Encoding writes `I64(5)` as a positive integer, so it is decoded as `U64(5)`.
+    let (json, losses) = transcode(&ToJson::new(), &val).unwrap();
+    assert_eq!(r#"{"5":{"nil":1},"[1, \"a\"]":2}"#, json);
+    assert_eq!(vec![
+        loss(LossKind::NonStringKey, "/5"),
+        loss(LossKind::NonStringKey, "/5/nil"),
+        loss(LossKind::NonStringKey, "/[1, \"a\"]"),
+    ], losses);
+}
+
+#[test]
+fn to_json_with_custom_policies() {
+    let val = msgpack!([bin([1, 2]), (i64::min_value()), (::std::f32::NEG_INFINITY), {nil: 1}]);
+
+    let mut conv = ToJson::new();
+    conv.set_binary_policy(BinaryPolicy::Array);
+    conv.set_int_policy(IntPolicy::String);
+    conv.set_float_policy(FloatPolicy::String);
+
+    let (json, losses) = conv.convert(&val).unwrap();
+
+    assert_eq!(json!([[1, 2], "-9223372036854775808", "-Infinity", {"nil": 1}]), json);
+    assert_eq!(vec![
+        loss(LossKind::Binary, "/0"),
+        loss(LossKind::LargeInteger, "/1"),
+        loss(LossKind::NonFiniteFloat, "/2"),
+        loss(LossKind::NonStringKey, "/3/nil"),
+    ], losses);
+}
+
+#[test]
+fn to_json_replaces_invalid_utf8() {
+    let invalid = || Value::String(Utf8String::from_bytes(vec![0x61, 0xff]));
+    let val = Value::Map(vec![(invalid(), Value::Array(vec![invalid()]))]);
+
+    let (json, losses) = ToJson::new().convert(&val).unwrap();
+
+    assert_eq!(json!({"a\u{fffd}": ["a\u{fffd}"]}), json);
+    assert_eq!(vec![
+        loss(LossKind::InvalidUtf8, "/a\u{fffd}"),
+        loss(LossKind::InvalidUtf8, "/a\u{fffd}/0"),
+    ], losses);
+
+    let (text, transcoded) = transcode(&ToJson::new(), &val).unwrap();
+    assert_eq!(json, serde_json::from_str::<serde_json::Value>(&text).unwrap());
+    assert_eq!(losses, transcoded);
+}
+
+#[test]
+fn to_json_keeps_safe_integers() {
+    let mut conv = ToJson::new();
+    conv.set_int_policy(IntPolicy::Error);
+
+    let (json, _) = conv.convert(&msgpack!([9007199254740992u64, -9007199254740992i64])).unwrap();
+    assert_eq!(json!([9007199254740992u64, -9007199254740992i64]), json);
+}
+
+#[test]
+fn to_json_errors() {
+    let val = msgpack!({"a": [1, bin([])], "b/c": {1: ext(1, [])}});
+
+    let mut conv = ToJson::new();
+    conv.set_binary_policy(BinaryPolicy::Error);
+    assert_eq!(Err(Error { kind: LossKind::Binary, path: "/a/1".to_owned() }),
+               conv.convert(&val));
+
+    let mut conv = ToJson::new();
+    conv.set_key_policy(KeyPolicy::Error);
+    assert_eq!(Err(Error { kind: LossKind::NonStringKey, path: "/b~1c".to_owned() }),
+               conv.convert(&val));
+
+    let mut conv = ToJson::new();
+    conv.set_ext_policy(ExtPolicy::Error);
+    assert_eq!(Err(Error { kind: LossKind::Ext, path: "/b~1c/1".to_owned() }),
+               conv.convert(&val));
+
+    let mut conv = ToJson::new();
+    conv.set_float_policy(FloatPolicy::Error);
+    let err = conv.convert(&msgpack!([(::std::f64::INFINITY)])).unwrap_err();
+    assert_eq!("non-finite float at \"/0\" cannot be represented in JSON", format!("{}", err));
+
+    let mut conv = ToJson::new();
+    conv.set_int_policy(IntPolicy::Error);
+    assert_eq!(Err(Error { kind: LossKind::LargeInteger, path: "".to_owned() }),
+               conv.convert(&Value::from(u64::max_value())));
+}
+
+#[test]
+fn transcode_matches_convert() {
+    let val = msgpack!({
+        "nil": nil,
+        "bools": [true, false],
+        "ints": [0, 127, 255, 65536, (u64::max_value()), -1, -200, (i64::min_value())],
+        "floats": [0.5, -1.25, 1e300, (1.5f32), (::std::f64::NAN)],
+        "str": "line\n\"quoted\" \u{1} юникод",
+        "bin": bin([0, 1, 2, 3, 4, 255]),
+        "ext": ext(3, [1, 2, 3]),
+        "nested": {"a/b": [[], {}], nil: 1, [1, 2]: {1.5: "x"}},
+    });
+
+    for &binary in &[BinaryPolicy::Base64, BinaryPolicy::Array] {
+        for &floats in &[FloatPolicy::Null, FloatPolicy::String] {
+            for &ints in &[IntPolicy::Exact, IntPolicy::String] {
+                let mut conv = ToJson::new();
+                conv.set_binary_policy(binary);
+                conv.set_float_policy(floats);
+                conv.set_int_policy(ints);
+
+                let (expected, expected_losses) = conv.convert(&val).unwrap();
+                let (json, losses) = transcode(&conv, &val).unwrap();
+
+                assert_eq!(expected, serde_json::from_str::<serde_json::Value>(&json).unwrap());
+                assert_eq!(expected_losses, losses);
+            }
+        }
+    }
+}
+
+#[test]
+fn transcode_keeps_order_and_duplicates() {
+    let val = msgpack!({"b": 1, "a": 2, "b": 3, 1: 4});
+
+    let (json, losses) = transcode(&ToJson::new(), &val).unwrap();
+
+    assert_eq!(r#"{"b":1,"a":2,"b":3,"1":4}"#, json);
+    assert_eq!(vec![loss(LossKind::NonStringKey, "/1")], losses);
+}
+
+#[test]
+fn transcode_large_binary() {
+    let data: Vec<u8> = (0..10000).map(|n| n as u8).collect();
+    let val = Value::Binary(data.clone());
+
+    let (expected, _) = ToJson::new().convert(&val).unwrap();
+    let (json, _) = transcode(&ToJson::new(), &val).unwrap();
+
+    assert_eq!(expected, serde_json::from_str::<serde_json::Value>(&json).unwrap());
+}
+
+#[test]
+fn transcode_errors() {
+    let val = msgpack!({"a": [1, bin([])], "b": {1: ext(1, [])}});
+
+    let mut conv = ToJson::new();
+    conv.set_binary_policy(BinaryPolicy::Error);
+    match transcode(&conv, &val) {
+        Err(TranscodeError::Policy(err)) => {
+            assert_eq!(Error { kind: LossKind::Binary, path: "/a/1".to_owned() }, err);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut conv = ToJson::new();
+    conv.set_key_policy(KeyPolicy::Error);
+    match transcode(&conv, &val) {
+        Err(TranscodeError::Policy(err)) => {
+            assert_eq!(Error { kind: LossKind::NonStringKey, path: "/b".to_owned() }, err);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let buf = encode(&msgpack!([1, 2]));
+    match ToJson::new().transcode(&mut &buf[..2], &mut Vec::new()) {
+        Err(TranscodeError::Decode(ref err)) if err.insufficient_bytes() => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn transcode_truncated_huge_strings() {
+    // Both a string value and a string key claiming 4 GiB, followed by a few bytes only.
+    for buf in &[&[0xdb, 0xff, 0xff, 0xff, 0xff, 0x61][..],
+                 &[0x81, 0xdb, 0xff, 0xff, 0xff, 0xff, 0x61][..]] {
+        match ToJson::new().transcode(&mut &buf[..], &mut Vec::new()) {
+            Err(TranscodeError::Decode(ref err)) if err.insufficient_bytes() => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn transcode_depth_limit() {
+    let mut buf = vec![0x91; 200_000];
+    buf.push(0xc0);
+    match ToJson::new().transcode(&mut &buf[..], &mut Vec::new()) {
+        Err(TranscodeError::DepthLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut conv = ToJson::new();
+    conv.set_max_depth(2);
+
+    let (json, _) = transcode(&conv, &msgpack!([{"a": 1}])).unwrap();
+    assert_eq!(r#"[{"a":1}]"#, json);
+
+    // Non-string keys count as nested as well.
+    for val in &[msgpack!([[[1]]]), msgpack!({[[1]]: nil}), msgpack!({[{1: 2}]: nil})] {
+        match transcode(&conv, val) {
+            Err(TranscodeError::DepthLimitExceeded) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    let (json, _) = transcode(&conv, &msgpack!({[1]: nil})).unwrap();
+    assert_eq!(r#"{"[1]":null}"#, json);
+}
+
+#[test]
+fn transcode_from_json_matches_conversion() {
+    let json = r#"{"a": [null, true, false, 0, -1, 18446744073709551615, 0.5, -1e300],
+                   "b": "\u00e9\n", "c": {}, "d": [[], [{}]]}"#;
+
+    let mut buf = Cursor::new(Vec::new());
+    transcode_from_json(&mut json.as_bytes(), &mut buf).unwrap();
+
+    let expected = Value::from(serde_json::from_str::<serde_json::Value>(json).unwrap());
+    let val = read_value(&mut &buf.get_ref()[..]).unwrap();
+    assert_eq!(msgpack!({
+        "a": [nil, true, false, 0, -1, (u64::max_value()), 0.5, -1e300],
+        "b": "é\n",
+        "c": {},
+        "d": [[], [{}]],
+    }), val);
+    assert_eq!(expected, val);
+}
+
+#[test]
+fn transcode_from_json_uses_32_bit_lengths() {
+    let mut buf = Cursor::new(Vec::new());
+    transcode_from_json(&mut &b"[{\"a\": 1}]"[..], &mut buf).unwrap();
+
+    assert_eq!(vec![0xdd, 0, 0, 0, 1, 0xdf, 0, 0, 0, 1, 0xa1, 0x61, 0x01], buf.into_inner());
+}
+
+#[test]
+fn transcode_from_json_errors() {
+    let mut buf = Cursor::new(Vec::new());
+    match transcode_from_json(&mut &b"[1, 2"[..], &mut buf) {
+        Err(TranscodeError::Json(..)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    match transcode_from_json(&mut &b"1 2"[..], &mut Cursor::new(Vec::new())) {
+        Err(TranscodeError::Json(..)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut small = [0u8; 4];
+    match transcode_from_json(&mut &b"[1, 2]"[..], &mut Cursor::new(&mut small[..])) {
+        Err(TranscodeError::Write(..)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -5,7 +5,7 @@ extern crate rmpv;
 use serde::Serialize;
 
 use rmp_serde::Serializer;
-use rmpv::Value;
+use rmpv::{Utf8String, Value};
 
 #[test]
 fn pass_value_nil() {
@@ -58,6 +58,11 @@ fn pass_value_string() {
         &[0xaa, 0x6c, 0x65, 0x20, 0x6d, 0x65, 0x73, 0x73, 0x61, 0x67, 0x65]);
 }
 
+#[test]
+fn pass_value_invalid_utf8_string_as_bin() {
+    check_ser(Value::String(Utf8String::from_bytes(vec![0xff, 0x80])), &[0xc4, 0x02, 0xff, 0x80]);
+}
+
 #[test]
 fn pass_value_bin() {
     check_ser(Value::Binary(vec![0xcc, 0x80]), &[0xc4, 0x02, 0xcc, 0x80]);
@@ -97,7 +97,8 @@ fn from_complex_write_value_ref(b: &mut Bencher) {
     use rmpv::encode::write_value_ref;
 
     let val = Array(vec![Nil, U64(42), F64(3.1415),
-        String("Lorem ipsum dolor sit amet."), Map(vec![(String("key"), String("value"))])]);
+        String("Lorem ipsum dolor sit amet.".into()),
+        Map(vec![(String("key".into()), String("value".into()))])]);
 
     let mut buf = [0u8; 64];
 
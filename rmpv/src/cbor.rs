@@ -0,0 +1,809 @@
+//! Streaming transcoding between MessagePack and CBOR (RFC 7049).
+//!
+//! Both directions work token by token without building a `Value`, so only strings, timestamps
+//! and CBOR indefinite-length items are buffered. Each function transcodes a single item, so a
+//! stream of concatenated items is transcoded by calling it repeatedly. Offsets reported in losses
+//! and errors are relative to the beginning of the item passed to the call.
+//!
+//! Items nested deeper than 512 levels are rejected. Arrays and maps count as a level in both
+//! directions, as do CBOR tags.
+//!
+//! Types are mapped as follows:
+//!
+//! - nil, booleans, integers and floats are mapped to their CBOR counterparts, while CBOR half
+//!   precision floats are widened to `F32`;
+//! - strings are mapped to text strings and binaries to byte strings;
+//! - arrays and maps are mapped to arrays and maps with the same order of entries;
+//! - timestamps, i.e. extensions of type `-1`, are mapped to epoch-based date/time (tag 1),
+//!   holding integer seconds or, if the timestamp has a fractional part, float seconds;
+//! - other extensions are mapped to `EXT_TAG` wrapping a two-element array of the extension type
+//!   and a byte string with the data.
+//!
+//! What cannot be mapped exactly is reported as a `Loss`: timestamps, which float seconds cannot
+//! represent exactly, CBOR simple values other than booleans and null, which become `Nil`,
+//! negative integers below `i64::MIN`, which become `F64`, and tags other than the ones above,
+//! which are dropped, keeping the tagged item.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate rmpv;
+//!
+//! use rmpv::cbor;
+//! use rmpv::decode::read_value;
+//! use rmpv::encode::write_value;
+//!
+//! # fn main() {
+//! let mut msgpack = Vec::new();
+//! write_value(&mut msgpack, &msgpack!({"id": 42, "data": bin([1, 2])})).unwrap();
+//!
+//! let mut buf = Vec::new();
+//! cbor::to_cbor(&mut &msgpack[..], &mut buf, |_| {}).unwrap();
+//! assert_eq!(vec![0xa2, 0x62, 0x69, 0x64, 0x18, 0x2a, 0x64, 0x64, 0x61, 0x74, 0x61, 0x42, 0x01,
+//!                 0x02], buf);
+//!
+//! let mut res = Vec::new();
+//! cbor::from_cbor(&mut &buf[..], &mut res, |_| {}).unwrap();
+//! assert_eq!(msgpack!({"id": 42, "data": bin([1, 2])}), read_value(&mut &res[..]).unwrap());
+//! # }
+//! ```
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, ErrorKind, Read, Write};
+use std::str;
+
+use rmp::Marker;
+use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64,
+                  read_data_i8, read_data_i16, read_data_i32, read_data_i64, read_data_f32,
+                  read_data_f64, ValueReadError};
+use rmp::encode::{write_nil, write_bool, write_uint, write_sint, write_f32, write_f64,
+                  write_str_len, write_bin_len, write_array_len, write_map_len};
+
+/// The CBOR tag, which MessagePack extensions other than timestamps are mapped to.
+///
+/// It is taken from the first come first served range of the IANA CBOR tags registry.
+pub const EXT_TAG: u64 = 0x6d70_6578;
+
+/// The MessagePack extension type of timestamps.
+const TIMESTAMP_TYPE: i8 = -1;
+
+/// The CBOR tag of epoch-based date/time.
+const EPOCH_TAG: u64 = 1;
+
+/// The number of bytes of binaries and extensions copied at once.
+const CHUNK_SIZE: usize = 4096;
+
+/// The maximum nesting depth of arrays, maps and CBOR tags.
+const MAX_DEPTH: usize = 512;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_NINT: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+/// This type represents all possible errors that can occur when transcoding between MessagePack
+/// and CBOR.
+///
+/// Offsets are zero-based byte positions of the offending item relative to the beginning of the
+/// transcoded item, i.e. to the position of the reader when the function was called.
+#[derive(Debug)]
+pub enum Error {
+    /// I/O error while reading the input, including its unexpected end.
+    Read(io::Error),
+    /// I/O error while writing the output.
+    Write(io::Error),
+    /// The item at the given offset is malformed or not allowed at its position, like a reserved
+    /// marker, an unexpected break or a malformed `EXT_TAG` item.
+    Malformed(u64),
+    /// The string at the given offset is not valid UTF-8.
+    InvalidUtf8(u64),
+    /// The item at the given offset has more than 2<sup>32</sup> - 1 bytes or elements, which
+    /// MessagePack cannot represent.
+    TooLong(u64),
+    /// The item at the given offset is nested deeper than 512 levels.
+    DepthLimitExceeded(u64),
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Read(..) => "error while reading the input",
+            Error::Write(..) => "error while writing the output",
+            Error::Malformed(..) => "malformed item",
+            Error::InvalidUtf8(..) => "string is not valid UTF-8",
+            Error::TooLong(..) => "item is too long for MessagePack",
+            Error::DepthLimitExceeded(..) => "depth limit exceeded",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Error::Read(ref err) => write!(fmt, "error while reading the input: {}", err),
+            Error::Write(ref err) => write!(fmt, "error while writing the output: {}", err),
+            Error::Malformed(pos) => write!(fmt, "malformed item at offset {}", pos),
+            Error::InvalidUtf8(pos) => write!(fmt, "string at offset {} is not valid UTF-8", pos),
+            Error::TooLong(pos) => {
+                write!(fmt, "item at offset {} is too long for MessagePack", pos)
+            }
+            Error::DepthLimitExceeded(pos) => {
+                write!(fmt, "item at offset {} exceeds the depth limit", pos)
+            }
+        }
+    }
+}
+
+/// A kind of item, which cannot be mapped exactly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LossKind {
+    /// A timestamp with nanoseconds, which float seconds cannot represent exactly.
+    TimestampPrecision,
+    /// An epoch-based date/time, which content is not a number representable as a timestamp. The
+    /// tag is dropped.
+    InvalidTimestamp,
+    /// A CBOR tag with the given number, which has no MessagePack counterpart. The tag is dropped.
+    UnknownTag(u64),
+    /// A CBOR simple value with the given number, including `undefined`, which is mapped to `Nil`.
+    SimpleValue(u8),
+    /// A CBOR negative integer below `i64::MIN`, which is mapped to `F64`.
+    LargeNegativeInteger,
+}
+
+/// An item, which was not mapped exactly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Loss {
+    /// The kind of the item.
+    pub kind: LossKind,
+    /// The zero-based byte position of the item relative to the beginning of the transcoded item.
+    pub offset: u64,
+}
+
+/// Reads a single MessagePack item and writes it as CBOR, calling `on_loss` for each item, which
+/// cannot be mapped exactly.
+///
+/// # Errors
+///
+/// Returns an error if the input is not a valid MessagePack item, it is nested too deep or the
+/// output cannot be written. In that case the output contains an incomplete CBOR item.
+pub fn to_cbor<R, W, F>(rd: &mut R, wr: &mut W, on_loss: F) -> Result<(), Error>
+    where R: Read,
+          W: Write,
+          F: FnMut(Loss)
+{
+    let mut tr = Transcoder {
+        rd: Counter { rd: rd, pos: 0 },
+        depth: 0,
+        on_loss: on_loss,
+    };
+    tr.msgpack_item(wr)
+}
+
+/// Reads a single CBOR item and writes it as MessagePack, calling `on_loss` for each item, which
+/// cannot be mapped exactly.
+///
+/// Indefinite-length strings, arrays and maps are buffered until their end, because MessagePack
+/// requires their length in advance.
+///
+/// # Errors
+///
+/// Returns an error if the input is not a well-formed CBOR item, some string is not valid UTF-8,
+/// some item is too long for MessagePack or nested too deep or the output cannot be written. In
+/// that case the output contains an incomplete MessagePack item.
+pub fn from_cbor<R, W, F>(rd: &mut R, wr: &mut W, on_loss: F) -> Result<(), Error>
+    where R: Read,
+          W: Write,
+          F: FnMut(Loss)
+{
+    let mut tr = Transcoder {
+        rd: Counter { rd: rd, pos: 0 },
+        depth: 0,
+        on_loss: on_loss,
+    };
+    let pos = tr.rd.pos;
+    match tr.cbor_item(wr)? {
+        Item::Value => Ok(()),
+        Item::Break => Err(Error::Malformed(pos)),
+    }
+}
+
+// Counts bytes read to report offsets.
+struct Counter<R> {
+    rd: R,
+    pos: u64,
+}
+
+impl<R: Read> Read for Counter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.rd.read(buf)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+struct Transcoder<R, F> {
+    rd: Counter<R>,
+    depth: usize,
+    on_loss: F,
+}
+
+// A CBOR item head: the major type and the argument, which is None for indefinite lengths.
+struct Head {
+    pos: u64,
+    major: u8,
+    info: u8,
+    arg: Option<u64>,
+}
+
+enum Item {
+    Value,
+    Break,
+}
+
+impl<R: Read, F: FnMut(Loss)> Transcoder<R, F> {
+    fn msgpack_item<W: Write>(&mut self, wr: &mut W) -> Result<(), Error> {
+        let pos = self.rd.pos;
+        let marker = read_marker(&mut self.rd).map_err(|err| Error::Read(err.0))?;
+        let rd = &mut self.rd;
+
+        match marker {
+            Marker::Null => write(wr, &[0xf6]),
+            Marker::True => write(wr, &[0xf5]),
+            Marker::False => write(wr, &[0xf4]),
+            Marker::FixPos(val) => write_head(wr, MAJOR_UINT, val as u64),
+            Marker::FixNeg(val) => write_int(wr, val as i64),
+            Marker::U8 => write_head(wr, MAJOR_UINT, data(read_data_u8(rd))? as u64),
+            Marker::U16 => write_head(wr, MAJOR_UINT, data(read_data_u16(rd))? as u64),
+            Marker::U32 => write_head(wr, MAJOR_UINT, data(read_data_u32(rd))? as u64),
+            Marker::U64 => write_head(wr, MAJOR_UINT, data(read_data_u64(rd))?),
+            Marker::I8 => write_int(wr, data(read_data_i8(rd))? as i64),
+            Marker::I16 => write_int(wr, data(read_data_i16(rd))? as i64),
+            Marker::I32 => write_int(wr, data(read_data_i32(rd))? as i64),
+            Marker::I64 => write_int(wr, data(read_data_i64(rd))?),
+            Marker::F32 => {
+                let val = data(read_data_f32(rd))?;
+                write_be(wr, 0xfa, val.to_bits() as u64, 4)
+            }
+            Marker::F64 => {
+                let val = data(read_data_f64(rd))?;
+                write_be(wr, 0xfb, val.to_bits(), 8)
+            }
+            Marker::FixStr(len) => copy_str(rd, wr, len as u32, pos),
+            Marker::Str8 => {
+                let len = data(read_data_u8(rd))?;
+                copy_str(rd, wr, len as u32, pos)
+            }
+            Marker::Str16 => {
+                let len = data(read_data_u16(rd))?;
+                copy_str(rd, wr, len as u32, pos)
+            }
+            Marker::Str32 => {
+                let len = data(read_data_u32(rd))?;
+                copy_str(rd, wr, len, pos)
+            }
+            Marker::Bin8 => {
+                let len = data(read_data_u8(rd))?;
+                write_head(wr, MAJOR_BYTES, len as u64)?;
+                copy(rd, wr, len as u64)
+            }
+            Marker::Bin16 => {
+                let len = data(read_data_u16(rd))?;
+                write_head(wr, MAJOR_BYTES, len as u64)?;
+                copy(rd, wr, len as u64)
+            }
+            Marker::Bin32 => {
+                let len = data(read_data_u32(rd))?;
+                write_head(wr, MAJOR_BYTES, len as u64)?;
+                copy(rd, wr, len as u64)
+            }
+            Marker::FixArray(len) => self.msgpack_array(wr, len as u32, pos),
+            Marker::Array16 => {
+                let len = data(read_data_u16(rd))?;
+                self.msgpack_array(wr, len as u32, pos)
+            }
+            Marker::Array32 => {
+                let len = data(read_data_u32(rd))?;
+                self.msgpack_array(wr, len, pos)
+            }
+            Marker::FixMap(len) => self.msgpack_map(wr, len as u32, pos),
+            Marker::Map16 => {
+                let len = data(read_data_u16(rd))?;
+                self.msgpack_map(wr, len as u32, pos)
+            }
+            Marker::Map32 => {
+                let len = data(read_data_u32(rd))?;
+                self.msgpack_map(wr, len, pos)
+            }
+            Marker::FixExt1 => self.msgpack_ext(wr, 1, pos),
+            Marker::FixExt2 => self.msgpack_ext(wr, 2, pos),
+            Marker::FixExt4 => self.msgpack_ext(wr, 4, pos),
+            Marker::FixExt8 => self.msgpack_ext(wr, 8, pos),
+            Marker::FixExt16 => self.msgpack_ext(wr, 16, pos),
+            Marker::Ext8 => {
+                let len = data(read_data_u8(rd))?;
+                self.msgpack_ext(wr, len as u32, pos)
+            }
+            Marker::Ext16 => {
+                let len = data(read_data_u16(rd))?;
+                self.msgpack_ext(wr, len as u32, pos)
+            }
+            Marker::Ext32 => {
+                let len = data(read_data_u32(rd))?;
+                self.msgpack_ext(wr, len, pos)
+            }
+            Marker::Reserved => Err(Error::Malformed(pos)),
+        }
+    }
+
+    fn msgpack_array<W: Write>(&mut self, wr: &mut W, len: u32, pos: u64) -> Result<(), Error> {
+        self.enter(pos)?;
+        write_head(wr, MAJOR_ARRAY, len as u64)?;
+        for _ in 0..len {
+            self.msgpack_item(wr)?;
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn msgpack_map<W: Write>(&mut self, wr: &mut W, len: u32, pos: u64) -> Result<(), Error> {
+        self.enter(pos)?;
+        write_head(wr, MAJOR_MAP, len as u64)?;
+        for _ in 0..len {
+            self.msgpack_item(wr)?;
+            self.msgpack_item(wr)?;
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn msgpack_ext<W: Write>(&mut self, wr: &mut W, len: u32, pos: u64) -> Result<(), Error> {
+        let ty = data(read_data_i8(&mut self.rd))?;
+
+        if ty == TIMESTAMP_TYPE && (len == 4 || len == 8 || len == 12) {
+            let mut buf = [0; 12];
+            let buf = &mut buf[..len as usize];
+            self.rd.read_exact(buf).map_err(Error::Read)?;
+
+            if let Some((secs, nsecs)) = decode_timestamp(buf) {
+                return self.msgpack_timestamp(wr, secs, nsecs, pos);
+            }
+
+            write_ext_tag(wr, ty, len)?;
+            return write(wr, buf);
+        }
+
+        write_ext_tag(wr, ty, len)?;
+        copy(&mut self.rd, wr, len as u64)
+    }
+
+    fn msgpack_timestamp<W: Write>(&mut self, wr: &mut W, secs: i64, nsecs: u32, pos: u64)
+        -> Result<(), Error>
+    {
+        write_head(wr, MAJOR_TAG, EPOCH_TAG)?;
+        if nsecs == 0 {
+            return write_int(wr, secs);
+        }
+
+        let val = secs as f64 + nsecs as f64 / 1e9;
+        if split_seconds(val) != Some((secs, nsecs)) {
+            (self.on_loss)(Loss { kind: LossKind::TimestampPrecision, offset: pos });
+        }
+        write_be(wr, 0xfb, val.to_bits(), 8)
+    }
+
+    fn cbor_item<W: Write>(&mut self, wr: &mut W) -> Result<Item, Error> {
+        let head = self.read_head()?;
+        self.cbor_item_with_head(wr, head)
+    }
+
+    fn cbor_item_with_head<W: Write>(&mut self, wr: &mut W, head: Head) -> Result<Item, Error> {
+        // Arrays, maps and tags contain nested items.
+        let nested = head.major == MAJOR_ARRAY || head.major == MAJOR_MAP ||
+                     head.major == MAJOR_TAG;
+        if nested {
+            self.enter(head.pos)?;
+        }
+        let item = self.cbor_contents(wr, head)?;
+        if nested {
+            self.depth -= 1;
+        }
+        Ok(item)
+    }
+
+    fn cbor_contents<W: Write>(&mut self, wr: &mut W, head: Head) -> Result<Item, Error> {
+        let pos = head.pos;
+        match (head.major, head.arg) {
+            (MAJOR_UINT, Some(val)) => write_value(write_uint(wr, val))?,
+            (MAJOR_NINT, Some(val)) => {
+                if val <= i64::max_value() as u64 {
+                    write_value(write_sint(wr, -1 - val as i64))?;
+                } else {
+                    (self.on_loss)(Loss { kind: LossKind::LargeNegativeInteger, offset: pos });
+                    write_value(write_f64(wr, -1.0 - val as f64))?;
+                }
+            }
+            (MAJOR_BYTES, Some(len)) => {
+                write_value(write_bin_len(wr, to_u32(len, pos)?))?;
+                copy(&mut self.rd, wr, len)?;
+            }
+            (MAJOR_BYTES, None) => {
+                let buf = self.read_chunks(MAJOR_BYTES, pos)?;
+                write_value(write_bin_len(wr, to_u32(buf.len() as u64, pos)?))?;
+                write(wr, &buf)?;
+            }
+            (MAJOR_TEXT, Some(len)) => {
+                let mut buf = Vec::new();
+                read_into(&mut self.rd, &mut buf, to_u32(len, pos)?)?;
+                write_text(wr, &buf, pos)?;
+            }
+            (MAJOR_TEXT, None) => {
+                let buf = self.read_chunks(MAJOR_TEXT, pos)?;
+                write_text(wr, &buf, pos)?;
+            }
+            (MAJOR_ARRAY, Some(len)) => {
+                write_value(write_array_len(wr, to_u32(len, pos)?))?;
+                for _ in 0..len {
+                    self.cbor_value(wr)?;
+                }
+            }
+            (MAJOR_ARRAY, None) => {
+                let mut buf = Vec::new();
+                let mut len = 0;
+                while let Item::Value = self.cbor_item(&mut buf)? {
+                    len += 1;
+                }
+                write_value(write_array_len(wr, to_u32(len, pos)?))?;
+                write(wr, &buf)?;
+            }
+            (MAJOR_MAP, Some(len)) => {
+                write_value(write_map_len(wr, to_u32(len, pos)?))?;
+                for _ in 0..len {
+                    self.cbor_value(wr)?;
+                    self.cbor_value(wr)?;
+                }
+            }
+            (MAJOR_MAP, None) => {
+                let mut buf = Vec::new();
+                let mut len = 0;
+                while let Item::Value = self.cbor_item(&mut buf)? {
+                    self.cbor_value(&mut buf)?;
+                    len += 1;
+                }
+                write_value(write_map_len(wr, to_u32(len, pos)?))?;
+                write(wr, &buf)?;
+            }
+            (MAJOR_TAG, Some(EPOCH_TAG)) => self.cbor_timestamp(wr, pos)?,
+            (MAJOR_TAG, Some(EXT_TAG)) => self.cbor_ext(wr, pos)?,
+            (MAJOR_TAG, Some(tag)) => {
+                (self.on_loss)(Loss { kind: LossKind::UnknownTag(tag), offset: pos });
+                self.cbor_value(wr)?;
+            }
+            (MAJOR_SIMPLE, None) => return Ok(Item::Break),
+            (MAJOR_SIMPLE, Some(val)) => {
+                match head.info {
+                    20 => write_value(write_bool(wr, false))?,
+                    21 => write_value(write_bool(wr, true))?,
+                    22 => write_value(write_nil(wr))?,
+                    25 => write_value(write_f32(wr, f16_to_f32(val as u16)))?,
+                    26 => write_value(write_f32(wr, f32::from_bits(val as u32)))?,
+                    27 => write_value(write_f64(wr, f64::from_bits(val)))?,
+                    24 if val < 32 => return Err(Error::Malformed(pos)),
+                    _ => {
+                        let loss = Loss { kind: LossKind::SimpleValue(val as u8), offset: pos };
+                        (self.on_loss)(loss);
+                        write_value(write_nil(wr))?;
+                    }
+                }
+            }
+            _ => return Err(Error::Malformed(pos)),
+        }
+
+        Ok(Item::Value)
+    }
+
+    // Transcodes an item, which must not be a break.
+    fn cbor_value<W: Write>(&mut self, wr: &mut W) -> Result<(), Error> {
+        let pos = self.rd.pos;
+        match self.cbor_item(wr)? {
+            Item::Value => Ok(()),
+            Item::Break => Err(Error::Malformed(pos)),
+        }
+    }
+
+    fn cbor_timestamp<W: Write>(&mut self, wr: &mut W, pos: u64) -> Result<(), Error> {
+        let head = self.read_head()?;
+
+        let split = match (head.major, head.info, head.arg) {
+            (MAJOR_UINT, _, Some(val)) if val <= i64::max_value() as u64 => Some((val as i64, 0)),
+            (MAJOR_NINT, _, Some(val)) if val <= i64::max_value() as u64 => {
+                Some((-1 - val as i64, 0))
+            }
+            (MAJOR_SIMPLE, 25, Some(val)) => split_seconds(f16_to_f32(val as u16) as f64),
+            (MAJOR_SIMPLE, 26, Some(val)) => split_seconds(f32::from_bits(val as u32) as f64),
+            (MAJOR_SIMPLE, 27, Some(val)) => split_seconds(f64::from_bits(val)),
+            _ => None,
+        };
+
+        match split {
+            Some((secs, nsecs)) => write_timestamp(wr, secs, nsecs),
+            None => {
+                (self.on_loss)(Loss { kind: LossKind::InvalidTimestamp, offset: pos });
+                match self.cbor_item_with_head(wr, head)? {
+                    Item::Value => Ok(()),
+                    Item::Break => Err(Error::Malformed(pos)),
+                }
+            }
+        }
+    }
+
+    fn cbor_ext<W: Write>(&mut self, wr: &mut W, pos: u64) -> Result<(), Error> {
+        let head = self.read_head()?;
+        if head.major != MAJOR_ARRAY || head.arg != Some(2) {
+            return Err(Error::Malformed(pos));
+        }
+
+        let head = self.read_head()?;
+        let ty = match (head.major, head.arg) {
+            (MAJOR_UINT, Some(val)) if val <= i8::max_value() as u64 => val as i8,
+            (MAJOR_NINT, Some(val)) if val <= i8::max_value() as u64 => -1 - val as i8,
+            _ => return Err(Error::Malformed(pos)),
+        };
+
+        let head = self.read_head()?;
+        match (head.major, head.arg) {
+            (MAJOR_BYTES, Some(len)) => {
+                write_ext_head(wr, ty, to_u32(len, pos)?)?;
+                copy(&mut self.rd, wr, len)
+            }
+            (MAJOR_BYTES, None) => {
+                let buf = self.read_chunks(MAJOR_BYTES, pos)?;
+                write_ext_head(wr, ty, to_u32(buf.len() as u64, pos)?)?;
+                write(wr, &buf)
+            }
+            _ => Err(Error::Malformed(pos)),
+        }
+    }
+
+    // Enters an array, a map or a tag at `pos`, failing if it is nested too deep.
+    fn enter(&mut self, pos: u64) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::DepthLimitExceeded(pos));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn read_head(&mut self) -> Result<Head, Error> {
+        let pos = self.rd.pos;
+        let mut byte = [0];
+        self.rd.read_exact(&mut byte).map_err(Error::Read)?;
+
+        let (major, info) = (byte[0] >> 5, byte[0] & 0x1f);
+        let arg = match info {
+            0..=23 => Some(info as u64),
+            24..=27 => {
+                let mut buf = [0; 8];
+                let buf = &mut buf[..1 << (info - 24)];
+                self.rd.read_exact(buf).map_err(Error::Read)?;
+                Some(buf.iter().fold(0, |acc, &b| acc << 8 | b as u64))
+            }
+            31 if major >= MAJOR_BYTES && major != MAJOR_TAG => None,
+            _ => return Err(Error::Malformed(pos)),
+        };
+
+        Ok(Head { pos: pos, major: major, info: info, arg: arg })
+    }
+
+    // Reads chunks of an indefinite-length string at `pos` up to the break.
+    fn read_chunks(&mut self, major: u8, pos: u64) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        loop {
+            let head = self.read_head()?;
+            match head.arg {
+                Some(len) if head.major == major => {
+                    to_u32(buf.len() as u64 + len, pos)?;
+                    read_into(&mut self.rd, &mut buf, len as u32)?;
+                }
+                None if head.major == MAJOR_SIMPLE => return Ok(buf),
+                _ => return Err(Error::Malformed(head.pos)),
+            }
+        }
+    }
+}
+
+fn data<T>(res: Result<T, ValueReadError>) -> Result<T, Error> {
+    res.map_err(|err| match err {
+        ValueReadError::InvalidMarkerRead(err) |
+        ValueReadError::InvalidDataRead(err) => Error::Read(err),
+        ValueReadError::TypeMismatch(..) => unreachable!("reading data never checks markers"),
+    })
+}
+
+fn write<W: Write>(wr: &mut W, buf: &[u8]) -> Result<(), Error> {
+    wr.write_all(buf).map_err(Error::Write)
+}
+
+fn write_value<T, E: Into<io::Error>>(res: Result<T, E>) -> Result<(), Error> {
+    res.map(|_| ()).map_err(|err| Error::Write(err.into()))
+}
+
+// Writes the byte followed by the lowest `len` bytes of the value in big-endian order.
+fn write_be<W: Write>(wr: &mut W, byte: u8, val: u64, len: usize) -> Result<(), Error> {
+    let mut buf = [byte, 0, 0, 0, 0, 0, 0, 0, 0];
+    for idx in 0..len {
+        buf[len - idx] = (val >> (8 * idx)) as u8;
+    }
+    write(wr, &buf[..len + 1])
+}
+
+fn write_head<W: Write>(wr: &mut W, major: u8, val: u64) -> Result<(), Error> {
+    let major = major << 5;
+    if val < 24 {
+        write(wr, &[major | val as u8])
+    } else if val <= u8::max_value() as u64 {
+        write_be(wr, major | 24, val, 1)
+    } else if val <= u16::max_value() as u64 {
+        write_be(wr, major | 25, val, 2)
+    } else if val <= u32::max_value() as u64 {
+        write_be(wr, major | 26, val, 4)
+    } else {
+        write_be(wr, major | 27, val, 8)
+    }
+}
+
+fn write_int<W: Write>(wr: &mut W, val: i64) -> Result<(), Error> {
+    if val < 0 {
+        write_head(wr, MAJOR_NINT, !val as u64)
+    } else {
+        write_head(wr, MAJOR_UINT, val as u64)
+    }
+}
+
+fn write_text<W: Write>(wr: &mut W, buf: &[u8], pos: u64) -> Result<(), Error> {
+    str::from_utf8(buf).map_err(|_| Error::InvalidUtf8(pos))?;
+    write_value(write_str_len(wr, to_u32(buf.len() as u64, pos)?))?;
+    write(wr, buf)
+}
+
+// Writes the `EXT_TAG` item up to the head of the byte string with the extension data.
+fn write_ext_tag<W: Write>(wr: &mut W, ty: i8, len: u32) -> Result<(), Error> {
+    write_head(wr, MAJOR_TAG, EXT_TAG)?;
+    write_head(wr, MAJOR_ARRAY, 2)?;
+    write_int(wr, ty as i64)?;
+    write_head(wr, MAJOR_BYTES, len as u64)
+}
+
+// Writes the MessagePack extension header, which `rmp` cannot write for negative types.
+fn write_ext_head<W: Write>(wr: &mut W, ty: i8, len: u32) -> Result<(), Error> {
+    match len {
+        1 => write(wr, &[Marker::FixExt1.to_u8(), ty as u8]),
+        2 => write(wr, &[Marker::FixExt2.to_u8(), ty as u8]),
+        4 => write(wr, &[Marker::FixExt4.to_u8(), ty as u8]),
+        8 => write(wr, &[Marker::FixExt8.to_u8(), ty as u8]),
+        16 => write(wr, &[Marker::FixExt16.to_u8(), ty as u8]),
+        len if len < 256 => write(wr, &[Marker::Ext8.to_u8(), len as u8, ty as u8]),
+        len if len < 65536 => {
+            write_be(wr, Marker::Ext16.to_u8(), len as u64, 2)?;
+            write(wr, &[ty as u8])
+        }
+        len => {
+            write_be(wr, Marker::Ext32.to_u8(), len as u64, 4)?;
+            write(wr, &[ty as u8])
+        }
+    }
+}
+
+fn write_timestamp<W: Write>(wr: &mut W, secs: i64, nsecs: u32) -> Result<(), Error> {
+    if nsecs == 0 && secs >= 0 && secs <= u32::max_value() as i64 {
+        write_ext_head(wr, TIMESTAMP_TYPE, 4)?;
+        write(wr, &be_bytes(secs as u64, 4))
+    } else if secs >= 0 && secs < 1 << 34 {
+        write_ext_head(wr, TIMESTAMP_TYPE, 8)?;
+        let val = (nsecs as u64) << 34 | secs as u64;
+        write(wr, &be_bytes(val, 8))
+    } else {
+        write_ext_head(wr, TIMESTAMP_TYPE, 12)?;
+        write(wr, &be_bytes(nsecs as u64, 4))?;
+        write(wr, &be_bytes(secs as u64, 8))
+    }
+}
+
+fn be_bytes(val: u64, len: usize) -> Vec<u8> {
+    (0..len).rev().map(|idx| (val >> (8 * idx)) as u8).collect()
+}
+
+// Decodes the timestamp data of 4, 8 or 12 bytes into seconds and nanoseconds.
+fn decode_timestamp(buf: &[u8]) -> Option<(i64, u32)> {
+    let int = |buf: &[u8]| buf.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+
+    let (secs, nsecs) = match buf.len() {
+        4 => (int(buf) as i64, 0),
+        8 => {
+            let val = int(buf);
+            ((val & ((1 << 34) - 1)) as i64, (val >> 34) as u32)
+        }
+        _ => (int(&buf[4..]) as i64, int(&buf[..4]) as u32),
+    };
+
+    if nsecs < 1_000_000_000 {
+        Some((secs, nsecs))
+    } else {
+        None
+    }
+}
+
+// Splits float seconds into whole seconds and nanoseconds, rounding to the nearest nanosecond.
+fn split_seconds(val: f64) -> Option<(i64, u32)> {
+    if !val.is_finite() || val < i64::min_value() as f64 || val >= i64::max_value() as f64 {
+        return None;
+    }
+
+    let secs = val.floor();
+    let nsecs = ((val - secs) * 1e9).round() as u32;
+    if nsecs >= 1_000_000_000 {
+        Some((secs as i64 + 1, nsecs - 1_000_000_000))
+    } else {
+        Some((secs as i64, nsecs))
+    }
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let exp = (half >> 10) & 0x1f;
+    let mant = (half & 0x3ff) as f32;
+
+    let val = match exp {
+        0 => mant * 2f32.powi(-24),
+        31 if mant == 0.0 => ::std::f32::INFINITY,
+        31 => ::std::f32::NAN,
+        exp => (mant + 1024.0) * 2f32.powi(exp as i32 - 25),
+    };
+
+    if half & 0x8000 != 0 { -val } else { val }
+}
+
+fn to_u32(len: u64, pos: u64) -> Result<u32, Error> {
+    if len <= u32::max_value() as u64 {
+        Ok(len as u32)
+    } else {
+        Err(Error::TooLong(pos))
+    }
+}
+
+// Appends `len` bytes to the buffer. Since the length comes from the input, the buffer grows with
+// the bytes actually read instead of being allocated upfront.
+fn read_into<R: Read>(rd: &mut R, buf: &mut Vec<u8>, len: u32) -> Result<(), Error> {
+    let start = buf.len();
+    rd.take(len as u64).read_to_end(buf).map_err(Error::Read)?;
+    if buf.len() - start < len as usize {
+        let err = io::Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer");
+        return Err(Error::Read(err));
+    }
+    Ok(())
+}
+
+fn copy_str<R: Read, W: Write>(rd: &mut R, wr: &mut W, len: u32, pos: u64) -> Result<(), Error> {
+    let mut buf = Vec::new();
+    read_into(rd, &mut buf, len)?;
+    str::from_utf8(&buf).map_err(|_| Error::InvalidUtf8(pos))?;
+    write_head(wr, MAJOR_TEXT, len as u64)?;
+    write(wr, &buf)
+}
+
+// Copies `len` bytes in chunks.
+fn copy<R: Read, W: Write>(rd: &mut R, wr: &mut W, mut len: u64) -> Result<(), Error> {
+    let mut buf = [0; CHUNK_SIZE];
+    while len > 0 {
+        let size = len.min(CHUNK_SIZE as u64) as usize;
+        let chunk = &mut buf[..size];
+        rd.read_exact(chunk).map_err(Error::Read)?;
+        write(wr, chunk)?;
+        len -= size as u64;
+    }
+    Ok(())
+}
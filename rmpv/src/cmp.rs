@@ -0,0 +1,160 @@
+//! Total equality, ordering and hashing for `Value` and `ValueRef`.
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use {Value, ValueRef};
+
+// Compares floats totally: all NaNs are equal to each other and greater than any other number,
+// while negative and positive zeros are equal.
+fn cmp_f64(lhs: f64, rhs: f64) -> Ordering {
+    match (lhs.is_nan(), rhs.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => lhs.partial_cmp(&rhs).unwrap(),
+    }
+}
+
+// Returns the bits of a float consistent with `cmp_f64`, i.e. equal floats have equal bits.
+fn f64_bits(val: f64) -> u64 {
+    if val.is_nan() {
+        0x7ff8000000000000
+    } else if val == 0.0 {
+        0
+    } else {
+        val.to_bits()
+    }
+}
+
+// Compares a signed integer with an unsigned one by their numeric values.
+fn cmp_i64_u64(lhs: i64, rhs: u64) -> Ordering {
+    if lhs < 0 {
+        Ordering::Less
+    } else {
+        (lhs as u64).cmp(&rhs)
+    }
+}
+
+macro_rules! impl_cmp {
+    ($ty:ident $(<$lt:lifetime>)*) => {
+        impl $(<$lt>)* $ty $(<$lt>)* {
+            // Returns the rank of the value kind, which defines the order of values of different
+            // kinds.
+            fn kind_rank(&self) -> u8 {
+                match *self {
+                    $ty::Nil => 0,
+                    $ty::Boolean(..) => 1,
+                    $ty::U64(..) | $ty::I64(..) => 2,
+                    $ty::F32(..) | $ty::F64(..) => 3,
+                    $ty::String(..) => 4,
+                    $ty::Binary(..) => 5,
+                    $ty::Array(..) => 6,
+                    $ty::Map(..) => 7,
+                    $ty::Ext(..) => 8,
+                }
+            }
+        }
+
+        impl $(<$lt>)* Ord for $ty $(<$lt>)* {
+            fn cmp(&self, other: &Self) -> Ordering {
+                match (self, other) {
+                    (&$ty::Nil, &$ty::Nil) => Ordering::Equal,
+                    (&$ty::Boolean(lhs), &$ty::Boolean(rhs)) => lhs.cmp(&rhs),
+                    (&$ty::U64(lhs), &$ty::U64(rhs)) => lhs.cmp(&rhs),
+                    (&$ty::I64(lhs), &$ty::I64(rhs)) => lhs.cmp(&rhs),
+                    // Equal numbers with different representations are ordered signed first.
+                    (&$ty::I64(lhs), &$ty::U64(rhs)) => {
+                        cmp_i64_u64(lhs, rhs).then(Ordering::Less)
+                    }
+                    (&$ty::U64(lhs), &$ty::I64(rhs)) => {
+                        cmp_i64_u64(rhs, lhs).reverse().then(Ordering::Greater)
+                    }
+                    (&$ty::F32(lhs), &$ty::F32(rhs)) => cmp_f64(lhs as f64, rhs as f64),
+                    (&$ty::F64(lhs), &$ty::F64(rhs)) => cmp_f64(lhs, rhs),
+                    // Equal numbers with different precision are ordered `F32` first.
+                    (&$ty::F32(lhs), &$ty::F64(rhs)) => {
+                        cmp_f64(lhs as f64, rhs).then(Ordering::Less)
+                    }
+                    (&$ty::F64(lhs), &$ty::F32(rhs)) => {
+                        cmp_f64(lhs, rhs as f64).then(Ordering::Greater)
+                    }
+                    (&$ty::String(ref lhs), &$ty::String(ref rhs)) => lhs.cmp(rhs),
+                    (&$ty::Binary(ref lhs), &$ty::Binary(ref rhs)) => lhs.cmp(rhs),
+                    (&$ty::Array(ref lhs), &$ty::Array(ref rhs)) => lhs.cmp(rhs),
+                    (&$ty::Map(ref lhs), &$ty::Map(ref rhs)) => lhs.cmp(rhs),
+                    (&$ty::Ext(lty, ref lhs), &$ty::Ext(rty, ref rhs)) => {
+                        lty.cmp(&rty).then_with(|| lhs.cmp(rhs))
+                    }
+                    (lhs, rhs) => lhs.kind_rank().cmp(&rhs.kind_rank()),
+                }
+            }
+        }
+
+        impl $(<$lt>)* PartialOrd for $ty $(<$lt>)* {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl $(<$lt>)* PartialEq for $ty $(<$lt>)* {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+
+        impl $(<$lt>)* Eq for $ty $(<$lt>)* {}
+
+        impl $(<$lt>)* Hash for $ty $(<$lt>)* {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                match *self {
+                    $ty::Nil => 0u8.hash(state),
+                    $ty::Boolean(val) => {
+                        1u8.hash(state);
+                        val.hash(state);
+                    }
+                    $ty::U64(val) => {
+                        2u8.hash(state);
+                        val.hash(state);
+                    }
+                    $ty::I64(val) => {
+                        3u8.hash(state);
+                        val.hash(state);
+                    }
+                    $ty::F32(val) => {
+                        4u8.hash(state);
+                        f64_bits(val as f64).hash(state);
+                    }
+                    $ty::F64(val) => {
+                        5u8.hash(state);
+                        f64_bits(val).hash(state);
+                    }
+                    $ty::String(ref val) => {
+                        6u8.hash(state);
+                        val.hash(state);
+                    }
+                    $ty::Binary(ref val) => {
+                        7u8.hash(state);
+                        val.hash(state);
+                    }
+                    $ty::Array(ref val) => {
+                        8u8.hash(state);
+                        val.hash(state);
+                    }
+                    $ty::Map(ref val) => {
+                        9u8.hash(state);
+                        val.hash(state);
+                    }
+                    $ty::Ext(ty, ref val) => {
+                        10u8.hash(state);
+                        ty.hash(state);
+                        val.hash(state);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl_cmp!(Value);
+impl_cmp!(ValueRef<'a>);
@@ -0,0 +1,280 @@
+//! Fallible conversions from `Value` into Rust types.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::{Infallible, TryFrom};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{BuildHasher, Hash};
+
+use Value;
+
+/// An error describing why a `Value` cannot be converted into the requested Rust type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TryFromValueError {
+    /// The value has a type other than the requested one.
+    ///
+    /// Contains the name of the requested type and the name of the actual value type.
+    TypeMismatch(&'static str, &'static str),
+    /// The number does not fit in the requested type without loss.
+    ///
+    /// Contains the name of the requested type.
+    OutOfRange(&'static str),
+}
+
+impl error::Error for TryFromValueError {
+    fn description(&self) -> &str {
+        match *self {
+            TryFromValueError::TypeMismatch(..) => "value type mismatch",
+            TryFromValueError::OutOfRange(..) => "number out of range",
+        }
+    }
+}
+
+impl Display for TryFromValueError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            TryFromValueError::TypeMismatch(expected, actual) => {
+                write!(fmt, "invalid type: expected {}, found {}", expected, actual)
+            }
+            TryFromValueError::OutOfRange(expected) => {
+                write!(fmt, "number out of range of {}", expected)
+            }
+        }
+    }
+}
+
+impl From<Infallible> for TryFromValueError {
+    fn from(err: Infallible) -> TryFromValueError {
+        match err {}
+    }
+}
+
+// The name of strings containing invalid UTF-8, which are never converted into Rust strings.
+const INVALID_UTF8: &str = "invalid UTF-8 string";
+
+impl Value {
+    // Returns the name of the value type used in conversion errors.
+    fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Nil => "nil",
+            Value::Boolean(..) => "boolean",
+            Value::U64(..) | Value::I64(..) => "integer",
+            Value::F32(..) | Value::F64(..) => "float",
+            Value::String(ref val) if val.is_err() => INVALID_UTF8,
+            Value::String(..) => "string",
+            Value::Binary(..) => "binary",
+            Value::Array(..) => "array",
+            Value::Map(..) => "map",
+            Value::Ext(..) => "ext",
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for bool {
+    type Error = TryFromValueError;
+
+    fn try_from(val: &Value) -> Result<bool, TryFromValueError> {
+        val.as_bool().ok_or_else(|| TryFromValueError::TypeMismatch("bool", val.type_name()))
+    }
+}
+
+macro_rules! impl_try_from_int {
+    ($($ty:ident)*) => {
+        $(
+            impl<'a> TryFrom<&'a Value> for $ty {
+                type Error = TryFromValueError;
+
+                fn try_from(val: &Value) -> Result<$ty, TryFromValueError> {
+                    let res = match *val {
+                        Value::U64(n) => $ty::try_from(n).ok(),
+                        Value::I64(n) => $ty::try_from(n).ok(),
+                        ref val => {
+                            return Err(TryFromValueError::TypeMismatch(stringify!($ty),
+                                                                       val.type_name()));
+                        }
+                    };
+
+                    res.ok_or(TryFromValueError::OutOfRange(stringify!($ty)))
+                }
+            }
+        )*
+    }
+}
+
+impl_try_from_int!(u8 u16 u32 u64 usize i8 i16 i32 i64 isize);
+
+/// Converts `F32` values and `F64` values, which are exactly representable as `f32`.
+impl<'a> TryFrom<&'a Value> for f32 {
+    type Error = TryFromValueError;
+
+    fn try_from(val: &Value) -> Result<f32, TryFromValueError> {
+        match *val {
+            Value::F32(n) => Ok(n),
+            Value::F64(n) if n as f32 as f64 == n || n.is_nan() => Ok(n as f32),
+            Value::F64(..) => Err(TryFromValueError::OutOfRange("f32")),
+            ref val => Err(TryFromValueError::TypeMismatch("f32", val.type_name())),
+        }
+    }
+}
+
+/// Converts both `F32` and `F64` values.
+impl<'a> TryFrom<&'a Value> for f64 {
+    type Error = TryFromValueError;
+
+    fn try_from(val: &Value) -> Result<f64, TryFromValueError> {
+        match *val {
+            Value::F32(n) => Ok(n as f64),
+            Value::F64(n) => Ok(n),
+            ref val => Err(TryFromValueError::TypeMismatch("f64", val.type_name())),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = TryFromValueError;
+
+    fn try_from(val: &'a Value) -> Result<&'a str, TryFromValueError> {
+        val.as_str().ok_or_else(|| TryFromValueError::TypeMismatch("string", val.type_name()))
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a [u8] {
+    type Error = TryFromValueError;
+
+    fn try_from(val: &'a Value) -> Result<&'a [u8], TryFromValueError> {
+        val.as_slice().ok_or_else(|| TryFromValueError::TypeMismatch("binary", val.type_name()))
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a [Value] {
+    type Error = TryFromValueError;
+
+    fn try_from(val: &'a Value) -> Result<&'a [Value], TryFromValueError> {
+        match val.as_array() {
+            Some(array) => Ok(array),
+            None => Err(TryFromValueError::TypeMismatch("array", val.type_name())),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a [(Value, Value)] {
+    type Error = TryFromValueError;
+
+    fn try_from(val: &'a Value) -> Result<&'a [(Value, Value)], TryFromValueError> {
+        match val.as_map() {
+            Some(map) => Ok(map),
+            None => Err(TryFromValueError::TypeMismatch("map", val.type_name())),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for String {
+    type Error = TryFromValueError;
+
+    fn try_from(val: &Value) -> Result<String, TryFromValueError> {
+        <&str>::try_from(val).map(|s| s.to_owned())
+    }
+}
+
+macro_rules! impl_try_from_owned_scalar {
+    ($($ty:ident)*) => {
+        $(
+            impl TryFrom<Value> for $ty {
+                type Error = TryFromValueError;
+
+                fn try_from(val: Value) -> Result<$ty, TryFromValueError> {
+                    $ty::try_from(&val)
+                }
+            }
+        )*
+    }
+}
+
+impl_try_from_owned_scalar!(bool u8 u16 u32 u64 usize i8 i16 i32 i64 isize f32 f64);
+
+impl TryFrom<Value> for String {
+    type Error = TryFromValueError;
+
+    fn try_from(val: Value) -> Result<String, TryFromValueError> {
+        match val {
+            Value::String(s) => {
+                s.into_str().ok_or(TryFromValueError::TypeMismatch("string", INVALID_UTF8))
+            }
+            val => Err(TryFromValueError::TypeMismatch("string", val.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<u8> {
+    type Error = TryFromValueError;
+
+    fn try_from(val: Value) -> Result<Vec<u8>, TryFromValueError> {
+        match val {
+            Value::Binary(buf) => Ok(buf),
+            val => Err(TryFromValueError::TypeMismatch("binary", val.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<Value> {
+    type Error = TryFromValueError;
+
+    fn try_from(val: Value) -> Result<Vec<Value>, TryFromValueError> {
+        match val {
+            Value::Array(array) => Ok(array),
+            val => Err(TryFromValueError::TypeMismatch("array", val.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<(Value, Value)> {
+    type Error = TryFromValueError;
+
+    fn try_from(val: Value) -> Result<Vec<(Value, Value)>, TryFromValueError> {
+        match val {
+            Value::Map(map) => Ok(map),
+            val => Err(TryFromValueError::TypeMismatch("map", val.type_name())),
+        }
+    }
+}
+
+/// Converts a map converting each key and value, where later entries with equal keys win.
+impl<K, V, S> TryFrom<Value> for HashMap<K, V, S>
+    where K: TryFrom<Value> + Eq + Hash,
+          V: TryFrom<Value>,
+          S: BuildHasher + Default,
+          TryFromValueError: From<K::Error> + From<V::Error>
+{
+    type Error = TryFromValueError;
+
+    fn try_from(val: Value) -> Result<HashMap<K, V, S>, TryFromValueError> {
+        let map = Vec::<(Value, Value)>::try_from(val)?;
+
+        let mut res = HashMap::with_capacity_and_hasher(map.len(), S::default());
+        for (k, v) in map {
+            res.insert(K::try_from(k)?, V::try_from(v)?);
+        }
+
+        Ok(res)
+    }
+}
+
+/// Converts a map converting each key and value, where later entries with equal keys win.
+impl<K, V> TryFrom<Value> for BTreeMap<K, V>
+    where K: TryFrom<Value> + Ord,
+          V: TryFrom<Value>,
+          TryFromValueError: From<K::Error> + From<V::Error>
+{
+    type Error = TryFromValueError;
+
+    fn try_from(val: Value) -> Result<BTreeMap<K, V>, TryFromValueError> {
+        let map = Vec::<(Value, Value)>::try_from(val)?;
+
+        let mut res = BTreeMap::new();
+        for (k, v) in map {
+            res.insert(K::try_from(k)?, V::try_from(v)?);
+        }
+
+        Ok(res)
+    }
+}
@@ -1,5 +1,7 @@
 pub mod value;
 pub mod value_ref;
+mod wire;
 
-pub use self::value::read_value;
+pub use self::value::{read_value, read_value_with_policy, DuplicateKeys};
 pub use self::value_ref::read_value_ref;
+pub use self::wire::read_wire_value;
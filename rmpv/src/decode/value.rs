@@ -1,12 +1,16 @@
+use std::collections::HashSet;
+use std::error;
+use std::fmt::{self, Display, Formatter};
 use std::io::{self, ErrorKind, Read};
-use std::string::FromUtf8Error;
 
 use rmp::Marker;
 use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64,
                   read_data_i8, read_data_i16, read_data_i32, read_data_i64, read_data_f32,
                   read_data_f64, MarkerReadError, ValueReadError};
+use rmp::encode::{write_uint, write_array_len, write_map_len};
 
-use Value;
+use {Utf8String, Value};
+use encode::write_value;
 
 /// This type represents all possible errors that can occur when deserializing a value.
 #[derive(Debug)]
@@ -17,8 +21,13 @@ pub enum Error {
     InvalidDataRead(io::Error),
     /// Decoded value type isn't equal with the expected one.
     TypeMismatch(Marker),
-    /// Failed to properly decode UTF8.
-    FromUtf8Error(FromUtf8Error),
+    /// The map contains a key equal to one of its previous keys, which is rejected by
+    /// `DuplicateKeys::Reject`.
+    ///
+    /// Contains the index of the duplicate entry within its map.
+    DuplicateKey(usize),
+    /// Arrays and maps are nested deeper than the maximum depth.
+    DepthLimitExceeded,
 }
 
 impl Error {
@@ -29,7 +38,8 @@ impl Error {
             Error::InvalidMarkerRead(..) |
             Error::InvalidDataRead(..) |
             Error::TypeMismatch(..) |
-            Error::FromUtf8Error(..) => false,
+            Error::DuplicateKey(..) |
+            Error::DepthLimitExceeded => false,
         }
     }
 
@@ -38,21 +48,41 @@ impl Error {
             Error::InvalidMarkerRead(ref err) => err.kind(),
             Error::InvalidDataRead(ref err) => err.kind(),
             Error::TypeMismatch(..) |
-            Error::FromUtf8Error(..) => ErrorKind::Other,
+            Error::DuplicateKey(..) |
+            Error::DepthLimitExceeded => ErrorKind::Other,
         }
     }
 }
 
-// TODO: Soon.
-// impl error::Error for Error {
-//     fn description(&self) -> &str {
-//         unimplemented!();
-//     }
-//
-//     fn cause(&self) -> Option<&error::Error> {
-//         unimplemented!();
-//     }
-// }
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::InvalidMarkerRead(..) => "failed to read MessagePack marker",
+            Error::InvalidDataRead(..) => "failed to read MessagePack data",
+            Error::TypeMismatch(..) => "unexpected MessagePack marker",
+            Error::DuplicateKey(..) => "duplicate map key",
+            Error::DepthLimitExceeded => "depth limit exceeded",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Error::InvalidMarkerRead(ref err) => {
+                write!(fmt, "failed to read MessagePack marker: {}", err)
+            }
+            Error::InvalidDataRead(ref err) => {
+                write!(fmt, "failed to read MessagePack data: {}", err)
+            }
+            Error::TypeMismatch(marker) => {
+                write!(fmt, "unexpected MessagePack marker {:?}", marker)
+            }
+            Error::DuplicateKey(idx) => write!(fmt, "duplicate map key at entry {}", idx),
+            Error::DepthLimitExceeded => fmt.write_str("depth limit exceeded"),
+        }
+    }
+}
 
 impl From<MarkerReadError> for Error {
     fn from(err: MarkerReadError) -> Error {
@@ -70,39 +100,103 @@ impl From<ValueReadError> for Error {
     }
 }
 
-impl From<FromUtf8Error> for Error {
-    fn from(err: FromUtf8Error) -> Error {
-        Error::FromUtf8Error(err)
-    }
+/// Policy applied to map entries with duplicate keys while decoding.
+///
+/// Keys are considered duplicates if their decoded values have the same encoded form, which means
+/// that integers are compared by their numeric value regardless of the marker used, while floats
+/// are compared bitwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// All entries are kept in their original order. This is the default.
+    Allow,
+    /// Only the first entry with each key is kept.
+    KeepFirst,
+    /// Only the last entry with each key is kept at its position.
+    KeepLast,
+    /// Decoding fails with `Error::DuplicateKey` pointing at the first duplicate entry.
+    Reject,
 }
 
-fn read_array_data<R: Read>(rd: &mut R, mut len: usize) -> Result<Vec<Value>, Error> {
+fn read_array_data<R: Read>(rd: &mut R, mut len: usize, keys: DuplicateKeys) ->
+    Result<Vec<Value>, Error>
+{
     let mut vec = Vec::with_capacity(len);
 
     while len > 0 {
-        vec.push(read_value(rd)?);
+        vec.push(read_value_with_policy(rd, keys)?);
         len -= 1;
     }
 
     Ok(vec)
 }
 
-fn read_map_data<R: Read>(rd: &mut R, mut len: usize) -> Result<Vec<(Value, Value)>, Error> {
+fn read_map_data<R: Read>(rd: &mut R, mut len: usize, keys: DuplicateKeys) ->
+    Result<Vec<(Value, Value)>, Error>
+{
     let mut vec = Vec::with_capacity(len);
 
     while len > 0 {
-        vec.push((read_value(rd)?, read_value(rd)?));
+        vec.push((read_value_with_policy(rd, keys)?, read_value_with_policy(rd, keys)?));
         len -= 1;
     }
 
-    Ok(vec)
+    if keys == DuplicateKeys::Allow {
+        return Ok(vec);
+    }
+
+    if keys == DuplicateKeys::KeepLast {
+        vec.reverse();
+    }
+
+    let mut seen = HashSet::with_capacity(vec.len());
+    let mut map = Vec::with_capacity(vec.len());
+    for (idx, (key, val)) in vec.into_iter().enumerate() {
+        let mut buf = Vec::new();
+        write_key(&mut buf, &key);
+
+        if seen.insert(buf) {
+            map.push((key, val));
+        } else if keys == DuplicateKeys::Reject {
+            return Err(Error::DuplicateKey(idx));
+        }
+    }
+
+    if keys == DuplicateKeys::KeepLast {
+        map.reverse();
+    }
+
+    Ok(map)
 }
 
-fn read_str_data<R: Read>(rd: &mut R, len: usize) -> Result<String, Error> {
-    String::from_utf8(read_bin_data(rd, len)?).map_err(From::from)
+// Writes the key encoding non-negative integers as unsigned ones, so that equal integers have the
+// same encoded form regardless of their sign.
+fn write_key(buf: &mut Vec<u8>, key: &Value) {
+    match *key {
+        Value::I64(val) if val >= 0 => {
+            write_uint(buf, val as u64).expect("writing into a Vec never fails");
+        }
+        Value::Array(ref vec) => {
+            write_array_len(buf, vec.len() as u32).expect("writing into a Vec never fails");
+            for v in vec {
+                write_key(buf, v);
+            }
+        }
+        Value::Map(ref map) => {
+            write_map_len(buf, map.len() as u32).expect("writing into a Vec never fails");
+            for &(ref k, ref v) in map {
+                write_key(buf, k);
+                write_key(buf, v);
+            }
+        }
+        ref key => write_value(buf, key).expect("writing into a Vec never fails"),
+    }
+}
+
+pub(crate) fn read_str_data<R: Read>(rd: &mut R, len: usize) -> Result<Utf8String, Error> {
+    read_bin_data(rd, len).map(Utf8String::from_bytes)
 }
 
-fn read_bin_data<R: Read>(rd: &mut R, len: usize) -> Result<Vec<u8>, Error> {
+pub(crate) fn read_bin_data<R: Read>(rd: &mut R, len: usize) -> Result<Vec<u8>, Error> {
     let mut buf = Vec::with_capacity(len);
     buf.resize(len as usize, 0u8);
     rd.read_exact(&mut buf[..]).map_err(Error::InvalidDataRead)?;
@@ -110,7 +204,7 @@ fn read_bin_data<R: Read>(rd: &mut R, len: usize) -> Result<Vec<u8>, Error> {
     Ok(buf)
 }
 
-fn read_ext_body<R: Read>(rd: &mut R, len: usize) -> Result<(i8, Vec<u8>), Error> {
+pub(crate) fn read_ext_body<R: Read>(rd: &mut R, len: usize) -> Result<(i8, Vec<u8>), Error> {
     let ty = read_data_i8(rd)?;
     let vec = read_bin_data(rd, len)?;
 
@@ -119,6 +213,9 @@ fn read_ext_body<R: Read>(rd: &mut R, len: usize) -> Result<(i8, Vec<u8>), Error
 
 /// Attempts to read bytes from the given reader and interpret them as a `Value`.
 ///
+/// Strings containing invalid UTF-8 are not rejected, but decoded as `Utf8String` keeping their
+/// raw bytes.
+///
 /// # Errors
 ///
 /// This function will return `Error` on any I/O error while either reading or decoding a `Value`.
@@ -126,6 +223,32 @@ fn read_ext_body<R: Read>(rd: &mut R, len: usize) -> Result<(i8, Vec<u8>), Error
 /// operation is retried.
 pub fn read_value<R>(rd: &mut R) -> Result<Value, Error>
     where R: Read
+{
+    read_value_with_policy(rd, DuplicateKeys::Allow)
+}
+
+/// Attempts to read bytes from the given reader and interpret them as a `Value`, applying the
+/// given policy to every map with duplicate keys, including nested ones.
+///
+/// # Errors
+///
+/// In addition to errors returned by `read_value`, this function returns `Error::DuplicateKey`
+/// if the policy is `DuplicateKeys::Reject` and some map contains duplicate keys.
+///
+/// # Examples
+///
+/// ```
+/// use rmpv::Value;
+/// use rmpv::decode::{read_value_with_policy, DuplicateKeys};
+///
+/// // {1: "a", 1: "b"}
+/// let buf = [0x82, 0x01, 0xa1, 0x61, 0x01, 0xa1, 0x62];
+///
+/// let val = read_value_with_policy(&mut &buf[..], DuplicateKeys::KeepLast).unwrap();
+/// assert_eq!(Value::Map(vec![(Value::from(1), Value::String("b".into()))]), val);
+/// ```
+pub fn read_value_with_policy<R>(rd: &mut R, keys: DuplicateKeys) -> Result<Value, Error>
+    where R: Read
 {
     let val = match read_marker(rd)? {
         Marker::Null => Value::Nil,
@@ -163,31 +286,31 @@ pub fn read_value<R>(rd: &mut R) -> Result<Value, Error>
             Value::String(res)
         }
         Marker::FixArray(len) => {
-            let vec = read_array_data(rd, len as usize)?;
+            let vec = read_array_data(rd, len as usize, keys)?;
             Value::Array(vec)
         }
         Marker::Array16 => {
             let len = read_data_u16(rd)?;
-            let vec = read_array_data(rd, len as usize)?;
+            let vec = read_array_data(rd, len as usize, keys)?;
             Value::Array(vec)
         }
         Marker::Array32 => {
             let len = read_data_u32(rd)?;
-            let vec = read_array_data(rd, len as usize)?;
+            let vec = read_array_data(rd, len as usize, keys)?;
             Value::Array(vec)
         }
         Marker::FixMap(len) => {
-            let map = read_map_data(rd, len as usize)?;
+            let map = read_map_data(rd, len as usize, keys)?;
             Value::Map(map)
         }
         Marker::Map16 => {
             let len = read_data_u16(rd)?;
-            let map = read_map_data(rd, len as usize)?;
+            let map = read_map_data(rd, len as usize, keys)?;
             Value::Map(map)
         }
         Marker::Map32 => {
             let len = read_data_u32(rd)?;
-            let map = read_map_data(rd, len as usize)?;
+            let map = read_map_data(rd, len as usize, keys)?;
             Value::Map(map)
         }
         Marker::Bin8 => {
@@ -2,38 +2,32 @@ use std;
 // use std::error;
 // use std::fmt;
 use std::io::{self, Cursor, ErrorKind, Read};
-use std::str::{from_utf8, Utf8Error};
 
 use rmp::Marker;
 use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64,
                   read_data_i8, read_data_i16, read_data_i32, read_data_i64, read_data_f32,
                   read_data_f64, MarkerReadError, ValueReadError};
 
-use ValueRef;
+use {Utf8StringRef, ValueRef};
 
 #[derive(Debug)]
-pub enum Error<'r> {
+pub enum Error {
     /// Failed to read the type marker value.
     InvalidMarkerRead(io::Error),
     /// Failed to read packed non-marker data.
     InvalidDataRead(io::Error),
     /// Decoded value type isn't equal with the expected one.
     TypeMismatch(Marker),
-    /// Failed to interpret a byte slice as a UTF-8 string.
-    ///
-    /// Contains untouched bytearray with the underlying decoding error.
-    InvalidUtf8(&'r [u8], Utf8Error),
 }
 
-impl<'r> Error<'r> {
+impl Error {
     pub fn insufficient_bytes(&self) -> bool {
         match *self {
             Error::InvalidMarkerRead(ref err) if err.kind() == ErrorKind::UnexpectedEof => true,
             Error::InvalidDataRead(ref err) if err.kind() == ErrorKind::UnexpectedEof => true,
             Error::InvalidMarkerRead(..) |
             Error::InvalidDataRead(..) |
-            Error::TypeMismatch(..) |
-            Error::InvalidUtf8(..) => false,
+            Error::TypeMismatch(..) => false,
         }
     }
 
@@ -41,8 +35,7 @@ impl<'r> Error<'r> {
         match *self {
             Error::InvalidMarkerRead(ref err) => err.kind(),
             Error::InvalidDataRead(ref err) => err.kind(),
-            Error::TypeMismatch(..) |
-            Error::InvalidUtf8(..) => ErrorKind::Other,
+            Error::TypeMismatch(..) => ErrorKind::Other,
         }
     }
 }
@@ -81,14 +74,14 @@ impl<'r> Error<'r> {
 // }
 //
 
-impl<'r> From<MarkerReadError> for Error<'r> {
-    fn from(err: MarkerReadError) -> Error<'r> {
+impl From<MarkerReadError> for Error {
+    fn from(err: MarkerReadError) -> Error {
         Error::InvalidMarkerRead(err.0)
     }
 }
 
-impl<'r> From<ValueReadError> for Error<'r> {
-    fn from(err: ValueReadError) -> Error<'r> {
+impl From<ValueReadError> for Error {
+    fn from(err: ValueReadError) -> Error {
         match err {
             ValueReadError::InvalidMarkerRead(err) => Error::InvalidMarkerRead(err),
             ValueReadError::InvalidDataRead(err) => Error::InvalidDataRead(err),
@@ -104,21 +97,20 @@ impl<'r> From<ValueReadError> for Error<'r> {
 //     D::read(rd).map_err(From::from)
 // }
 //
-// fn read_num<'a, R, D>(mut rd: &mut R) -> Result<D, Error<'a>>
+// fn read_num<'a, R, D>(mut rd: &mut R) -> Result<D, Error>
 //     where R: BorrowRead<'a>,
 //           D: BigEndianRead
 // {
 //     D::read(&mut rd).map_err(|err| Error::InvalidDataRead(From::from(err)))
 // }
 
-fn read_str_data<'a, R>(rd: &mut R, len: usize) -> Result<&'a str, Error<'a>>
+fn read_str_data<'a, R>(rd: &mut R, len: usize) -> Result<Utf8StringRef<'a>, Error>
     where R: BorrowRead<'a>
 {
-    let buf = read_bin_data(rd, len)?;
-    from_utf8(buf).map_err(|err| Error::InvalidUtf8(buf, err))
+    read_bin_data(rd, len).map(Utf8StringRef::from_bytes)
 }
 
-fn read_bin_data<'a, R>(rd: &mut R, len: usize) -> Result<&'a [u8], Error<'a>>
+fn read_bin_data<'a, R>(rd: &mut R, len: usize) -> Result<&'a [u8], Error>
     where R: BorrowRead<'a>
 {
     let buf = rd.fill_buf();
@@ -134,7 +126,7 @@ fn read_bin_data<'a, R>(rd: &mut R, len: usize) -> Result<&'a [u8], Error<'a>>
     Ok(buf)
 }
 
-fn read_ext_body<'a, R>(rd: &mut R, len: usize) -> Result<(i8, &'a [u8]), Error<'a>>
+fn read_ext_body<'a, R>(rd: &mut R, len: usize) -> Result<(i8, &'a [u8]), Error>
     where R: BorrowRead<'a>
 {
     let ty = read_data_i8(rd)?;
@@ -143,7 +135,7 @@ fn read_ext_body<'a, R>(rd: &mut R, len: usize) -> Result<(i8, &'a [u8]), Error<
     Ok((ty, buf))
 }
 
-fn read_array_data<'a, R>(rd: &mut R, mut len: usize) -> Result<Vec<ValueRef<'a>>, Error<'a>>
+fn read_array_data<'a, R>(rd: &mut R, mut len: usize) -> Result<Vec<ValueRef<'a>>, Error>
     where R: BorrowRead<'a>
 {
     let mut vec = Vec::with_capacity(len);
@@ -156,7 +148,7 @@ fn read_array_data<'a, R>(rd: &mut R, mut len: usize) -> Result<Vec<ValueRef<'a>
     Ok(vec)
 }
 
-fn read_map_data<'a, R>(rd: &mut R, mut len: usize) -> Result<Vec<(ValueRef<'a>, ValueRef<'a>)>, Error<'a>>
+fn read_map_data<'a, R>(rd: &mut R, mut len: usize) -> Result<Vec<(ValueRef<'a>, ValueRef<'a>)>, Error>
     where R: BorrowRead<'a>
 {
     let mut vec = Vec::with_capacity(len);
@@ -219,6 +211,9 @@ impl<'a> BorrowRead<'a> for Cursor<&'a [u8]> {
 /// Returns either a non-owning `ValueRef`, which borrows the buffer from the given reader or an
 /// error.
 ///
+/// Strings containing invalid UTF-8 are not rejected, but decoded as `Utf8StringRef` borrowing
+/// their raw bytes.
+///
 /// The reader should meet the requirement of a special `BorrowRead` trait, which allows to mutate
 /// itself but permits to mutate the buffer it contains. It allows to perform a completely
 /// zero-copy reading without a data loss fear in case of an error.
@@ -241,9 +236,9 @@ impl<'a> BorrowRead<'a> for Cursor<&'a [u8]> {
 /// let buf = [0xaa, 0x6c, 0x65, 0x20, 0x6d, 0x65, 0x73, 0x73, 0x61, 0x67, 0x65];
 /// let mut rd = &buf[..];
 ///
-/// assert_eq!(ValueRef::String("le message"), read_value_ref(&mut rd).unwrap());
+/// assert_eq!(ValueRef::from("le message"), read_value_ref(&mut rd).unwrap());
 /// ```
-pub fn read_value_ref<'a, R>(rd: &mut R) -> Result<ValueRef<'a>, Error<'a>>
+pub fn read_value_ref<'a, R>(rd: &mut R) -> Result<ValueRef<'a>, Error>
     where R: BorrowRead<'a>
 {
     let mut rd = rd;
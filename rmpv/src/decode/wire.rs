@@ -0,0 +1,111 @@
+use std::io::Read;
+
+use rmp::Marker;
+use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64,
+                  read_data_i8, read_data_i16, read_data_i32, read_data_i64, read_data_f32,
+                  read_data_f64};
+
+use wire::{WireKind, WireValue};
+use super::value::{read_str_data, read_bin_data, read_ext_body, Error};
+
+/// Attempts to read bytes from the given reader and interpret them as a `WireValue`, which
+/// remembers the marker of every node.
+///
+/// # Errors
+///
+/// This function returns the same errors as `read_value`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate rmp;
+/// extern crate rmpv;
+///
+/// use rmp::Marker;
+/// use rmpv::decode::read_wire_value;
+/// use rmpv::wire::WireKind;
+///
+/// # fn main() {
+/// let val = read_wire_value(&mut &[0xcd, 0x00, 0x2a][..]).unwrap();
+///
+/// assert_eq!(Some(Marker::U16), val.marker);
+/// assert_eq!(WireKind::U64(42), val.kind);
+/// # }
+/// ```
+pub fn read_wire_value<R>(rd: &mut R) -> Result<WireValue, Error>
+    where R: Read
+{
+    let marker = read_marker(rd)?;
+    let kind = match marker {
+        Marker::Null => WireKind::Nil,
+        Marker::True => WireKind::Boolean(true),
+        Marker::False => WireKind::Boolean(false),
+        Marker::FixPos(val) => WireKind::U64(val as u64),
+        Marker::FixNeg(val) => WireKind::I64(val as i64),
+        Marker::U8 => WireKind::U64(read_data_u8(rd)? as u64),
+        Marker::U16 => WireKind::U64(read_data_u16(rd)? as u64),
+        Marker::U32 => WireKind::U64(read_data_u32(rd)? as u64),
+        Marker::U64 => WireKind::U64(read_data_u64(rd)?),
+        Marker::I8 => WireKind::I64(read_data_i8(rd)? as i64),
+        Marker::I16 => WireKind::I64(read_data_i16(rd)? as i64),
+        Marker::I32 => WireKind::I64(read_data_i32(rd)? as i64),
+        Marker::I64 => WireKind::I64(read_data_i64(rd)?),
+        Marker::F32 => WireKind::F32(read_data_f32(rd)?),
+        Marker::F64 => WireKind::F64(read_data_f64(rd)?),
+        Marker::FixStr(..) | Marker::Str8 | Marker::Str16 | Marker::Str32 => {
+            let len = read_len(rd, marker)?;
+            WireKind::String(read_str_data(rd, len)?)
+        }
+        Marker::Bin8 | Marker::Bin16 | Marker::Bin32 => {
+            let len = read_len(rd, marker)?;
+            WireKind::Binary(read_bin_data(rd, len)?)
+        }
+        Marker::FixArray(..) | Marker::Array16 | Marker::Array32 => {
+            let len = read_len(rd, marker)?;
+            let mut vec = Vec::with_capacity(len);
+            for _ in 0..len {
+                vec.push(read_wire_value(rd)?);
+            }
+            WireKind::Array(vec)
+        }
+        Marker::FixMap(..) | Marker::Map16 | Marker::Map32 => {
+            let len = read_len(rd, marker)?;
+            let mut map = Vec::with_capacity(len);
+            for _ in 0..len {
+                map.push((read_wire_value(rd)?, read_wire_value(rd)?));
+            }
+            WireKind::Map(map)
+        }
+        Marker::FixExt1 | Marker::FixExt2 | Marker::FixExt4 | Marker::FixExt8 |
+        Marker::FixExt16 | Marker::Ext8 | Marker::Ext16 | Marker::Ext32 => {
+            let len = read_len(rd, marker)?;
+            let (ty, vec) = read_ext_body(rd, len)?;
+            WireKind::Ext(ty, vec)
+        }
+        Marker::Reserved => return Err(Error::TypeMismatch(Marker::Reserved)),
+    };
+
+    Ok(WireValue {
+        marker: Some(marker),
+        kind: kind,
+    })
+}
+
+// Reads the length following the marker of a string, binary, array, map or extension.
+fn read_len<R: Read>(rd: &mut R, marker: Marker) -> Result<usize, Error> {
+    let len = match marker {
+        Marker::FixStr(len) | Marker::FixArray(len) | Marker::FixMap(len) => len as usize,
+        Marker::FixExt1 => 1,
+        Marker::FixExt2 => 2,
+        Marker::FixExt4 => 4,
+        Marker::FixExt8 => 8,
+        Marker::FixExt16 => 16,
+        Marker::Str8 | Marker::Bin8 | Marker::Ext8 => read_data_u8(rd)? as usize,
+        Marker::Str16 | Marker::Bin16 | Marker::Array16 | Marker::Map16 | Marker::Ext16 => {
+            read_data_u16(rd)? as usize
+        }
+        _ => read_data_u32(rd)? as usize,
+    };
+
+    Ok(len)
+}
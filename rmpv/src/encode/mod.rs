@@ -2,6 +2,8 @@ pub use rmp::encode::ValueWriteError as Error;
 
 mod value;
 mod value_ref;
+mod wire;
 
-pub use self::value::write_value;
+pub use self::value::{write_value, write_value_canonical};
 pub use self::value_ref::write_value_ref;
+pub use self::wire::write_wire_value;
@@ -1,7 +1,9 @@
+use std::f32;
+use std::f64;
 use std::io::Write;
 
-use rmp::encode::{write_nil, write_bool, write_uint, write_sint, write_f32, write_f64, write_str,
-                  write_bin, write_array_len, write_map_len, write_ext_meta};
+use rmp::encode::{write_nil, write_bool, write_uint, write_sint, write_f32, write_f64,
+                  write_str_len, write_bin, write_array_len, write_map_len, write_ext_meta};
 
 use Value;
 use super::Error;
@@ -35,7 +37,8 @@ pub fn write_value<W>(wr: &mut W, val: &Value) -> Result<(), Error>
             write_f64(wr, val)?;
         }
         Value::String(ref val) => {
-            write_str(wr, &val)?;
+            write_str_len(wr, val.as_bytes().len() as u32)?;
+            wr.write_all(val.as_bytes()).map_err(Error::InvalidDataWrite)?;
         }
         Value::Binary(ref val) => {
             write_bin(wr, &val)?;
@@ -61,3 +64,79 @@ pub fn write_value<W>(wr: &mut W, val: &Value) -> Result<(), Error>
 
     Ok(())
 }
+
+/// Encodes and attempts to write the given Value in the canonical form.
+///
+/// Unlike `write_value`, the output depends only on the value itself, which makes it suitable for
+/// signing and content addressing:
+///
+/// - map entries are sorted by their encoded keys in bytewise lexicographic order;
+/// - entries with duplicate keys are collapsed, keeping the last one, the same way inserting them
+///   into a `HashMap` would do;
+/// - non-negative integers are always written using the unsigned family;
+/// - every NaN is written as the canonical quiet NaN of its width;
+/// - all markers use the most compact representation.
+///
+/// The result can be verified using `rmp::decode::check_canonical`.
+///
+/// # Examples
+///
+/// ```
+/// use rmpv::Value;
+/// use rmpv::encode::write_value_canonical;
+///
+/// let val = Value::Map(vec![
+///     (Value::from(2), Value::Nil),
+///     (Value::from(1), Value::Nil),
+/// ]);
+///
+/// let mut buf = Vec::new();
+/// write_value_canonical(&mut buf, &val).unwrap();
+///
+/// assert_eq!(vec![0x82, 0x01, 0xc0, 0x02, 0xc0], buf);
+/// ```
+pub fn write_value_canonical<W>(wr: &mut W, val: &Value) -> Result<(), Error>
+    where W: Write
+{
+    match *val {
+        Value::I64(val) if val >= 0 => {
+            write_uint(wr, val as u64)?;
+        }
+        Value::F32(val) if val.is_nan() => {
+            write_f32(wr, f32::NAN)?;
+        }
+        Value::F64(val) if val.is_nan() => {
+            write_f64(wr, f64::NAN)?;
+        }
+        Value::Array(ref vec) => {
+            write_array_len(wr, vec.len() as u32)?;
+            for v in vec {
+                write_value_canonical(wr, v)?;
+            }
+        }
+        Value::Map(ref map) => {
+            let mut entries = Vec::with_capacity(map.len());
+            for &(ref key, ref val) in map {
+                let mut kbuf = Vec::new();
+                let mut vbuf = Vec::new();
+                write_value_canonical(&mut kbuf, key)?;
+                write_value_canonical(&mut vbuf, val)?;
+                entries.push((kbuf, vbuf));
+            }
+
+            // Reverse first, so that the stable sort followed by dedup keeps the last duplicate.
+            entries.reverse();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries.dedup_by(|a, b| a.0 == b.0);
+
+            write_map_len(wr, entries.len() as u32)?;
+            for &(ref key, ref val) in &entries {
+                wr.write_all(key).map_err(|err| Error::InvalidDataWrite(err))?;
+                wr.write_all(val).map_err(|err| Error::InvalidDataWrite(err))?;
+            }
+        }
+        _ => write_value(wr, val)?,
+    }
+
+    Ok(())
+}
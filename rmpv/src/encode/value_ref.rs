@@ -1,7 +1,7 @@
 use std::io::Write;
 
-use rmp::encode::{write_bool, write_nil, write_sint, write_uint, write_f32, write_f64, write_str,
-                  write_bin, write_array_len, write_map_len, write_ext_meta};
+use rmp::encode::{write_bool, write_nil, write_sint, write_uint, write_f32, write_f64,
+                  write_str_len, write_bin, write_array_len, write_map_len, write_ext_meta};
 
 use ValueRef;
 use super::Error;
@@ -19,7 +19,7 @@ use super::Error;
 /// use rmpv::encode::write_value_ref;
 ///
 /// let mut buf = Vec::new();
-/// let val = ValueRef::String("le message");
+/// let val = ValueRef::String("le message".into());
 ///
 /// write_value_ref(&mut buf, &val).unwrap();
 /// assert_eq!(vec![0xaa, 0x6c, 0x65, 0x20, 0x6d, 0x65, 0x73, 0x73, 0x61, 0x67, 0x65], buf);
@@ -47,7 +47,8 @@ pub fn write_value_ref<W>(wr: &mut W, val: &ValueRef) -> Result<(), Error>
             write_f64(wr, val)?;
         }
         ValueRef::String(val) => {
-            write_str(wr, val)?;
+            write_str_len(wr, val.as_bytes().len() as u32)?;
+            wr.write_all(val.as_bytes()).map_err(Error::InvalidDataWrite)?;
         }
         ValueRef::Binary(val) => {
             write_bin(wr, val)?;
@@ -0,0 +1,187 @@
+use std::io::Write;
+
+use rmp::Marker;
+use rmp::encode::{write_nil, write_bool, write_pfix, write_nfix, write_u8, write_u16, write_u32,
+                  write_u64, write_i8, write_i16, write_i32, write_i64, write_uint, write_sint,
+                  write_f32, write_f64, write_str_len, write_bin_len, write_array_len,
+                  write_map_len, write_ext_meta};
+
+use wire::{WireKind, WireValue};
+use super::Error;
+
+/// Encodes and attempts to write the given `WireValue`, using the marker of each node if it can
+/// represent the contents of the node and the most efficient representation otherwise.
+///
+/// Nodes read using `read_wire_value`, which were not modified, are written exactly as they were
+/// read.
+///
+/// # Note
+///
+/// All instances of `ErrorKind::Interrupted` are handled by this function and the underlying
+/// operation is retried.
+pub fn write_wire_value<W>(wr: &mut W, val: &WireValue) -> Result<(), Error>
+    where W: Write
+{
+    match val.kind {
+        WireKind::Nil => {
+            write_nil(wr).map_err(Error::InvalidMarkerWrite)?;
+        }
+        WireKind::Boolean(val) => {
+            write_bool(wr, val).map_err(Error::InvalidMarkerWrite)?;
+        }
+        WireKind::U64(num) => {
+            let sint = if num <= i64::max_value() as u64 { Some(num as i64) } else { None };
+            write_int(wr, val.marker, Some(num), sint)?;
+        }
+        WireKind::I64(num) => {
+            let uint = if num >= 0 { Some(num as u64) } else { None };
+            write_int(wr, val.marker, uint, Some(num))?;
+        }
+        WireKind::F32(num) => {
+            // Widening is exact, so a float32 may keep a float64 marker.
+            if val.marker == Some(Marker::F64) {
+                write_f64(wr, num as f64)?;
+            } else {
+                write_f32(wr, num)?;
+            }
+        }
+        WireKind::F64(num) => {
+            write_f64(wr, num)?;
+        }
+        WireKind::String(ref data) => {
+            write_len(wr, val.marker, Family::Str, data.as_bytes().len())?;
+            wr.write_all(data.as_bytes()).map_err(Error::InvalidDataWrite)?;
+        }
+        WireKind::Binary(ref data) => {
+            write_len(wr, val.marker, Family::Bin, data.len())?;
+            wr.write_all(data).map_err(Error::InvalidDataWrite)?;
+        }
+        WireKind::Array(ref vec) => {
+            write_len(wr, val.marker, Family::Array, vec.len())?;
+            for v in vec {
+                write_wire_value(wr, v)?;
+            }
+        }
+        WireKind::Map(ref map) => {
+            write_len(wr, val.marker, Family::Map, map.len())?;
+            for &(ref key, ref val) in map {
+                write_wire_value(wr, key)?;
+                write_wire_value(wr, val)?;
+            }
+        }
+        WireKind::Ext(ty, ref data) => {
+            write_len(wr, val.marker, Family::Ext(ty), data.len())?;
+            wr.write_all(data).map_err(Error::InvalidDataWrite)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Writes an integer given as unsigned and signed, if representable, using the marker if it fits.
+fn write_int<W: Write>(wr: &mut W, marker: Option<Marker>, uint: Option<u64>, sint: Option<i64>)
+    -> Result<(), Error>
+{
+    match (marker, uint, sint) {
+        (Some(Marker::FixPos(..)), Some(val), _) if val < 128 => {
+            write_pfix(wr, val as u8).map_err(Error::InvalidMarkerWrite)
+        }
+        (Some(Marker::FixNeg(..)), _, Some(val)) if (-32..0).contains(&val) => {
+            write_nfix(wr, val as i8).map_err(Error::InvalidMarkerWrite)
+        }
+        (Some(Marker::U8), Some(val), _) if val <= u8::max_value() as u64 => {
+            write_u8(wr, val as u8)
+        }
+        (Some(Marker::U16), Some(val), _) if val <= u16::max_value() as u64 => {
+            write_u16(wr, val as u16)
+        }
+        (Some(Marker::U32), Some(val), _) if val <= u32::max_value() as u64 => {
+            write_u32(wr, val as u32)
+        }
+        (Some(Marker::U64), Some(val), _) => write_u64(wr, val),
+        (Some(Marker::I8), _, Some(val)) if val >= i8::min_value() as i64 &&
+                                             val <= i8::max_value() as i64 => {
+            write_i8(wr, val as i8)
+        }
+        (Some(Marker::I16), _, Some(val)) if val >= i16::min_value() as i64 &&
+                                              val <= i16::max_value() as i64 => {
+            write_i16(wr, val as i16)
+        }
+        (Some(Marker::I32), _, Some(val)) if val >= i32::min_value() as i64 &&
+                                              val <= i32::max_value() as i64 => {
+            write_i32(wr, val as i32)
+        }
+        (Some(Marker::I64), _, Some(val)) => write_i64(wr, val),
+        (_, Some(val), _) => write_uint(wr, val).map(|_| ()),
+        (_, None, Some(val)) => write_sint(wr, val).map(|_| ()),
+        (_, None, None) => unreachable!("every integer is either unsigned or signed"),
+    }
+}
+
+// Families of markers followed by a length.
+#[derive(Clone, Copy)]
+enum Family {
+    Str,
+    Bin,
+    Array,
+    Map,
+    Ext(i8),
+}
+
+// Writes the marker and the length of a string, binary, array, map or extension, using the given
+// marker if it belongs to the family and can represent the length.
+fn write_len<W: Write>(wr: &mut W, marker: Option<Marker>, family: Family, len: usize)
+    -> Result<(), Error>
+{
+    let fits = |max: u64| len as u64 <= max;
+    // The marker to write and the number of bytes of the length following it.
+    let explicit = match (family, marker) {
+        (Family::Str, Some(Marker::FixStr(..))) if len < 32 => Some((Marker::FixStr(len as u8), 0)),
+        (Family::Str, Some(Marker::Str8)) if fits(0xff) => Some((Marker::Str8, 1)),
+        (Family::Str, Some(Marker::Str16)) if fits(0xffff) => Some((Marker::Str16, 2)),
+        (Family::Str, Some(Marker::Str32)) if fits(0xffffffff) => Some((Marker::Str32, 4)),
+        (Family::Bin, Some(Marker::Bin8)) if fits(0xff) => Some((Marker::Bin8, 1)),
+        (Family::Bin, Some(Marker::Bin16)) if fits(0xffff) => Some((Marker::Bin16, 2)),
+        (Family::Bin, Some(Marker::Bin32)) if fits(0xffffffff) => Some((Marker::Bin32, 4)),
+        (Family::Array, Some(Marker::FixArray(..))) if len < 16 => {
+            Some((Marker::FixArray(len as u8), 0))
+        }
+        (Family::Array, Some(Marker::Array16)) if fits(0xffff) => Some((Marker::Array16, 2)),
+        (Family::Array, Some(Marker::Array32)) if fits(0xffffffff) => Some((Marker::Array32, 4)),
+        (Family::Map, Some(Marker::FixMap(..))) if len < 16 => Some((Marker::FixMap(len as u8), 0)),
+        (Family::Map, Some(Marker::Map16)) if fits(0xffff) => Some((Marker::Map16, 2)),
+        (Family::Map, Some(Marker::Map32)) if fits(0xffffffff) => Some((Marker::Map32, 4)),
+        (Family::Ext(..), Some(Marker::FixExt1)) if len == 1 => Some((Marker::FixExt1, 0)),
+        (Family::Ext(..), Some(Marker::FixExt2)) if len == 2 => Some((Marker::FixExt2, 0)),
+        (Family::Ext(..), Some(Marker::FixExt4)) if len == 4 => Some((Marker::FixExt4, 0)),
+        (Family::Ext(..), Some(Marker::FixExt8)) if len == 8 => Some((Marker::FixExt8, 0)),
+        (Family::Ext(..), Some(Marker::FixExt16)) if len == 16 => Some((Marker::FixExt16, 0)),
+        (Family::Ext(..), Some(Marker::Ext8)) if fits(0xff) => Some((Marker::Ext8, 1)),
+        (Family::Ext(..), Some(Marker::Ext16)) if fits(0xffff) => Some((Marker::Ext16, 2)),
+        (Family::Ext(..), Some(Marker::Ext32)) if fits(0xffffffff) => Some((Marker::Ext32, 4)),
+        _ => None,
+    };
+
+    match explicit {
+        Some((marker, size)) => {
+            wr.write_all(&[marker.to_u8()]).map_err(Error::InvalidMarkerWrite)?;
+            let len = (len as u32).to_be_bytes();
+            wr.write_all(&len[4 - size..]).map_err(Error::InvalidDataWrite)?;
+            if let Family::Ext(ty) = family {
+                wr.write_all(&[ty as u8]).map_err(Error::InvalidDataWrite)?;
+            }
+        }
+        None => {
+            let len = len as u32;
+            match family {
+                Family::Str => write_str_len(wr, len)?,
+                Family::Bin => write_bin_len(wr, len)?,
+                Family::Array => write_array_len(wr, len)?,
+                Family::Map => write_map_len(wr, len)?,
+                Family::Ext(ty) => write_ext_meta(wr, len, ty)?,
+            };
+        }
+    }
+
+    Ok(())
+}
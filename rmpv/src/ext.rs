@@ -16,7 +16,11 @@ impl Serialize for Value {
             Value::U64(v) => s.serialize_u64(v),
             Value::F32(v) => s.serialize_f32(v),
             Value::F64(v) => s.serialize_f64(v),
-            Value::String(ref v) => s.serialize_str(v),
+            Value::String(ref v) => match v.as_str() {
+                Some(v) => s.serialize_str(v),
+                // Strings containing invalid UTF-8 can only be serialized as bytes.
+                None => Bytes::from(v.as_bytes()).serialize(s),
+            },
             Value::Binary(ref v) => Bytes::from(v).serialize(s),
             Value::Array(ref array) => {
                 let mut state = s.serialize_seq(Some(array.len()))?;
@@ -104,7 +108,7 @@ impl Deserialize for Value {
 
             #[inline]
             fn visit_string<E>(self, value: String) -> Result<Value, E> {
-                Ok(Value::String(value))
+                Ok(Value::from(value))
             }
 
             #[inline]
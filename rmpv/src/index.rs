@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+/// A hashed index of string keys of a map, which allows to look up values in constant time.
+///
+/// The index borrows the map it was built from, so the map cannot be modified while the index is
+/// alive. Entries with non-string keys are not indexed. If the map contains duplicate keys, the
+/// first entry wins, which is consistent with `Value::get`.
+///
+/// Created by `Value::key_index` or `ValueRef::key_index`.
+#[derive(Clone, Debug)]
+pub struct KeyIndex<'a, V: 'a> {
+    map: HashMap<&'a str, &'a V>,
+}
+
+impl<'a, V: 'a> KeyIndex<'a, V> {
+    pub(crate) fn new<I>(entries: I) -> Self
+        where I: Iterator<Item = (&'a str, &'a V)>
+    {
+        let mut map = HashMap::with_capacity(entries.size_hint().0);
+        for (key, val) in entries {
+            if let Entry::Vacant(entry) = map.entry(key) {
+                entry.insert(val);
+            }
+        }
+
+        KeyIndex { map: map }
+    }
+
+    /// Returns a reference to the value corresponding to the given key.
+    pub fn get(&self, key: &str) -> Option<&'a V> {
+        self.map.get(key).map(|val| *val)
+    }
+
+    /// Returns true if the index contains the given key.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Returns the number of indexed keys.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if no keys are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
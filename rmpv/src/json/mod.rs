@@ -0,0 +1,458 @@
+//! Conversions between `Value` and `serde_json::Value`.
+//!
+//! Every JSON value can be represented as a `Value`, so the conversion from JSON is infallible.
+//! The opposite direction is configured using `ToJson`, which allows to choose how to represent
+//! values JSON cannot express: binaries, extensions, non-string map keys, non-finite floats and
+//! integers beyond 2<sup>53</sup>, which are not exactly representable by most JSON parsers.
+//! Strings containing invalid UTF-8 are always converted replacing invalid sequences with
+//! `U+FFFD`. Each conversion, which changes the representation of a value, is reported as a
+//! `Loss`.
+//!
+//! Note that `serde_json` sorts object keys unless its `preserve_order` feature is enabled.
+//!
+//! Large documents can be transcoded between MessagePack and JSON without building a `Value`
+//! using `ToJson::transcode` and `transcode_from_json`.
+//!
+//! This module is available with the `with-serde-json` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate rmpv;
+//! extern crate serde_json;
+//!
+//! use rmpv::json::{BinaryPolicy, LossKind, ToJson};
+//!
+//! # fn main() {
+//! let val = msgpack!({"data": bin([0, 255]), "id": 42});
+//!
+//! let mut conv = ToJson::new();
+//! conv.set_binary_policy(BinaryPolicy::Array);
+//!
+//! let (json, losses) = conv.convert(&val).unwrap();
+//!
+//! assert_eq!(r#"{"data":[0,255],"id":42}"#, serde_json::to_string(&json).unwrap());
+//! assert_eq!(LossKind::Binary, losses[0].kind);
+//! assert_eq!("/data", losses[0].path);
+//! # }
+//! ```
+
+pub use self::stream::{transcode_from_json, TranscodeError};
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+
+use serde_json::{self, Map, Number};
+
+use Value;
+use pointer::escape;
+
+mod stream;
+
+/// The largest integer, which is exactly representable as a double precision float.
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+
+/// The default maximum nesting depth of transcoded arrays and maps.
+const MAX_DEPTH: usize = 512;
+
+/// Specifies how binaries are converted into JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryPolicy {
+    /// Converts binaries into base64 encoded strings with padding. This is the default.
+    Base64,
+    /// Converts binaries into arrays of integers.
+    Array,
+    /// Fails the conversion.
+    Error,
+}
+
+/// Specifies how extensions are converted into JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtPolicy {
+    /// Converts extensions into tagged objects like `{"$ext": {"type": 1, "data": "AP8="}}`,
+    /// where the data is base64 encoded. This is the default.
+    Tagged,
+    /// Fails the conversion.
+    Error,
+}
+
+/// Specifies how non-string map keys are converted into JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyPolicy {
+    /// Converts keys into strings using their text notation, e.g. `42`, `i64(5)` or `nil`, and
+    /// reports each of them as `LossKind::NonStringKey`. This is the default.
+    Stringify,
+    /// Fails the conversion.
+    Error,
+}
+
+/// Specifies how NaN and infinite floats are converted into JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatPolicy {
+    /// Converts such floats into `null`. This is the default.
+    Null,
+    /// Converts such floats into `"NaN"`, `"Infinity"` or `"-Infinity"` strings.
+    String,
+    /// Fails the conversion.
+    Error,
+}
+
+/// Specifies how integers with absolute value greater than 2<sup>53</sup> are converted into
+/// JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntPolicy {
+    /// Keeps such integers as numbers, which `serde_json` represents exactly. This is the
+    /// default.
+    Exact,
+    /// Converts such integers into decimal strings.
+    String,
+    /// Fails the conversion.
+    Error,
+}
+
+/// A kind of value, which cannot be represented in JSON as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LossKind {
+    /// A binary.
+    Binary,
+    /// An extension.
+    Ext,
+    /// A map key, which is not a string.
+    NonStringKey,
+    /// A map key, which is equal to a previous key of the same map after conversion. The last
+    /// entry wins.
+    DuplicateKey,
+    /// A NaN or infinite float.
+    NonFiniteFloat,
+    /// An integer with absolute value greater than 2<sup>53</sup>.
+    LargeInteger,
+    /// A string or a map key containing invalid UTF-8, which is replaced with `U+FFFD`.
+    InvalidUtf8,
+}
+
+impl LossKind {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            LossKind::Binary => "binary",
+            LossKind::Ext => "extension",
+            LossKind::NonStringKey => "non-string key",
+            LossKind::DuplicateKey => "duplicate key",
+            LossKind::NonFiniteFloat => "non-finite float",
+            LossKind::LargeInteger => "large integer",
+            LossKind::InvalidUtf8 => "invalid UTF-8 string",
+        }
+    }
+}
+
+impl Display for LossKind {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        fmt.write_str(self.as_str())
+    }
+}
+
+/// A value, which representation was changed during the conversion into JSON.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Loss {
+    /// The kind of the value.
+    pub kind: LossKind,
+    /// JSON pointer to the value in the resulting JSON. For keys it points to the entry.
+    pub path: String,
+}
+
+/// An error, which occurs when a value cannot be converted into JSON due to the configured
+/// policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    /// The kind of the value.
+    pub kind: LossKind,
+    /// JSON pointer to the value in the resulting JSON. For keys it points to the map.
+    pub path: String,
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "value cannot be represented in JSON"
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "{} at {:?} cannot be represented in JSON", self.kind.as_str(), self.path)
+    }
+}
+
+/// Converts `Value` into `serde_json::Value` according to the configured policies.
+#[derive(Clone, Debug)]
+pub struct ToJson {
+    binary: BinaryPolicy,
+    ext: ExtPolicy,
+    keys: KeyPolicy,
+    floats: FloatPolicy,
+    ints: IntPolicy,
+    max_depth: usize,
+}
+
+impl ToJson {
+    /// Constructs a new converter with the default policies, which never fail.
+    pub fn new() -> ToJson {
+        ToJson {
+            binary: BinaryPolicy::Base64,
+            ext: ExtPolicy::Tagged,
+            keys: KeyPolicy::Stringify,
+            floats: FloatPolicy::Null,
+            ints: IntPolicy::Exact,
+            max_depth: MAX_DEPTH,
+        }
+    }
+
+    /// Sets how binaries are converted.
+    pub fn set_binary_policy(&mut self, policy: BinaryPolicy) {
+        self.binary = policy;
+    }
+
+    /// Sets how extensions are converted.
+    pub fn set_ext_policy(&mut self, policy: ExtPolicy) {
+        self.ext = policy;
+    }
+
+    /// Sets how non-string map keys are converted.
+    pub fn set_key_policy(&mut self, policy: KeyPolicy) {
+        self.keys = policy;
+    }
+
+    /// Sets how NaN and infinite floats are converted.
+    pub fn set_float_policy(&mut self, policy: FloatPolicy) {
+        self.floats = policy;
+    }
+
+    /// Sets how integers beyond 2<sup>53</sup> are converted.
+    pub fn set_int_policy(&mut self, policy: IntPolicy) {
+        self.ints = policy;
+    }
+
+    /// Sets the maximum nesting depth of arrays and maps, which `transcode` and `transcode_with`
+    /// accept. The default is 512.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Converts the value into JSON, returning it along with all lossy conversions made in
+    /// document order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first value, which cannot be converted due to a policy
+    /// set to `Error`.
+    pub fn convert(&self, val: &Value) -> Result<(serde_json::Value, Vec<Loss>), Error> {
+        let mut losses = Vec::new();
+        let json = self.convert_into(val, &mut String::new(), &mut losses)?;
+        Ok((json, losses))
+    }
+
+    fn convert_into(&self,
+                    val: &Value,
+                    path: &mut String,
+                    losses: &mut Vec<Loss>)
+                    -> Result<serde_json::Value, Error>
+    {
+        let fail = |kind, path: &str| Err(Error { kind: kind, path: path.to_owned() });
+
+        let json = match *val {
+            Value::Nil => serde_json::Value::Null,
+            Value::Boolean(val) => serde_json::Value::Bool(val),
+            Value::U64(n) => self.convert_int(n.into(), n > MAX_SAFE_INTEGER, path, losses)?,
+            Value::I64(n) => {
+                let large = n < -(MAX_SAFE_INTEGER as i64) || n > MAX_SAFE_INTEGER as i64;
+                self.convert_int(n.into(), large, path, losses)?
+            }
+            Value::F32(n) => self.convert_float(n as f64, path, losses)?,
+            Value::F64(n) => self.convert_float(n, path, losses)?,
+            Value::String(ref val) => {
+                if val.is_err() {
+                    losses.push(Loss { kind: LossKind::InvalidUtf8, path: path.clone() });
+                }
+                serde_json::Value::String(val.to_string())
+            }
+            Value::Binary(ref buf) => {
+                let json = match self.binary {
+                    BinaryPolicy::Base64 => serde_json::Value::String(encode_base64(buf)),
+                    BinaryPolicy::Array => {
+                        serde_json::Value::Array(buf.iter().map(|&b| b.into()).collect())
+                    }
+                    BinaryPolicy::Error => return fail(LossKind::Binary, path),
+                };
+                losses.push(Loss { kind: LossKind::Binary, path: path.clone() });
+                json
+            }
+            Value::Array(ref array) => {
+                let mut res = Vec::with_capacity(array.len());
+                for (idx, val) in array.iter().enumerate() {
+                    let len = path.len();
+                    path.push('/');
+                    path.push_str(&idx.to_string());
+                    res.push(self.convert_into(val, path, losses)?);
+                    path.truncate(len);
+                }
+                serde_json::Value::Array(res)
+            }
+            Value::Map(ref map) => {
+                let mut res = Map::new();
+                for &(ref key, ref val) in map {
+                    let (key, loss) = match *key {
+                        Value::String(ref key) if key.is_str() => (key.to_string(), None),
+                        Value::String(ref key) => (key.to_string(), Some(LossKind::InvalidUtf8)),
+                        _ if self.keys == KeyPolicy::Error => {
+                            return fail(LossKind::NonStringKey, path);
+                        }
+                        ref key => (key.to_string(), Some(LossKind::NonStringKey)),
+                    };
+
+                    let len = path.len();
+                    path.push('/');
+                    path.push_str(&escape(&key));
+                    if let Some(kind) = loss {
+                        losses.push(Loss { kind: kind, path: path.clone() });
+                    }
+                    let val = self.convert_into(val, path, losses)?;
+                    if res.insert(key, val).is_some() {
+                        losses.push(Loss { kind: LossKind::DuplicateKey, path: path.clone() });
+                    }
+                    path.truncate(len);
+                }
+                serde_json::Value::Object(res)
+            }
+            Value::Ext(ty, ref buf) => {
+                if self.ext == ExtPolicy::Error {
+                    return fail(LossKind::Ext, path);
+                }
+
+                let mut ext = Map::new();
+                ext.insert("type".to_owned(), ty.into());
+                ext.insert("data".to_owned(), serde_json::Value::String(encode_base64(buf)));
+                let mut res = Map::new();
+                res.insert("$ext".to_owned(), serde_json::Value::Object(ext));
+
+                losses.push(Loss { kind: LossKind::Ext, path: path.clone() });
+                serde_json::Value::Object(res)
+            }
+        };
+
+        Ok(json)
+    }
+
+    fn convert_int(&self, num: Number, large: bool, path: &str, losses: &mut Vec<Loss>)
+        -> Result<serde_json::Value, Error>
+    {
+        if !large {
+            return Ok(serde_json::Value::Number(num));
+        }
+
+        let json = match self.ints {
+            IntPolicy::Exact => return Ok(serde_json::Value::Number(num)),
+            IntPolicy::String => serde_json::Value::String(num.to_string()),
+            IntPolicy::Error => {
+                return Err(Error { kind: LossKind::LargeInteger, path: path.to_owned() });
+            }
+        };
+
+        losses.push(Loss { kind: LossKind::LargeInteger, path: path.to_owned() });
+        Ok(json)
+    }
+
+    fn convert_float(&self, n: f64, path: &str, losses: &mut Vec<Loss>)
+        -> Result<serde_json::Value, Error>
+    {
+        if let Some(num) = Number::from_f64(n) {
+            return Ok(serde_json::Value::Number(num));
+        }
+
+        let json = match self.floats {
+            FloatPolicy::Null => serde_json::Value::Null,
+            FloatPolicy::String => {
+                let name = if n.is_nan() {
+                    "NaN"
+                } else if n > 0.0 {
+                    "Infinity"
+                } else {
+                    "-Infinity"
+                };
+                serde_json::Value::String(name.to_owned())
+            }
+            FloatPolicy::Error => {
+                return Err(Error { kind: LossKind::NonFiniteFloat, path: path.to_owned() });
+            }
+        };
+
+        losses.push(Loss { kind: LossKind::NonFiniteFloat, path: path.to_owned() });
+        Ok(json)
+    }
+}
+
+impl Default for ToJson {
+    fn default() -> ToJson {
+        ToJson::new()
+    }
+}
+
+/// Converts the value into JSON using the default policies of `ToJson`, ignoring losses.
+///
+/// Use `ToJson::convert` to find out which values were converted lossily, for example which map
+/// keys were stringified.
+impl<'a> From<&'a Value> for serde_json::Value {
+    fn from(val: &Value) -> serde_json::Value {
+        ToJson::new().convert(val).expect("default policies never fail").0
+    }
+}
+
+/// Converts JSON into a `Value`.
+///
+/// Non-negative integers are converted into `U64`, negative ones into `I64` and other numbers into
+/// `F64`. Objects are converted into maps with string keys.
+impl From<serde_json::Value> for Value {
+    fn from(val: serde_json::Value) -> Value {
+        match val {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(val) => Value::Boolean(val),
+            serde_json::Value::Number(num) => {
+                if let Some(n) = num.as_u64() {
+                    Value::U64(n)
+                } else if let Some(n) = num.as_i64() {
+                    Value::I64(n)
+                } else {
+                    Value::F64(num.as_f64().unwrap())
+                }
+            }
+            serde_json::Value::String(val) => Value::from(val),
+            serde_json::Value::Array(array) => {
+                Value::Array(array.into_iter().map(Value::from).collect())
+            }
+            serde_json::Value::Object(map) => {
+                Value::Map(map.into_iter()
+                    .map(|(key, val)| (Value::from(key), Value::from(val)))
+                    .collect())
+            }
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(buf: &[u8]) -> String {
+    let mut res = String::with_capacity((buf.len() + 2) / 3 * 4);
+    for chunk in buf.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                res.push(BASE64_ALPHABET[(n >> (18 - 6 * idx)) & 0x3f] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+
+    res
+}
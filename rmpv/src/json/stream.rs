@@ -0,0 +1,683 @@
+//! Streaming transcoding between MessagePack and JSON without building an intermediate `Value`.
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use rmp::Marker;
+use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64,
+                  read_data_i8, read_data_i16, read_data_i32, read_data_i64, read_data_f32,
+                  read_data_f64, MarkerReadError, ValueReadError};
+use rmp::encode::{write_nil, write_bool, write_uint, write_sint, write_f64, write_str};
+use serde::de::{self, DeserializeSeed, Deserializer, MapVisitor, SeqVisitor, Visitor};
+use serde_json;
+
+use Value;
+use decode::read_value;
+use decode::value::Error as DecodeError;
+use pointer::escape;
+use text;
+use super::{encode_base64, BinaryPolicy, Error, ExtPolicy, FloatPolicy, IntPolicy, KeyPolicy,
+            Loss, LossKind, ToJson, MAX_SAFE_INTEGER};
+
+/// The number of bytes of binaries and extensions read at once. Must be a multiple of 3, so that
+/// base64 encoded chunks can be concatenated.
+const CHUNK_SIZE: usize = 3 * 1024;
+
+/// This type represents all possible errors that can occur when transcoding between MessagePack
+/// and JSON.
+#[derive(Debug)]
+pub enum TranscodeError {
+    /// Error while reading or decoding the MessagePack input.
+    Decode(DecodeError),
+    /// Error while reading or parsing the JSON input.
+    Json(serde_json::Error),
+    /// I/O error while writing the output.
+    Write(io::Error),
+    /// The value cannot be represented in JSON due to the configured policy.
+    Policy(Error),
+    /// Arrays and maps are nested deeper than the configured maximum depth.
+    DepthLimitExceeded,
+}
+
+impl error::Error for TranscodeError {
+    fn description(&self) -> &str {
+        match *self {
+            TranscodeError::Decode(..) => "error while decoding MessagePack",
+            TranscodeError::Json(..) => "error while parsing JSON",
+            TranscodeError::Write(..) => "error while writing the output",
+            TranscodeError::Policy(..) => "value cannot be represented in JSON",
+            TranscodeError::DepthLimitExceeded => "depth limit exceeded",
+        }
+    }
+}
+
+impl Display for TranscodeError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            TranscodeError::Decode(ref err) => Display::fmt(err, fmt),
+            TranscodeError::Json(ref err) => write!(fmt, "error while parsing JSON: {}", err),
+            TranscodeError::Write(ref err) => {
+                write!(fmt, "error while writing the output: {}", err)
+            }
+            TranscodeError::Policy(ref err) => Display::fmt(err, fmt),
+            TranscodeError::DepthLimitExceeded => fmt.write_str("depth limit exceeded"),
+        }
+    }
+}
+
+impl From<DecodeError> for TranscodeError {
+    fn from(err: DecodeError) -> TranscodeError {
+        TranscodeError::Decode(err)
+    }
+}
+
+impl From<MarkerReadError> for TranscodeError {
+    fn from(err: MarkerReadError) -> TranscodeError {
+        TranscodeError::Decode(err.into())
+    }
+}
+
+impl From<ValueReadError> for TranscodeError {
+    fn from(err: ValueReadError) -> TranscodeError {
+        TranscodeError::Decode(err.into())
+    }
+}
+
+impl From<serde_json::Error> for TranscodeError {
+    fn from(err: serde_json::Error) -> TranscodeError {
+        TranscodeError::Json(err)
+    }
+}
+
+impl From<Error> for TranscodeError {
+    fn from(err: Error) -> TranscodeError {
+        TranscodeError::Policy(err)
+    }
+}
+
+impl ToJson {
+    /// Reads a single MessagePack value and writes it as JSON according to the configured
+    /// policies, ignoring losses.
+    ///
+    /// See `transcode_with` for details.
+    pub fn transcode<R, W>(&self, rd: &mut R, wr: &mut W) -> Result<(), TranscodeError>
+        where R: Read,
+              W: Write
+    {
+        self.transcode_with(rd, wr, |_| {})
+    }
+
+    /// Reads a single MessagePack value and writes it as JSON according to the configured
+    /// policies, calling `on_loss` for each lossy conversion in document order.
+    ///
+    /// Unlike `convert`, the value is never held in memory as a whole: strings and non-string map
+    /// keys are buffered one at a time, while binaries and extensions are processed in chunks,
+    /// so the memory used depends only on the nesting depth and the longest string. Since keys
+    /// are not remembered, duplicate keys are written as is and never reported as losses. Map
+    /// entries are written in their original order.
+    ///
+    /// Both the reader and the writer are accessed with many small operations, so wrapping them
+    /// into `BufReader` and `BufWriter` is highly recommended.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input is not a valid MessagePack value, the output cannot be
+    /// written, arrays and maps are nested deeper than the maximum depth or some value cannot be
+    /// converted due to a policy set to `Error`. In that case the output contains an incomplete
+    /// JSON document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::json::{LossKind, ToJson};
+    ///
+    /// // {"data": bin([1, 2, 3])}
+    /// let buf = [0x81, 0xa4, 0x64, 0x61, 0x74, 0x61, 0xc4, 0x03, 0x01, 0x02, 0x03];
+    ///
+    /// let mut json = Vec::new();
+    /// let mut losses = Vec::new();
+    /// ToJson::new().transcode_with(&mut &buf[..], &mut json, |loss| losses.push(loss)).unwrap();
+    ///
+    /// assert_eq!(r#"{"data":"AQID"}"#, String::from_utf8(json).unwrap());
+    /// assert_eq!(LossKind::Binary, losses[0].kind);
+    /// ```
+    pub fn transcode_with<R, W, F>(&self, rd: &mut R, wr: &mut W, on_loss: F)
+        -> Result<(), TranscodeError>
+        where R: Read,
+              W: Write,
+              F: FnMut(Loss)
+    {
+        let mut enc = JsonEncoder {
+            conv: self,
+            wr: wr,
+            path: String::new(),
+            depth: 0,
+            on_loss: on_loss,
+        };
+        enc.transcode(rd)
+    }
+}
+
+struct JsonEncoder<'a, W: 'a, F> {
+    conv: &'a ToJson,
+    wr: &'a mut W,
+    path: String,
+    depth: usize,
+    on_loss: F,
+}
+
+impl<'a, W: Write, F: FnMut(Loss)> JsonEncoder<'a, W, F> {
+    fn transcode<R: Read>(&mut self, rd: &mut R) -> Result<(), TranscodeError> {
+        match read_marker(rd)? {
+            Marker::Null => self.write(b"null"),
+            Marker::True => self.write(b"true"),
+            Marker::False => self.write(b"false"),
+            Marker::FixPos(val) => self.write_uint(val as u64),
+            Marker::FixNeg(val) => self.write_sint(val as i64),
+            Marker::U8 => self.write_uint(read_data_u8(rd)? as u64),
+            Marker::U16 => self.write_uint(read_data_u16(rd)? as u64),
+            Marker::U32 => self.write_uint(read_data_u32(rd)? as u64),
+            Marker::U64 => self.write_uint(read_data_u64(rd)?),
+            Marker::I8 => self.write_sint(read_data_i8(rd)? as i64),
+            Marker::I16 => self.write_sint(read_data_i16(rd)? as i64),
+            Marker::I32 => self.write_sint(read_data_i32(rd)? as i64),
+            Marker::I64 => self.write_sint(read_data_i64(rd)?),
+            Marker::F32 => self.write_float(read_data_f32(rd)? as f64),
+            Marker::F64 => self.write_float(read_data_f64(rd)?),
+            Marker::FixStr(len) => self.transcode_str(rd, len as usize),
+            Marker::Str8 => {
+                let len = read_data_u8(rd)?;
+                self.transcode_str(rd, len as usize)
+            }
+            Marker::Str16 => {
+                let len = read_data_u16(rd)?;
+                self.transcode_str(rd, len as usize)
+            }
+            Marker::Str32 => {
+                let len = read_data_u32(rd)?;
+                self.transcode_str(rd, len as usize)
+            }
+            Marker::FixArray(len) => self.transcode_array(rd, len as usize),
+            Marker::Array16 => {
+                let len = read_data_u16(rd)?;
+                self.transcode_array(rd, len as usize)
+            }
+            Marker::Array32 => {
+                let len = read_data_u32(rd)?;
+                self.transcode_array(rd, len as usize)
+            }
+            Marker::FixMap(len) => self.transcode_map(rd, len as usize),
+            Marker::Map16 => {
+                let len = read_data_u16(rd)?;
+                self.transcode_map(rd, len as usize)
+            }
+            Marker::Map32 => {
+                let len = read_data_u32(rd)?;
+                self.transcode_map(rd, len as usize)
+            }
+            Marker::Bin8 => {
+                let len = read_data_u8(rd)?;
+                self.transcode_bin(rd, len as usize)
+            }
+            Marker::Bin16 => {
+                let len = read_data_u16(rd)?;
+                self.transcode_bin(rd, len as usize)
+            }
+            Marker::Bin32 => {
+                let len = read_data_u32(rd)?;
+                self.transcode_bin(rd, len as usize)
+            }
+            Marker::FixExt1 => self.transcode_ext(rd, 1),
+            Marker::FixExt2 => self.transcode_ext(rd, 2),
+            Marker::FixExt4 => self.transcode_ext(rd, 4),
+            Marker::FixExt8 => self.transcode_ext(rd, 8),
+            Marker::FixExt16 => self.transcode_ext(rd, 16),
+            Marker::Ext8 => {
+                let len = read_data_u8(rd)?;
+                self.transcode_ext(rd, len as usize)
+            }
+            Marker::Ext16 => {
+                let len = read_data_u16(rd)?;
+                self.transcode_ext(rd, len as usize)
+            }
+            Marker::Ext32 => {
+                let len = read_data_u32(rd)?;
+                self.transcode_ext(rd, len as usize)
+            }
+            Marker::Reserved => Err(DecodeError::TypeMismatch(Marker::Reserved).into()),
+        }
+    }
+
+    fn transcode_str<R: Read>(&mut self, rd: &mut R, len: usize) -> Result<(), TranscodeError> {
+        let (val, loss) = read_str(rd, len)?;
+        if let Some(kind) = loss {
+            self.loss(kind);
+        }
+        self.write_str(&val)
+    }
+
+    fn transcode_array<R: Read>(&mut self, rd: &mut R, len: usize) -> Result<(), TranscodeError> {
+        self.enter()?;
+        self.write(b"[")?;
+        for idx in 0..len {
+            if idx > 0 {
+                self.write(b",")?;
+            }
+            let plen = self.push_token(&idx.to_string());
+            self.transcode(rd)?;
+            self.path.truncate(plen);
+        }
+        self.depth -= 1;
+        self.write(b"]")
+    }
+
+    fn transcode_map<R: Read>(&mut self, rd: &mut R, len: usize) -> Result<(), TranscodeError> {
+        self.enter()?;
+        self.write(b"{")?;
+        for idx in 0..len {
+            if idx > 0 {
+                self.write(b",")?;
+            }
+
+            let (key, loss) = match read_marker(rd)? {
+                Marker::FixStr(len) => read_str(rd, len as usize)?,
+                Marker::Str8 => {
+                    let len = read_data_u8(rd)?;
+                    read_str(rd, len as usize)?
+                }
+                Marker::Str16 => {
+                    let len = read_data_u16(rd)?;
+                    read_str(rd, len as usize)?
+                }
+                Marker::Str32 => {
+                    let len = read_data_u32(rd)?;
+                    read_str(rd, len as usize)?
+                }
+                _ if self.conv.keys == KeyPolicy::Error => {
+                    return Err(self.fail(LossKind::NonStringKey));
+                }
+                marker => {
+                    let key = read_key(rd, marker, self.conv.max_depth - self.depth)?;
+                    (key.to_string(), Some(LossKind::NonStringKey))
+                }
+            };
+
+            self.write_str(&key)?;
+            self.write(b":")?;
+
+            let plen = self.push_token(&key);
+            if let Some(kind) = loss {
+                self.loss(kind);
+            }
+            self.transcode(rd)?;
+            self.path.truncate(plen);
+        }
+        self.depth -= 1;
+        self.write(b"}")
+    }
+
+    fn transcode_bin<R: Read>(&mut self, rd: &mut R, len: usize) -> Result<(), TranscodeError> {
+        match self.conv.binary {
+            BinaryPolicy::Base64 => self.transcode_base64(rd, len)?,
+            BinaryPolicy::Array => {
+                self.write(b"[")?;
+                let mut buf = [0; CHUNK_SIZE];
+                let mut first = true;
+                for_each_chunk(rd, len, &mut buf, |chunk| {
+                    for byte in chunk {
+                        if !first {
+                            self.write(b",")?;
+                        }
+                        first = false;
+                        self.write(byte.to_string().as_bytes())?;
+                    }
+                    Ok(())
+                })?;
+                self.write(b"]")?;
+            }
+            BinaryPolicy::Error => return Err(self.fail(LossKind::Binary)),
+        }
+
+        self.loss(LossKind::Binary);
+        Ok(())
+    }
+
+    fn transcode_ext<R: Read>(&mut self, rd: &mut R, len: usize) -> Result<(), TranscodeError> {
+        if self.conv.ext == ExtPolicy::Error {
+            return Err(self.fail(LossKind::Ext));
+        }
+
+        let ty = read_data_i8(rd)?;
+        self.write(format!(r#"{{"$ext":{{"type":{},"data":"#, ty).as_bytes())?;
+        self.transcode_base64(rd, len)?;
+        self.write(b"}}")?;
+
+        self.loss(LossKind::Ext);
+        Ok(())
+    }
+
+    fn transcode_base64<R: Read>(&mut self, rd: &mut R, len: usize)
+        -> Result<(), TranscodeError>
+    {
+        self.write(b"\"")?;
+        let mut buf = [0; CHUNK_SIZE];
+        for_each_chunk(rd, len, &mut buf, |chunk| self.write(encode_base64(chunk).as_bytes()))?;
+        self.write(b"\"")
+    }
+
+    // Enters an array or a map, failing if it is nested too deep.
+    fn enter(&mut self) -> Result<(), TranscodeError> {
+        if self.depth == self.conv.max_depth {
+            return Err(TranscodeError::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn write_uint(&mut self, val: u64) -> Result<(), TranscodeError> {
+        self.write_int(val, val > MAX_SAFE_INTEGER)
+    }
+
+    fn write_sint(&mut self, val: i64) -> Result<(), TranscodeError> {
+        let large = val < -(MAX_SAFE_INTEGER as i64) || val > MAX_SAFE_INTEGER as i64;
+        self.write_int(val, large)
+    }
+
+    fn write_int<T: Display>(&mut self, val: T, large: bool) -> Result<(), TranscodeError> {
+        if large {
+            match self.conv.ints {
+                IntPolicy::Exact => {}
+                IntPolicy::String => {
+                    self.loss(LossKind::LargeInteger);
+                    return self.write(format!("\"{}\"", val).as_bytes());
+                }
+                IntPolicy::Error => return Err(self.fail(LossKind::LargeInteger)),
+            }
+        }
+
+        self.write(val.to_string().as_bytes())
+    }
+
+    fn write_float(&mut self, val: f64) -> Result<(), TranscodeError> {
+        if val.is_finite() {
+            // Debug formatting is the shortest one, which is parsed back into the same value.
+            return self.write(format!("{:?}", val).as_bytes());
+        }
+
+        let json: &[u8] = match self.conv.floats {
+            FloatPolicy::Null => b"null",
+            FloatPolicy::String if val.is_nan() => b"\"NaN\"",
+            FloatPolicy::String if val > 0.0 => b"\"Infinity\"",
+            FloatPolicy::String => b"\"-Infinity\"",
+            FloatPolicy::Error => return Err(self.fail(LossKind::NonFiniteFloat)),
+        };
+
+        self.loss(LossKind::NonFiniteFloat);
+        self.write(json)
+    }
+
+    fn write_str(&mut self, val: &str) -> Result<(), TranscodeError> {
+        let mut buf = String::with_capacity(val.len() + 2);
+        text::write_str(&mut buf, val).expect("writing into a String never fails");
+        self.write(buf.as_bytes())
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<(), TranscodeError> {
+        self.wr.write_all(buf).map_err(TranscodeError::Write)
+    }
+
+    // Appends an escaped reference token to the path, returning the previous length of the path.
+    fn push_token(&mut self, token: &str) -> usize {
+        let len = self.path.len();
+        self.path.push('/');
+        self.path.push_str(&escape(token));
+        len
+    }
+
+    fn loss(&mut self, kind: LossKind) {
+        let loss = Loss { kind: kind, path: self.path.clone() };
+        (self.on_loss)(loss);
+    }
+
+    fn fail(&self, kind: LossKind) -> TranscodeError {
+        TranscodeError::Policy(Error { kind: kind, path: self.path.clone() })
+    }
+}
+
+// Reads a non-string map key, which marker is already consumed, allowing arrays and maps to be
+// nested at most `depth` levels deep.
+fn read_key<R: Read>(rd: &mut R, marker: Marker, depth: usize) -> Result<Value, TranscodeError> {
+    let len = match marker {
+        Marker::FixArray(len) | Marker::FixMap(len) => len as usize,
+        Marker::Array16 | Marker::Map16 => read_data_u16(rd)? as usize,
+        Marker::Array32 | Marker::Map32 => read_data_u32(rd)? as usize,
+        marker => {
+            // Feed the marker back to decode the whole key.
+            let buf = [marker.to_u8()];
+            return read_value(&mut (&buf[..]).chain(rd)).map_err(From::from);
+        }
+    };
+
+    if depth == 0 {
+        return Err(TranscodeError::DepthLimitExceeded);
+    }
+
+    let read = |rd: &mut R| read_marker(rd).map_err(From::from)
+        .and_then(|marker| read_key(rd, marker, depth - 1));
+
+    match marker {
+        Marker::FixArray(..) | Marker::Array16 | Marker::Array32 => {
+            // The length comes from the input, so the vector grows with the elements actually
+            // read instead of being allocated upfront.
+            let mut vec = Vec::new();
+            for _ in 0..len {
+                vec.push(read(rd)?);
+            }
+            Ok(Value::Array(vec))
+        }
+        _ => {
+            let mut map = Vec::new();
+            for _ in 0..len {
+                let key = read(rd)?;
+                map.push((key, read(rd)?));
+            }
+            Ok(Value::Map(map))
+        }
+    }
+}
+
+// Reads a string replacing invalid UTF-8 sequences, which is reported as a loss.
+fn read_str<R: Read>(rd: &mut R, len: usize)
+    -> Result<(String, Option<LossKind>), TranscodeError>
+{
+    // The length comes from the input, so the buffer grows with the bytes actually read instead
+    // of being allocated upfront.
+    let mut buf = Vec::new();
+    rd.by_ref().take(len as u64).read_to_end(&mut buf).map_err(DecodeError::InvalidDataRead)?;
+    if buf.len() < len {
+        let err = io::Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer");
+        return Err(DecodeError::InvalidDataRead(err).into());
+    }
+
+    match String::from_utf8(buf) {
+        Ok(val) => Ok((val, None)),
+        Err(err) => {
+            let val = String::from_utf8_lossy(err.as_bytes()).into_owned();
+            Ok((val, Some(LossKind::InvalidUtf8)))
+        }
+    }
+}
+
+// Reads `len` bytes in chunks of the buffer size, passing each chunk to the given function.
+fn for_each_chunk<R, F>(rd: &mut R, mut len: usize, buf: &mut [u8], mut f: F)
+    -> Result<(), TranscodeError>
+    where R: Read,
+          F: FnMut(&[u8]) -> Result<(), TranscodeError>
+{
+    while len > 0 {
+        let size = len.min(buf.len());
+        let chunk = &mut buf[..size];
+        rd.read_exact(chunk).map_err(DecodeError::InvalidDataRead)?;
+        f(chunk)?;
+        len -= chunk.len();
+    }
+
+    Ok(())
+}
+
+/// Reads a single JSON value and writes it as MessagePack without building a `Value`.
+///
+/// Values are encoded the same way converting `serde_json::Value` into `Value` and writing it
+/// would do: non-negative integers are written as unsigned, negative ones as signed and other
+/// numbers as 64-bit floats.
+///
+/// Since MessagePack arrays and maps are prefixed with their length, which is unknown until the
+/// closing bracket is reached, they are always written using the 32-bit length markers and the
+/// length is filled in by seeking back afterwards. This keeps the memory used independent of the
+/// input size, but makes the output larger than `write_value` would produce. Strings are
+/// buffered one at a time.
+///
+/// The reader is accessed byte by byte, so wrapping it into `BufReader` is highly recommended.
+///
+/// # Errors
+///
+/// Returns an error if the input is not a valid JSON document, including trailing characters
+/// other than whitespace, or the output cannot be written.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use rmpv::Value;
+/// use rmpv::decode::read_value;
+/// use rmpv::json::transcode_from_json;
+///
+/// let mut buf = Cursor::new(Vec::new());
+/// transcode_from_json(&mut &br#"{"id": -1}"#[..], &mut buf).unwrap();
+///
+/// let val = read_value(&mut &buf.get_ref()[..]).unwrap();
+/// assert_eq!(Value::Map(vec![(Value::from("id"), Value::from(-1))]), val);
+/// ```
+pub fn transcode_from_json<R, W>(rd: &mut R, wr: &mut W) -> Result<(), TranscodeError>
+    where R: Read,
+          W: Write + Seek
+{
+    let mut de = serde_json::Deserializer::from_reader(rd);
+    let mut err = None;
+
+    let res = MsgpackEncoder { wr: wr, err: &mut err }.deserialize(&mut de)
+        .and_then(|()| de.end());
+
+    // Write errors are reported to serde as custom errors, so restore the original one.
+    match err {
+        Some(err) => Err(TranscodeError::Write(err)),
+        None => res.map_err(TranscodeError::Json),
+    }
+}
+
+struct MsgpackEncoder<'a, W: 'a> {
+    wr: &'a mut W,
+    err: &'a mut Option<io::Error>,
+}
+
+impl<'a, W: Write + Seek> MsgpackEncoder<'a, W> {
+    fn reborrow<'r>(&'r mut self) -> MsgpackEncoder<'r, W> {
+        MsgpackEncoder { wr: self.wr, err: self.err }
+    }
+
+    // Calls the function with the writer, stashing an I/O error to be restored afterwards.
+    fn write<T, E, F>(self, f: F) -> Result<T, E>
+        where E: de::Error,
+              F: FnOnce(&mut W) -> io::Result<T>
+    {
+        let err = self.err;
+        f(self.wr).map_err(|e| {
+            *err = Some(e);
+            E::custom("failed to write MessagePack")
+        })
+    }
+}
+
+// Writes the marker followed by a zero 32-bit length, returning the position of the marker.
+fn begin<W: Write + Seek>(wr: &mut W, marker: Marker) -> io::Result<u64> {
+    let pos = wr.stream_position()?;
+    wr.write_all(&[marker.to_u8(), 0, 0, 0, 0])?;
+    Ok(pos)
+}
+
+// Fills in the length of the container, which marker is written at the given position.
+fn end<W: Write + Seek>(wr: &mut W, pos: u64, len: u32) -> io::Result<()> {
+    let end = wr.stream_position()?;
+    wr.seek(SeekFrom::Start(pos + 1))?;
+    wr.write_all(&[(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8])?;
+    wr.seek(SeekFrom::Start(end))?;
+    Ok(())
+}
+
+impl<'a, W: Write + Seek> DeserializeSeed for MsgpackEncoder<'a, W> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer>(self, de: D) -> Result<(), D::Error> {
+        de.deserialize(self)
+    }
+}
+
+impl<'a, W: Write + Seek> Visitor for MsgpackEncoder<'a, W> {
+    type Value = ();
+
+    fn expecting(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        fmt.write_str("any valid JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        self.write(|wr| write_nil(wr))
+    }
+
+    fn visit_bool<E: de::Error>(self, val: bool) -> Result<(), E> {
+        self.write(|wr| write_bool(wr, val))
+    }
+
+    fn visit_u64<E: de::Error>(self, val: u64) -> Result<(), E> {
+        self.write(|wr| write_uint(wr, val).map(|_| ()).map_err(From::from))
+    }
+
+    fn visit_i64<E: de::Error>(self, val: i64) -> Result<(), E> {
+        if val >= 0 {
+            return self.visit_u64(val as u64);
+        }
+        self.write(|wr| write_sint(wr, val).map(|_| ()).map_err(From::from))
+    }
+
+    fn visit_f64<E: de::Error>(self, val: f64) -> Result<(), E> {
+        self.write(|wr| write_f64(wr, val).map_err(From::from))
+    }
+
+    fn visit_str<E: de::Error>(self, val: &str) -> Result<(), E> {
+        self.write(|wr| write_str(wr, val).map_err(From::from))
+    }
+
+    fn visit_seq<V: SeqVisitor>(mut self, mut visitor: V) -> Result<(), V::Error> {
+        let pos = self.reborrow().write(|wr| begin(wr, Marker::Array32))?;
+
+        let mut len = 0u32;
+        while visitor.visit_seed(self.reborrow())?.is_some() {
+            len = len.checked_add(1).ok_or_else(|| de::Error::custom("array is too long"))?;
+        }
+
+        self.write(|wr| end(wr, pos, len))
+    }
+
+    fn visit_map<V: MapVisitor>(mut self, mut visitor: V) -> Result<(), V::Error> {
+        let pos = self.reborrow().write(|wr| begin(wr, Marker::Map32))?;
+
+        let mut len = 0u32;
+        while visitor.visit_key_seed(self.reborrow())?.is_some() {
+            visitor.visit_value_seed(self.reborrow())?;
+            len = len.checked_add(1).ok_or_else(|| de::Error::custom("map is too long"))?;
+        }
+
+        self.write(|wr| end(wr, pos, len))
+    }
+}
@@ -8,17 +8,57 @@
 #[cfg(feature = "with-serde")]
 #[macro_use]
 extern crate serde;
+#[cfg(feature = "with-serde-json")]
+extern crate serde_json;
 extern crate rmp;
 
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+use std::mem;
 use std::ops::Index;
 
+pub use convert::TryFromValueError;
+pub use index::KeyIndex;
+pub use utf8::{Utf8String, Utf8StringRef};
+
+#[macro_use]
+mod macros;
+
+pub mod cbor;
 pub mod decode;
 pub mod encode;
+pub mod patch;
+pub mod profile;
+pub mod query;
+pub mod schema;
+pub mod text;
+pub mod wire;
+
+#[cfg(feature = "with-serde-json")]
+pub mod json;
+
+mod cmp;
+mod convert;
+mod index;
+mod pointer;
+mod utf8;
 
 #[cfg(feature = "with-serde")]
 mod ext;
 
-#[derive(Clone, Debug, PartialEq)]
+/// Represents any valid MessagePack value.
+///
+/// Values implement `Eq`, `Hash` and `Ord`, so they can be used as keys in hashed and ordered
+/// collections. Floats are compared totally: all NaNs are equal to each other and greater than any
+/// other number, while `-0.0` is equal to `0.0`.
+///
+/// Values of different kinds are ordered as follows: nil, booleans, integers, floats, strings,
+/// binaries, arrays, maps and extensions. Integers are ordered by their numeric value, while equal
+/// numbers with different representations are not equal, so `I64(1)` goes before `U64(1)`,
+/// similarly `F32` goes before an equal `F64`. Arrays and maps are compared lexicographically,
+/// preserving the order of map entries.
+#[derive(Clone, Debug)]
 pub enum Value {
     /// Nil represents nil.
     Nil,
@@ -33,7 +73,9 @@ pub enum Value {
     /// A 64-bit floating point number.
     F64(f64),
     /// String extending Raw type represents a UTF-8 string.
-    String(String),
+    ///
+    /// Strings containing invalid UTF-8 keep their raw bytes, see `Utf8String`.
+    String(Utf8String),
     /// Binary extending Raw type represents a byte array.
     Binary(Vec<u8>),
     /// Array represents a sequence of objects.
@@ -314,21 +356,22 @@ impl Value {
         }
     }
 
-    /// If the `Value` is a String, returns the associated str.
+    /// If the `Value` is a String containing valid UTF-8, returns the associated str.
     /// Returns None otherwise.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rmpv::Value;
+    /// use rmpv::{Utf8String, Value};
     ///
     /// assert_eq!(Some("le message"), Value::String("le message".into()).as_str());
     ///
+    /// assert_eq!(None, Value::String(Utf8String::from_bytes(vec![0xff])).as_str());
     /// assert_eq!(None, Value::Boolean(true).as_str());
     /// ```
     pub fn as_str(&self) -> Option<&str> {
         if let Value::String(ref val) = *self {
-            Some(val)
+            val.as_str()
         } else {
             None
         }
@@ -421,6 +464,288 @@ impl Value {
             None
         }
     }
+
+    /// Returns a reference to the value corresponding to the given string key if the `Value` is
+    /// a map containing such key. Returns None otherwise.
+    ///
+    /// Maps are searched linearly and the first matching entry wins. Use `key_index` for
+    /// repeated lookups in large maps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::Value;
+    ///
+    /// let val = Value::Map(vec![(Value::String("name".into()), Value::from(42))]);
+    ///
+    /// assert_eq!(Some(&Value::from(42)), val.get("name"));
+    /// assert_eq!(None, val.get("age"));
+    /// assert_eq!(None, Value::Nil.get("name"));
+    /// ```
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_map().and_then(|map| {
+            map.iter().find(|&&(ref k, _)| k.as_str() == Some(key)).map(|&(_, ref v)| v)
+        })
+    }
+
+    /// Returns a mutable reference to the value corresponding to the given string key if the
+    /// `Value` is a map containing such key. Returns None otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::Value;
+    ///
+    /// let mut val = Value::Map(vec![(Value::String("name".into()), Value::from(42))]);
+    ///
+    /// *val.get_mut("name").unwrap() = Value::from(43);
+    /// assert_eq!(Value::from(43), val["name"]);
+    /// ```
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        if let Value::Map(ref mut map) = *self {
+            map.iter_mut()
+                .find(|&&mut (ref k, _)| k.as_str() == Some(key))
+                .map(|&mut (_, ref mut v)| v)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the `Value` is a map containing the given string key. Returns false
+    /// otherwise.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Builds a hashed index of string keys if the `Value` is a map. Returns None otherwise.
+    ///
+    /// Building the index takes linear time, after which each lookup takes constant time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::Value;
+    ///
+    /// let map = (0..100).map(|i| (Value::from(i.to_string()), Value::from(i))).collect();
+    /// let val = Value::Map(map);
+    /// let index = val.key_index().unwrap();
+    ///
+    /// assert_eq!(Some(&Value::from(42)), index.get("42"));
+    /// ```
+    pub fn key_index(&self) -> Option<KeyIndex<Value>> {
+        self.as_map().map(|map| {
+            KeyIndex::new(map.iter().filter_map(|&(ref k, ref v)| k.as_str().map(|k| (k, v))))
+        })
+    }
+
+    /// Borrows the `Value` as a `ValueRef`, which allows to pass an owned tree to code working
+    /// with borrowed values.
+    ///
+    /// Unlike `ValueRef::to_owned`, this only allocates vectors for arrays and maps, while strings
+    /// and binaries are borrowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::{Value, ValueRef};
+    ///
+    /// let val = Value::Array(vec![Value::from("le message")]);
+    ///
+    /// assert_eq!(ValueRef::Array(vec![ValueRef::String("le message".into())]), val.as_ref());
+    /// ```
+    pub fn as_ref(&self) -> ValueRef {
+        match *self {
+            Value::Nil => ValueRef::Nil,
+            Value::Boolean(val) => ValueRef::Boolean(val),
+            Value::U64(val) => ValueRef::U64(val),
+            Value::I64(val) => ValueRef::I64(val),
+            Value::F32(val) => ValueRef::F32(val),
+            Value::F64(val) => ValueRef::F64(val),
+            Value::String(ref val) => ValueRef::String(val.as_ref()),
+            Value::Binary(ref val) => ValueRef::Binary(val),
+            Value::Array(ref val) => ValueRef::Array(val.iter().map(|v| v.as_ref()).collect()),
+            Value::Map(ref val) => {
+                ValueRef::Map(val.iter().map(|&(ref k, ref v)| (k.as_ref(), v.as_ref())).collect())
+            }
+            Value::Ext(ty, ref buf) => ValueRef::Ext(ty, buf),
+        }
+    }
+
+    pub fn as_string_mut(&mut self) -> Option<&mut String> {
+        if let Value::String(ref mut val) = *self {
+            val.as_string_mut()
+        } else {
+            None
+        }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> Option<&mut Vec<u8>> {
+        if let Value::Binary(ref mut val) = *self {
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    /// If the `Value` is an array, returns a mutable reference to its elements. Returns None
+    /// otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::Value;
+    ///
+    /// let mut val = Value::Array(vec![Value::from(1)]);
+    /// val.as_array_mut().unwrap().push(Value::from(2));
+    ///
+    /// assert_eq!(Value::Array(vec![Value::from(1), Value::from(2)]), val);
+    /// ```
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        if let Value::Array(ref mut array) = *self {
+            Some(array)
+        } else {
+            None
+        }
+    }
+
+    /// If the `Value` is a map, returns a mutable reference to its entries. Returns None
+    /// otherwise.
+    pub fn as_map_mut(&mut self) -> Option<&mut Vec<(Value, Value)>> {
+        if let Value::Map(ref mut map) = *self {
+            Some(map)
+        } else {
+            None
+        }
+    }
+
+    /// Takes the value out, leaving `Nil` in its place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::Value;
+    ///
+    /// let mut val = Value::Array(vec![Value::from("le message")]);
+    /// let msg = val.as_array_mut().unwrap()[0].take();
+    ///
+    /// assert_eq!(Value::from("le message"), msg);
+    /// assert_eq!(Value::Array(vec![Value::Nil]), val);
+    /// ```
+    pub fn take(&mut self) -> Value {
+        mem::replace(self, Value::Nil)
+    }
+
+    pub fn into_string(self) -> Option<String> {
+        if let Value::String(val) = self {
+            val.into_str()
+        } else {
+            None
+        }
+    }
+
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        if let Value::Binary(val) = self {
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    pub fn into_array(self) -> Option<Vec<Value>> {
+        if let Value::Array(array) = self {
+            Some(array)
+        } else {
+            None
+        }
+    }
+
+    pub fn into_map(self) -> Option<Vec<(Value, Value)>> {
+        if let Value::Map(map) = self {
+            Some(map)
+        } else {
+            None
+        }
+    }
+
+    pub fn into_ext(self) -> Option<(i8, Vec<u8>)> {
+        if let Value::Ext(ty, buf) = self {
+            Some((ty, buf))
+        } else {
+            None
+        }
+    }
+
+    /// Inserts an entry into the map, returning the previous value if the map already contained
+    /// an entry with an equal key.
+    ///
+    /// The value of the first entry with an equal key is replaced in place, preserving the order
+    /// of entries. Otherwise the new entry is appended to the end of the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Value` is not a map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::Value;
+    ///
+    /// let mut val = Value::Map(vec![]);
+    ///
+    /// assert_eq!(None, val.insert("id", 42));
+    /// assert_eq!(Some(Value::from(42)), val.insert("id", 43));
+    /// assert_eq!(Value::from(43), val["id"]);
+    /// ```
+    pub fn insert<K, V>(&mut self, key: K, val: V) -> Option<Value>
+        where K: Into<Value>,
+              V: Into<Value>
+    {
+        let map = match *self {
+            Value::Map(ref mut map) => map,
+            ref val => panic!("cannot insert an entry into non-map value: {}", val),
+        };
+
+        let key = key.into();
+        let val = val.into();
+
+        if let Some(&mut (_, ref mut v)) = map.iter_mut().find(|&&mut (ref k, _)| *k == key) {
+            return Some(mem::replace(v, val));
+        }
+
+        map.push((key, val));
+        None
+    }
+
+    /// Removes all entries with a key equal to the given one from the map, returning the value of
+    /// the first removed entry.
+    ///
+    /// Returns None if the `Value` is not a map or contains no such key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::Value;
+    ///
+    /// let mut val = Value::Map(vec![(Value::from("id"), Value::from(42))]);
+    ///
+    /// assert_eq!(Some(Value::from(42)), val.remove("id"));
+    /// assert_eq!(None, val.remove("id"));
+    /// ```
+    pub fn remove<K>(&mut self, key: K) -> Option<Value>
+        where K: Into<Value>
+    {
+        let map = match *self {
+            Value::Map(ref mut map) => map,
+            _ => return None,
+        };
+
+        let key = key.into();
+        let pos = map.iter().position(|&(ref k, _)| *k == key)?;
+        let (_, val) = map.remove(pos);
+        map.retain(|&(ref k, _)| *k != key);
+
+        Some(val)
+    }
 }
 
 static NIL: Value = Value::Nil;
@@ -434,6 +759,14 @@ impl Index<usize> for Value {
     }
 }
 
+impl<'a> Index<&'a str> for Value {
+    type Output = Value;
+
+    fn index(&self, index: &str) -> &Value {
+        self.get(index).unwrap_or(&NIL)
+    }
+}
+
 impl From<bool> for Value {
     fn from(v: bool) -> Value {
         Value::Boolean(v)
@@ -532,53 +865,129 @@ impl From<f64> for Value {
     }
 }
 
-impl ::std::fmt::Display for Value {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-        match *self {
-            Value::Nil => write!(f, "nil"),
-            Value::Boolean(val) => write!(f, "{}", val),
-            Value::U64(val) => write!(f, "{}", val),
-            Value::I64(val) => write!(f, "{}", val),
-            Value::F32(val) => write!(f, "{}", val),
-            Value::F64(val) => write!(f, "{}", val),
-            Value::String(ref val) => write!(f, "\"{}\"", val),
-            Value::Binary(ref val) => write!(f, "{:?}", val),
-            Value::Array(ref vec) => {
-                // TODO: This can be slower than naive implementation. Need benchmarks for more
-                // information.
-                let res = vec.iter()
-                    .map(|val| format!("{}", val))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-
-                write!(f, "[{}]", res)
-            }
-            Value::Map(ref vec) => {
-                try!(write!(f, "{{"));
-
-                match vec.iter().take(1).next() {
-                    Some(&(ref k, ref v)) => {
-                        try!(write!(f, "{}: {}", k, v));
-                    }
-                    None => {
-                        try!(write!(f, ""));
-                    }
-                }
-
-                for &(ref k, ref v) in vec.iter().skip(1) {
-                    try!(write!(f, ", {}: {}", k, v));
-                }
-
-                write!(f, "}}")
-            }
-            Value::Ext(ty, ref data) => {
-                write!(f, "[{}, {:?}]", ty, data)
-            }
+impl From<String> for Value {
+    fn from(v: String) -> Value {
+        Value::String(v.into())
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(v: &str) -> Value {
+        Value::String(v.into())
+    }
+}
+
+impl From<Utf8String> for Value {
+    fn from(v: Utf8String) -> Value {
+        Value::String(v)
+    }
+}
+
+/// Converts a byte vector into `Value::Binary`.
+///
+/// Note, that vectors of other types are converted into arrays using `FromIterator`, while
+/// `Vec<Value>` has its own `From` implementation.
+impl From<Vec<u8>> for Value {
+    fn from(v: Vec<u8>) -> Value {
+        Value::Binary(v)
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value {
+    fn from(v: &[u8]) -> Value {
+        Value::Binary(v.to_vec())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Value {
+        Value::Array(v)
+    }
+}
+
+impl From<Vec<(Value, Value)>> for Value {
+    fn from(v: Vec<(Value, Value)>) -> Value {
+        Value::Map(v)
+    }
+}
+
+/// Converts `None` into `Value::Nil` and `Some` into the contained value.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Value {
+        match v {
+            Some(v) => v.into(),
+            None => Value::Nil,
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// Converts a `HashMap` into `Value::Map` with entries in the iteration order of the map.
+impl<K, V, S> From<HashMap<K, V, S>> for Value
+    where K: Into<Value> + Eq + Hash,
+          V: Into<Value>,
+          S: BuildHasher
+{
+    fn from(v: HashMap<K, V, S>) -> Value {
+        v.into_iter().collect()
+    }
+}
+
+/// Converts a `BTreeMap` into `Value::Map` with entries sorted by key.
+impl<K, V> From<BTreeMap<K, V>> for Value
+    where K: Into<Value> + Ord,
+          V: Into<Value>
+{
+    fn from(v: BTreeMap<K, V>) -> Value {
+        v.into_iter().collect()
+    }
+}
+
+/// Collects an iterator into `Value::Array`.
+///
+/// # Examples
+///
+/// ```
+/// use rmpv::Value;
+///
+/// let val: Value = (1..3).collect();
+///
+/// assert_eq!(Value::Array(vec![Value::from(1), Value::from(2)]), val);
+/// ```
+impl<T: Into<Value>> FromIterator<T> for Value {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Value {
+        Value::Array(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Collects an iterator of key-value pairs into `Value::Map`, preserving their order.
+///
+/// # Examples
+///
+/// ```
+/// use rmpv::Value;
+///
+/// let val: Value = vec![("a", 1)].into_iter().collect();
+///
+/// assert_eq!(Value::Map(vec![(Value::from("a"), Value::from(1))]), val);
+/// ```
+impl<K: Into<Value>, V: Into<Value>> FromIterator<(K, V)> for Value {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Value {
+        Value::Map(iter.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+/// Represents any valid MessagePack value, borrowing strings and binaries from the buffer.
+///
+/// Values implement `Eq`, `Hash` and `Ord`, so they can be used as keys in hashed and ordered
+/// collections. Floats are compared totally: all NaNs are equal to each other and greater than any
+/// other number, while `-0.0` is equal to `0.0`.
+///
+/// Values of different kinds are ordered as follows: nil, booleans, integers, floats, strings,
+/// binaries, arrays, maps and extensions. Integers are ordered by their numeric value, while equal
+/// numbers with different representations are not equal, so `I64(1)` goes before `U64(1)`,
+/// similarly `F32` goes before an equal `F64`. Arrays and maps are compared lexicographically,
+/// preserving the order of map entries.
+#[derive(Clone, Debug)]
 pub enum ValueRef<'a> {
     /// Nil represents nil.
     Nil,
@@ -593,7 +1002,9 @@ pub enum ValueRef<'a> {
     /// A 64-bit floating point number.
     F64(f64),
     /// String extending Raw type represents a UTF-8 string.
-    String(&'a str),
+    ///
+    /// Strings containing invalid UTF-8 keep their raw bytes, see `Utf8StringRef`.
+    String(Utf8StringRef<'a>),
     /// Binary extending Raw type represents a byte array.
     Binary(&'a [u8]),
     /// Array represents a sequence of objects.
@@ -622,7 +1033,7 @@ impl<'a> ValueRef<'a> {
     ///    ValueRef::Nil,
     ///    ValueRef::U64(42),
     ///    ValueRef::Array(vec![
-    ///        ValueRef::String("le message"),
+    ///        ValueRef::String("le message".into()),
     ///    ])
     /// ]);
     ///
@@ -630,7 +1041,7 @@ impl<'a> ValueRef<'a> {
     ///     Value::Nil,
     ///     Value::U64(42),
     ///     Value::Array(vec![
-    ///         Value::String("le message".to_string())
+    ///         Value::String("le message".into())
     ///     ])
     /// ]);
     ///
@@ -660,6 +1071,109 @@ impl<'a> ValueRef<'a> {
         self.as_array().and_then(|v| v.get(index)).unwrap_or(&NIL_REF)
     }
 
+    /// Returns true if the `ValueRef` is a Null. Returns false otherwise.
+    pub fn is_nil(&self) -> bool {
+        if let ValueRef::Nil = *self {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if the `ValueRef` is a Boolean. Returns false otherwise.
+    pub fn is_bool(&self) -> bool {
+        self.as_bool().is_some()
+    }
+
+    /// Returns true if (and only if) the `ValueRef` is a i64. Returns false otherwise.
+    pub fn is_i64(&self) -> bool {
+        if let ValueRef::I64(..) = *self {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if (and only if) the `ValueRef` is a u64. Returns false otherwise.
+    pub fn is_u64(&self) -> bool {
+        if let ValueRef::U64(..) = *self {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if (and only if) the `ValueRef` is a f32. Returns false otherwise.
+    pub fn is_f32(&self) -> bool {
+        if let ValueRef::F32(..) = *self {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if (and only if) the `ValueRef` is a f64. Returns false otherwise.
+    pub fn is_f64(&self) -> bool {
+        if let ValueRef::F64(..) = *self {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if the `ValueRef` is a Number. Returns false otherwise.
+    pub fn is_number(&self) -> bool {
+        match *self {
+            ValueRef::U64(..) | ValueRef::I64(..) | ValueRef::F32(..) | ValueRef::F64(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the `ValueRef` is a String. Returns false otherwise.
+    pub fn is_str(&self) -> bool {
+        self.as_str().is_some()
+    }
+
+    /// Returns true if the `ValueRef` is a Binary. Returns false otherwise.
+    pub fn is_bin(&self) -> bool {
+        self.as_slice().is_some()
+    }
+
+    /// Returns true if the `ValueRef` is an Array. Returns false otherwise.
+    pub fn is_array(&self) -> bool {
+        self.as_array().is_some()
+    }
+
+    /// Returns true if the `ValueRef` is a Map. Returns false otherwise.
+    pub fn is_map(&self) -> bool {
+        self.as_map().is_some()
+    }
+
+    /// Returns true if the `ValueRef` is an Ext. Returns false otherwise.
+    pub fn is_ext(&self) -> bool {
+        self.as_ext().is_some()
+    }
+
+    /// If the `ValueRef` is a Boolean, returns the associated bool.
+    /// Returns None otherwise.
+    pub fn as_bool(&self) -> Option<bool> {
+        if let ValueRef::Boolean(val) = *self {
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    /// If the `ValueRef` is an integer, return or cast it to a i64.
+    /// Returns None otherwise.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ValueRef::I64(n) => Some(n),
+            ValueRef::U64(n) if n <= i64::max_value() as u64 => Some(n as i64),
+            _ => None,
+        }
+    }
+
     /// If the `ValueRef` is an integer, return or cast it to a u64.
     /// Returns None otherwise.
     ///
@@ -695,7 +1209,7 @@ impl<'a> ValueRef<'a> {
     /// assert_eq!(Some(&vec![ValueRef::Nil, ValueRef::Boolean(true)]), val.as_array());
     /// assert_eq!(None, ValueRef::Nil.as_array());
     /// ```
-    pub fn as_array(&self) -> Option<&Vec<ValueRef>> {
+    pub fn as_array(&self) -> Option<&Vec<ValueRef<'a>>> {
         if let ValueRef::Array(ref array) = *self {
             Some(&*array)
         } else {
@@ -703,6 +1217,100 @@ impl<'a> ValueRef<'a> {
         }
     }
 
+    /// If the `ValueRef` is a number, return or cast it to a f64.
+    /// Returns None otherwise.
+    ///
+    /// Integers are converted using the same rules as `Value::as_f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            ValueRef::I64(n) if i32::min_value() as i64 <= n && n <= i32::max_value() as i64 => {
+                Some(From::from(n as i32))
+            }
+            ValueRef::U64(n) if n <= u32::max_value() as u64 => {
+                Some(From::from(n as u32))
+            }
+            ValueRef::F32(n) => Some(From::from(n)),
+            ValueRef::F64(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// If the `ValueRef` is a String containing valid UTF-8, returns the associated str.
+    /// Returns None otherwise.
+    pub fn as_str(&self) -> Option<&'a str> {
+        if let ValueRef::String(val) = *self {
+            val.as_str()
+        } else {
+            None
+        }
+    }
+
+    /// If the `ValueRef` is a Binary, returns the associated slice.
+    /// Returns None otherwise.
+    pub fn as_slice(&self) -> Option<&'a [u8]> {
+        if let ValueRef::Binary(val) = *self {
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    /// If the `ValueRef` is a Map, returns the associated vector of key-value tuples.
+    /// Returns None otherwise.
+    pub fn as_map(&self) -> Option<&Vec<(ValueRef<'a>, ValueRef<'a>)>> {
+        if let ValueRef::Map(ref map) = *self {
+            Some(map)
+        } else {
+            None
+        }
+    }
+
+    /// If the `ValueRef` is an Ext, returns the associated tuple with a ty and slice.
+    /// Returns None otherwise.
+    pub fn as_ext(&self) -> Option<(i8, &'a [u8])> {
+        if let ValueRef::Ext(ty, buf) = *self {
+            Some((ty, buf))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the given string key if the `ValueRef`
+    /// is a map containing such key. Returns None otherwise.
+    ///
+    /// Maps are searched linearly and the first matching entry wins. Use `key_index` for
+    /// repeated lookups in large maps.
+    pub fn get(&self, key: &str) -> Option<&ValueRef<'a>> {
+        self.as_map().and_then(|map| {
+            map.iter().find(|&&(ref k, _)| k.as_str() == Some(key)).map(|&(_, ref v)| v)
+        })
+    }
+
+    /// Returns a mutable reference to the value corresponding to the given string key if the
+    /// `ValueRef` is a map containing such key. Returns None otherwise.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut ValueRef<'a>> {
+        if let ValueRef::Map(ref mut map) = *self {
+            map.iter_mut()
+                .find(|&&mut (ref k, _)| k.as_str() == Some(key))
+                .map(|&mut (_, ref mut v)| v)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the `ValueRef` is a map containing the given string key. Returns false
+    /// otherwise.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Builds a hashed index of string keys if the `ValueRef` is a map. Returns None otherwise.
+    pub fn key_index(&self) -> Option<KeyIndex<ValueRef<'a>>> {
+        self.as_map().map(|map| {
+            KeyIndex::new(map.iter().filter_map(|&(ref k, ref v)| k.as_str().map(|k| (k, v))))
+        })
+    }
+
     pub fn into_array(self) -> Option<Vec<ValueRef<'a>>> {
         if let ValueRef::Array(array) = self {
             Some(array)
@@ -710,48 +1318,169 @@ impl<'a> ValueRef<'a> {
             None
         }
     }
+
+    pub fn into_str(self) -> Option<&'a str> {
+        if let ValueRef::String(val) = self {
+            val.as_str()
+        } else {
+            None
+        }
+    }
+
+    pub fn into_bytes(self) -> Option<&'a [u8]> {
+        if let ValueRef::Binary(val) = self {
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    pub fn into_map(self) -> Option<Vec<(ValueRef<'a>, ValueRef<'a>)>> {
+        if let ValueRef::Map(map) = self {
+            Some(map)
+        } else {
+            None
+        }
+    }
+
+    pub fn into_ext(self) -> Option<(i8, &'a [u8])> {
+        if let ValueRef::Ext(ty, buf) = self {
+            Some((ty, buf))
+        } else {
+            None
+        }
+    }
 }
 
-impl<'a> ::std::fmt::Display for ValueRef<'a> {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-        match *self {
-            ValueRef::Nil => write!(f, "nil"),
-            ValueRef::Boolean(val) => write!(f, "{}", val),
-            ValueRef::U64(val) => write!(f, "{}", val),
-            ValueRef::I64(val) => write!(f, "{}", val),
-            ValueRef::F32(val) => write!(f, "{}", val),
-            ValueRef::F64(val) => write!(f, "{}", val),
-            ValueRef::String(ref val) => write!(f, "\"{}\"", val),
-            ValueRef::Binary(ref val) => write!(f, "{:?}", val),
-            ValueRef::Array(ref vec) => {
-                let res = vec.iter()
-                    .map(|val| format!("{}", val))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-
-                write!(f, "[{}]", res)
-            }
-            ValueRef::Map(ref vec) => {
-                try!(write!(f, "{{"));
-
-                match vec.iter().take(1).next() {
-                    Some(&(ref k, ref v)) => {
-                        try!(write!(f, "{}: {}", k, v));
-                    }
-                    None => {
-                        try!(write!(f, ""));
-                    }
-                }
-
-                for &(ref k, ref v) in vec.iter().skip(1) {
-                    try!(write!(f, ", {}: {}", k, v));
-                }
-
-                write!(f, "}}")
-            }
-            ValueRef::Ext(ty, ref data) => {
-                write!(f, "[{}, {:?}]", ty, data)
-            }
+impl<'a> Index<usize> for ValueRef<'a> {
+    type Output = ValueRef<'a>;
+
+    fn index(&self, index: usize) -> &ValueRef<'a> {
+        self.as_array().and_then(|v| v.get(index)).unwrap_or(&NIL_REF)
+    }
+}
+
+impl<'a, 'b> Index<&'b str> for ValueRef<'a> {
+    type Output = ValueRef<'a>;
+
+    fn index(&self, index: &str) -> &ValueRef<'a> {
+        self.get(index).unwrap_or(&NIL_REF)
+    }
+}
+
+impl<'a> From<bool> for ValueRef<'a> {
+    fn from(v: bool) -> ValueRef<'a> {
+        ValueRef::Boolean(v)
+    }
+}
+
+impl<'a> From<u8> for ValueRef<'a> {
+    fn from(v: u8) -> ValueRef<'a> {
+        ValueRef::U64(From::from(v))
+    }
+}
+
+impl<'a> From<u16> for ValueRef<'a> {
+    fn from(v: u16) -> ValueRef<'a> {
+        ValueRef::U64(From::from(v))
+    }
+}
+
+impl<'a> From<u32> for ValueRef<'a> {
+    fn from(v: u32) -> ValueRef<'a> {
+        ValueRef::U64(From::from(v))
+    }
+}
+
+impl<'a> From<u64> for ValueRef<'a> {
+    fn from(v: u64) -> ValueRef<'a> {
+        ValueRef::U64(From::from(v))
+    }
+}
+
+impl<'a> From<usize> for ValueRef<'a> {
+    fn from(v: usize) -> ValueRef<'a> {
+        ValueRef::U64(v as u64)
+    }
+}
+
+impl<'a> From<i8> for ValueRef<'a> {
+    fn from(v: i8) -> ValueRef<'a> {
+        if v < 0 {
+            ValueRef::I64(From::from(v))
+        } else {
+            ValueRef::from(v as u8)
         }
     }
 }
+
+impl<'a> From<i16> for ValueRef<'a> {
+    fn from(v: i16) -> ValueRef<'a> {
+        if v < 0 {
+            ValueRef::I64(From::from(v))
+        } else {
+            ValueRef::from(v as u16)
+        }
+    }
+}
+
+impl<'a> From<i32> for ValueRef<'a> {
+    fn from(v: i32) -> ValueRef<'a> {
+        if v < 0 {
+            ValueRef::I64(From::from(v))
+        } else {
+            ValueRef::from(v as u32)
+        }
+    }
+}
+
+impl<'a> From<i64> for ValueRef<'a> {
+    fn from(v: i64) -> ValueRef<'a> {
+        if v < 0 {
+            ValueRef::I64(From::from(v))
+        } else {
+            ValueRef::from(v as u64)
+        }
+    }
+}
+
+impl<'a> From<isize> for ValueRef<'a> {
+    fn from(v: isize) -> ValueRef<'a> {
+        if v < 0 {
+            ValueRef::I64(v as i64)
+        } else {
+            ValueRef::from(v as usize)
+        }
+    }
+}
+
+impl<'a> From<f32> for ValueRef<'a> {
+    fn from(v: f32) -> ValueRef<'a> {
+        ValueRef::F32(v)
+    }
+}
+
+impl<'a> From<f64> for ValueRef<'a> {
+    fn from(v: f64) -> ValueRef<'a> {
+        ValueRef::F64(v)
+    }
+}
+
+impl<'a> From<&'a str> for ValueRef<'a> {
+    fn from(v: &'a str) -> ValueRef<'a> {
+        ValueRef::String(v.into())
+    }
+}
+
+impl<'a> From<Utf8StringRef<'a>> for ValueRef<'a> {
+    fn from(v: Utf8StringRef<'a>) -> ValueRef<'a> {
+        ValueRef::String(v)
+    }
+}
+
+impl<'a> From<&'a [u8]> for ValueRef<'a> {
+    fn from(v: &'a [u8]) -> ValueRef<'a> {
+        ValueRef::Binary(v)
+    }
+}
+
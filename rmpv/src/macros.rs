@@ -0,0 +1,218 @@
+/// Constructs a `Value` using JSON-like syntax.
+///
+/// The following forms are supported:
+///
+/// - `nil` or `null` for `Value::Nil`, `true` and `false` for booleans;
+/// - `[..]` for arrays and `{key: value, ..}` for maps, where keys may be any supported form,
+///   including parenthesized expressions;
+/// - `bin(data)` for binaries, where `data` is anything that can be viewed as `&[u8]`;
+/// - `ext(ty, data)` for extensions, where `ty` is an `i8` and `data` is like above;
+/// - any other expression, which implements `Into<Value>`, for example numbers, strings or
+///   another `Value`.
+///
+/// Map entries are kept in the written order, including duplicates.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate rmpv;
+///
+/// use rmpv::Value;
+///
+/// # fn main() {
+/// let name = "John";
+///
+/// let val = msgpack!({
+///     "name": name,
+///     "age": 42,
+///     "tags": [true, null, 2.5],
+///     "key": bin(b"\x00\x01"),
+///     42: ext(1, vec![0xff]),
+/// });
+///
+/// assert_eq!(Value::from("John"), val["name"]);
+/// assert_eq!(Value::Binary(vec![0, 1]), val["key"]);
+/// assert_eq!(Value::F64(2.5), val["tags"][2]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! msgpack {
+    // Munches array elements, accumulating them in the bracketed list of expressions.
+
+    (@array [$($elems:expr,)*]) => {
+        vec![$($elems,)*]
+    };
+
+    (@array [$($elems:expr),*]) => {
+        vec![$($elems),*]
+    };
+
+    (@array [$($elems:expr,)*] nil $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!(nil)] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] null $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!(null)] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] true $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!(true)] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] false $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!(false)] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] bin ($($data:tt)*) $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!(bin($($data)*))] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] ext ($($data:tt)*) $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!(ext($($data)*))] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] [$($array:tt)*] $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!([$($array)*])] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!({$($map)*})] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!($next),] $($rest)*)
+    };
+
+    (@array [$($elems:expr,)*] $last:expr) => {
+        $crate::msgpack!(@array [$($elems,)* $crate::msgpack!($last)])
+    };
+
+    (@array [$($elems:expr),*] , $($rest:tt)*) => {
+        $crate::msgpack!(@array [$($elems,)*] $($rest)*)
+    };
+
+    // Munches map entries, pushing them into the vector named `$map`. The key is accumulated in
+    // parentheses first, then the value is parsed into an expression in brackets.
+
+    (@map $map:ident () () ()) => {};
+
+    (@map $map:ident [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
+        $map.push(($crate::msgpack!($($key)+), $value));
+        $crate::msgpack!(@map $map () ($($rest)*) ($($rest)*));
+    };
+
+    (@map $map:ident [$($key:tt)+] ($value:expr)) => {
+        $map.push(($crate::msgpack!($($key)+), $value));
+    };
+
+    (@map $map:ident ($($key:tt)+) (: nil $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!(nil)) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: null $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!(null)) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: true $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!(true)) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: false $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!(false)) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: bin ($($data:tt)*) $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!(bin($($data)*))) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: ext ($($data:tt)*) $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!(ext($($data)*))) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: [$($array:tt)*] $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!([$($array)*])) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: {$($inner:tt)*} $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!({$($inner)*})) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: $value:expr , $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!($value)) , $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: $value:expr) $copy:tt) => {
+        $crate::msgpack!(@map $map [$($key)+] ($crate::msgpack!($value)));
+    };
+
+    (@map $map:ident ($($key:tt)*) ($tt:tt $($rest:tt)*) $copy:tt) => {
+        $crate::msgpack!(@map $map ($($key)* $tt) ($($rest)*) ($($rest)*));
+    };
+
+    // Entry points.
+
+    (nil) => {
+        $crate::Value::Nil
+    };
+
+    (null) => {
+        $crate::Value::Nil
+    };
+
+    (true) => {
+        $crate::Value::Boolean(true)
+    };
+
+    (false) => {
+        $crate::Value::Boolean(false)
+    };
+
+    ([]) => {
+        $crate::Value::Array(vec![])
+    };
+
+    // Elements and entries, which are single token trees, negative literals or calls like
+    // `bin(..)`, are collected in one pass. Otherwise they are munched one by one, which nests
+    // an expansion per element and may hit the recursion limit for large literals.
+
+    ([ $($head:tt $($tail:literal)? $(($($args:tt)*))?),+ $(,)? ]) => {
+        $crate::Value::Array(vec![$($crate::msgpack!($head $($tail)? $(($($args)*))?)),+])
+    };
+
+    ([ $($tt:tt)+ ]) => {
+        $crate::Value::Array($crate::msgpack!(@array [] $($tt)+))
+    };
+
+    ({}) => {
+        $crate::Value::Map(vec![])
+    };
+
+    ({ $($khead:tt $($ktail:literal)? $(($($kargs:tt)*))? :
+         $vhead:tt $($vtail:literal)? $(($($vargs:tt)*))?),+ $(,)? }) => {
+        $crate::Value::Map(vec![$((
+            $crate::msgpack!($khead $($ktail)? $(($($kargs)*))?),
+            $crate::msgpack!($vhead $($vtail)? $(($($vargs)*))?),
+        )),+])
+    };
+
+    ({ $($tt:tt)+ }) => {
+        $crate::Value::Map({
+            let mut map = Vec::new();
+            $crate::msgpack!(@map map () ($($tt)+) ($($tt)+));
+            map
+        })
+    };
+
+    (bin($data:expr)) => {
+        $crate::Value::Binary(::std::convert::AsRef::<[u8]>::as_ref(&$data).to_vec())
+    };
+
+    (ext($ty:expr, $data:expr)) => {
+        $crate::Value::Ext($ty, ::std::convert::AsRef::<[u8]>::as_ref(&$data).to_vec())
+    };
+
+    ($other:expr) => {
+        ::std::convert::Into::<$crate::Value>::into($other)
+    };
+}
@@ -0,0 +1,405 @@
+//! Structural diff, JSON Patch (RFC 6902) and JSON Merge Patch (RFC 7396) for `Value`.
+//!
+//! Patches are represented as `Value`s, so they can be shipped as MessagePack themselves. Paths
+//! are JSON pointers, which are resolved like `Value::pointer` does, i.e. map entries are looked
+//! up by string key first, then by integer key, and the first matching entry wins if the map
+//! contains duplicate keys.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate rmpv;
+//!
+//! use rmpv::patch;
+//!
+//! # fn main() {
+//! let from = msgpack!({"name": "John", "tags": ["a"]});
+//! let to = msgpack!({"name": "Jane", "tags": ["a", "b"]});
+//!
+//! let diff = patch::diff(&from, &to);
+//! assert_eq!(msgpack!([
+//!     {"op": "replace", "path": "/name", "value": "Jane"},
+//!     {"op": "add", "path": "/tags/1", "value": "b"},
+//! ]), diff);
+//!
+//! let mut doc = from.clone();
+//! patch::apply(&mut doc, &diff).unwrap();
+//! assert_eq!(to, doc);
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+
+use Value;
+use pointer::{array_index, escape, position, unescape};
+
+/// This type represents all possible errors that can occur when applying a JSON Patch.
+///
+/// Operation indices are zero-based positions within the patch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The patch is not an array of operations.
+    InvalidPatch,
+    /// The operation at the given index is not a map with valid `op`, `path`, `from` and
+    /// `value` members, or moves a value into one of its children.
+    InvalidOperation(usize),
+    /// The operation at the given index refers to a path that does not exist.
+    PathNotFound(usize),
+    /// The `test` operation at the given index failed.
+    TestFailed(usize),
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::InvalidPatch => "patch is not an array of operations",
+            Error::InvalidOperation(..) => "invalid patch operation",
+            Error::PathNotFound(..) => "path not found",
+            Error::TestFailed(..) => "test operation failed",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Error::InvalidPatch => write!(fmt, "patch is not an array of operations"),
+            Error::InvalidOperation(idx) => write!(fmt, "invalid patch operation {}", idx),
+            Error::PathNotFound(idx) => write!(fmt, "path not found at operation {}", idx),
+            Error::TestFailed(idx) => write!(fmt, "test operation {} failed", idx),
+        }
+    }
+}
+
+/// Computes a JSON Patch, which transforms `from` into `to` when applied.
+///
+/// Maps are compared entry by entry if both have unique string keys and applying the patch keeps
+/// the order of entries of `to`, otherwise the whole map is replaced. Arrays are compared element
+/// by element, while extra elements are added to or removed from the end. Values of different
+/// types are replaced, as well as floats with different bits, like `-0.0` and `0.0`.
+///
+/// Applying the result to `from` produces a value equal to `to`, including the order of map
+/// entries.
+pub fn diff(from: &Value, to: &Value) -> Value {
+    let mut ops = Vec::new();
+    diff_into(from, to, &mut String::new(), &mut ops);
+    Value::Array(ops)
+}
+
+fn diff_into(from: &Value, to: &Value, path: &mut String, ops: &mut Vec<Value>) {
+    if is_identical(from, to) {
+        return;
+    }
+
+    match (from, to) {
+        (&Value::Map(ref lhs), &Value::Map(ref rhs)) if is_diffable(lhs, rhs) => {
+            for &(ref key, ref val) in lhs {
+                let key = key.as_str().unwrap();
+                let len = push_token(path, key);
+                match rhs.iter().find(|&&(ref k, _)| k.as_str() == Some(key)) {
+                    Some(&(_, ref new)) => diff_into(val, new, path, ops),
+                    None => ops.push(msgpack!({"op": "remove", "path": &path[..]})),
+                }
+                path.truncate(len);
+            }
+
+            for &(ref key, ref val) in rhs {
+                let key = key.as_str().unwrap();
+                if !lhs.iter().any(|&(ref k, _)| k.as_str() == Some(key)) {
+                    let len = push_token(path, key);
+                    ops.push(msgpack!({"op": "add", "path": &path[..], "value": val.clone()}));
+                    path.truncate(len);
+                }
+            }
+        }
+        (&Value::Array(ref lhs), &Value::Array(ref rhs)) => {
+            for (idx, (val, new)) in lhs.iter().zip(rhs).enumerate() {
+                let len = push_token(path, &idx.to_string());
+                diff_into(val, new, path, ops);
+                path.truncate(len);
+            }
+
+            for idx in (rhs.len()..lhs.len()).rev() {
+                let len = push_token(path, &idx.to_string());
+                ops.push(msgpack!({"op": "remove", "path": &path[..]}));
+                path.truncate(len);
+            }
+
+            for (idx, val) in rhs.iter().enumerate().skip(lhs.len()) {
+                let len = push_token(path, &idx.to_string());
+                ops.push(msgpack!({"op": "add", "path": &path[..], "value": val.clone()}));
+                path.truncate(len);
+            }
+        }
+        _ => ops.push(msgpack!({"op": "replace", "path": &path[..], "value": to.clone()})),
+    }
+}
+
+// Compares values exactly, where floats are equal only if they have the same bits, so that
+// changes between negative and positive zeros or between NaN payloads are not lost.
+fn is_identical(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (&Value::F32(lhs), &Value::F32(rhs)) => lhs.to_bits() == rhs.to_bits(),
+        (&Value::F64(lhs), &Value::F64(rhs)) => lhs.to_bits() == rhs.to_bits(),
+        (&Value::Array(ref lhs), &Value::Array(ref rhs)) => {
+            lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(lhs, rhs)| is_identical(lhs, rhs))
+        }
+        (&Value::Map(ref lhs), &Value::Map(ref rhs)) => {
+            lhs.len() == rhs.len() &&
+                lhs.iter().zip(rhs).all(|(&(ref lk, ref lv), &(ref rk, ref rv))| {
+                    is_identical(lk, rk) && is_identical(lv, rv)
+                })
+        }
+        _ => lhs == rhs,
+    }
+}
+
+// Appends an escaped reference token to the path, returning the previous length of the path.
+fn push_token(path: &mut String, token: &str) -> usize {
+    let len = path.len();
+    path.push('/');
+    path.push_str(&escape(token));
+    len
+}
+
+// Checks whether maps can be diffed entry by entry: all keys must be unique strings and the
+// patch must reproduce the order of entries of the target map, i.e. the kept entries go first in
+// their original order, followed by the added ones.
+fn is_diffable(lhs: &[(Value, Value)], rhs: &[(Value, Value)]) -> bool {
+    let lkeys = match unique_str_keys(lhs) {
+        Some(keys) => keys,
+        None => return false,
+    };
+    let rkeys = match unique_str_keys(rhs) {
+        Some(keys) => keys,
+        None => return false,
+    };
+
+    let expected = lkeys.iter()
+        .filter(|key| rkeys.contains(*key))
+        .chain(rkeys.iter().filter(|key| !lkeys.contains(*key)));
+
+    expected.eq(rkeys.iter())
+}
+
+fn unique_str_keys(map: &[(Value, Value)]) -> Option<Vec<&str>> {
+    let mut seen = HashSet::with_capacity(map.len());
+    let mut keys = Vec::with_capacity(map.len());
+    for &(ref key, _) in map {
+        let key = key.as_str()?;
+        if !seen.insert(key) {
+            return None;
+        }
+        keys.push(key);
+    }
+
+    Some(keys)
+}
+
+/// Applies a JSON Patch to the value.
+///
+/// The patch must be an array of operation maps as defined in RFC 6902: `add`, `remove`,
+/// `replace`, `move`, `copy` and `test`. Adding to a map replaces the value of an existing entry
+/// in place or appends a new entry with a string key. The `test` operation compares integers by
+/// their numeric values, so `I64(1)` is equal to `U64(1)`.
+///
+/// The patch is applied atomically: if any operation fails, the value is left unchanged.
+///
+/// # Errors
+///
+/// Returns an error describing the first operation, which cannot be applied.
+pub fn apply(val: &mut Value, patch: &Value) -> Result<(), Error> {
+    let ops = patch.as_array().ok_or(Error::InvalidPatch)?;
+
+    let mut res = val.clone();
+    for (idx, op) in ops.iter().enumerate() {
+        apply_op(&mut res, op).map_err(|err| err(idx))?;
+    }
+
+    *val = res;
+    Ok(())
+}
+
+type Failure = fn(usize) -> Error;
+
+fn apply_op(doc: &mut Value, op: &Value) -> Result<(), Failure> {
+    let member = |name| op.get(name).ok_or(Error::InvalidOperation as Failure);
+    let str_member = |name| member(name)?.as_str().ok_or(Error::InvalidOperation as Failure);
+
+    let path = str_member("path")?;
+    match str_member("op")? {
+        "add" => add(doc, path, member("value")?.clone()),
+        "remove" => remove(doc, path).map(|_| ()),
+        "replace" => {
+            let val = member("value")?.clone();
+            *doc.pointer_mut(path).ok_or(Error::PathNotFound as Failure)? = val;
+            Ok(())
+        }
+        "move" => {
+            let from = str_member("from")?;
+            if path == from {
+                return doc.pointer(path).map(|_| ()).ok_or(Error::PathNotFound);
+            }
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(Error::InvalidOperation);
+            }
+
+            let val = remove(doc, from)?;
+            add(doc, path, val)
+        }
+        "copy" => {
+            let from = str_member("from")?;
+            let val = doc.pointer(from).ok_or(Error::PathNotFound as Failure)?.clone();
+            add(doc, path, val)
+        }
+        "test" => {
+            match doc.pointer(path) {
+                Some(val) if is_equal(val, member("value")?) => Ok(()),
+                _ => Err(Error::TestFailed),
+            }
+        }
+        _ => Err(Error::InvalidOperation),
+    }
+}
+
+// Compares values for the `test` operation, where integers are equal if they have the same numeric
+// value regardless of their representation.
+fn is_equal(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (&Value::U64(..), &Value::I64(..)) | (&Value::I64(..), &Value::U64(..)) => {
+            lhs.as_i64().is_some() && lhs.as_i64() == rhs.as_i64()
+        }
+        (&Value::Array(ref lhs), &Value::Array(ref rhs)) => {
+            lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(lhs, rhs)| is_equal(lhs, rhs))
+        }
+        (&Value::Map(ref lhs), &Value::Map(ref rhs)) => {
+            lhs.len() == rhs.len() &&
+                lhs.iter().zip(rhs).all(|(&(ref lk, ref lv), &(ref rk, ref rv))| {
+                    is_equal(lk, rk) && is_equal(lv, rv)
+                })
+        }
+        _ => lhs == rhs,
+    }
+}
+
+// Splits the path into the parent path and the unescaped last token. Returns None for the root.
+fn split_last<'p>(path: &'p str) -> Result<Option<(&'p str, Cow<'p, str>)>, Failure> {
+    if path.is_empty() {
+        Ok(None)
+    } else if path.starts_with('/') {
+        let idx = path.rfind('/').unwrap();
+        Ok(Some((&path[..idx], unescape(&path[idx + 1..]))))
+    } else {
+        Err(Error::InvalidOperation)
+    }
+}
+
+fn add(doc: &mut Value, path: &str, val: Value) -> Result<(), Failure> {
+    let (parent, token) = match split_last(path)? {
+        Some(split) => split,
+        None => {
+            *doc = val;
+            return Ok(());
+        }
+    };
+
+    match *doc.pointer_mut(parent).ok_or(Error::PathNotFound as Failure)? {
+        Value::Array(ref mut array) => {
+            if token == "-" {
+                array.push(val);
+            } else {
+                match array_index(&token) {
+                    Some(idx) if idx <= array.len() => array.insert(idx, val),
+                    _ => return Err(Error::PathNotFound),
+                }
+            }
+        }
+        Value::Map(ref mut map) => {
+            match position(map, &token) {
+                Some(idx) => map[idx].1 = val,
+                None => map.push((Value::from(token.into_owned()), val)),
+            }
+        }
+        _ => return Err(Error::PathNotFound),
+    }
+
+    Ok(())
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, Failure> {
+    let (parent, token) = split_last(path)?.ok_or(Error::InvalidOperation as Failure)?;
+
+    match *doc.pointer_mut(parent).ok_or(Error::PathNotFound as Failure)? {
+        Value::Array(ref mut array) => {
+            match array_index(&token) {
+                Some(idx) if idx < array.len() => Ok(array.remove(idx)),
+                _ => Err(Error::PathNotFound),
+            }
+        }
+        Value::Map(ref mut map) => {
+            match position(map, &token) {
+                Some(idx) => Ok(map.remove(idx).1),
+                None => Err(Error::PathNotFound),
+            }
+        }
+        _ => Err(Error::PathNotFound),
+    }
+}
+
+/// Applies a JSON Merge Patch as defined in RFC 7396 to the value.
+///
+/// If the patch is a map, its entries are merged into the value recursively, replacing the value
+/// with an empty map first if it is not a map. `Nil` values remove all entries with an equal key,
+/// while other values are merged into the first entry with an equal key or appended as new
+/// entries. Keys of any type are supported. If the patch is not a map, it replaces the value.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate rmpv;
+///
+/// use rmpv::patch;
+///
+/// # fn main() {
+/// let mut val = msgpack!({"name": "John", "age": 42, "tags": {"a": 1}});
+///
+/// patch::merge(&mut val, &msgpack!({"age": null, "tags": {"b": 2}}));
+///
+/// assert_eq!(msgpack!({"name": "John", "tags": {"a": 1, "b": 2}}), val);
+/// # }
+/// ```
+pub fn merge(val: &mut Value, patch: &Value) {
+    let patch = match *patch {
+        Value::Map(ref patch) => patch,
+        ref patch => {
+            *val = patch.clone();
+            return;
+        }
+    };
+
+    if !val.is_map() {
+        *val = Value::Map(Vec::new());
+    }
+
+    for &(ref key, ref value) in patch {
+        if value.is_nil() {
+            val.remove(key.clone());
+            continue;
+        }
+
+        let map = val.as_map_mut().unwrap();
+        let idx = match map.iter().position(|&(ref k, _)| k == key) {
+            Some(idx) => idx,
+            None => {
+                map.push((key.clone(), Value::Nil));
+                map.len() - 1
+            }
+        };
+        merge(&mut map[idx].1, value);
+    }
+}
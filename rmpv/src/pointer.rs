@@ -0,0 +1,246 @@
+//! JSON pointer (RFC 6901) access to nested values.
+
+use std::borrow::Cow;
+use std::str::Split;
+
+use {Value, ValueRef};
+
+// Iterates over unescaped reference tokens of a JSON pointer.
+struct Tokens<'p> {
+    iter: Split<'p, char>,
+}
+
+impl<'p> Iterator for Tokens<'p> {
+    type Item = Cow<'p, str>;
+
+    fn next(&mut self) -> Option<Cow<'p, str>> {
+        self.iter.next().map(unescape)
+    }
+}
+
+// Unescapes `~1` and `~0` sequences of a reference token.
+pub(crate) fn unescape<'t>(token: &'t str) -> Cow<'t, str> {
+    if token.contains('~') {
+        Cow::Owned(token.replace("~1", "/").replace("~0", "~"))
+    } else {
+        Cow::Borrowed(token)
+    }
+}
+
+// Escapes `~` and `/` characters of a reference token.
+pub(crate) fn escape<'t>(token: &'t str) -> Cow<'t, str> {
+    if token.contains('~') || token.contains('/') {
+        Cow::Owned(token.replace('~', "~0").replace('/', "~1"))
+    } else {
+        Cow::Borrowed(token)
+    }
+}
+
+// Splits the pointer into tokens. Returns None if the pointer is neither empty nor starts with a
+// slash.
+fn tokens<'p>(pointer: &'p str) -> Option<Tokens<'p>> {
+    if pointer.is_empty() || pointer.starts_with('/') {
+        let mut iter = pointer.split('/');
+        iter.next();
+        Some(Tokens { iter: iter })
+    } else {
+        None
+    }
+}
+
+// Parses an array index, rejecting leading zeros and signs as RFC 6901 requires.
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.len() > 1 && token.starts_with('0')) {
+        None
+    } else {
+        token.parse().ok()
+    }
+}
+
+// An integer map key, which a token may refer to when there is no such string key.
+pub(crate) enum IntKey {
+    Pos(u64),
+    Neg(i64),
+}
+
+impl IntKey {
+    // Parses the token only if it is the canonical decimal representation of an integer.
+    fn parse(token: &str) -> Option<IntKey> {
+        if let Ok(n) = token.parse::<u64>() {
+            if n.to_string() == token {
+                return Some(IntKey::Pos(n));
+            }
+        } else if let Ok(n) = token.parse::<i64>() {
+            if n.to_string() == token {
+                return Some(IntKey::Neg(n));
+            }
+        }
+
+        None
+    }
+}
+
+pub(crate) trait Key {
+    fn key_str(&self) -> Option<&str>;
+    fn key_int(&self, int: &IntKey) -> bool;
+}
+
+impl Key for Value {
+    fn key_str(&self) -> Option<&str> {
+        self.as_str()
+    }
+
+    fn key_int(&self, int: &IntKey) -> bool {
+        match *int {
+            IntKey::Pos(n) => self.as_u64() == Some(n),
+            IntKey::Neg(n) => self.as_i64() == Some(n),
+        }
+    }
+}
+
+impl<'a> Key for ValueRef<'a> {
+    fn key_str(&self) -> Option<&str> {
+        self.as_str()
+    }
+
+    fn key_int(&self, int: &IntKey) -> bool {
+        match *int {
+            IntKey::Pos(n) => self.as_u64() == Some(n),
+            IntKey::Neg(n) => self.as_i64() == Some(n),
+        }
+    }
+}
+
+// Returns the position of the first entry with the given string key, falling back to the first
+// entry with an integer key if the token is an integer.
+pub(crate) fn position<V: Key>(map: &[(V, V)], token: &str) -> Option<usize> {
+    map.iter().position(|&(ref k, _)| k.key_str() == Some(token)).or_else(|| {
+        IntKey::parse(token).and_then(|int| map.iter().position(|&(ref k, _)| k.key_int(&int)))
+    })
+}
+
+impl Value {
+    /// Looks up a nested value by a JSON pointer as defined in RFC 6901.
+    ///
+    /// The pointer is either empty, referring to the whole value, or a sequence of reference
+    /// tokens, each prefixed by `/`, where `~1` and `~0` are unescaped as `/` and `~`
+    /// respectively. Tokens index arrays by number and maps by string key. If a map has no such
+    /// string key, but the token is an integer, the first entry with an equal integer key is
+    /// returned.
+    ///
+    /// Returns None if the pointer is malformed or does not refer to an existing value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate rmpv;
+    ///
+    /// # fn main() {
+    /// let val = msgpack!({
+    ///     "users": [{"name": "John"}],
+    ///     "a/b": {42: true},
+    /// });
+    ///
+    /// assert_eq!(Some(&msgpack!("John")), val.pointer("/users/0/name"));
+    /// assert_eq!(Some(&msgpack!(true)), val.pointer("/a~1b/42"));
+    /// assert_eq!(None, val.pointer("/users/1"));
+    /// # }
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        let mut target = self;
+        for token in tokens(pointer)? {
+            target = match *target {
+                Value::Array(ref array) => array.get(array_index(&token)?)?,
+                Value::Map(ref map) => &map[position(map, &token)?].1,
+                _ => return None,
+            };
+        }
+
+        Some(target)
+    }
+
+    /// Looks up a nested value by a JSON pointer, returning a mutable reference to it.
+    ///
+    /// See `pointer` for the syntax and lookup rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate rmpv;
+    ///
+    /// # fn main() {
+    /// let mut val = msgpack!({"users": [{"name": "John"}]});
+    ///
+    /// *val.pointer_mut("/users/0/name").unwrap() = msgpack!("Jane");
+    ///
+    /// assert_eq!(msgpack!({"users": [{"name": "Jane"}]}), val);
+    /// # }
+    /// ```
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        let mut target = self;
+        for token in tokens(pointer)? {
+            target = match *target {
+                Value::Array(ref mut array) => array.get_mut(array_index(&token)?)?,
+                Value::Map(ref mut map) => {
+                    let idx = position(map, &token)?;
+                    &mut map[idx].1
+                }
+                _ => return None,
+            };
+        }
+
+        Some(target)
+    }
+}
+
+impl<'a> ValueRef<'a> {
+    /// Looks up a nested value by a JSON pointer as defined in RFC 6901.
+    ///
+    /// See `Value::pointer` for the syntax and lookup rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rmpv::ValueRef;
+    ///
+    /// let val = ValueRef::Map(vec![
+    ///     (ValueRef::from("tags"), ValueRef::Array(vec![ValueRef::from("rust")])),
+    /// ]);
+    ///
+    /// assert_eq!(Some(&ValueRef::from("rust")), val.pointer("/tags/0"));
+    /// assert_eq!(None, val.pointer("tags"));
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&ValueRef<'a>> {
+        let mut target = self;
+        for token in tokens(pointer)? {
+            target = match *target {
+                ValueRef::Array(ref array) => array.get(array_index(&token)?)?,
+                ValueRef::Map(ref map) => &map[position(map, &token)?].1,
+                _ => return None,
+            };
+        }
+
+        Some(target)
+    }
+
+    /// Looks up a nested value by a JSON pointer, returning a mutable reference to it.
+    ///
+    /// See `Value::pointer` for the syntax and lookup rules.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut ValueRef<'a>> {
+        let mut target = self;
+        for token in tokens(pointer)? {
+            target = match *target {
+                ValueRef::Array(ref mut array) => array.get_mut(array_index(&token)?)?,
+                ValueRef::Map(ref mut map) => {
+                    let idx = position(map, &token)?;
+                    &mut map[idx].1
+                }
+                _ => return None,
+            };
+        }
+
+        Some(target)
+    }
+}
@@ -0,0 +1,400 @@
+//! Encoded size profiling of MessagePack data by logical path.
+//!
+//! `Profiler` walks encoded values without decoding them into `Value`s and aggregates, for each
+//! path, the number of values found there, their total encoded size, the size spent on map keys
+//! leading to them and the distribution of their markers. This shows which fields cost the most
+//! bytes and which of them could use a more compact encoding.
+//!
+//! Paths use the syntax of the `query` module, so they can be used to select the values:
+//!
+//! - `$` denotes each top-level value;
+//! - `.name` or `['name']` denotes the value of a map entry with the given string key;
+//! - `[n]` denotes the value of a map entry with the given integer key;
+//! - `[*]` denotes every array element and values of map entries with any other key.
+//!
+//! For example, the SKUs of all ordered items are aggregated under `$.orders[*].items[*].sku`.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate rmpv;
+//!
+//! use rmpv::encode::write_value;
+//! use rmpv::profile::Profiler;
+//!
+//! # fn main() {
+//! let mut buf = Vec::new();
+//! write_value(&mut buf, &msgpack!({"id": 1, "tags": ["a", "bc"]})).unwrap();
+//! write_value(&mut buf, &msgpack!({"id": 300, "tags": []})).unwrap();
+//!
+//! let mut profiler = Profiler::new();
+//! assert_eq!(2, profiler.profile_stream(&mut &buf[..]).unwrap());
+//!
+//! let stats = profiler.get("$.id").unwrap();
+//! assert_eq!(2, stats.count);
+//! assert_eq!(4, stats.bytes);
+//! assert_eq!(6, stats.key_bytes);
+//! assert_eq!(Some(&1), stats.markers.get("uint16"));
+//!
+//! assert_eq!(5, profiler.get("$.tags[*]").unwrap().bytes);
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Iter;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Read};
+use std::str;
+
+use rmp::Marker;
+use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64,
+                  read_data_i8, read_data_i16, read_data_i32, read_data_i64};
+
+use decode::value::Error;
+use query;
+
+/// The default maximum nesting depth of arrays and maps.
+const MAX_DEPTH: usize = 512;
+
+/// Statistics of values found at a single path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathStats {
+    /// The number of values.
+    pub count: u64,
+    /// The total encoded size of the values in bytes, including nested values.
+    pub bytes: u64,
+    /// The total encoded size of map keys leading to the values in bytes.
+    pub key_bytes: u64,
+    /// The number of values per marker, named by `Marker::name`, e.g. `"uint16"` or `"fixstr"`.
+    pub markers: BTreeMap<&'static str, u64>,
+}
+
+/// Aggregates encoded sizes of values by path over any number of messages.
+///
+/// The report is available through `get` and `paths` or as a table using `Display`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profiler {
+    paths: BTreeMap<String, PathStats>,
+    messages: u64,
+    bytes: u64,
+    max_depth: usize,
+}
+
+impl Profiler {
+    /// Creates an empty profiler.
+    pub fn new() -> Profiler {
+        Profiler {
+            paths: BTreeMap::new(),
+            messages: 0,
+            bytes: 0,
+            max_depth: MAX_DEPTH,
+        }
+    }
+
+    /// Sets the maximum nesting depth of arrays and maps. The default is 512.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Returns the number of top-level values profiled.
+    pub fn messages(&self) -> u64 {
+        self.messages
+    }
+
+    /// Returns the total size of top-level values profiled in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns statistics for the given path.
+    pub fn get(&self, path: &str) -> Option<&PathStats> {
+        self.paths.get(path)
+    }
+
+    /// Returns an iterator over all paths and their statistics ordered by path.
+    pub fn paths<'a>(&'a self) -> Iter<'a, String, PathStats> {
+        self.paths.iter()
+    }
+
+    /// Reads a single value and adds it to the statistics.
+    ///
+    /// Only string map keys are buffered, while other data is skipped as it is read, so the
+    /// memory used depends only on the number of distinct paths.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be read, contains the reserved marker or nests arrays
+    /// and maps deeper than the maximum depth. In that case the statistics include the part of
+    /// the value read before the failure.
+    pub fn profile<R: Read>(&mut self, rd: &mut R) -> Result<(), Error> {
+        let mut rd = Counter { rd: rd, pos: 0 };
+        let mut path = "$".to_owned();
+
+        let depth = self.max_depth;
+        let res = self.profile_value(&mut rd, &mut path, depth);
+        if res.is_ok() {
+            self.messages += 1;
+            self.bytes += rd.pos;
+        }
+        res
+    }
+
+    /// Reads concatenated values until the end of the input, adding them to the statistics and
+    /// returning their number.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `profile`.
+    pub fn profile_stream<R: BufRead>(&mut self, rd: &mut R) -> Result<u64, Error> {
+        let mut count = 0;
+        while !rd.fill_buf().map_err(Error::InvalidMarkerRead)?.is_empty() {
+            self.profile(rd)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Profiles a value, allowing arrays and maps to be nested at most `depth` levels deep.
+    fn profile_value<R: Read>(&mut self, rd: &mut Counter<R>, path: &mut String, depth: usize)
+        -> Result<(), Error>
+    {
+        let start = rd.pos;
+        let marker = read_marker(rd)?;
+
+        match read_header(rd, marker)? {
+            Header::Skip(len) => skip(rd, len)?,
+            Header::Array(..) | Header::Map(..) if depth == 0 => {
+                return Err(Error::DepthLimitExceeded);
+            }
+            Header::Array(len) => {
+                let parent = path.len();
+                path.push_str("[*]");
+                for _ in 0..len {
+                    self.profile_value(rd, path, depth - 1)?;
+                }
+                path.truncate(parent);
+            }
+            Header::Map(len) => {
+                let parent = path.len();
+                for _ in 0..len {
+                    let key_start = rd.pos;
+                    push_key(rd, path, depth - 1)?;
+                    let key_bytes = rd.pos - key_start;
+                    self.stats(path).key_bytes += key_bytes;
+                    self.profile_value(rd, path, depth - 1)?;
+                    path.truncate(parent);
+                }
+            }
+        }
+
+        let stats = self.stats(path);
+        stats.count += 1;
+        stats.bytes += rd.pos - start;
+        *stats.markers.entry(marker.name()).or_insert(0) += 1;
+
+        Ok(())
+    }
+
+    fn stats(&mut self, path: &str) -> &mut PathStats {
+        if !self.paths.contains_key(path) {
+            self.paths.insert(path.to_owned(), PathStats::default());
+        }
+        self.paths.get_mut(path).unwrap()
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
+
+impl Display for Profiler {
+    /// Formats the statistics as a table with a row per path ordered by path.
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        let width = self.paths.keys().map(|path| path.chars().count()).max().unwrap_or(0);
+        let width = width.max(4);
+
+        writeln!(fmt, "{:<width$}  {:>10}  {:>12}  {:>6}  {:>10}  markers",
+                 "path",
+                 "count",
+                 "bytes",
+                 "%",
+                 "key bytes",
+                 width = width)?;
+
+        for (path, stats) in &self.paths {
+            let share = if self.bytes == 0 {
+                0.0
+            } else {
+                100.0 * (stats.bytes + stats.key_bytes) as f64 / self.bytes as f64
+            };
+
+            write!(fmt, "{:<width$}  {:>10}  {:>12}  {:>6.1}  {:>10}  ",
+                   path,
+                   stats.count,
+                   stats.bytes,
+                   share,
+                   stats.key_bytes,
+                   width = width)?;
+
+            for (idx, (name, count)) in stats.markers.iter().enumerate() {
+                if idx > 0 {
+                    fmt.write_str(", ")?;
+                }
+                write!(fmt, "{}: {}", name, count)?;
+            }
+            writeln!(fmt)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A reader counting bytes read.
+struct Counter<'a, R: 'a> {
+    rd: &'a mut R,
+    pos: u64,
+}
+
+impl<'a, R: Read> Read for Counter<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.rd.read(buf)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+/// What follows a marker.
+enum Header {
+    /// Data of the given size without nested values.
+    Skip(u64),
+    /// The given number of elements.
+    Array(u32),
+    /// The given number of entries.
+    Map(u32),
+}
+
+fn read_header<R: Read>(rd: &mut R, marker: Marker) -> Result<Header, Error> {
+    let header = match marker {
+        Marker::Null | Marker::True | Marker::False | Marker::FixPos(..) | Marker::FixNeg(..) => {
+            Header::Skip(0)
+        }
+        Marker::U8 | Marker::I8 => Header::Skip(1),
+        Marker::U16 | Marker::I16 => Header::Skip(2),
+        Marker::U32 | Marker::I32 | Marker::F32 => Header::Skip(4),
+        Marker::U64 | Marker::I64 | Marker::F64 => Header::Skip(8),
+        Marker::FixStr(len) => Header::Skip(len as u64),
+        Marker::Str8 | Marker::Bin8 => Header::Skip(read_data_u8(rd)? as u64),
+        Marker::Str16 | Marker::Bin16 => Header::Skip(read_data_u16(rd)? as u64),
+        Marker::Str32 | Marker::Bin32 => Header::Skip(read_data_u32(rd)? as u64),
+        Marker::FixArray(len) => Header::Array(len as u32),
+        Marker::Array16 => Header::Array(read_data_u16(rd)? as u32),
+        Marker::Array32 => Header::Array(read_data_u32(rd)?),
+        Marker::FixMap(len) => Header::Map(len as u32),
+        Marker::Map16 => Header::Map(read_data_u16(rd)? as u32),
+        Marker::Map32 => Header::Map(read_data_u32(rd)?),
+        // Extensions are followed by the type byte.
+        Marker::FixExt1 => Header::Skip(2),
+        Marker::FixExt2 => Header::Skip(3),
+        Marker::FixExt4 => Header::Skip(5),
+        Marker::FixExt8 => Header::Skip(9),
+        Marker::FixExt16 => Header::Skip(17),
+        Marker::Ext8 => Header::Skip(read_data_u8(rd)? as u64 + 1),
+        Marker::Ext16 => Header::Skip(read_data_u16(rd)? as u64 + 1),
+        Marker::Ext32 => Header::Skip(read_data_u32(rd)? as u64 + 1),
+        Marker::Reserved => return Err(Error::TypeMismatch(Marker::Reserved)),
+    };
+
+    Ok(header)
+}
+
+fn skip<R: Read>(rd: &mut R, len: u64) -> Result<(), Error> {
+    let skipped = io::copy(&mut rd.take(len), &mut io::sink()).map_err(Error::InvalidDataRead)?;
+    if skipped < len {
+        Err(Error::InvalidDataRead(io::ErrorKind::UnexpectedEof.into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Skips a value, which marker is already read, allowing arrays and maps to be nested at most
+/// `depth` levels deep.
+fn skip_value<R: Read>(rd: &mut R, marker: Marker, depth: usize) -> Result<(), Error> {
+    match read_header(rd, marker)? {
+        Header::Skip(len) => skip(rd, len),
+        Header::Array(..) | Header::Map(..) if depth == 0 => Err(Error::DepthLimitExceeded),
+        Header::Array(len) => {
+            for _ in 0..len {
+                let marker = read_marker(rd)?;
+                skip_value(rd, marker, depth - 1)?;
+            }
+            Ok(())
+        }
+        Header::Map(len) => {
+            for _ in 0..2 * len as u64 {
+                let marker = read_marker(rd)?;
+                skip_value(rd, marker, depth - 1)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Reads a map key and appends the corresponding path segment.
+fn push_key<R: Read>(rd: &mut R, path: &mut String, depth: usize) -> Result<(), Error> {
+    let marker = read_marker(rd)?;
+
+    let segment = match marker {
+        Marker::FixStr(len) => read_str_key(rd, len as u32)?,
+        Marker::Str8 => {
+            let len = read_data_u8(rd)? as u32;
+            read_str_key(rd, len)?
+        }
+        Marker::Str16 => {
+            let len = read_data_u16(rd)? as u32;
+            read_str_key(rd, len)?
+        }
+        Marker::Str32 => {
+            let len = read_data_u32(rd)?;
+            read_str_key(rd, len)?
+        }
+        Marker::FixPos(val) => format!("[{}]", val),
+        Marker::FixNeg(val) => format!("[{}]", val),
+        Marker::U8 => format!("[{}]", read_data_u8(rd)?),
+        Marker::U16 => format!("[{}]", read_data_u16(rd)?),
+        Marker::U32 => format!("[{}]", read_data_u32(rd)?),
+        Marker::U64 => format!("[{}]", read_data_u64(rd)?),
+        Marker::I8 => format!("[{}]", read_data_i8(rd)?),
+        Marker::I16 => format!("[{}]", read_data_i16(rd)?),
+        Marker::I32 => format!("[{}]", read_data_i32(rd)?),
+        Marker::I64 => format!("[{}]", read_data_i64(rd)?),
+        marker => {
+            skip_value(rd, marker, depth)?;
+            "[*]".to_owned()
+        }
+    };
+
+    path.push_str(&segment);
+    Ok(())
+}
+
+/// Reads a string key of the given length and returns the corresponding path segment.
+fn read_str_key<R: Read>(rd: &mut R, len: u32) -> Result<String, Error> {
+    let mut buf = Vec::new();
+    rd.take(len as u64).read_to_end(&mut buf).map_err(Error::InvalidDataRead)?;
+    if buf.len() < len as usize {
+        return Err(Error::InvalidDataRead(io::ErrorKind::UnexpectedEof.into()));
+    }
+
+    match str::from_utf8(&buf) {
+        Ok(name) => {
+            let mut segment = String::new();
+            query::push_key(&mut segment, name);
+            Ok(segment)
+        }
+        Err(..) => Ok("[*]".to_owned()),
+    }
+}
+
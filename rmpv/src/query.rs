@@ -0,0 +1,794 @@
+//! JSONPath-like queries selecting nodes from `Value` and `ValueRef` trees.
+//!
+//! A query starts with an optional `$`, which denotes the root, followed by a sequence of
+//! segments:
+//!
+//! - `.name` or `['name']` selects the value of the first map entry with the given string key;
+//! - `[n]` selects the n-th array element, counting from the end if negative, or the value of the
+//!   first map entry with the given integer key;
+//! - `.*` or `[*]` selects all array elements or map values;
+//! - `[start:end:step]` selects a slice of an array, where each part is optional and negative
+//!   numbers count from the end, like in Python;
+//! - `[?(@.path op literal)]` selects array elements or map values, for which the given relative
+//!   path exists and, if an operator is present, compares to the literal. Supported operators are
+//!   `==`, `!=`, `<`, `<=`, `>` and `>=`, literals are numbers, quoted strings, `true`, `false`
+//!   and `null`. Numbers are compared by their numeric values, strings lexicographically, while
+//!   values of different types are never equal;
+//! - `..` followed by any of the above except `.name` selects from the node itself and all its
+//!   descendants, e.g. `..name` or `..[0]`.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate rmpv;
+//!
+//! use rmpv::query::Query;
+//!
+//! # fn main() {
+//! let val = msgpack!({
+//!     "items": [
+//!         {"price": 10, "status": "ok"},
+//!         {"price": 20, "status": "failed"},
+//!     ],
+//! });
+//!
+//! let query = Query::parse("$.items[*].price").unwrap();
+//! assert_eq!(vec![&msgpack!(10), &msgpack!(20)], query.select(&val));
+//!
+//! let failed = val.query("$..[?(@.status == 'failed')]").unwrap();
+//! assert_eq!(vec![&msgpack!({"price": 20, "status": "failed"})], failed);
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use {Value, ValueRef};
+
+/// This type represents all possible errors that can occur when parsing a query.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The query ended unexpectedly.
+    UnexpectedEnd,
+    /// The query contains an unexpected character at the given byte offset.
+    UnexpectedChar(char, usize),
+    /// The query contains an invalid number literal at the given byte offset.
+    InvalidNumber(usize),
+    /// The query contains an invalid escape sequence in a string literal at the given byte
+    /// offset.
+    InvalidEscape(usize),
+    /// The slice at the given byte offset has zero step.
+    ZeroStep(usize),
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::UnexpectedEnd => "unexpected end of query",
+            Error::UnexpectedChar(..) => "unexpected character in query",
+            Error::InvalidNumber(..) => "invalid number in query",
+            Error::InvalidEscape(..) => "invalid escape sequence in query",
+            Error::ZeroStep(..) => "slice step cannot be zero",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Error::UnexpectedEnd => write!(fmt, "unexpected end of query"),
+            Error::UnexpectedChar(ch, pos) => {
+                write!(fmt, "unexpected character {:?} at position {}", ch, pos)
+            }
+            Error::InvalidNumber(pos) => write!(fmt, "invalid number at position {}", pos),
+            Error::InvalidEscape(pos) => {
+                write!(fmt, "invalid escape sequence at position {}", pos)
+            }
+            Error::ZeroStep(pos) => write!(fmt, "slice step cannot be zero at position {}", pos),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Number {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl Number {
+    pub(crate) fn partial_cmp(self, other: Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::U64(lhs), Number::U64(rhs)) => Some(lhs.cmp(&rhs)),
+            (Number::I64(lhs), Number::I64(rhs)) => Some(lhs.cmp(&rhs)),
+            (Number::I64(lhs), Number::U64(..)) if lhs < 0 => Some(Ordering::Less),
+            (Number::I64(lhs), Number::U64(rhs)) => Some((lhs as u64).cmp(&rhs)),
+            (Number::U64(..), Number::I64(..)) => other.partial_cmp(self).map(Ordering::reverse),
+            (lhs, rhs) => lhs.as_f64().partial_cmp(&rhs.as_f64()),
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::U64(n) => n as f64,
+            Number::I64(n) => n as f64,
+            Number::F64(n) => n,
+        }
+    }
+}
+
+// A scalar view of a node used for key lookups and filter comparisons.
+#[derive(Debug, PartialEq)]
+enum Scalar<'s> {
+    Nil,
+    Boolean(bool),
+    Number(Number),
+    String(&'s str),
+    Other,
+}
+
+impl<'s> Scalar<'s> {
+    fn partial_cmp(&self, other: &Scalar) -> Option<Ordering> {
+        match (self, other) {
+            (&Scalar::Nil, &Scalar::Nil) => Some(Ordering::Equal),
+            (&Scalar::Boolean(lhs), &Scalar::Boolean(rhs)) => Some(lhs.cmp(&rhs)),
+            (&Scalar::Number(lhs), &Scalar::Number(rhs)) => lhs.partial_cmp(rhs),
+            (&Scalar::String(lhs), &Scalar::String(rhs)) => Some(lhs.cmp(rhs)),
+            (..) => None,
+        }
+    }
+}
+
+// Abstracts over `Value` and `ValueRef` for query evaluation.
+trait Node: Sized {
+    fn array(&self) -> Option<&[Self]>;
+    fn map(&self) -> Option<&[(Self, Self)]>;
+    fn scalar<'s>(&'s self) -> Scalar<'s>;
+}
+
+impl Node for Value {
+    fn array(&self) -> Option<&[Value]> {
+        self.as_array().map(|array| &array[..])
+    }
+
+    fn map(&self) -> Option<&[(Value, Value)]> {
+        self.as_map().map(|map| &map[..])
+    }
+
+    fn scalar<'s>(&'s self) -> Scalar<'s> {
+        match *self {
+            Value::Nil => Scalar::Nil,
+            Value::Boolean(val) => Scalar::Boolean(val),
+            Value::U64(val) => Scalar::Number(Number::U64(val)),
+            Value::I64(val) => Scalar::Number(Number::I64(val)),
+            Value::F32(val) => Scalar::Number(Number::F64(val as f64)),
+            Value::F64(val) => Scalar::Number(Number::F64(val)),
+            Value::String(ref val) => val.as_str().map_or(Scalar::Other, Scalar::String),
+            _ => Scalar::Other,
+        }
+    }
+}
+
+impl<'a> Node for ValueRef<'a> {
+    fn array(&self) -> Option<&[ValueRef<'a>]> {
+        self.as_array().map(|array| &array[..])
+    }
+
+    fn map(&self) -> Option<&[(ValueRef<'a>, ValueRef<'a>)]> {
+        self.as_map().map(|map| &map[..])
+    }
+
+    fn scalar<'s>(&'s self) -> Scalar<'s> {
+        match *self {
+            ValueRef::Nil => Scalar::Nil,
+            ValueRef::Boolean(val) => Scalar::Boolean(val),
+            ValueRef::U64(val) => Scalar::Number(Number::U64(val)),
+            ValueRef::I64(val) => Scalar::Number(Number::I64(val)),
+            ValueRef::F32(val) => Scalar::Number(Number::F64(val as f64)),
+            ValueRef::F64(val) => Scalar::Number(Number::F64(val)),
+            ValueRef::String(val) => val.as_str().map_or(Scalar::Other, Scalar::String),
+            _ => Scalar::Other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Literal {
+    Nil,
+    Boolean(bool),
+    Number(Number),
+    String(String),
+}
+
+impl Literal {
+    fn as_scalar<'s>(&'s self) -> Scalar<'s> {
+        match *self {
+            Literal::Nil => Scalar::Nil,
+            Literal::Boolean(val) => Scalar::Boolean(val),
+            Literal::Number(val) => Scalar::Number(val),
+            Literal::String(ref val) => Scalar::String(val),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn matches(self, ord: Option<Ordering>) -> bool {
+        match (self, ord) {
+            (Op::Ne, None) => true,
+            (_, None) => false,
+            (Op::Eq, Some(ord)) => ord == Ordering::Equal,
+            (Op::Ne, Some(ord)) => ord != Ordering::Equal,
+            (Op::Lt, Some(ord)) => ord == Ordering::Less,
+            (Op::Le, Some(ord)) => ord != Ordering::Greater,
+            (Op::Gt, Some(ord)) => ord == Ordering::Greater,
+            (Op::Ge, Some(ord)) => ord != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Filter {
+    path: Vec<Selector>,
+    cmp: Option<(Op, Literal)>,
+}
+
+impl Filter {
+    fn matches<V: Node>(&self, node: &V) -> bool {
+        let mut node = node;
+        for selector in &self.path {
+            let mut found = None;
+            selector.apply(node, &mut |child| if found.is_none() { found = Some(child) });
+            node = match found {
+                Some(child) => child,
+                None => return false,
+            };
+        }
+
+        match self.cmp {
+            Some((op, ref literal)) => op.matches(node.scalar().partial_cmp(&literal.as_scalar())),
+            None => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Selector {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    Slice(Option<i64>, Option<i64>, i64),
+    Filter(Filter),
+}
+
+impl Selector {
+    fn apply<'v, V: Node, F: FnMut(&'v V)>(&self, node: &'v V, f: &mut F) {
+        match *self {
+            Selector::Key(ref key) => {
+                if let Some(map) = node.map() {
+                    let entry = map.iter().find(|&&(ref k, _)| k.scalar() == Scalar::String(key));
+                    if let Some(&(_, ref v)) = entry {
+                        f(v);
+                    }
+                }
+            }
+            Selector::Index(idx) => {
+                if let Some(array) = node.array() {
+                    let idx = if idx < 0 { idx + array.len() as i64 } else { idx };
+                    if 0 <= idx && idx < array.len() as i64 {
+                        f(&array[idx as usize]);
+                    }
+                } else if let Some(map) = node.map() {
+                    let key = Number::I64(idx);
+                    let entry = map.iter().find(|&&(ref k, _)| match k.scalar() {
+                        Scalar::Number(n @ Number::U64(..)) |
+                        Scalar::Number(n @ Number::I64(..)) => {
+                            n.partial_cmp(key) == Some(Ordering::Equal)
+                        }
+                        _ => false,
+                    });
+                    if let Some(&(_, ref v)) = entry {
+                        f(v);
+                    }
+                }
+            }
+            Selector::Wildcard => {
+                if let Some(array) = node.array() {
+                    for v in array {
+                        f(v);
+                    }
+                } else if let Some(map) = node.map() {
+                    for &(_, ref v) in map {
+                        f(v);
+                    }
+                }
+            }
+            Selector::Slice(start, end, step) => {
+                if let Some(array) = node.array() {
+                    for idx in slice_indices(array.len() as i64, start, end, step) {
+                        f(&array[idx]);
+                    }
+                }
+            }
+            Selector::Filter(ref filter) => {
+                if let Some(array) = node.array() {
+                    for v in array {
+                        if filter.matches(v) {
+                            f(v);
+                        }
+                    }
+                } else if let Some(map) = node.map() {
+                    for &(_, ref v) in map {
+                        if filter.matches(v) {
+                            f(v);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Returns indices selected by a slice using Python semantics.
+fn slice_indices(len: i64, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let normalize = |idx: i64| if idx < 0 { idx + len } else { idx };
+
+    let mut indices = Vec::new();
+    if step > 0 {
+        let start = start.map_or(0, |idx| normalize(idx).max(0).min(len));
+        let end = end.map_or(len, |idx| normalize(idx).max(0).min(len));
+        let mut idx = start;
+        while idx < end {
+            indices.push(idx as usize);
+            idx = match idx.checked_add(step) {
+                Some(idx) => idx,
+                None => break,
+            };
+        }
+    } else {
+        let start = start.map_or(len - 1, |idx| normalize(idx).max(-1).min(len - 1));
+        let end = end.map_or(-1, |idx| normalize(idx).max(-1).min(len - 1));
+        let mut idx = start;
+        while idx > end {
+            indices.push(idx as usize);
+            idx = match idx.checked_add(step) {
+                Some(idx) => idx,
+                None => break,
+            };
+        }
+    }
+
+    indices
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Segment {
+    descendants: bool,
+    selector: Selector,
+}
+
+/// A parsed query, which can be evaluated against multiple values.
+///
+/// See the [module documentation](index.html) for the syntax.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+impl Query {
+    /// Parses the given query.
+    pub fn parse(query: &str) -> Result<Query, Error> {
+        Parser { query: query, pos: 0 }.parse()
+    }
+
+    /// Evaluates the query against the given value, returning references to all matching nodes
+    /// in document order.
+    pub fn select<'v>(&self, val: &'v Value) -> Vec<&'v Value> {
+        self.eval(val)
+    }
+
+    /// Evaluates the query against the given borrowed value, returning references to all
+    /// matching nodes in document order.
+    pub fn select_ref<'v, 'a>(&self, val: &'v ValueRef<'a>) -> Vec<&'v ValueRef<'a>> {
+        self.eval(val)
+    }
+
+    fn eval<'v, V: Node>(&self, root: &'v V) -> Vec<&'v V> {
+        let mut nodes = vec![root];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for node in nodes {
+                if segment.descendants {
+                    let mut stack = vec![node];
+                    while let Some(node) = stack.pop() {
+                        segment.selector.apply(node, &mut |v| next.push(v));
+                        push_children_rev(node, &mut stack);
+                    }
+                } else {
+                    segment.selector.apply(node, &mut |v| next.push(v));
+                }
+            }
+            nodes = next;
+        }
+
+        nodes
+    }
+}
+
+// Pushes children of the node in reverse order, so they are popped in document order.
+fn push_children_rev<'v, V: Node>(node: &'v V, stack: &mut Vec<&'v V>) {
+    if let Some(array) = node.array() {
+        stack.extend(array.iter().rev());
+    } else if let Some(map) = node.map() {
+        stack.extend(map.iter().rev().map(|&(_, ref v)| v));
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(query: &str) -> Result<Query, Error> {
+        Query::parse(query)
+    }
+}
+
+// Appends a segment selecting the value of the map entry with the given string key, using the dot
+// notation if the key allows it.
+pub(crate) fn push_key(query: &mut String, key: &str) {
+    let is_name = !key.is_empty() &&
+                  key.chars().all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-');
+    if is_name {
+        query.push('.');
+        query.push_str(key);
+        return;
+    }
+
+    query.push_str("['");
+    for ch in key.chars() {
+        match ch {
+            '\'' => query.push_str("\\'"),
+            '\\' => query.push_str("\\\\"),
+            '\n' => query.push_str("\\n"),
+            '\r' => query.push_str("\\r"),
+            '\t' => query.push_str("\\t"),
+            ch => query.push(ch),
+        }
+    }
+    query.push_str("']");
+}
+
+struct Parser<'q> {
+    query: &'q str,
+    pos: usize,
+}
+
+impl<'q> Parser<'q> {
+    fn peek(&self) -> Option<char> {
+        self.query[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek();
+        if let Some(ch) = ch {
+            self.pos += ch.len_utf8();
+        }
+        ch
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        let pos = self.pos;
+        match self.bump() {
+            Some(ch) if ch == expected => Ok(()),
+            Some(ch) => Err(Error::UnexpectedChar(ch, pos)),
+            None => Err(Error::UnexpectedEnd),
+        }
+    }
+
+    fn unexpected(&self) -> Error {
+        match self.peek() {
+            Some(ch) => Error::UnexpectedChar(ch, self.pos),
+            None => Error::UnexpectedEnd,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(ch) = self.peek() {
+            if !ch.is_whitespace() {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn parse(mut self) -> Result<Query, Error> {
+        if self.peek() == Some('$') {
+            self.bump();
+        }
+
+        let mut segments = Vec::new();
+        while let Some(ch) = self.peek() {
+            let segment = match ch {
+                '.' => {
+                    self.bump();
+                    if self.peek() == Some('.') {
+                        self.bump();
+                        let selector = match self.peek() {
+                            Some('[') => self.parse_bracket()?,
+                            _ => self.parse_dot_selector()?,
+                        };
+                        Segment { descendants: true, selector: selector }
+                    } else {
+                        Segment { descendants: false, selector: self.parse_dot_selector()? }
+                    }
+                }
+                '[' => Segment { descendants: false, selector: self.parse_bracket()? },
+                ch => return Err(Error::UnexpectedChar(ch, self.pos)),
+            };
+            segments.push(segment);
+        }
+
+        Ok(Query { segments: segments })
+    }
+
+    fn parse_dot_selector(&mut self) -> Result<Selector, Error> {
+        if self.peek() == Some('*') {
+            self.bump();
+            Ok(Selector::Wildcard)
+        } else {
+            self.parse_name().map(Selector::Key)
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        while let Some(ch) = self.peek() {
+            if !(ch.is_alphanumeric() || ch == '_' || ch == '-') {
+                break;
+            }
+            self.bump();
+        }
+
+        if start == self.pos {
+            Err(self.unexpected())
+        } else {
+            Ok(self.query[start..self.pos].to_owned())
+        }
+    }
+
+    fn parse_bracket(&mut self) -> Result<Selector, Error> {
+        let start = self.pos;
+        self.expect('[')?;
+        self.skip_whitespace();
+
+        let selector = match self.peek() {
+            Some('*') => {
+                self.bump();
+                Selector::Wildcard
+            }
+            Some('\'') | Some('"') => Selector::Key(self.parse_string()?),
+            Some('?') => {
+                self.bump();
+                self.skip_whitespace();
+                if self.peek() == Some('(') {
+                    self.bump();
+                    let filter = self.parse_filter()?;
+                    self.skip_whitespace();
+                    self.expect(')')?;
+                    Selector::Filter(filter)
+                } else {
+                    Selector::Filter(self.parse_filter()?)
+                }
+            }
+            _ => {
+                let first = self.parse_optional_int()?;
+                self.skip_whitespace();
+                if self.peek() == Some(':') {
+                    self.bump();
+                    self.skip_whitespace();
+                    let end = self.parse_optional_int()?;
+                    self.skip_whitespace();
+                    let mut step = 1;
+                    if self.peek() == Some(':') {
+                        self.bump();
+                        self.skip_whitespace();
+                        step = self.parse_optional_int()?.unwrap_or(1);
+                        if step == 0 {
+                            return Err(Error::ZeroStep(start));
+                        }
+                    }
+                    Selector::Slice(first, end, step)
+                } else {
+                    match first {
+                        Some(idx) => Selector::Index(idx),
+                        None => return Err(self.unexpected()),
+                    }
+                }
+            }
+        };
+
+        self.skip_whitespace();
+        self.expect(']')?;
+
+        Ok(selector)
+    }
+
+    fn parse_optional_int(&mut self) -> Result<Option<i64>, Error> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.bump();
+        }
+        while let Some('0'..='9') = self.peek() {
+            self.bump();
+        }
+
+        if start == self.pos {
+            Ok(None)
+        } else {
+            self.query[start..self.pos].parse().map(Some).map_err(|_| Error::InvalidNumber(start))
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, Error> {
+        let quote = self.bump().unwrap();
+        let mut res = String::new();
+        loop {
+            let pos = self.pos;
+            match self.bump() {
+                Some(ch) if ch == quote => return Ok(res),
+                Some('\\') => {
+                    let ch = match self.bump() {
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('\'') => '\'',
+                        Some('"') => '"',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some(..) => return Err(Error::InvalidEscape(pos)),
+                        None => return Err(Error::UnexpectedEnd),
+                    };
+                    res.push(ch);
+                }
+                Some(ch) => res.push(ch),
+                None => return Err(Error::UnexpectedEnd),
+            }
+        }
+    }
+
+    fn parse_filter(&mut self) -> Result<Filter, Error> {
+        self.skip_whitespace();
+        self.expect('@')?;
+
+        let mut path = Vec::new();
+        loop {
+            match self.peek() {
+                Some('.') => {
+                    self.bump();
+                    path.push(Selector::Key(self.parse_name()?));
+                }
+                Some('[') => {
+                    let pos = self.pos;
+                    match self.parse_bracket()? {
+                        selector @ Selector::Key(..) |
+                        selector @ Selector::Index(..) => path.push(selector),
+                        _ => return Err(Error::UnexpectedChar('[', pos)),
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        self.skip_whitespace();
+        let op = match self.peek() {
+            Some('=') => {
+                self.bump();
+                self.expect('=')?;
+                Op::Eq
+            }
+            Some('!') => {
+                self.bump();
+                self.expect('=')?;
+                Op::Ne
+            }
+            Some('<') => {
+                self.bump();
+                if self.peek() == Some('=') {
+                    self.bump();
+                    Op::Le
+                } else {
+                    Op::Lt
+                }
+            }
+            Some('>') => {
+                self.bump();
+                if self.peek() == Some('=') {
+                    self.bump();
+                    Op::Ge
+                } else {
+                    Op::Gt
+                }
+            }
+            _ => return Ok(Filter { path: path, cmp: None }),
+        };
+
+        self.skip_whitespace();
+        let literal = self.parse_literal()?;
+
+        Ok(Filter { path: path, cmp: Some((op, literal)) })
+    }
+
+    fn parse_literal(&mut self) -> Result<Literal, Error> {
+        match self.peek() {
+            Some('\'') | Some('"') => self.parse_string().map(Literal::String),
+            Some('-') | Some('0'..='9') => self.parse_number().map(Literal::Number),
+            Some(ch) if ch.is_alphabetic() => {
+                let start = self.pos;
+                match &self.parse_name()?[..] {
+                    "null" | "nil" => Ok(Literal::Nil),
+                    "true" => Ok(Literal::Boolean(true)),
+                    "false" => Ok(Literal::Boolean(false)),
+                    _ => Err(Error::UnexpectedChar(ch, start)),
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Number, Error> {
+        let start = self.pos;
+        let mut float = false;
+        while let Some(ch) = self.peek() {
+            match ch {
+                '0'..='9' | '-' | '+' => {}
+                '.' | 'e' | 'E' => float = true,
+                _ => break,
+            }
+            self.bump();
+        }
+
+        let num = &self.query[start..self.pos];
+        let res = if float {
+            num.parse().map(Number::F64).ok()
+        } else {
+            num.parse()
+                .map(Number::U64)
+                .or_else(|_| num.parse().map(Number::I64))
+                .ok()
+        };
+
+        res.ok_or(Error::InvalidNumber(start))
+    }
+}
+
+impl Value {
+    /// Parses the query and evaluates it against the `Value`, returning references to all
+    /// matching nodes.
+    ///
+    /// See the [`query`](query/index.html) module for the syntax. Use `Query` directly to
+    /// evaluate the same query multiple times.
+    pub fn query(&self, query: &str) -> Result<Vec<&Value>, Error> {
+        Query::parse(query).map(|query| query.select(self))
+    }
+}
+
+impl<'a> ValueRef<'a> {
+    /// Parses the query and evaluates it against the `ValueRef`, returning references to all
+    /// matching nodes.
+    ///
+    /// See the [`query`](query/index.html) module for the syntax.
+    pub fn query(&self, query: &str) -> Result<Vec<&ValueRef<'a>>, Error> {
+        Query::parse(query).map(|query| query.select_ref(self))
+    }
+}
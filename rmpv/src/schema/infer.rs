@@ -0,0 +1,601 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+
+use Value;
+use query;
+
+use super::{normalize_int, number};
+
+/// The default maximum number of distinct strings, which are reported as an enumeration.
+pub const DEFAULT_MAX_ENUM_VALUES: usize = 16;
+
+/// Infers a schema from sample values.
+///
+/// Each node of the schema corresponds to a path, e.g. `$.orders[*].sku`, and collects the
+/// statistics of values found there: observed types, how often map keys are present, ranges of
+/// numbers and lengths and distinct strings, if there are only a few of them. Samples can be added
+/// one by one and inferences built separately can be merged. Numeric ranges are omitted at nodes
+/// containing NaNs, so that every sample conforms to the inferred schema.
+///
+/// The result is available as a schema `Value` described in the `schema` module, which is
+/// annotated with the number of values at each node and the mean length, and as a text report
+/// using `Display`, which also suggests a Rust type for each path to ease writing structs for
+/// `rmp_serde`.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate rmpv;
+///
+/// use rmpv::schema::Inference;
+///
+/// # fn main() {
+/// let mut inference = Inference::new();
+/// inference.add(&msgpack!({"id": 1, "status": "ok"}));
+/// inference.add(&msgpack!({"id": 300, "status": "ok", "note": "late"}));
+///
+/// let schema = inference.to_value();
+///
+/// assert_eq!(msgpack!("map"), schema["type"]);
+/// assert_eq!(msgpack!(["id", "status"]), schema["required"]);
+/// assert_eq!(msgpack!(300), schema["properties"]["id"]["max"]);
+/// assert_eq!(msgpack!(["ok"]), schema["properties"]["status"]["enum"]);
+///
+/// assert!(inference.to_string().contains("$.id: int 1..=300 -> u16"));
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inference {
+    root: Node,
+    max_enum: usize,
+}
+
+impl Inference {
+    /// Creates an empty inference.
+    pub fn new() -> Inference {
+        Inference {
+            root: Node::default(),
+            max_enum: DEFAULT_MAX_ENUM_VALUES,
+        }
+    }
+
+    /// Sets the maximum number of distinct strings at a path, which are reported as an
+    /// enumeration. Strings are reported as an enumeration only if some of them repeat.
+    ///
+    /// Only affects values added afterwards.
+    pub fn set_max_enum_values(&mut self, max: usize) {
+        self.max_enum = max;
+    }
+
+    /// Returns the number of samples.
+    pub fn samples(&self) -> u64 {
+        self.root.count
+    }
+
+    /// Adds a sample.
+    pub fn add(&mut self, val: &Value) {
+        self.root.add(val, self.max_enum);
+    }
+
+    /// Merges another inference into this one as if all its samples were added to this one.
+    pub fn merge(&mut self, other: &Inference) {
+        self.root.merge(&other.root, self.max_enum);
+    }
+
+    /// Returns the inferred schema.
+    ///
+    /// Since only observed values are known, the schema is as strict as the samples allow: map
+    /// keys present in every sample are required and ranges are the observed ones.
+    pub fn to_value(&self) -> Value {
+        self.root.to_value()
+    }
+}
+
+impl Default for Inference {
+    fn default() -> Inference {
+        Inference::new()
+    }
+}
+
+impl Display for Inference {
+    /// Formats the inferred schema as a text report with a line per path.
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        self.root.report(fmt, &mut "$".to_owned(), None)
+    }
+}
+
+/// Statistics of lengths.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Len {
+    count: u64,
+    min: u64,
+    max: u64,
+    total: u64,
+}
+
+impl Len {
+    fn add(&mut self, len: usize) {
+        let len = len as u64;
+        self.merge(&Len {
+            count: 1,
+            min: len,
+            max: len,
+            total: len,
+        });
+    }
+
+    fn merge(&mut self, other: &Len) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+    }
+}
+
+/// Statistics of values at a single path.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Node {
+    count: u64,
+    nils: u64,
+    bools: u64,
+    ints: u64,
+    // Integer bounds, where non-negative integers are always `U64`.
+    int_bounds: Option<(Value, Value)>,
+    f32s: u64,
+    f64s: u64,
+    // Float bounds ignoring NaNs.
+    float_min: Option<f64>,
+    float_max: Option<f64>,
+    // Whether a NaN was seen, in which case no bounds are inferred, since NaNs never satisfy them.
+    float_nan: bool,
+    strs: Len,
+    // Distinct strings, unless there were too many of them.
+    str_values: BTreeSet<String>,
+    str_overflow: bool,
+    bins: Len,
+    arrays: Len,
+    items: Option<Box<Node>>,
+    maps: Len,
+    properties: BTreeMap<String, Node>,
+    // Values of entries with non-string keys.
+    additional: Option<Box<Node>>,
+    exts: Len,
+    ext_types: BTreeSet<i8>,
+}
+
+impl Node {
+    fn add(&mut self, val: &Value, max_enum: usize) {
+        self.count += 1;
+
+        match *val {
+            Value::Nil => self.nils += 1,
+            Value::Boolean(..) => self.bools += 1,
+            Value::U64(..) | Value::I64(..) => {
+                self.ints += 1;
+                let val = normalize_int(val);
+                self.merge_ints(&Some((val.clone(), val)));
+            }
+            Value::F32(val) => {
+                self.f32s += 1;
+                self.float_nan |= val.is_nan();
+                self.merge_floats(Some(val as f64), Some(val as f64));
+            }
+            Value::F64(val) => {
+                self.f64s += 1;
+                self.float_nan |= val.is_nan();
+                self.merge_floats(Some(val), Some(val));
+            }
+            Value::String(ref val) => {
+                self.strs.add(val.as_bytes().len());
+                match val.as_str() {
+                    Some(val) if !self.str_overflow => {
+                        self.str_values.insert(val.to_owned());
+                        self.check_enum(max_enum);
+                    }
+                    Some(..) => {}
+                    // Strings containing invalid UTF-8 can't be enumerated.
+                    None => {
+                        self.str_values.clear();
+                        self.str_overflow = true;
+                    }
+                }
+            }
+            Value::Binary(ref val) => self.bins.add(val.len()),
+            Value::Array(ref vals) => {
+                self.arrays.add(vals.len());
+                if !vals.is_empty() {
+                    let items = self.items.get_or_insert_with(Box::default);
+                    for val in vals {
+                        items.add(val, max_enum);
+                    }
+                }
+            }
+            Value::Map(ref entries) => {
+                self.maps.add(entries.len());
+                for &(ref key, ref val) in entries {
+                    match key.as_str() {
+                        Some(key) => {
+                            self.properties
+                                .entry(key.to_owned())
+                                .or_default()
+                                .add(val, max_enum);
+                        }
+                        None => {
+                            self.additional
+                                .get_or_insert_with(Box::default)
+                                .add(val, max_enum);
+                        }
+                    }
+                }
+            }
+            Value::Ext(ty, ref data) => {
+                self.exts.add(data.len());
+                self.ext_types.insert(ty);
+            }
+        }
+    }
+
+    fn merge(&mut self, other: &Node, max_enum: usize) {
+        self.count += other.count;
+        self.nils += other.nils;
+        self.bools += other.bools;
+        self.ints += other.ints;
+        self.merge_ints(&other.int_bounds);
+        self.f32s += other.f32s;
+        self.f64s += other.f64s;
+        self.merge_floats(other.float_min, other.float_max);
+        self.float_nan |= other.float_nan;
+
+        self.strs.merge(&other.strs);
+        if other.str_overflow {
+            self.str_values.clear();
+            self.str_overflow = true;
+        } else if !self.str_overflow {
+            self.str_values.extend(other.str_values.iter().cloned());
+            self.check_enum(max_enum);
+        }
+
+        self.bins.merge(&other.bins);
+
+        self.arrays.merge(&other.arrays);
+        if let Some(ref items) = other.items {
+            self.items.get_or_insert_with(Box::default).merge(items, max_enum);
+        }
+
+        self.maps.merge(&other.maps);
+        for (key, node) in &other.properties {
+            self.properties
+                .entry(key.clone())
+                .or_default()
+                .merge(node, max_enum);
+        }
+        if let Some(ref additional) = other.additional {
+            self.additional.get_or_insert_with(Box::default).merge(additional, max_enum);
+        }
+
+        self.exts.merge(&other.exts);
+        self.ext_types.extend(other.ext_types.iter().cloned());
+    }
+
+    fn merge_ints(&mut self, bounds: &Option<(Value, Value)>) {
+        let (min, max) = match *bounds {
+            Some((ref min, ref max)) => (min, max),
+            None => return,
+        };
+
+        match self.int_bounds {
+            Some((ref mut cur_min, ref mut cur_max)) => {
+                if min < cur_min {
+                    *cur_min = min.clone();
+                }
+                if max > cur_max {
+                    *cur_max = max.clone();
+                }
+            }
+            None => self.int_bounds = Some((min.clone(), max.clone())),
+        }
+    }
+
+    fn merge_floats(&mut self, min: Option<f64>, max: Option<f64>) {
+        // Comparisons with NaN are false, so NaNs never become bounds.
+        if let Some(min) = min {
+            if self.float_min.map_or(!min.is_nan(), |cur| min < cur) {
+                self.float_min = Some(min);
+            }
+        }
+        if let Some(max) = max {
+            if self.float_max.map_or(!max.is_nan(), |cur| max > cur) {
+                self.float_max = Some(max);
+            }
+        }
+    }
+
+    fn check_enum(&mut self, max_enum: usize) {
+        if self.str_values.len() > max_enum {
+            self.str_values.clear();
+            self.str_overflow = true;
+        }
+    }
+
+    fn types(&self) -> Vec<&'static str> {
+        let counts = [
+            (self.nils, "nil"),
+            (self.bools, "bool"),
+            (self.ints, "int"),
+            (self.f32s + self.f64s, "float"),
+            (self.strs.count, "str"),
+            (self.bins.count, "bin"),
+            (self.arrays.count, "array"),
+            (self.maps.count, "map"),
+            (self.exts.count, "ext"),
+        ];
+
+        counts.iter().filter(|&&(count, _)| count > 0).map(|&(_, name)| name).collect()
+    }
+
+    // Returns the strings forming an enumeration, if the node contains only strings and nils and
+    // some strings repeat.
+    fn enum_values(&self) -> Option<&BTreeSet<String>> {
+        let only_strs = self.strs.count > 0 && self.nils + self.strs.count == self.count;
+        if only_strs && !self.str_overflow && self.strs.count > self.str_values.len() as u64 {
+            Some(&self.str_values)
+        } else {
+            None
+        }
+    }
+
+    // Returns the numeric bounds, taking both integers and floats into account. Integers and
+    // floats are compared the same way validation does.
+    fn bounds(&self) -> Option<(Value, Value)> {
+        if self.float_nan {
+            return None;
+        }
+
+        let ints = self.int_bounds.clone();
+        let floats = match (self.float_min, self.float_max) {
+            (Some(min), Some(max)) => Some((Value::F64(min), Value::F64(max))),
+            _ => None,
+        };
+
+        match (ints, floats) {
+            (Some((imin, imax)), Some((fmin, fmax))) => {
+                let cmp = |lhs: &Value, rhs: &Value| {
+                    number(lhs).unwrap().partial_cmp(number(rhs).unwrap())
+                };
+                let min = if cmp(&imin, &fmin) == Some(Ordering::Less) { imin } else { fmin };
+                let max = if cmp(&imax, &fmax) == Some(Ordering::Greater) { imax } else { fmax };
+                Some((min, max))
+            }
+            (ints, floats) => ints.or(floats),
+        }
+    }
+
+    fn lens(&self) -> Len {
+        let mut len = Len::default();
+        for other in &[self.strs, self.bins, self.arrays, self.maps, self.exts] {
+            len.merge(other);
+        }
+        len
+    }
+
+    fn required(&self) -> Vec<&str> {
+        self.properties
+            .iter()
+            .filter(|&(_, node)| node.count >= self.maps.count)
+            .map(|(key, _)| &key[..])
+            .collect()
+    }
+
+    fn to_value(&self) -> Value {
+        let mut schema = Vec::new();
+
+        let mut types: Vec<Value> = self.types().into_iter().map(Value::from).collect();
+        match types.len() {
+            0 => {}
+            1 => schema.push((Value::from("type"), types.remove(0))),
+            _ => schema.push((Value::from("type"), Value::Array(types))),
+        }
+        schema.push((Value::from("count"), Value::from(self.count)));
+
+        if let Some(values) = self.enum_values() {
+            let mut values: Vec<Value> = values.iter().map(|val| Value::from(&val[..])).collect();
+            if self.nils > 0 {
+                values.insert(0, Value::Nil);
+            }
+            schema.push((Value::from("enum"), Value::Array(values)));
+        }
+
+        if let Some((min, max)) = self.bounds() {
+            schema.push((Value::from("min"), min));
+            schema.push((Value::from("max"), max));
+        }
+
+        let lens = self.lens();
+        if lens.count > 0 {
+            schema.push((Value::from("min_len"), Value::from(lens.min)));
+            schema.push((Value::from("max_len"), Value::from(lens.max)));
+            schema.push((Value::from("mean_len"),
+                         Value::from(lens.total as f64 / lens.count as f64)));
+        }
+
+        if !self.ext_types.is_empty() {
+            let types = self.ext_types.iter().map(|&ty| Value::from(ty)).collect();
+            schema.push((Value::from("ext_types"), Value::Array(types)));
+        }
+
+        if let Some(ref items) = self.items {
+            schema.push((Value::from("items"), items.to_value()));
+        }
+
+        if !self.properties.is_empty() {
+            let properties = self.properties
+                .iter()
+                .map(|(key, node)| (Value::from(&key[..]), node.to_value()))
+                .collect();
+            schema.push((Value::from("properties"), Value::Map(properties)));
+
+            let required: Vec<Value> = self.required().into_iter().map(Value::from).collect();
+            if !required.is_empty() {
+                schema.push((Value::from("required"), Value::Array(required)));
+            }
+        }
+
+        if let Some(ref additional) = self.additional {
+            schema.push((Value::from("additional"), additional.to_value()));
+        }
+
+        Value::Map(schema)
+    }
+
+    fn report(&self, fmt: &mut Formatter, path: &mut String, parent: Option<u64>)
+        -> Result<(), fmt::Error>
+    {
+        let optional = parent.is_some_and(|parent| self.count < parent);
+
+        let mut types = Vec::new();
+        if self.nils > 0 {
+            types.push("nil".to_owned());
+        }
+        if self.bools > 0 {
+            types.push("bool".to_owned());
+        }
+        if let Some((ref min, ref max)) = self.int_bounds {
+            types.push(format!("int {}..={}", min, max));
+        }
+        if self.f32s + self.f64s > 0 {
+            match (self.float_min, self.float_max) {
+                (Some(min), Some(max)) => types.push(format!("float {:?}..={:?}", min, max)),
+                _ => types.push("float".to_owned()),
+            }
+        }
+        if self.strs.count > 0 {
+            types.push(format!("str len {}..={} (mean {:.1})",
+                               self.strs.min,
+                               self.strs.max,
+                               self.strs.total as f64 / self.strs.count as f64));
+        }
+        if self.bins.count > 0 {
+            types.push(format!("bin len {}..={}", self.bins.min, self.bins.max));
+        }
+        if self.arrays.count > 0 {
+            types.push(format!("array len {}..={}", self.arrays.min, self.arrays.max));
+        }
+        if self.maps.count > 0 {
+            types.push(format!("map len {}..={}", self.maps.min, self.maps.max));
+        }
+        if self.exts.count > 0 {
+            let ext_types: Vec<_> = self.ext_types.iter().map(|ty| ty.to_string()).collect();
+            types.push(format!("ext types {} len {}..={}",
+                               ext_types.join(", "),
+                               self.exts.min,
+                               self.exts.max));
+        }
+        write!(fmt, "{}: {}", path, types.join(" | "))?;
+
+        if let Some(values) = self.enum_values() {
+            let values: Vec<_> = values.iter()
+                .map(|val| Value::from(&val[..]).to_string())
+                .collect();
+            write!(fmt, "; one of {}", values.join(", "))?;
+        }
+        if let (true, Some(parent)) = (optional, parent) {
+            write!(fmt, "; optional ({} of {})", self.count, parent)?;
+        }
+        writeln!(fmt, " -> {}", self.rust_type(optional))?;
+
+        let len = path.len();
+        if let Some(ref items) = self.items {
+            path.push_str("[*]");
+            items.report(fmt, path, None)?;
+            path.truncate(len);
+        }
+        for (key, node) in &self.properties {
+            query::push_key(path, key);
+            node.report(fmt, path, Some(self.maps.count))?;
+            path.truncate(len);
+        }
+        if let Some(ref additional) = self.additional {
+            path.push_str("[*]");
+            additional.report(fmt, path, None)?;
+            path.truncate(len);
+        }
+
+        Ok(())
+    }
+
+    // Suggests a Rust type for values at this node.
+    fn rust_type(&self, optional: bool) -> String {
+        let types: Vec<_> = self.types().into_iter().filter(|&ty| ty != "nil").collect();
+
+        let ty = match &types[..] {
+            [] => "()".to_owned(),
+            ["bool"] => "bool".to_owned(),
+            ["int"] => {
+                match self.int_bounds {
+                    Some((ref min, ref max)) => int_type(min, max).to_owned(),
+                    None => "rmpv::Value".to_owned(),
+                }
+            }
+            ["float"] if self.f64s == 0 => "f32".to_owned(),
+            ["float"] | ["int", "float"] => "f64".to_owned(),
+            ["str"] => "String".to_owned(),
+            ["bin"] => "Vec<u8>".to_owned(),
+            ["array"] => {
+                match self.items {
+                    Some(ref items) => format!("Vec<{}>", items.rust_type(false)),
+                    None => "Vec<rmpv::Value>".to_owned(),
+                }
+            }
+            ["map"] if self.additional.is_none() && !self.properties.is_empty() => {
+                "struct".to_owned()
+            }
+            _ => "rmpv::Value".to_owned(),
+        };
+
+        if (self.nils > 0 && !types.is_empty() || optional) && ty != "rmpv::Value" {
+            format!("Option<{}>", ty)
+        } else {
+            ty
+        }
+    }
+}
+
+// Returns the smallest Rust integer type, which can hold both bounds.
+fn int_type(min: &Value, max: &Value) -> &'static str {
+    if let (Some(..), Some(max)) = (min.as_u64(), max.as_u64()) {
+        return if max <= u8::max_value() as u64 {
+            "u8"
+        } else if max <= u16::max_value() as u64 {
+            "u16"
+        } else if max <= u32::max_value() as u64 {
+            "u32"
+        } else {
+            "u64"
+        };
+    }
+
+    match (min.as_i64(), max.as_i64()) {
+        (Some(min), Some(max)) => {
+            if min >= i8::min_value() as i64 && max <= i8::max_value() as i64 {
+                "i8"
+            } else if min >= i16::min_value() as i64 && max <= i16::max_value() as i64 {
+                "i16"
+            } else if min >= i32::min_value() as i64 && max <= i32::max_value() as i64 {
+                "i32"
+            } else {
+                "i64"
+            }
+        }
+        _ => "rmpv::Value",
+    }
+}
@@ -0,0 +1,54 @@
+//! Schemas describing the structure of `Value` documents.
+//!
+//! A schema is itself a `Value`: a map with the following optional keys, each of which constrains
+//! values at its node.
+//!
+//! - `"type"` is a type name or an array of them: `"nil"`, `"bool"`, `"int"`, `"float"`, `"str"`,
+//!   `"bin"`, `"array"`, `"map"` or `"ext"`.
+//! - `"enum"` is an array of allowed values. Integers are compared by their numeric values.
+//! - `"min"` and `"max"` are inclusive bounds of integers and floats. NaNs never satisfy them.
+//! - `"min_len"` and `"max_len"` are inclusive bounds of the length of strings, binaries and
+//!   extension data in bytes, and of the number of array elements and map entries.
+//! - `"pattern"` is a glob pattern, which strings must match as a whole: `*` matches any sequence
+//!   of characters, `?` matches any single character and `\\` escapes the following character.
+//! - `"ext_types"` is an array of allowed extension types.
+//! - `"items"` is the schema of all array elements.
+//! - `"properties"` is a map from string keys to schemas of values of the corresponding map
+//!   entries.
+//! - `"required"` is an array of string keys, which must be present in maps.
+//! - `"additional"` is the schema of values of map entries with keys not listed in
+//!   `"properties"`, or a boolean, which allows (the default) or denies such entries.
+//!
+//! Other keys are ignored, so schemas may carry annotations, e.g. `"count"`. Types are matched
+//! exactly, e.g. an integer is not a float, while other keys apply only to values of the
+//! corresponding types, e.g. `"min_len"` does not constrain integers.
+//!
+//! `Inference` builds schemas from sample values and `Schema` validates values against them.
+
+use Value;
+use query::Number;
+
+pub use self::infer::{Inference, DEFAULT_MAX_ENUM_VALUES};
+pub use self::validate::{Error, Schema, Violation, ViolationKind};
+
+mod infer;
+mod validate;
+
+// Converts non-negative integers into `U64`, so equal integers are represented equally.
+fn normalize_int(val: &Value) -> Value {
+    match *val {
+        Value::I64(val) if val >= 0 => Value::U64(val as u64),
+        ref val => val.clone(),
+    }
+}
+
+// Returns the value as a number, if it is an integer or a float.
+fn number(val: &Value) -> Option<Number> {
+    match *val {
+        Value::U64(val) => Some(Number::U64(val)),
+        Value::I64(val) => Some(Number::I64(val)),
+        Value::F32(val) => Some(Number::F64(val as f64)),
+        Value::F64(val) => Some(Number::F64(val)),
+        _ => None,
+    }
+}
@@ -0,0 +1,631 @@
+use std::cmp::Ordering;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+
+use {Utf8StringRef, Value, ValueRef};
+use query::{self, Number};
+
+use super::{normalize_int, number};
+
+/// Names of types in the order of the `Value` variants.
+const TYPES: [&str; 9] = ["nil", "bool", "int", "float", "str", "bin", "array", "map", "ext"];
+
+/// This type represents all possible errors that can occur when building a `Schema` from a value.
+///
+/// Each error contains the query path of the offending node of the schema value, e.g.
+/// `$.properties.id.min`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The schema at the given path is not a map.
+    NotAMap(String),
+    /// The value at the given path is invalid for its key, e.g. `"min"` is not a number.
+    InvalidKeyword(String),
+    /// The type name at the given path is unknown.
+    UnknownType(String, String),
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::NotAMap(..) => "schema is not a map",
+            Error::InvalidKeyword(..) => "invalid schema keyword value",
+            Error::UnknownType(..) => "unknown type in schema",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Error::NotAMap(ref path) => write!(fmt, "schema at {} is not a map", path),
+            Error::InvalidKeyword(ref path) => write!(fmt, "invalid schema value at {}", path),
+            Error::UnknownType(ref path, ref name) => {
+                write!(fmt, "unknown type {:?} at {}", name, path)
+            }
+        }
+    }
+}
+
+/// The kind of a schema violation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ViolationKind {
+    /// The value has none of the allowed types.
+    Type {
+        /// The allowed types.
+        expected: Vec<&'static str>,
+        /// The type of the value.
+        found: &'static str,
+    },
+    /// The value is not one of the enumerated values.
+    Enum,
+    /// The number is less than the given minimum or is NaN.
+    Min(Value),
+    /// The number is greater than the given maximum or is NaN.
+    Max(Value),
+    /// The length is less than the given minimum.
+    MinLen(u64),
+    /// The length is greater than the given maximum.
+    MaxLen(u64),
+    /// The string does not match the given pattern.
+    Pattern(String),
+    /// The extension type is not allowed.
+    ExtType(i8),
+    /// The map lacks the given required key.
+    MissingKey(String),
+    /// The map entry has a key not listed in the properties, while additional keys are denied.
+    AdditionalKey,
+}
+
+impl Display for ViolationKind {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ViolationKind::Type { ref expected, found } => {
+                write!(fmt, "expected {}, found {}", expected.join(" or "), found)
+            }
+            ViolationKind::Enum => write!(fmt, "not one of the allowed values"),
+            ViolationKind::Min(ref min) => write!(fmt, "less than the minimum {}", min),
+            ViolationKind::Max(ref max) => write!(fmt, "greater than the maximum {}", max),
+            ViolationKind::MinLen(min) => write!(fmt, "shorter than the minimum length {}", min),
+            ViolationKind::MaxLen(max) => write!(fmt, "longer than the maximum length {}", max),
+            ViolationKind::Pattern(ref pattern) => {
+                write!(fmt, "does not match the pattern {:?}", pattern)
+            }
+            ViolationKind::ExtType(ty) => write!(fmt, "extension type {} is not allowed", ty),
+            ViolationKind::MissingKey(ref key) => write!(fmt, "missing required key {:?}", key),
+            ViolationKind::AdditionalKey => write!(fmt, "key is not allowed"),
+        }
+    }
+}
+
+/// A value, which does not conform to a schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// The kind of the violation.
+    pub kind: ViolationKind,
+    /// Query path to the value, e.g. `$.items[3].id`. For missing keys it points to the map.
+    ///
+    /// Values of map entries with keys, which are neither strings nor integers, are denoted by
+    /// `[*]`.
+    pub path: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "{}: {}", self.path, self.kind)
+    }
+}
+
+/// A schema built from a `Value`, which can validate multiple values.
+///
+/// See the [module documentation](index.html) for the schema format.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate rmpv;
+///
+/// use rmpv::schema::{Schema, ViolationKind};
+///
+/// # fn main() {
+/// let schema = Schema::from_value(&msgpack!({
+///     "type": "map",
+///     "properties": {
+///         "id": {"type": "int", "min": 1},
+///         "tags": {"type": "array", "items": {"type": "str", "max_len": 8}},
+///     },
+///     "required": ["id"],
+///     "additional": false,
+/// })).unwrap();
+///
+/// assert_eq!(Ok(()), schema.validate(&msgpack!({"id": 1, "tags": ["a"]})));
+///
+/// let violations = schema.validate(&msgpack!({"id": 0, "tags": ["too long a tag"]}))
+///     .unwrap_err();
+/// assert_eq!(2, violations.len());
+/// assert_eq!("$.id: less than the minimum 1", violations[0].to_string());
+/// assert_eq!("$.tags[0]", violations[1].path);
+/// assert_eq!(ViolationKind::MaxLen(8), violations[1].kind);
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schema {
+    root: Rule,
+}
+
+impl Schema {
+    /// Builds a schema from the given value.
+    pub fn from_value(schema: &Value) -> Result<Schema, Error> {
+        let root = Rule::from_value(schema, &mut "$".to_owned())?;
+        Ok(Schema { root: root })
+    }
+
+    /// Validates the given value, returning all violations in document order.
+    pub fn validate(&self, val: &Value) -> Result<(), Vec<Violation>> {
+        self.check(val)
+    }
+
+    /// Validates the given borrowed value, returning all violations in document order.
+    pub fn validate_ref(&self, val: &ValueRef) -> Result<(), Vec<Violation>> {
+        self.check(val)
+    }
+
+    fn check<V: Node>(&self, val: &V) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        self.root.check(val, &mut "$".to_owned(), &mut violations);
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+// A view of a node, which exposes what validation needs.
+enum Kind<'s, V: 's> {
+    Nil,
+    Boolean,
+    Int(Number),
+    Float(f64),
+    Str(Utf8StringRef<'s>),
+    Bin(&'s [u8]),
+    Array(&'s [V]),
+    Map(&'s [(V, V)]),
+    Ext(i8, &'s [u8]),
+}
+
+impl<'s, V> Kind<'s, V> {
+    fn name(&self) -> &'static str {
+        let idx = match *self {
+            Kind::Nil => 0,
+            Kind::Boolean => 1,
+            Kind::Int(..) => 2,
+            Kind::Float(..) => 3,
+            Kind::Str(..) => 4,
+            Kind::Bin(..) => 5,
+            Kind::Array(..) => 6,
+            Kind::Map(..) => 7,
+            Kind::Ext(..) => 8,
+        };
+        TYPES[idx]
+    }
+}
+
+// Abstracts over `Value` and `ValueRef` for validation.
+trait Node: Sized {
+    fn kind<'s>(&'s self) -> Kind<'s, Self>;
+    fn to_value(&self) -> Value;
+}
+
+impl Node for Value {
+    fn kind<'s>(&'s self) -> Kind<'s, Value> {
+        match *self {
+            Value::Nil => Kind::Nil,
+            Value::Boolean(..) => Kind::Boolean,
+            Value::U64(val) => Kind::Int(Number::U64(val)),
+            Value::I64(val) => Kind::Int(Number::I64(val)),
+            Value::F32(val) => Kind::Float(val as f64),
+            Value::F64(val) => Kind::Float(val),
+            Value::String(ref val) => Kind::Str(val.as_ref()),
+            Value::Binary(ref val) => Kind::Bin(val),
+            Value::Array(ref vals) => Kind::Array(vals),
+            Value::Map(ref entries) => Kind::Map(entries),
+            Value::Ext(ty, ref data) => Kind::Ext(ty, data),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl<'a> Node for ValueRef<'a> {
+    fn kind<'s>(&'s self) -> Kind<'s, ValueRef<'a>> {
+        match *self {
+            ValueRef::Nil => Kind::Nil,
+            ValueRef::Boolean(..) => Kind::Boolean,
+            ValueRef::U64(val) => Kind::Int(Number::U64(val)),
+            ValueRef::I64(val) => Kind::Int(Number::I64(val)),
+            ValueRef::F32(val) => Kind::Float(val as f64),
+            ValueRef::F64(val) => Kind::Float(val),
+            ValueRef::String(val) => Kind::Str(val),
+            ValueRef::Binary(val) => Kind::Bin(val),
+            ValueRef::Array(ref vals) => Kind::Array(vals),
+            ValueRef::Map(ref entries) => Kind::Map(entries),
+            ValueRef::Ext(ty, data) => Kind::Ext(ty, data),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        self.to_owned()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Additional {
+    Any,
+    Deny,
+    Schema(Box<Rule>),
+}
+
+/// A compiled schema node.
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    types: Option<Vec<&'static str>>,
+    values: Option<Vec<Value>>,
+    min: Option<Number>,
+    max: Option<Number>,
+    min_len: Option<u64>,
+    max_len: Option<u64>,
+    pattern: Option<Pattern>,
+    ext_types: Option<Vec<i8>>,
+    items: Option<Box<Rule>>,
+    properties: Vec<(String, Rule)>,
+    required: Vec<String>,
+    additional: Additional,
+}
+
+impl Rule {
+    fn from_value(schema: &Value, path: &mut String) -> Result<Rule, Error> {
+        let entries = match schema.as_map() {
+            Some(entries) => entries,
+            None => return Err(Error::NotAMap(path.clone())),
+        };
+
+        let mut rule = Rule {
+            types: None,
+            values: None,
+            min: None,
+            max: None,
+            min_len: None,
+            max_len: None,
+            pattern: None,
+            ext_types: None,
+            items: None,
+            properties: Vec::new(),
+            required: Vec::new(),
+            additional: Additional::Any,
+        };
+
+        let len = path.len();
+        for &(ref key, ref val) in entries {
+            let key = match key.as_str() {
+                Some(key) => key,
+                None => continue,
+            };
+            query::push_key(path, key);
+            rule.set(key, val, path)?;
+            path.truncate(len);
+        }
+
+        Ok(rule)
+    }
+
+    // Sets the given keyword, where the path points to its value.
+    fn set(&mut self, key: &str, val: &Value, path: &mut String) -> Result<(), Error> {
+        match key {
+            "type" => {
+                let names = match *val {
+                    Value::Array(ref names) => &names[..],
+                    ref name => ::std::slice::from_ref(name),
+                };
+                let mut types = Vec::new();
+                for name in names {
+                    let name = name.as_str().ok_or_else(|| invalid(path))?;
+                    match TYPES.iter().find(|&&ty| ty == name) {
+                        Some(ty) => types.push(*ty),
+                        None => return Err(Error::UnknownType(path.clone(), name.to_owned())),
+                    }
+                }
+                self.types = Some(types);
+            }
+            "enum" => {
+                let values = val.as_array().ok_or_else(|| invalid(path))?;
+                self.values = Some(values.iter().map(normalize_int).collect());
+            }
+            "min" => self.min = Some(number(val).ok_or_else(|| invalid(path))?),
+            "max" => self.max = Some(number(val).ok_or_else(|| invalid(path))?),
+            "min_len" => self.min_len = Some(val.as_u64().ok_or_else(|| invalid(path))?),
+            "max_len" => self.max_len = Some(val.as_u64().ok_or_else(|| invalid(path))?),
+            "pattern" => {
+                let pattern = val.as_str().and_then(Pattern::parse).ok_or_else(|| invalid(path))?;
+                self.pattern = Some(pattern);
+            }
+            "ext_types" => {
+                let mut types = Vec::new();
+                for ty in val.as_array().ok_or_else(|| invalid(path))? {
+                    let ty = ty.as_i64()
+                        .filter(|&ty| ty >= i8::min_value() as i64 && ty <= i8::max_value() as i64)
+                        .ok_or_else(|| invalid(path))?;
+                    types.push(ty as i8);
+                }
+                self.ext_types = Some(types);
+            }
+            "items" => self.items = Some(Box::new(Rule::from_value(val, path)?)),
+            "properties" => {
+                let len = path.len();
+                for &(ref key, ref val) in val.as_map().ok_or_else(|| invalid(path))? {
+                    let key = key.as_str().ok_or_else(|| invalid(path))?;
+                    query::push_key(path, key);
+                    self.properties.push((key.to_owned(), Rule::from_value(val, path)?));
+                    path.truncate(len);
+                }
+            }
+            "required" => {
+                for key in val.as_array().ok_or_else(|| invalid(path))? {
+                    self.required.push(key.as_str().ok_or_else(|| invalid(path))?.to_owned());
+                }
+            }
+            "additional" => {
+                self.additional = match *val {
+                    Value::Boolean(true) => Additional::Any,
+                    Value::Boolean(false) => Additional::Deny,
+                    ref val => Additional::Schema(Box::new(Rule::from_value(val, path)?)),
+                };
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn check<V: Node>(&self, val: &V, path: &mut String, violations: &mut Vec<Violation>) {
+        let kind = val.kind();
+        if let Some(ref types) = self.types {
+            if !types.contains(&kind.name()) {
+                let kind = ViolationKind::Type {
+                    expected: types.clone(),
+                    found: kind.name(),
+                };
+                violate(violations, kind, path);
+                return;
+            }
+        }
+
+        if let Some(ref values) = self.values {
+            if !values.contains(&normalize_int(&val.to_value())) {
+                violate(violations, ViolationKind::Enum, path);
+            }
+        }
+
+        match kind {
+            Kind::Nil | Kind::Boolean => {}
+            Kind::Int(num) => self.check_bounds(num, path, violations),
+            Kind::Float(num) => self.check_bounds(Number::F64(num), path, violations),
+            Kind::Str(val) => {
+                self.check_len(val.as_bytes().len(), path, violations);
+                if let Some(ref pattern) = self.pattern {
+                    // Strings containing invalid UTF-8 never match a pattern.
+                    let matched = match val.as_str() {
+                        Some(val) => pattern.matches(val),
+                        None => false,
+                    };
+                    if !matched {
+                        let kind = ViolationKind::Pattern(pattern.source.clone());
+                        violate(violations, kind, path);
+                    }
+                }
+            }
+            Kind::Bin(val) => self.check_len(val.len(), path, violations),
+            Kind::Array(vals) => {
+                self.check_len(vals.len(), path, violations);
+                if let Some(ref items) = self.items {
+                    let len = path.len();
+                    for (idx, val) in vals.iter().enumerate() {
+                        path.push_str(&format!("[{}]", idx));
+                        items.check(val, path, violations);
+                        path.truncate(len);
+                    }
+                }
+            }
+            Kind::Map(entries) => {
+                self.check_len(entries.len(), path, violations);
+                for key in &self.required {
+                    let present = entries.iter().any(|&(ref k, _)| match k.kind() {
+                        Kind::Str(k) => k.as_str() == Some(key),
+                        _ => false,
+                    });
+                    if !present {
+                        violate(violations, ViolationKind::MissingKey(key.clone()), path);
+                    }
+                }
+
+                let len = path.len();
+                for &(ref key, ref val) in entries {
+                    let key = key.kind();
+                    push_entry(path, &key);
+
+                    let property = match key {
+                        Kind::Str(key) => {
+                            self.properties.iter().find(|&&(ref k, _)| key.as_str() == Some(k))
+                        }
+                        _ => None,
+                    };
+                    match (property, &self.additional) {
+                        (Some(&(_, ref rule)), _) => rule.check(val, path, violations),
+                        (None, &Additional::Any) => {}
+                        (None, &Additional::Deny) => {
+                            violate(violations, ViolationKind::AdditionalKey, path)
+                        }
+                        (None, &Additional::Schema(ref rule)) => {
+                            rule.check(val, path, violations)
+                        }
+                    }
+                    path.truncate(len);
+                }
+            }
+            Kind::Ext(ty, data) => {
+                self.check_len(data.len(), path, violations);
+                if let Some(ref types) = self.ext_types {
+                    if !types.contains(&ty) {
+                        violate(violations, ViolationKind::ExtType(ty), path);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_bounds(&self, num: Number, path: &str, violations: &mut Vec<Violation>) {
+        // NaNs are incomparable, so they never satisfy bounds.
+        if let Some(min) = self.min {
+            match num.partial_cmp(min) {
+                Some(Ordering::Less) | None => {
+                    violate(violations, ViolationKind::Min(number_value(min)), path);
+                }
+                _ => {}
+            }
+        }
+        if let Some(max) = self.max {
+            match num.partial_cmp(max) {
+                Some(Ordering::Greater) | None => {
+                    violate(violations, ViolationKind::Max(number_value(max)), path);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_len(&self, len: usize, path: &str, violations: &mut Vec<Violation>) {
+        let len = len as u64;
+        if let Some(min) = self.min_len {
+            if len < min {
+                violate(violations, ViolationKind::MinLen(min), path);
+            }
+        }
+        if let Some(max) = self.max_len {
+            if len > max {
+                violate(violations, ViolationKind::MaxLen(max), path);
+            }
+        }
+    }
+}
+
+fn violate(violations: &mut Vec<Violation>, kind: ViolationKind, path: &str) {
+    violations.push(Violation {
+        kind: kind,
+        path: path.to_owned(),
+    });
+}
+
+// Appends a segment selecting the value of the map entry with the given key.
+fn push_entry<V>(path: &mut String, key: &Kind<V>) {
+    match *key {
+        Kind::Str(key) => match key.as_str() {
+            Some(key) => query::push_key(path, key),
+            None => query::push_key(path, &key.to_string()),
+        },
+        Kind::Int(Number::U64(key)) => path.push_str(&format!("[{}]", key)),
+        Kind::Int(Number::I64(key)) => path.push_str(&format!("[{}]", key)),
+        _ => path.push_str("[*]"),
+    }
+}
+
+fn invalid(path: &str) -> Error {
+    Error::InvalidKeyword(path.to_owned())
+}
+
+fn number_value(num: Number) -> Value {
+    match num {
+        Number::U64(val) => Value::U64(val),
+        Number::I64(val) => Value::I64(val),
+        Number::F64(val) => Value::F64(val),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Char(char),
+    // Matches any single character.
+    One,
+    // Matches any sequence of characters.
+    Any,
+}
+
+/// A glob pattern matching whole strings.
+#[derive(Clone, Debug, PartialEq)]
+struct Pattern {
+    source: String,
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    // Returns `None` if the pattern ends with an unfinished escape sequence.
+    fn parse(source: &str) -> Option<Pattern> {
+        let mut tokens = Vec::new();
+        let mut chars = source.chars();
+        while let Some(ch) = chars.next() {
+            tokens.push(match ch {
+                '*' => Token::Any,
+                '?' => Token::One,
+                '\\' => Token::Char(chars.next()?),
+                ch => Token::Char(ch),
+            });
+        }
+
+        Some(Pattern {
+            source: source.to_owned(),
+            tokens: tokens,
+        })
+    }
+
+    fn matches(&self, val: &str) -> bool {
+        let chars: Vec<char> = val.chars().collect();
+        let (mut tok, mut pos) = (0, 0);
+        // The position after the last `*` and the input position it is matched up to.
+        let mut backtrack = None;
+
+        while pos < chars.len() {
+            match self.tokens.get(tok) {
+                Some(&Token::Any) => {
+                    tok += 1;
+                    backtrack = Some((tok, pos));
+                    continue;
+                }
+                Some(&Token::One) => {
+                    tok += 1;
+                    pos += 1;
+                    continue;
+                }
+                Some(&Token::Char(ch)) if ch == chars[pos] => {
+                    tok += 1;
+                    pos += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // Let the last `*` consume one more character.
+            match backtrack {
+                Some((star_tok, star_pos)) => {
+                    tok = star_tok;
+                    pos = star_pos + 1;
+                    backtrack = Some((star_tok, pos));
+                }
+                None => return false,
+            }
+        }
+
+        self.tokens[tok..].iter().all(|&token| token == Token::Any)
+    }
+}
@@ -0,0 +1,648 @@
+//! Diagnostic text notation for `Value` and `ValueRef`.
+//!
+//! Values are displayed in a JSON-like notation, which can be parsed back into an equal `Value`
+//! using `FromStr`:
+//!
+//! - `nil`, `true` and `false`;
+//! - integers are written as is, while non-negative numbers stored as `I64` are annotated as
+//!   `i64(42)`;
+//! - 64-bit floats always contain a decimal point or an exponent, e.g. `1.0` or `1e300`, while
+//!   32-bit floats are annotated as `f32(1.5)`. Special values are written as `NaN`, `Infinity`
+//!   and `-Infinity`;
+//! - strings are double-quoted with JSON escape sequences, while bytes of strings, which are not
+//!   valid UTF-8, are escaped as `\xff`;
+//! - binaries are written in hex as `h'00ff'`, base64 notation `b64'AP8='` is accepted as well;
+//! - arrays are written as `[1, 2]` and maps as `{"key": "value"}`, where keys may be of any type;
+//! - extensions are written as `ext(1, h'00ff')`.
+//!
+//! The alternate flag, i.e. `{:#}`, enables pretty printing with two-space indentation.
+//!
+//! Parsing rejects arrays and maps nested deeper than 512 levels, which protects the stack from
+//! overflowing on malicious input.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate rmpv;
+//!
+//! use rmpv::Value;
+//!
+//! # fn main() {
+//! let val = msgpack!({"id": 42, "data": bin([0, 255]), "ratio": 0.5});
+//!
+//! assert_eq!(r#"{"id": 42, "data": h'00ff', "ratio": 0.5}"#, format!("{}", val));
+//! assert_eq!(val, format!("{:#}", val).parse::<Value>().unwrap());
+//! # }
+//! ```
+
+use std::error;
+use std::fmt::{self, Display, Formatter, Write};
+use std::str::FromStr;
+
+use {Utf8String, Utf8StringRef, Value, ValueRef};
+
+/// This type represents all possible errors that can occur when parsing a value from its text
+/// notation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The text ended unexpectedly.
+    UnexpectedEnd,
+    /// The text contains an unexpected character at the given byte offset.
+    UnexpectedChar(char, usize),
+    /// The text contains an invalid or out of range number at the given byte offset.
+    InvalidNumber(usize),
+    /// The text contains an invalid escape sequence in a string at the given byte offset.
+    InvalidEscape(usize),
+    /// The text contains an invalid hex or base64 binary at the given byte offset.
+    InvalidBinary(usize),
+    /// The text contains an array or a map nested too deep at the given byte offset.
+    DepthLimitExceeded(usize),
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::UnexpectedEnd => "unexpected end of text",
+            Error::UnexpectedChar(..) => "unexpected character",
+            Error::InvalidNumber(..) => "invalid number",
+            Error::InvalidEscape(..) => "invalid escape sequence",
+            Error::InvalidBinary(..) => "invalid binary",
+            Error::DepthLimitExceeded(..) => "depth limit exceeded",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Error::UnexpectedEnd => write!(fmt, "unexpected end of text"),
+            Error::UnexpectedChar(ch, pos) => {
+                write!(fmt, "unexpected character {:?} at position {}", ch, pos)
+            }
+            Error::InvalidNumber(pos) => write!(fmt, "invalid number at position {}", pos),
+            Error::InvalidEscape(pos) => {
+                write!(fmt, "invalid escape sequence at position {}", pos)
+            }
+            Error::InvalidBinary(pos) => write!(fmt, "invalid binary at position {}", pos),
+            Error::DepthLimitExceeded(pos) => {
+                write!(fmt, "depth limit exceeded at position {}", pos)
+            }
+        }
+    }
+}
+
+// A borrowed view of a value, which allows to share formatting between `Value` and `ValueRef`.
+enum View<'v, V: 'v> {
+    Nil,
+    Boolean(bool),
+    U64(u64),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    String(Utf8StringRef<'v>),
+    Binary(&'v [u8]),
+    Array(&'v [V]),
+    Map(&'v [(V, V)]),
+    Ext(i8, &'v [u8]),
+}
+
+trait Node: Sized {
+    fn view<'v>(&'v self) -> View<'v, Self>;
+}
+
+impl Node for Value {
+    fn view<'v>(&'v self) -> View<'v, Value> {
+        match *self {
+            Value::Nil => View::Nil,
+            Value::Boolean(val) => View::Boolean(val),
+            Value::U64(val) => View::U64(val),
+            Value::I64(val) => View::I64(val),
+            Value::F32(val) => View::F32(val),
+            Value::F64(val) => View::F64(val),
+            Value::String(ref val) => View::String(val.as_ref()),
+            Value::Binary(ref val) => View::Binary(val),
+            Value::Array(ref val) => View::Array(val),
+            Value::Map(ref val) => View::Map(val),
+            Value::Ext(ty, ref val) => View::Ext(ty, val),
+        }
+    }
+}
+
+impl<'a> Node for ValueRef<'a> {
+    fn view<'v>(&'v self) -> View<'v, ValueRef<'a>> {
+        match *self {
+            ValueRef::Nil => View::Nil,
+            ValueRef::Boolean(val) => View::Boolean(val),
+            ValueRef::U64(val) => View::U64(val),
+            ValueRef::I64(val) => View::I64(val),
+            ValueRef::F32(val) => View::F32(val),
+            ValueRef::F64(val) => View::F64(val),
+            ValueRef::String(val) => View::String(val),
+            ValueRef::Binary(val) => View::Binary(val),
+            ValueRef::Array(ref val) => View::Array(val),
+            ValueRef::Map(ref val) => View::Map(val),
+            ValueRef::Ext(ty, val) => View::Ext(ty, val),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let pretty = fmt.alternate();
+        write_value(fmt, self, pretty, 0)
+    }
+}
+
+impl<'a> Display for ValueRef<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let pretty = fmt.alternate();
+        write_value(fmt, self, pretty, 0)
+    }
+}
+
+fn write_value<V: Node>(fmt: &mut Formatter, val: &V, pretty: bool, depth: usize) -> fmt::Result {
+    match val.view() {
+        View::Nil => fmt.write_str("nil"),
+        View::Boolean(val) => write!(fmt, "{}", val),
+        View::U64(val) => write!(fmt, "{}", val),
+        View::I64(val) if val < 0 => write!(fmt, "{}", val),
+        View::I64(val) => write!(fmt, "i64({})", val),
+        View::F32(val) => {
+            fmt.write_str("f32(")?;
+            if val.is_finite() {
+                write!(fmt, "{:?}", val)?;
+            } else {
+                write_special_float(fmt, val as f64)?;
+            }
+            fmt.write_str(")")
+        }
+        View::F64(val) if val.is_finite() => write!(fmt, "{:?}", val),
+        View::F64(val) => write_special_float(fmt, val),
+        View::String(val) => match val.as_str() {
+            Some(val) => write_str(fmt, val),
+            None => write_invalid_str(fmt, val.as_bytes()),
+        },
+        View::Binary(val) => write_hex(fmt, val),
+        View::Array(array) => {
+            write_seq(fmt, array, ('[', ']'), pretty, depth, |fmt, val| {
+                write_value(fmt, val, pretty, depth + 1)
+            })
+        }
+        View::Map(map) => {
+            write_seq(fmt, map, ('{', '}'), pretty, depth, |fmt, &(ref key, ref val)| {
+                write_value(fmt, key, pretty, depth + 1)?;
+                fmt.write_str(": ")?;
+                write_value(fmt, val, pretty, depth + 1)
+            })
+        }
+        View::Ext(ty, data) => {
+            write!(fmt, "ext({}, ", ty)?;
+            write_hex(fmt, data)?;
+            fmt.write_str(")")
+        }
+    }
+}
+
+fn write_special_float(fmt: &mut Formatter, val: f64) -> fmt::Result {
+    if val.is_nan() {
+        fmt.write_str("NaN")
+    } else if val > 0.0 {
+        fmt.write_str("Infinity")
+    } else {
+        fmt.write_str("-Infinity")
+    }
+}
+
+pub(crate) fn write_str<W: fmt::Write>(fmt: &mut W, val: &str) -> fmt::Result {
+    fmt.write_char('"')?;
+    write_escaped(fmt, val)?;
+    fmt.write_char('"')
+}
+
+// Writes a string containing invalid UTF-8, escaping the offending bytes as `\xff`.
+fn write_invalid_str(fmt: &mut Formatter, val: &[u8]) -> fmt::Result {
+    fmt.write_char('"')?;
+    for chunk in val.utf8_chunks() {
+        write_escaped(fmt, chunk.valid())?;
+        for byte in chunk.invalid() {
+            write!(fmt, "\\x{:02x}", byte)?;
+        }
+    }
+    fmt.write_char('"')
+}
+
+fn write_escaped<W: fmt::Write>(fmt: &mut W, val: &str) -> fmt::Result {
+    for ch in val.chars() {
+        match ch {
+            '"' => fmt.write_str("\\\"")?,
+            '\\' => fmt.write_str("\\\\")?,
+            '\n' => fmt.write_str("\\n")?,
+            '\r' => fmt.write_str("\\r")?,
+            '\t' => fmt.write_str("\\t")?,
+            '\u{8}' => fmt.write_str("\\b")?,
+            '\u{c}' => fmt.write_str("\\f")?,
+            ch if ch.is_control() => {
+                let mut buf = [0; 2];
+                for unit in ch.encode_utf16(&mut buf) {
+                    write!(fmt, "\\u{:04x}", unit)?;
+                }
+            }
+            ch => fmt.write_char(ch)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_hex(fmt: &mut Formatter, data: &[u8]) -> fmt::Result {
+    fmt.write_str("h'")?;
+    for byte in data {
+        write!(fmt, "{:02x}", byte)?;
+    }
+    fmt.write_char('\'')
+}
+
+fn write_seq<T, F>(fmt: &mut Formatter,
+                   items: &[T],
+                   (open, close): (char, char),
+                   pretty: bool,
+                   depth: usize,
+                   mut f: F)
+                   -> fmt::Result
+    where F: FnMut(&mut Formatter, &T) -> fmt::Result
+{
+    fmt.write_char(open)?;
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            fmt.write_char(',')?;
+        }
+        if pretty {
+            fmt.write_char('\n')?;
+            write_indent(fmt, depth + 1)?;
+        } else if idx > 0 {
+            fmt.write_char(' ')?;
+        }
+        f(fmt, item)?;
+    }
+    if pretty && !items.is_empty() {
+        fmt.write_char('\n')?;
+        write_indent(fmt, depth)?;
+    }
+    fmt.write_char(close)
+}
+
+fn write_indent(fmt: &mut Formatter, depth: usize) -> fmt::Result {
+    for _ in 0..depth {
+        fmt.write_str("  ")?;
+    }
+    Ok(())
+}
+
+/// Parses a value from the text notation produced by `Display`.
+///
+/// Both the compact and the pretty printed forms are accepted, as well as `null` for nil and
+/// base64 binaries.
+impl FromStr for Value {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Value, Error> {
+        let mut parser = Parser { text: text, pos: 0, depth: 0 };
+        let val = parser.parse_value()?;
+        parser.skip_whitespace();
+
+        match parser.peek() {
+            Some(ch) => Err(Error::UnexpectedChar(ch, parser.pos)),
+            None => Ok(val),
+        }
+    }
+}
+
+// The maximum nesting depth of arrays and maps accepted by the parser.
+const MAX_DEPTH: usize = 512;
+
+struct Parser<'t> {
+    text: &'t str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek();
+        if let Some(ch) = ch {
+            self.pos += ch.len_utf8();
+        }
+        ch
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        match self.bump() {
+            Some(ch) if ch == expected => Ok(()),
+            Some(ch) => Err(Error::UnexpectedChar(ch, pos)),
+            None => Err(Error::UnexpectedEnd),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(ch) = self.peek() {
+            if !ch.is_whitespace() {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    // Consumes a run of characters, which may form a word or a number.
+    fn take_word(&mut self) -> &'t str {
+        let start = self.pos;
+        while let Some(ch) = self.peek() {
+            if !(ch.is_alphanumeric() || ch == '.' || ch == '+' || ch == '-' || ch == '_') {
+                break;
+            }
+            self.bump();
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn parse_value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+
+        let start = self.pos;
+        match self.peek() {
+            Some('"') => self.parse_str().map(Value::String),
+            Some('[') => {
+                self.bump();
+                let mut array = Vec::new();
+                self.parse_seq(']', |parser| {
+                    array.push(parser.parse_value()?);
+                    Ok(())
+                })?;
+                Ok(Value::Array(array))
+            }
+            Some('{') => {
+                self.bump();
+                let mut map = Vec::new();
+                self.parse_seq('}', |parser| {
+                    let key = parser.parse_value()?;
+                    parser.expect(':')?;
+                    map.push((key, parser.parse_value()?));
+                    Ok(())
+                })?;
+                Ok(Value::Map(map))
+            }
+            Some(ch) if ch.is_alphanumeric() || ch == '-' => {
+                let word = self.take_word();
+                match word {
+                    "nil" | "null" => Ok(Value::Nil),
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    "h" | "b64" if self.peek() == Some('\'') => {
+                        self.parse_bin(word == "h").map(Value::Binary)
+                    }
+                    "i64" if self.peek() == Some('(') => {
+                        self.bump();
+                        self.skip_whitespace();
+                        let pos = self.pos;
+                        let val = self.take_word().parse().map_err(|_| Error::InvalidNumber(pos))?;
+                        self.expect(')')?;
+                        Ok(Value::I64(val))
+                    }
+                    "f32" if self.peek() == Some('(') => {
+                        self.bump();
+                        self.skip_whitespace();
+                        let pos = self.pos;
+                        let val = parse_float(self.take_word()).ok_or(Error::InvalidNumber(pos))?;
+                        self.expect(')')?;
+                        Ok(Value::F32(val))
+                    }
+                    "ext" if self.peek() == Some('(') => {
+                        self.bump();
+                        self.skip_whitespace();
+                        let pos = self.pos;
+                        let ty = self.take_word().parse().map_err(|_| Error::InvalidNumber(pos))?;
+                        self.expect(',')?;
+                        self.skip_whitespace();
+                        let pos = self.pos;
+                        let data = match self.take_word() {
+                            "h" if self.peek() == Some('\'') => self.parse_bin(true)?,
+                            "b64" if self.peek() == Some('\'') => self.parse_bin(false)?,
+                            _ => return Err(Error::InvalidBinary(pos)),
+                        };
+                        self.expect(')')?;
+                        Ok(Value::Ext(ty, data))
+                    }
+                    word => parse_number(word).ok_or(Error::InvalidNumber(start)),
+                }
+            }
+            Some(ch) => Err(Error::UnexpectedChar(ch, start)),
+            None => Err(Error::UnexpectedEnd),
+        }
+    }
+
+    fn parse_seq<F>(&mut self, close: char, mut f: F) -> Result<(), Error>
+        where F: FnMut(&mut Parser<'t>) -> Result<(), Error>
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::DepthLimitExceeded(self.pos - 1));
+        }
+
+        self.depth += 1;
+        let res = self.parse_items(close, &mut f);
+        self.depth -= 1;
+        res
+    }
+
+    fn parse_items<F>(&mut self, close: char, f: &mut F) -> Result<(), Error>
+        where F: FnMut(&mut Parser<'t>) -> Result<(), Error>
+    {
+        self.skip_whitespace();
+        if self.peek() == Some(close) {
+            self.bump();
+            return Ok(());
+        }
+
+        loop {
+            f(self)?;
+            self.skip_whitespace();
+            let pos = self.pos;
+            match self.bump() {
+                Some(',') => {}
+                Some(ch) if ch == close => return Ok(()),
+                Some(ch) => return Err(Error::UnexpectedChar(ch, pos)),
+                None => return Err(Error::UnexpectedEnd),
+            }
+        }
+    }
+
+    fn parse_str(&mut self) -> Result<Utf8String, Error> {
+        self.bump();
+
+        // Collects bytes rather than chars, because `\xff` escapes may produce invalid UTF-8.
+        let mut res = Vec::new();
+        let mut buf = [0; 4];
+        loop {
+            let pos = self.pos;
+            match self.bump() {
+                Some('"') => return Ok(Utf8String::from_bytes(res)),
+                Some('\\') => {
+                    let ch = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => self.parse_unicode_escape(pos)?,
+                        Some('x') => {
+                            res.push(self.parse_hex(pos, 2)? as u8);
+                            continue;
+                        }
+                        Some(..) => return Err(Error::InvalidEscape(pos)),
+                        None => return Err(Error::UnexpectedEnd),
+                    };
+                    res.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                }
+                Some(ch) => res.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes()),
+                None => return Err(Error::UnexpectedEnd),
+            }
+        }
+    }
+
+    // Parses the rest of `\uXXXX` escape, including the following low surrogate if necessary.
+    fn parse_unicode_escape(&mut self, pos: usize) -> Result<char, Error> {
+        let high = self.parse_hex(pos, 4)?;
+        let code = match high {
+            0xd800..=0xdbff => {
+                if self.bump() != Some('\\') || self.bump() != Some('u') {
+                    return Err(Error::InvalidEscape(pos));
+                }
+                match self.parse_hex(pos, 4)? {
+                    low @ 0xdc00..=0xdfff => {
+                        0x10000 + (((high as u32) - 0xd800) << 10) + ((low as u32) - 0xdc00)
+                    }
+                    _ => return Err(Error::InvalidEscape(pos)),
+                }
+            }
+            code => code as u32,
+        };
+
+        ::std::char::from_u32(code).ok_or(Error::InvalidEscape(pos))
+    }
+
+    // Parses the given number of hex digits of an escape sequence.
+    fn parse_hex(&mut self, pos: usize, len: usize) -> Result<u16, Error> {
+        let start = self.pos;
+        for _ in 0..len {
+            match self.bump() {
+                Some(ch) if ch.is_digit(16) => {}
+                Some(..) => return Err(Error::InvalidEscape(pos)),
+                None => return Err(Error::UnexpectedEnd),
+            }
+        }
+
+        u16::from_str_radix(&self.text[start..self.pos], 16).map_err(|_| Error::InvalidEscape(pos))
+    }
+
+    fn parse_bin(&mut self, hex: bool) -> Result<Vec<u8>, Error> {
+        let start = self.pos;
+        self.bump();
+
+        let data_start = self.pos;
+        loop {
+            match self.bump() {
+                Some('\'') => break,
+                Some(..) => {}
+                None => return Err(Error::UnexpectedEnd),
+            }
+        }
+
+        let data = &self.text[data_start..self.pos - 1];
+        let res = if hex { decode_hex(data) } else { decode_base64(data) };
+        res.ok_or(Error::InvalidBinary(start))
+    }
+}
+
+fn parse_float<T: FromStr + From<f32> + Into<f64> + Copy>(word: &str) -> Option<T> {
+    match word {
+        "NaN" => Some(T::from(::std::f32::NAN)),
+        "Infinity" => Some(T::from(::std::f32::INFINITY)),
+        "-Infinity" => Some(T::from(::std::f32::NEG_INFINITY)),
+        word => {
+            // Rust accepts special values like "inf", which are not part of the notation, and
+            // rounds numbers out of range to infinity.
+            if word.chars().any(|ch| ch.is_alphabetic() && ch != 'e' && ch != 'E') {
+                None
+            } else {
+                word.parse().ok().filter(|val: &T| (*val).into().is_finite())
+            }
+        }
+    }
+}
+
+fn parse_number(word: &str) -> Option<Value> {
+    if word.contains(|ch| ch == '.' || ch == 'e' || ch == 'E') || word.ends_with("Infinity") ||
+       word == "NaN" {
+        parse_float(word).map(Value::F64)
+    } else if word.starts_with('-') {
+        word.parse().ok().map(Value::I64)
+    } else if word.starts_with('+') {
+        None
+    } else {
+        word.parse().ok().map(Value::U64)
+    }
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    if data.len() % 2 != 0 {
+        return None;
+    }
+
+    let mut res = Vec::with_capacity(data.len() / 2);
+    let mut digits = data.chars().map(|ch| ch.to_digit(16));
+    while let Some(hi) = digits.next() {
+        let lo = digits.next()?;
+        res.push((hi? * 16 + lo?) as u8);
+    }
+
+    Some(res)
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let data = data.trim_end_matches('=');
+
+    let mut res = Vec::with_capacity(data.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for ch in data.chars() {
+        let val = match ch {
+            'A'..='Z' => ch as u32 - 'A' as u32,
+            'a'..='z' => ch as u32 - 'a' as u32 + 26,
+            '0'..='9' => ch as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | val;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    // Leftover bits must be zero padding of the last byte.
+    if bits >= 6 || acc != 0 {
+        return None;
+    }
+
+    Some(res)
+}
@@ -0,0 +1,257 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::{self, Utf8Error};
+
+/// Represents a MessagePack string, which may contain invalid UTF-8.
+///
+/// MessagePack requires strings to be UTF-8 encoded, but there are encoders in the wild writing
+/// arbitrary bytes. Instead of failing to decode such messages, the raw bytes are kept together
+/// with the validation error, so they can be inspected and encoded back unchanged.
+///
+/// Strings are compared, ordered and hashed by their bytes.
+///
+/// # Examples
+///
+/// ```
+/// use rmpv::Utf8String;
+///
+/// let s = Utf8String::from("le message");
+/// assert_eq!(Some("le message"), s.as_str());
+///
+/// let s = Utf8String::from_bytes(vec![0x61, 0xff]);
+/// assert!(s.is_err());
+/// assert_eq!(None, s.as_str());
+/// assert_eq!(&[0x61, 0xff], s.as_bytes());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Utf8String {
+    s: Result<String, (Vec<u8>, Utf8Error)>,
+}
+
+impl Utf8String {
+    /// Validates the given bytes, keeping them as is if they are not valid UTF-8.
+    pub fn from_bytes(buf: Vec<u8>) -> Utf8String {
+        match String::from_utf8(buf) {
+            Ok(s) => Utf8String { s: Ok(s) },
+            Err(err) => {
+                let utf8_err = err.utf8_error();
+                Utf8String { s: Err((err.into_bytes(), utf8_err)) }
+            }
+        }
+    }
+
+    /// Returns true if the string is valid UTF-8.
+    pub fn is_str(&self) -> bool {
+        self.s.is_ok()
+    }
+
+    /// Returns true if the string contains invalid UTF-8.
+    pub fn is_err(&self) -> bool {
+        self.s.is_err()
+    }
+
+    /// Returns the string if it is valid UTF-8. Returns None otherwise.
+    pub fn as_str(&self) -> Option<&str> {
+        match self.s {
+            Ok(ref s) => Some(s),
+            Err(..) => None,
+        }
+    }
+
+    /// Returns the validation error if the string contains invalid UTF-8. Returns None
+    /// otherwise.
+    pub fn as_err(&self) -> Option<&Utf8Error> {
+        match self.s {
+            Ok(..) => None,
+            Err((_, ref err)) => Some(err),
+        }
+    }
+
+    /// Returns the raw bytes of the string regardless of its validity.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self.s {
+            Ok(ref s) => s.as_bytes(),
+            Err((ref buf, _)) => buf,
+        }
+    }
+
+    /// Returns a mutable reference to the string if it is valid UTF-8. Returns None otherwise.
+    pub fn as_string_mut(&mut self) -> Option<&mut String> {
+        match self.s {
+            Ok(ref mut s) => Some(s),
+            Err(..) => None,
+        }
+    }
+
+    /// Borrows the string as a `Utf8StringRef`.
+    pub fn as_ref(&self) -> Utf8StringRef<'_> {
+        match self.s {
+            Ok(ref s) => Utf8StringRef { s: Ok(s) },
+            Err((ref buf, err)) => Utf8StringRef { s: Err((buf, err)) },
+        }
+    }
+
+    /// Converts into the string if it is valid UTF-8. Returns None otherwise.
+    pub fn into_str(self) -> Option<String> {
+        self.s.ok()
+    }
+
+    /// Converts into the raw bytes of the string regardless of its validity.
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self.s {
+            Ok(s) => s.into_bytes(),
+            Err((buf, _)) => buf,
+        }
+    }
+}
+
+/// Writes the string replacing invalid UTF-8 sequences with `U+FFFD`.
+impl Display for Utf8String {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        self.as_ref().fmt(fmt)
+    }
+}
+
+impl PartialEq for Utf8String {
+    fn eq(&self, other: &Utf8String) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Utf8String {}
+
+impl PartialOrd for Utf8String {
+    fn partial_cmp(&self, other: &Utf8String) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Utf8String {
+    fn cmp(&self, other: &Utf8String) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl Hash for Utf8String {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl From<String> for Utf8String {
+    fn from(val: String) -> Utf8String {
+        Utf8String { s: Ok(val) }
+    }
+}
+
+impl From<&str> for Utf8String {
+    fn from(val: &str) -> Utf8String {
+        Utf8String { s: Ok(val.to_owned()) }
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for Utf8String {
+    fn from(val: Cow<'a, str>) -> Utf8String {
+        Utf8String { s: Ok(val.into_owned()) }
+    }
+}
+
+/// A borrowed `Utf8String`.
+#[derive(Clone, Copy, Debug)]
+pub struct Utf8StringRef<'a> {
+    s: Result<&'a str, (&'a [u8], Utf8Error)>,
+}
+
+impl<'a> Utf8StringRef<'a> {
+    /// Validates the given bytes, keeping them as is if they are not valid UTF-8.
+    pub fn from_bytes(buf: &'a [u8]) -> Utf8StringRef<'a> {
+        match str::from_utf8(buf) {
+            Ok(s) => Utf8StringRef { s: Ok(s) },
+            Err(err) => Utf8StringRef { s: Err((buf, err)) },
+        }
+    }
+
+    /// Returns true if the string is valid UTF-8.
+    pub fn is_str(&self) -> bool {
+        self.s.is_ok()
+    }
+
+    /// Returns true if the string contains invalid UTF-8.
+    pub fn is_err(&self) -> bool {
+        self.s.is_err()
+    }
+
+    /// Returns the string if it is valid UTF-8. Returns None otherwise.
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.s.ok()
+    }
+
+    /// Returns the validation error if the string contains invalid UTF-8. Returns None
+    /// otherwise.
+    pub fn as_err(&self) -> Option<&Utf8Error> {
+        match self.s {
+            Ok(..) => None,
+            Err((_, ref err)) => Some(err),
+        }
+    }
+
+    /// Returns the raw bytes of the string regardless of its validity.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self.s {
+            Ok(s) => s.as_bytes(),
+            Err((buf, _)) => buf,
+        }
+    }
+
+    /// Copies the string into an owned `Utf8String`.
+    pub fn to_owned(&self) -> Utf8String {
+        match self.s {
+            Ok(s) => Utf8String { s: Ok(s.to_owned()) },
+            Err((buf, err)) => Utf8String { s: Err((buf.to_vec(), err)) },
+        }
+    }
+}
+
+/// Writes the string replacing invalid UTF-8 sequences with `U+FFFD`.
+impl<'a> Display for Utf8StringRef<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self.s {
+            Ok(s) => fmt.write_str(s),
+            Err((buf, _)) => fmt.write_str(&String::from_utf8_lossy(buf)),
+        }
+    }
+}
+
+impl<'a> PartialEq for Utf8StringRef<'a> {
+    fn eq(&self, other: &Utf8StringRef<'a>) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl<'a> Eq for Utf8StringRef<'a> {}
+
+impl<'a> PartialOrd for Utf8StringRef<'a> {
+    fn partial_cmp(&self, other: &Utf8StringRef<'a>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Utf8StringRef<'a> {
+    fn cmp(&self, other: &Utf8StringRef<'a>) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl<'a> Hash for Utf8StringRef<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl<'a> From<&'a str> for Utf8StringRef<'a> {
+    fn from(val: &'a str) -> Utf8StringRef<'a> {
+        Utf8StringRef { s: Ok(val) }
+    }
+}
@@ -0,0 +1,191 @@
+//! Values, which remember their original wire encoding.
+//!
+//! `read_value` normalizes the encoding: a `0xcd 0x00 0x2a` uint16 becomes `Value::U64(42)`,
+//! which `write_value` writes back as the single byte `0x2a`. Decoding, modifying and encoding a
+//! message this way changes bytes of nodes, which were not touched, breaking signatures over
+//! forwarded messages.
+//!
+//! `WireValue` mirrors `Value`, but every node also keeps the `Marker` it was read with, including
+//! widths of integers, floats and lengths of strings, binaries, arrays, maps and extensions.
+//! `write_wire_value` writes each node using its marker whenever it can represent the current
+//! contents, so nodes, which were not modified, are reproduced byte-for-byte. Other nodes, e.g.
+//! a string, which became too long for its `str8` marker, or new nodes without a marker, are
+//! written in the most compact form like `write_value` does.
+//!
+//! # Examples
+//!
+//! ```
+//! use rmpv::decode::read_wire_value;
+//! use rmpv::encode::write_wire_value;
+//! use rmpv::wire::WireKind;
+//!
+//! // {"id": 42 as uint16, "name": "a" as str8}
+//! let buf = [0x82, 0xa2, 0x69, 0x64, 0xcd, 0x00, 0x2a, 0xa4, 0x6e, 0x61, 0x6d, 0x65, 0xd9, 0x01,
+//!            0x61];
+//!
+//! let mut val = read_wire_value(&mut &buf[..]).unwrap();
+//! val.get_mut("id").unwrap().kind = WireKind::U64(43);
+//!
+//! let mut out = Vec::new();
+//! write_wire_value(&mut out, &val).unwrap();
+//!
+//! assert_eq!(&[0xcd, 0x00, 0x2b], &out[4..7]);
+//! assert_eq!(&buf[7..], &out[7..]);
+//! ```
+
+use rmp::Marker;
+
+use {Utf8String, Value};
+
+/// The contents of a `WireValue` node, which mirror the variants of `Value`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WireKind {
+    /// Nil represents nil.
+    Nil,
+    /// Boolean represents true or false.
+    Boolean(bool),
+    /// Unsigned integer.
+    U64(u64),
+    /// Signed integer.
+    I64(i64),
+    /// A 32-bit floating point number.
+    F32(f32),
+    /// A 64-bit floating point number.
+    F64(f64),
+    /// String extending Raw type represents a UTF-8 string, which keeps invalid UTF-8 as is.
+    String(Utf8String),
+    /// Binary extending Raw type represents a byte array.
+    Binary(Vec<u8>),
+    /// Array represents a sequence of objects.
+    Array(Vec<WireValue>),
+    /// Map represents key-value pairs of objects.
+    Map(Vec<(WireValue, WireValue)>),
+    /// Extended implements Extension interface: represents a tuple of type information and a byte
+    /// array where type information is an integer whose meaning is defined by applications.
+    Ext(i8, Vec<u8>),
+}
+
+/// A value node together with the marker it was encoded with.
+///
+/// See the [module documentation](index.html) for details.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WireValue {
+    /// The marker the node was read with or `None` for nodes created otherwise.
+    ///
+    /// Replacing `kind` keeps the marker, so the node is written with the original width if it
+    /// still fits, while replacing the whole node with one converted from a `Value` resets it.
+    pub marker: Option<Marker>,
+    /// The contents of the node.
+    pub kind: WireKind,
+}
+
+impl WireValue {
+    /// Creates a node without a marker, which is written in the most compact form.
+    pub fn new(kind: WireKind) -> WireValue {
+        WireValue {
+            marker: None,
+            kind: kind,
+        }
+    }
+
+    /// Returns a reference to the node corresponding to the given string key if this is a map
+    /// containing such key. Returns None otherwise.
+    pub fn get(&self, key: &str) -> Option<&WireValue> {
+        if let WireKind::Map(ref map) = self.kind {
+            map.iter().find(|&&(ref k, _)| k.as_str() == Some(key)).map(|&(_, ref v)| v)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the node corresponding to the given string key if this is
+    /// a map containing such key. Returns None otherwise.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut WireValue> {
+        if let WireKind::Map(ref mut map) = self.kind {
+            map.iter_mut()
+                .find(|&&mut (ref k, _)| k.as_str() == Some(key))
+                .map(|&mut (_, ref mut v)| v)
+        } else {
+            None
+        }
+    }
+
+    /// If this is a string containing valid UTF-8, returns it. Returns None otherwise.
+    pub fn as_str(&self) -> Option<&str> {
+        if let WireKind::String(ref val) = self.kind {
+            val.as_str()
+        } else {
+            None
+        }
+    }
+
+    /// Converts the node into a `Value`, forgetting the markers.
+    pub fn to_value(&self) -> Value {
+        match self.kind {
+            WireKind::Nil => Value::Nil,
+            WireKind::Boolean(val) => Value::Boolean(val),
+            WireKind::U64(val) => Value::U64(val),
+            WireKind::I64(val) => Value::I64(val),
+            WireKind::F32(val) => Value::F32(val),
+            WireKind::F64(val) => Value::F64(val),
+            WireKind::String(ref val) => Value::String(val.clone()),
+            WireKind::Binary(ref val) => Value::Binary(val.clone()),
+            WireKind::Array(ref vals) => {
+                Value::Array(vals.iter().map(WireValue::to_value).collect())
+            }
+            WireKind::Map(ref entries) => {
+                Value::Map(entries.iter()
+                    .map(|&(ref k, ref v)| (k.to_value(), v.to_value()))
+                    .collect())
+            }
+            WireKind::Ext(ty, ref data) => Value::Ext(ty, data.clone()),
+        }
+    }
+}
+
+/// Converts a `Value` into nodes without markers.
+impl From<Value> for WireValue {
+    fn from(val: Value) -> WireValue {
+        let kind = match val {
+            Value::Nil => WireKind::Nil,
+            Value::Boolean(val) => WireKind::Boolean(val),
+            Value::U64(val) => WireKind::U64(val),
+            Value::I64(val) => WireKind::I64(val),
+            Value::F32(val) => WireKind::F32(val),
+            Value::F64(val) => WireKind::F64(val),
+            Value::String(val) => WireKind::String(val),
+            Value::Binary(val) => WireKind::Binary(val),
+            Value::Array(vals) => WireKind::Array(vals.into_iter().map(WireValue::from).collect()),
+            Value::Map(entries) => {
+                WireKind::Map(entries.into_iter()
+                    .map(|(k, v)| (WireValue::from(k), WireValue::from(v)))
+                    .collect())
+            }
+            Value::Ext(ty, data) => WireKind::Ext(ty, data),
+        };
+
+        WireValue::new(kind)
+    }
+}
+
+impl From<WireValue> for Value {
+    fn from(val: WireValue) -> Value {
+        match val.kind {
+            WireKind::Nil => Value::Nil,
+            WireKind::Boolean(val) => Value::Boolean(val),
+            WireKind::U64(val) => Value::U64(val),
+            WireKind::I64(val) => Value::I64(val),
+            WireKind::F32(val) => Value::F32(val),
+            WireKind::F64(val) => Value::F64(val),
+            WireKind::String(val) => Value::String(val),
+            WireKind::Binary(val) => Value::Binary(val),
+            WireKind::Array(vals) => Value::Array(vals.into_iter().map(Value::from).collect()),
+            WireKind::Map(entries) => {
+                Value::Map(entries.into_iter()
+                    .map(|(k, v)| (Value::from(k), Value::from(v)))
+                    .collect())
+            }
+            WireKind::Ext(ty, data) => Value::Ext(ty, data),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+#[macro_use]
+extern crate rmpv;
+
+use std::io::ErrorKind;
+
+use rmpv::Value;
+use rmpv::cbor::{from_cbor, to_cbor, Error, Loss, LossKind, EXT_TAG};
+use rmpv::decode::read_value;
+use rmpv::encode::write_value;
+
+fn encode(val: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_value(&mut buf, val).unwrap();
+    buf
+}
+
+fn msgpack_to_cbor(buf: &[u8]) -> (Vec<u8>, Vec<Loss>) {
+    let mut res = Vec::new();
+    let mut losses = Vec::new();
+    to_cbor(&mut &buf[..], &mut res, |loss| losses.push(loss)).unwrap();
+    (res, losses)
+}
+
+fn cbor_to_value(buf: &[u8]) -> (Value, Vec<Loss>) {
+    let mut res = Vec::new();
+    let mut losses = Vec::new();
+    from_cbor(&mut &buf[..], &mut res, |loss| losses.push(loss)).unwrap();
+    (read_value(&mut &res[..]).unwrap(), losses)
+}
+
+#[test]
+fn to_cbor_scalars() {
+    let cases = vec![
+        (Value::Nil, vec![0xf6]),
+        (Value::from(true), vec![0xf5]),
+        (Value::from(false), vec![0xf4]),
+        (Value::from(0), vec![0x00]),
+        (Value::from(23), vec![0x17]),
+        (Value::from(24), vec![0x18, 0x18]),
+        (Value::from(1000), vec![0x19, 0x03, 0xe8]),
+        (Value::from(1000000), vec![0x1a, 0x00, 0x0f, 0x42, 0x40]),
+        (Value::from(u64::max_value()), vec![0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+        (Value::from(-1), vec![0x20]),
+        (Value::from(-1000), vec![0x39, 0x03, 0xe7]),
+        (Value::from(i64::min_value()),
+         vec![0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+        (Value::F32(1.5), vec![0xfa, 0x3f, 0xc0, 0x00, 0x00]),
+        (Value::F64(1.1), vec![0xfb, 0x3f, 0xf1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a]),
+        (Value::from("IETF"), vec![0x64, 0x49, 0x45, 0x54, 0x46]),
+        (Value::from(&[1, 2, 3, 4][..]), vec![0x44, 0x01, 0x02, 0x03, 0x04]),
+    ];
+
+    for (val, expected) in cases {
+        assert_eq!((expected, vec![]), msgpack_to_cbor(&encode(&val)));
+    }
+}
+
+#[test]
+fn to_cbor_containers() {
+    let val = msgpack!({"a": 1, "b": [2, 3], 4: {}});
+
+    let (buf, _) = msgpack_to_cbor(&encode(&val));
+
+    assert_eq!(vec![0xa3, 0x61, 0x61, 0x01, 0x61, 0x62, 0x82, 0x02, 0x03, 0x04, 0xa0], buf);
+}
+
+#[test]
+fn to_cbor_timestamps() {
+    // Timestamp 32 with 1363896240 seconds.
+    let (buf, losses) = msgpack_to_cbor(&[0xd6, 0xff, 0x51, 0x4b, 0x67, 0xb0]);
+    assert_eq!(vec![0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0], buf);
+    assert!(losses.is_empty());
+
+    // Timestamp 64 with 1363896240 seconds and 500000000 nanoseconds.
+    let val = (500000000u64 << 34) | 1363896240;
+    let mut msgpack = vec![0xd7, 0xff];
+    msgpack.extend((0..8).rev().map(|idx| (val >> (8 * idx)) as u8));
+    let (buf, losses) = msgpack_to_cbor(&msgpack);
+    assert_eq!(vec![0xc1, 0xfb, 0x41, 0xd4, 0x52, 0xd9, 0xec, 0x20, 0x00, 0x00], buf);
+    assert!(losses.is_empty());
+
+    // Timestamp 96 with 2^40 seconds and 1 nanosecond, which float seconds cannot represent.
+    let msgpack = [0xc7, 0x0c, 0xff, 0, 0, 0, 1, 0, 0, 0x01, 0, 0, 0, 0, 0];
+    let (buf, losses) = msgpack_to_cbor(&msgpack);
+    assert_eq!(0xc1, buf[0]);
+    assert_eq!(vec![Loss { kind: LossKind::TimestampPrecision, offset: 0 }], losses);
+}
+
+#[test]
+fn to_cbor_ext() {
+    let (buf, _) = msgpack_to_cbor(&encode(&msgpack!([ext(5, [0xaa, 0xbb])])));
+
+    assert_eq!(vec![0x81, 0xda, 0x6d, 0x70, 0x65, 0x78, 0x82, 0x05, 0x42, 0xaa, 0xbb], buf);
+    assert_eq!(0x6d706578, EXT_TAG);
+}
+
+#[test]
+fn from_cbor_rfc_examples() {
+    let cases = vec![
+        (vec![0x18, 0x64], Value::from(100)),
+        (vec![0x3b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], Value::from(-1)),
+        (vec![0xf9, 0x3c, 0x00], Value::F32(1.0)),
+        (vec![0xf9, 0x80, 0x00], Value::F32(-0.0)),
+        (vec![0xf9, 0x7b, 0xff], Value::F32(65504.0)),
+        (vec![0xf9, 0x00, 0x01], Value::F32(5.960464477539063e-8)),
+        (vec![0xf9, 0x7c, 0x00], Value::F32(::std::f32::INFINITY)),
+        (vec![0xfa, 0x47, 0xc3, 0x50, 0x00], Value::F32(100000.0)),
+        (vec![0xfb, 0x7e, 0x37, 0xe4, 0x3c, 0x88, 0x00, 0x75, 0x9c], Value::F64(1.0e+300)),
+        (vec![0x62, 0xc3, 0xbc], Value::from("ü")),
+        (vec![0x5f, 0x42, 0x01, 0x02, 0x43, 0x03, 0x04, 0x05, 0xff],
+         Value::from(&[1, 2, 3, 4, 5][..])),
+        (vec![0x7f, 0x65, 0x73, 0x74, 0x72, 0x65, 0x61, 0x64, 0x6d, 0x69, 0x6e, 0x67, 0xff],
+         Value::from("streaming")),
+        (vec![0x9f, 0x01, 0x82, 0x02, 0x03, 0x9f, 0x04, 0x05, 0xff, 0xff],
+         msgpack!([1, [2, 3], [4, 5]])),
+        (vec![0xbf, 0x61, 0x61, 0x01, 0x61, 0x62, 0x9f, 0x02, 0x03, 0xff, 0xff],
+         msgpack!({"a": 1, "b": [2, 3]})),
+        (vec![0x82, 0x61, 0x61, 0xbf, 0x61, 0x62, 0x61, 0x63, 0xff], msgpack!(["a", {"b": "c"}])),
+    ];
+
+    for (buf, expected) in cases {
+        assert_eq!((expected, vec![]), cbor_to_value(&buf));
+    }
+}
+
+#[test]
+fn from_cbor_timestamps() {
+    // 1(1363896240)
+    assert_eq!(Value::Ext(-1, vec![0x51, 0x4b, 0x67, 0xb0]),
+               cbor_to_value(&[0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0]).0);
+
+    // 1(1363896240.5)
+    let (val, _) = cbor_to_value(&[0xc1, 0xfb, 0x41, 0xd4, 0x52, 0xd9, 0xec, 0x20, 0x00, 0x00]);
+    let expected = (500000000u64 << 34) | 1363896240;
+    assert_eq!(Value::Ext(-1, (0..8).rev().map(|idx| (expected >> (8 * idx)) as u8).collect()),
+               val);
+
+    // 1(-1)
+    assert_eq!(Value::Ext(-1, vec![0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+               cbor_to_value(&[0xc1, 0x20]).0);
+
+    // 1("x")
+    assert_eq!((Value::from("x"), vec![Loss { kind: LossKind::InvalidTimestamp, offset: 0 }]),
+               cbor_to_value(&[0xc1, 0x61, 0x78]));
+}
+
+#[test]
+fn from_cbor_losses() {
+    // [undefined, simple(255), 0("x"), -18446744073709551616]
+    let buf = [0x84, 0xf7, 0xf8, 0xff, 0xc0, 0x61, 0x78,
+               0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+
+    let (val, losses) = cbor_to_value(&buf);
+
+    assert_eq!(msgpack!([nil, nil, "x", (-18446744073709551616.0)]), val);
+    assert_eq!(vec![
+        Loss { kind: LossKind::SimpleValue(23), offset: 1 },
+        Loss { kind: LossKind::SimpleValue(255), offset: 2 },
+        Loss { kind: LossKind::UnknownTag(0), offset: 4 },
+        Loss { kind: LossKind::LargeNegativeInteger, offset: 7 },
+    ], losses);
+}
+
+#[test]
+fn roundtrip() {
+    let val = msgpack!({
+        "nil": nil,
+        "ints": [0, 255, 65536, (u64::max_value()), -1, -129, (i64::min_value())],
+        "floats": [(1.5f32), 0.1],
+        "str": "le message",
+        "bin": bin([0, 1, 255]),
+        "ext": ext(42, [1, 2, 3, 4, 5]),
+        [1, 2]: {true: false},
+    });
+
+    let (cbor, losses) = msgpack_to_cbor(&encode(&val));
+    assert!(losses.is_empty());
+
+    assert_eq!((val, vec![]), cbor_to_value(&cbor));
+}
+
+#[test]
+fn roundtrip_timestamps() {
+    let cases: Vec<&[u8]> = vec![
+        &[0xd6, 0xff, 0x00, 0x00, 0x00, 0x01],
+        &[0xd7, 0xff, 0x77, 0x35, 0x94, 0x00, 0x00, 0x00, 0x00, 0x01],
+        &[0xc7, 0x0c, 0xff, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe],
+    ];
+
+    for msgpack in cases {
+        let (cbor, _) = msgpack_to_cbor(msgpack);
+        let mut res = Vec::new();
+        from_cbor(&mut &cbor[..], &mut res, |_| {}).unwrap();
+        assert_eq!(msgpack, &res[..]);
+    }
+}
+
+#[test]
+fn errors() {
+    let mut res = Vec::new();
+
+    match to_cbor(&mut &[0xc1][..], &mut res, |_| {}) {
+        Err(Error::Malformed(0)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match to_cbor(&mut &[0xa2, 0x61][..], &mut res, |_| {}) {
+        Err(Error::Read(..)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match to_cbor(&mut &[0x91, 0xa1, 0xff][..], &mut res, |_| {}) {
+        Err(Error::InvalidUtf8(1)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match from_cbor(&mut &[0xff][..], &mut res, |_| {}) {
+        Err(Error::Malformed(0)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match from_cbor(&mut &[0x82, 0x01, 0xff][..], &mut res, |_| {}) {
+        Err(Error::Malformed(2)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match from_cbor(&mut &[0x5f, 0x61, 0x78, 0xff][..], &mut res, |_| {}) {
+        Err(Error::Malformed(1)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match from_cbor(&mut &[0x1c][..], &mut res, |_| {}) {
+        Err(Error::Malformed(0)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match from_cbor(&mut &[0xda, 0x6d, 0x70, 0x65, 0x78, 0x01][..], &mut res, |_| {}) {
+        Err(Error::Malformed(0)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!("malformed item at offset 3", format!("{}", Error::Malformed(3)));
+}
+
+#[test]
+fn depth_limit() {
+    let mut res = Vec::new();
+
+    // Arrays in both directions, chains of tags and indefinite-length arrays in CBOR.
+    let mut msgpack = vec![0x91; 100_000];
+    msgpack.push(0xc0);
+    match to_cbor(&mut &msgpack[..], &mut res, |_| {}) {
+        Err(Error::DepthLimitExceeded(512)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    for &byte in &[0x81, 0xc6, 0x9f] {
+        let mut cbor = vec![byte; 100_000];
+        cbor.push(0xf6);
+        match from_cbor(&mut &cbor[..], &mut Vec::new(), |_| {}) {
+            Err(Error::DepthLimitExceeded(512)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    // 512 levels are still fine.
+    let mut msgpack = vec![0x91; 512];
+    msgpack.push(0xc0);
+    let (cbor, _) = msgpack_to_cbor(&msgpack);
+    let mut res = Vec::new();
+    from_cbor(&mut &cbor[..], &mut res, |_| {}).unwrap();
+    assert_eq!(msgpack, res);
+}
+
+#[test]
+fn offsets_are_relative_to_the_item() {
+    // Two concatenated items, where the second one contains an unknown tag.
+    let buf = [0x01, 0x81, 0xc6, 0x02];
+    let mut rd = &buf[..];
+    let mut losses = Vec::new();
+
+    from_cbor(&mut rd, &mut Vec::new(), |loss| losses.push(loss)).unwrap();
+    from_cbor(&mut rd, &mut Vec::new(), |loss| losses.push(loss)).unwrap();
+    assert_eq!(vec![Loss { kind: LossKind::UnknownTag(6), offset: 1 }], losses);
+}
+
+#[test]
+fn huge_lengths() {
+    let mut res = Vec::new();
+
+    // Definite-length text claiming 2^64 - 1 bytes.
+    let buf = [0x7b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+    match from_cbor(&mut &buf[..], &mut res, |_| {}) {
+        Err(Error::TooLong(0)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // Definite-length text claiming 4 GiB - 1 bytes followed by a single byte.
+    match from_cbor(&mut &[0x7a, 0xff, 0xff, 0xff, 0xff, 0x61][..], &mut res, |_| {}) {
+        Err(Error::Read(ref err)) if err.kind() == ErrorKind::UnexpectedEof => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // Chunked binary and text with a chunk claiming 2^64 - 1 bytes.
+    for &major in &[0x40, 0x60] {
+        let buf = [major | 0x1f, major | 0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        match from_cbor(&mut &buf[..], &mut res, |_| {}) {
+            Err(Error::TooLong(0)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+    // Chunked text with a chunk claiming 4 GiB - 1 bytes followed by a single byte.
+    match from_cbor(&mut &[0x7f, 0x7a, 0xff, 0xff, 0xff, 0xff, 0x61][..], &mut res, |_| {}) {
+        Err(Error::Read(ref err)) if err.kind() == ErrorKind::UnexpectedEof => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // MessagePack string claiming 4 GiB - 1 bytes followed by a single byte.
+    match to_cbor(&mut &[0xdb, 0xff, 0xff, 0xff, 0xff, 0x61][..], &mut res, |_| {}) {
+        Err(Error::Read(ref err)) if err.kind() == ErrorKind::UnexpectedEof => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
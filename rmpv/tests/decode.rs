@@ -115,9 +115,12 @@ fn from_str8_invalid_utf8() {
     // Invalid 2 Octet Sequence.
     let buf: &[u8] = &[0xd9, 0x02, 0xc3, 0x28];
 
-    match read_value(&mut &buf[..]) {
-        Err(Error::FromUtf8Error(err)) => {
-            assert_eq!(buf[2..].to_vec(), err.into_bytes());
+    match read_value(&mut &buf[..]).unwrap() {
+        Value::String(s) => {
+            assert!(s.is_err());
+            assert_eq!(None, s.as_str());
+            assert_eq!(0, s.as_err().unwrap().valid_up_to());
+            assert_eq!(buf[2..].to_vec(), s.into_bytes());
         }
         other => panic!("unexpected result: {:?}", other)
     }
@@ -130,3 +133,81 @@ fn from_array_of_two_integers() {
     let vec = vec![Value::U64(4), Value::U64(42)];
     assert_eq!(Value::Array(vec), read_value(&mut &buf[..]).unwrap());
 }
+
+#[test]
+fn from_map_duplicate_keys_allow() {
+    use rmpv::decode::{read_value_with_policy, DuplicateKeys};
+
+    // {1: 2, 1: 3}
+    let buf = [0x82, 0x01, 0x02, 0x01, 0x03];
+
+    let val = read_value_with_policy(&mut &buf[..], DuplicateKeys::Allow).unwrap();
+    assert_eq!(Value::Map(vec![(Value::from(1), Value::from(2)), (Value::from(1), Value::from(3))]),
+               val);
+}
+
+#[test]
+fn from_map_duplicate_keys_keep_first() {
+    use rmpv::decode::{read_value_with_policy, DuplicateKeys};
+
+    // {1: 2, 4: 5, 1: 3}, where the last key is encoded using u16.
+    let buf = [0x83, 0x01, 0x02, 0x04, 0x05, 0xcd, 0x00, 0x01, 0x03];
+
+    let val = read_value_with_policy(&mut &buf[..], DuplicateKeys::KeepFirst).unwrap();
+    assert_eq!(Value::Map(vec![(Value::from(1), Value::from(2)), (Value::from(4), Value::from(5))]),
+               val);
+}
+
+#[test]
+fn from_map_duplicate_keys_keep_last() {
+    use rmpv::decode::{read_value_with_policy, DuplicateKeys};
+
+    // {1: 2, 4: 5, 1: 3}
+    let buf = [0x83, 0x01, 0x02, 0x04, 0x05, 0x01, 0x03];
+
+    let val = read_value_with_policy(&mut &buf[..], DuplicateKeys::KeepLast).unwrap();
+    assert_eq!(Value::Map(vec![(Value::from(4), Value::from(5)), (Value::from(1), Value::from(3))]),
+               val);
+}
+
+#[test]
+fn from_nested_map_duplicate_keys_reject() {
+    use rmpv::decode::{read_value_with_policy, DuplicateKeys};
+
+    // [{"a": nil, "b": nil, "a": nil}]
+    let buf = [0x91, 0x83, 0xa1, 0x61, 0xc0, 0xa1, 0x62, 0xc0, 0xa1, 0x61, 0xc0];
+
+    match read_value_with_policy(&mut &buf[..], DuplicateKeys::Reject) {
+        Err(Error::DuplicateKey(2)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn from_map_duplicate_large_int_keys_reject() {
+    use rmpv::decode::{read_value_with_policy, DuplicateKeys};
+
+    // {u64(2^32): nil, i64(2^32): nil}
+    let buf = [
+        0x82,
+        0xcf, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xc0,
+        0xd3, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xc0,
+    ];
+
+    match read_value_with_policy(&mut &buf[..], DuplicateKeys::Reject) {
+        Err(Error::DuplicateKey(1)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // {[i64(2^32)]: nil, [u64(2^32)]: nil}
+    let buf = [
+        0x82,
+        0x91, 0xd3, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xc0,
+        0x91, 0xcf, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xc0,
+    ];
+
+    match read_value_with_policy(&mut &buf[..], DuplicateKeys::Reject) {
+        Err(Error::DuplicateKey(1)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
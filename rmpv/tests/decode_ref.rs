@@ -160,7 +160,7 @@ fn from_f64() {
 fn from_strfix() {
     let buf = [0xaa, 0x6c, 0x65, 0x20, 0x6d, 0x65, 0x73, 0x73, 0x61, 0x67, 0x65];
 
-    assert_eq!(ValueRef::String("le message"), read_value_ref(&mut &buf[..]).unwrap());
+    assert_eq!(ValueRef::String("le message".into()), read_value_ref(&mut &buf[..]).unwrap());
 }
 
 #[test]
@@ -177,7 +177,7 @@ fn from_str8() {
 
     let mut slice = &buf[..];
 
-    assert_eq!(ValueRef::String("B123456789012345678901234567890E"),
+    assert_eq!(ValueRef::String("B123456789012345678901234567890E".into()),
         read_value_ref(&mut slice).ok().unwrap());
 }
 
@@ -195,7 +195,7 @@ fn from_str16() {
 
     let mut slice = &buf[..];
 
-    assert_eq!(ValueRef::String("B123456789012345678901234567890E"),
+    assert_eq!(ValueRef::String("B123456789012345678901234567890E".into()),
         read_value_ref(&mut slice).ok().unwrap());
 }
 
@@ -213,7 +213,7 @@ fn from_str32() {
 
     let mut slice = &buf[..];
 
-    assert_eq!(ValueRef::String("B123456789012345678901234567890E"),
+    assert_eq!(ValueRef::String("B123456789012345678901234567890E".into()),
         read_value_ref(&mut slice).ok().unwrap());
 }
 
@@ -292,8 +292,16 @@ fn from_string_invalid_utf8() {
 
     let mut rd = &buf[..];
 
-    match read_value_ref(&mut rd).err().unwrap() {
-        Error::InvalidUtf8(act, _) => { assert_eq!(&[0xc3, 0x28], act); },
+    let val = read_value_ref(&mut rd).unwrap();
+    assert_eq!(None, val.as_str());
+    assert_eq!(0, rd.len());
+
+    match val {
+        ValueRef::String(s) => {
+            assert!(s.is_err());
+            assert_eq!(&[0xc3, 0x28], s.as_bytes());
+            assert_eq!(0, s.as_err().unwrap().valid_up_to());
+        }
         _ => panic!(),
     }
 }
@@ -449,7 +457,7 @@ fn from_fixmap() {
 
     let map = vec![
         (ValueRef::U64(42), ValueRef::U64(100500)),
-        (ValueRef::String("key"), ValueRef::String("value")),
+        (ValueRef::String("key".into()), ValueRef::String("value".into())),
     ];
     let expected = ValueRef::Map(map);
 
@@ -466,7 +474,7 @@ fn from_map16() {
     ];
     let mut rd = &buf[..];
 
-    let map = vec![(ValueRef::String("key"), ValueRef::String("value"))];
+    let map = vec![(ValueRef::String("key".into()), ValueRef::String("value".into()))];
     let expected = ValueRef::Map(map);
 
     assert_eq!(expected, read_value_ref(&mut rd).unwrap());
@@ -482,7 +490,7 @@ fn from_map32() {
     ];
     let mut rd = &buf[..];
 
-    let map = vec![(ValueRef::String("key"), ValueRef::String("value"))];
+    let map = vec![(ValueRef::String("key".into()), ValueRef::String("value".into()))];
     let expected = ValueRef::Map(map);
 
     assert_eq!(expected, read_value_ref(&mut rd).unwrap());
@@ -497,7 +505,7 @@ fn from_fixarray() {
     ];
     let mut rd = &buf[..];
 
-    let vec = vec![ValueRef::String("v1"), ValueRef::String("v2")];
+    let vec = vec![ValueRef::String("v1".into()), ValueRef::String("v2".into())];
 
     assert_eq!(ValueRef::Array(vec), read_value_ref(&mut rd).unwrap());
 }
@@ -512,7 +520,7 @@ fn from_array16() {
     ];
     let mut rd = &buf[..];
 
-    let vec = vec![ValueRef::String("v1"), ValueRef::String("v2")];
+    let vec = vec![ValueRef::String("v1".into()), ValueRef::String("v2".into())];
 
     assert_eq!(ValueRef::Array(vec), read_value_ref(&mut rd).unwrap());
 }
@@ -527,7 +535,7 @@ fn from_array32() {
     ];
     let mut rd = &buf[..];
 
-    let vec = vec![ValueRef::String("v1"), ValueRef::String("v2")];
+    let vec = vec![ValueRef::String("v1".into()), ValueRef::String("v2".into())];
 
     assert_eq!(ValueRef::Array(vec), read_value_ref(&mut rd).unwrap());
 }
@@ -547,7 +555,7 @@ fn from_fixmap_using_cursor() {
 
     let map = vec![
         (ValueRef::U64(42), ValueRef::U64(100500)),
-        (ValueRef::String("key"), ValueRef::String("value")),
+        (ValueRef::String("key".into()), ValueRef::String("value".into())),
     ];
     let expected = ValueRef::Map(map);
 
@@ -568,11 +576,11 @@ fn get_complex_msgpack_value<'a>() -> ValueRef<'a> {
         ValueRef::Nil,
         ValueRef::U64(42),
         ValueRef::Array(vec![
-            ValueRef::String("le message"),
+            ValueRef::String("le message".into()),
         ]),
         ValueRef::Map(vec![
             (
-                ValueRef::String("map"),
+                ValueRef::String("map".into()),
                 ValueRef::Array(vec![
                     ValueRef::Boolean(true),
                     ValueRef::Map(vec![
@@ -584,8 +592,8 @@ fn get_complex_msgpack_value<'a>() -> ValueRef<'a> {
                 ])
             ),
             (
-                ValueRef::String("key"),
-                ValueRef::String("value")
+                ValueRef::String("key".into()),
+                ValueRef::String("value".into())
             )
         ]),
         ValueRef::Array(vec![
@@ -595,11 +603,11 @@ fn get_complex_msgpack_value<'a>() -> ValueRef<'a> {
         ]),
         ValueRef::Map(vec![
             (
-                ValueRef::String("key"),
+                ValueRef::String("key".into()),
                 ValueRef::Map(vec![
                     (
-                        ValueRef::String("k1"),
-                        ValueRef::String("v1")
+                        ValueRef::String("k1".into()),
+                        ValueRef::String("v1".into())
                     )
                 ])
             )
@@ -648,11 +656,11 @@ fn into_owned() {
         Value::Nil,
         Value::U64(42),
         Value::Array(vec![
-            Value::String("le message".to_string()),
+            Value::String("le message".into()),
         ]),
         Value::Map(vec![
             (
-                Value::String("map".to_string()),
+                Value::String("map".into()),
                 Value::Array(vec![
                     Value::Boolean(true),
                     Value::Map(vec![
@@ -664,8 +672,8 @@ fn into_owned() {
                 ])
             ),
             (
-                Value::String("key".to_string()),
-                Value::String("value".to_string())
+                Value::String("key".into()),
+                Value::String("value".into())
             )
         ]),
         Value::Array(vec![
@@ -675,11 +683,11 @@ fn into_owned() {
         ]),
         Value::Map(vec![
             (
-                Value::String("key".to_string()),
+                Value::String("key".into()),
                 Value::Map(vec![
                     (
-                        Value::String("k1".to_string()),
-                        Value::String("v1".to_string())
+                        Value::String("k1".into()),
+                        Value::String("v1".into())
                     )
                 ])
             )
@@ -688,3 +696,121 @@ fn into_owned() {
 
     assert_eq!(expected, val.to_owned());
 }
+
+#[test]
+fn index_by_str() {
+    let val = get_complex_msgpack_value();
+
+    let map = val.index(3);
+    assert_eq!(ValueRef::String("value".into()), map["key"]);
+    assert_eq!(ValueRef::Boolean(true), *map["map"].index(0));
+    assert_eq!(ValueRef::Nil, map["missing"]);
+    assert!(map.contains_key("map"));
+    assert!(!val.contains_key("map"));
+
+    let index = map.key_index().unwrap();
+    assert_eq!(Some(&ValueRef::String("value".into())), index.get("key"));
+}
+
+#[test]
+fn into_conversions() {
+    assert_eq!(Some("le message"), ValueRef::String("le message".into()).into_str());
+    assert_eq!(Some(&[1, 2][..]), ValueRef::Binary(&[1, 2]).into_bytes());
+    assert_eq!(Some(vec![(ValueRef::Nil, ValueRef::Nil)]),
+               ValueRef::Map(vec![(ValueRef::Nil, ValueRef::Nil)]).into_map());
+    assert_eq!(Some((1, &[2][..])), ValueRef::Ext(1, &[2]).into_ext());
+    assert_eq!(None, ValueRef::Nil.into_str());
+}
+
+#[test]
+fn accessors() {
+    assert!(ValueRef::Nil.is_nil());
+    assert!(ValueRef::Boolean(true).is_bool());
+    assert!(ValueRef::I64(-1).is_i64());
+    assert!(ValueRef::U64(1).is_u64());
+    assert!(ValueRef::F32(1.0).is_f32());
+    assert!(ValueRef::F64(1.0).is_f64());
+    assert!(ValueRef::F64(1.0).is_number());
+    assert!(ValueRef::String("".into()).is_str());
+    assert!(ValueRef::Binary(&[]).is_bin());
+    assert!(ValueRef::Array(vec![]).is_array());
+    assert!(ValueRef::Map(vec![]).is_map());
+    assert!(ValueRef::Ext(1, &[]).is_ext());
+    assert!(!ValueRef::Nil.is_number());
+
+    assert_eq!(Some(true), ValueRef::Boolean(true).as_bool());
+    assert_eq!(Some(42), ValueRef::U64(42).as_i64());
+    assert_eq!(None, ValueRef::U64(u64::max_value()).as_i64());
+    assert_eq!(Some(-42.0), ValueRef::I64(-42).as_f64());
+    assert_eq!(Some(&[1, 2][..]), ValueRef::Binary(&[1, 2]).as_slice());
+    assert_eq!(Some((1, &[2][..])), ValueRef::Ext(1, &[2]).as_ext());
+    assert_eq!(None, ValueRef::Nil.as_ext());
+}
+
+#[test]
+fn index_by_usize() {
+    let val = get_complex_msgpack_value();
+
+    assert_eq!(ValueRef::U64(42), val[1]);
+    assert_eq!(ValueRef::String("le message".into()), val[2][0]);
+    assert_eq!(ValueRef::Nil, val[100]);
+    assert_eq!(ValueRef::Nil, ValueRef::Nil[0]);
+}
+
+#[test]
+fn from_primitives() {
+    assert_eq!(ValueRef::Boolean(true), ValueRef::from(true));
+    assert_eq!(ValueRef::U64(42), ValueRef::from(42u8));
+    assert_eq!(ValueRef::U64(42), ValueRef::from(42i32));
+    assert_eq!(ValueRef::I64(-42), ValueRef::from(-42i64));
+    assert_eq!(ValueRef::F32(1.5), ValueRef::from(1.5f32));
+    assert_eq!(ValueRef::String("le message".into()), ValueRef::from("le message"));
+    assert_eq!(ValueRef::Binary(&[1, 2]), ValueRef::from(&[1u8, 2][..]));
+}
+
+#[test]
+fn borrow_owned_value() {
+    let val = get_complex_msgpack_value().to_owned();
+
+    assert_eq!(get_complex_msgpack_value(), val.as_ref());
+}
+
+#[test]
+fn cmp_and_hash() {
+    use std::collections::HashSet;
+    use std::f64;
+
+    assert_eq!(ValueRef::F64(f64::NAN), ValueRef::F64(f64::NAN));
+    assert!(ValueRef::from(42) < ValueRef::F32(0.0));
+    assert!(ValueRef::String("a".into()) < ValueRef::Binary(&[]));
+
+    let mut set = HashSet::new();
+    assert!(set.insert(ValueRef::String("key".into())));
+    assert!(!set.insert(ValueRef::String("key".into())));
+    assert!(set.insert(ValueRef::Binary(b"key")));
+}
+
+#[test]
+fn pointer() {
+    let buf = [0x81, 0xa1, 0x6b, 0x92, 0xc0, 0x01];
+    let mut val = read_value_ref(&mut &buf[..]).unwrap();
+
+    assert_eq!(Some(&ValueRef::from(1)), val.pointer("/k/1"));
+    assert_eq!(None, val.pointer("/k/2"));
+
+    *val.pointer_mut("/k/0").unwrap() = ValueRef::String("v".into());
+    assert_eq!(Some(&ValueRef::String("v".into())), val.pointer("/k/0"));
+}
+
+#[test]
+fn display() {
+    let val = ValueRef::Array(vec![
+        ValueRef::String("a\"b".into()),
+        ValueRef::Binary(&[0xca, 0xfe]),
+        ValueRef::F32(0.5),
+        ValueRef::Ext(1, &[]),
+    ]);
+
+    assert_eq!(r#"["a\"b", h'cafe', f32(0.5), ext(1, h'')]"#, format!("{}", val));
+    assert_eq!(val.to_owned(), format!("{:#}", val).parse().unwrap());
+}
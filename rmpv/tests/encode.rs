@@ -1,7 +1,11 @@
+extern crate rmp;
 extern crate rmpv;
 
+use std::f64;
+
+use rmp::decode::check_canonical;
 use rmpv::Value;
-use rmpv::encode::write_value;
+use rmpv::encode::{write_value, write_value_canonical};
 
 #[test]
 fn pack_nil() {
@@ -12,3 +16,82 @@ fn pack_nil() {
 
     assert_eq!([0xc0], buf);
 }
+
+#[test]
+fn pack_invalid_utf8_string_as_is() {
+    use rmpv::decode::read_value;
+
+    let buf = [0xa3, 0x61, 0xc3, 0x28];
+
+    let val = read_value(&mut &buf[..]).unwrap();
+    assert_eq!(None, val.as_str());
+
+    let mut out = Vec::new();
+    write_value(&mut out, &val).unwrap();
+    assert_eq!(buf.to_vec(), out);
+
+    let mut out = Vec::new();
+    write_value_canonical(&mut out, &val).unwrap();
+    assert_eq!(buf.to_vec(), out);
+}
+
+#[test]
+fn pack_canonical_map_sorted_by_encoded_keys() {
+    let mut buf = Vec::new();
+
+    // Keys are ordered by their encoded form: 0x01 < 0xa1 0x61 < 0xa1 0x62.
+    let val = Value::Map(vec![
+        (Value::String("b".into()), Value::Nil),
+        (Value::String("a".into()), Value::Nil),
+        (Value::U64(1), Value::Nil),
+    ]);
+    write_value_canonical(&mut buf, &val).unwrap();
+
+    assert_eq!(vec![0x83, 0x01, 0xc0, 0xa1, 0x61, 0xc0, 0xa1, 0x62, 0xc0], buf);
+    assert!(check_canonical(&buf).is_ok());
+}
+
+#[test]
+fn pack_canonical_map_duplicate_keys_keep_last() {
+    let mut buf = Vec::new();
+
+    let val = Value::Map(vec![
+        (Value::U64(1), Value::Boolean(false)),
+        (Value::I64(1), Value::Boolean(true)),
+    ]);
+    write_value_canonical(&mut buf, &val).unwrap();
+
+    assert_eq!(vec![0x81, 0x01, 0xc3], buf);
+}
+
+#[test]
+fn pack_canonical_nested_map() {
+    let mut buf = Vec::new();
+
+    let inner = Value::Map(vec![(Value::U64(2), Value::Nil), (Value::U64(1), Value::Nil)]);
+    let val = Value::Array(vec![inner]);
+    write_value_canonical(&mut buf, &val).unwrap();
+
+    assert_eq!(vec![0x91, 0x82, 0x01, 0xc0, 0x02, 0xc0], buf);
+}
+
+#[test]
+fn pack_canonical_nan() {
+    let mut buf = Vec::new();
+
+    write_value_canonical(&mut buf, &Value::F32(f32::from_bits(0x7fc00001))).unwrap();
+    write_value_canonical(&mut buf, &Value::F64(-f64::NAN)).unwrap();
+
+    assert_eq!(vec![0xca, 0x7f, 0xc0, 0x00, 0x00,
+                    0xcb, 0x7f, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], buf);
+}
+
+#[test]
+fn pack_canonical_large_positive_i64_as_uint() {
+    let mut buf = Vec::new();
+
+    write_value_canonical(&mut buf, &Value::I64(1 << 40)).unwrap();
+
+    assert_eq!(vec![0xcf, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00], buf);
+    assert!(check_canonical(&buf).is_ok());
+}
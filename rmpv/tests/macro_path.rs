@@ -0,0 +1,20 @@
+// The macro is invoked by path without `#[macro_use]`, so recursive expansions must be
+// qualified with `$crate`.
+extern crate rmpv;
+
+use rmpv::Value;
+
+#[test]
+fn macro_by_path() {
+    let val = rmpv::msgpack!({"a": [1, {"b": nil}], "c": Value::from(2)});
+
+    let expected = Value::Map(vec![
+        (Value::from("a"), Value::Array(vec![
+            Value::U64(1),
+            Value::Map(vec![(Value::from("b"), Value::Nil)]),
+        ])),
+        (Value::from("c"), Value::U64(2)),
+    ]);
+
+    assert_eq!(expected, val);
+}
@@ -0,0 +1,216 @@
+#[macro_use]
+extern crate rmpv;
+
+use rmpv::Value;
+use rmpv::patch::{apply, diff, merge, Error};
+
+fn assert_roundtrip(from: Value, to: Value) {
+    let patch = diff(&from, &to);
+
+    let mut val = from;
+    apply(&mut val, &patch).unwrap();
+    assert_eq!(to, val);
+}
+
+#[test]
+fn diff_equal() {
+    let val = msgpack!({"a": [1, 2]});
+
+    assert_eq!(msgpack!([]), diff(&val, &val));
+}
+
+#[test]
+fn diff_map() {
+    let from = msgpack!({"a": 1, "b": {"c": 2}, "d/e~": 3});
+    let to = msgpack!({"a": 1, "b": {"c": 4}, "f": 5});
+
+    assert_eq!(msgpack!([
+        {"op": "replace", "path": "/b/c", "value": 4},
+        {"op": "remove", "path": "/d~1e~0"},
+        {"op": "add", "path": "/f", "value": 5},
+    ]), diff(&from, &to));
+    assert_roundtrip(from, to);
+}
+
+#[test]
+fn diff_array() {
+    let from = msgpack!([1, 2, 3, 4]);
+    let to = msgpack!([1, 5]);
+
+    assert_eq!(msgpack!([
+        {"op": "replace", "path": "/1", "value": 5},
+        {"op": "remove", "path": "/3"},
+        {"op": "remove", "path": "/2"},
+    ]), diff(&from, &to));
+    assert_roundtrip(from.clone(), to.clone());
+    assert_roundtrip(to, from);
+}
+
+#[test]
+fn diff_replaces_maps_with_reordered_entries() {
+    let from = msgpack!({"a": 1, "b": 2});
+    let to = msgpack!({"b": 2, "a": 1});
+
+    assert_eq!(msgpack!([{"op": "replace", "path": "", "value": {"b": 2, "a": 1}}]),
+               diff(&from, &to));
+    assert_roundtrip(from, to);
+}
+
+#[test]
+fn diff_replaces_maps_with_non_string_or_duplicate_keys() {
+    let from = msgpack!({"a": {1: "x"}, "b": {"k": 1, "k": 2}});
+    let to = msgpack!({"a": {1: "y"}, "b": {"k": 1, "k": 3}});
+
+    assert_eq!(msgpack!([
+        {"op": "replace", "path": "/a", "value": {1: "y"}},
+        {"op": "replace", "path": "/b", "value": {"k": 1, "k": 3}},
+    ]), diff(&from, &to));
+    assert_roundtrip(from, to);
+}
+
+#[test]
+fn diff_different_types() {
+    assert_roundtrip(msgpack!({"a": [1]}), msgpack!({"a": {"0": 1}}));
+    assert_roundtrip(msgpack!(1), msgpack!(1.0));
+    assert_roundtrip(msgpack!([1]), msgpack!("1"));
+}
+
+#[test]
+fn diff_float_bits() {
+    let nan = f64::from_bits(0x7ff8000000000001);
+
+    assert_eq!(msgpack!([{"op": "replace", "path": "/0", "value": 0.0}]),
+               diff(&msgpack!([-0.0]), &msgpack!([0.0])));
+    assert_eq!(msgpack!([{"op": "replace", "path": "", "value": nan}]),
+               diff(&msgpack!(::std::f64::NAN), &msgpack!(nan)));
+    assert_eq!(msgpack!([]), diff(&msgpack!({"a": nan}), &msgpack!({"a": nan})));
+
+    let mut val = msgpack!([-0.0f32]);
+    let patch = diff(&val, &msgpack!([0.0f32]));
+    apply(&mut val, &patch).unwrap();
+    assert_eq!(0, val[0].as_f64().unwrap().to_bits());
+}
+
+#[test]
+fn apply_operations() {
+    let mut val = msgpack!({"foo": ["bar", "baz"], "qux": {"a": 1}});
+
+    apply(&mut val, &msgpack!([
+        {"op": "add", "path": "/foo/1", "value": "new"},
+        {"op": "add", "path": "/foo/-", "value": "end"},
+        {"op": "remove", "path": "/foo/0"},
+        {"op": "replace", "path": "/qux/a", "value": 2},
+        {"op": "copy", "from": "/qux", "path": "/copy"},
+        {"op": "move", "from": "/foo/2", "path": "/qux/b"},
+        {"op": "test", "path": "/copy/a", "value": 2},
+    ])).unwrap();
+
+    assert_eq!(msgpack!({
+        "foo": ["new", "baz"],
+        "qux": {"a": 2, "b": "end"},
+        "copy": {"a": 2},
+    }), val);
+}
+
+#[test]
+fn apply_test_compares_integers_numerically() {
+    let mut val = msgpack!({"a": [1], "b": -1});
+
+    apply(&mut val, &msgpack!([
+        {"op": "test", "path": "/a", "value": [Value::I64(1)]},
+        {"op": "test", "path": "/b", "value": Value::I64(-1)},
+    ])).unwrap();
+    assert_eq!(Err(Error::TestFailed(0)),
+               apply(&mut val, &msgpack!([{"op": "test", "path": "/a/0", "value": -1}])));
+    assert_eq!(Err(Error::TestFailed(0)),
+               apply(&mut val, &msgpack!([{"op": "test", "path": "/a/0", "value": 1.0}])));
+}
+
+#[test]
+fn apply_to_root() {
+    let mut val = msgpack!({"a": 1});
+
+    apply(&mut val, &msgpack!([{"op": "add", "path": "", "value": [1]}])).unwrap();
+
+    assert_eq!(msgpack!([1]), val);
+}
+
+#[test]
+fn apply_to_integer_keys() {
+    let mut val = msgpack!({42: "a"});
+
+    apply(&mut val, &msgpack!([{"op": "replace", "path": "/42", "value": "b"}])).unwrap();
+
+    assert_eq!(msgpack!({42: "b"}), val);
+}
+
+#[test]
+fn apply_is_atomic() {
+    let mut val = msgpack!({"a": 1});
+
+    let err = apply(&mut val, &msgpack!([
+        {"op": "add", "path": "/b", "value": 2},
+        {"op": "test", "path": "/a", "value": 2},
+    ])).unwrap_err();
+
+    assert_eq!(Error::TestFailed(1), err);
+    assert_eq!(msgpack!({"a": 1}), val);
+}
+
+#[test]
+fn apply_errors() {
+    let mut val = msgpack!({"a": [1]});
+
+    assert_eq!(Err(Error::InvalidPatch), apply(&mut val, &msgpack!({})));
+    assert_eq!(Err(Error::InvalidOperation(0)),
+               apply(&mut val, &msgpack!([{"op": "nope", "path": "/a"}])));
+    assert_eq!(Err(Error::InvalidOperation(0)),
+               apply(&mut val, &msgpack!([{"op": "add", "path": "/b"}])));
+    assert_eq!(Err(Error::InvalidOperation(0)),
+               apply(&mut val, &msgpack!([{"op": "move", "from": "/a", "path": "/a/0"}])));
+    assert_eq!(Err(Error::PathNotFound(0)),
+               apply(&mut val, &msgpack!([{"op": "remove", "path": "/b"}])));
+    assert_eq!(Err(Error::PathNotFound(0)),
+               apply(&mut val, &msgpack!([{"op": "add", "path": "/a/2", "value": 1}])));
+    assert_eq!(Err(Error::PathNotFound(0)),
+               apply(&mut val, &msgpack!([{"op": "add", "path": "/b/c", "value": 1}])));
+    assert_eq!("path not found at operation 0",
+               format!("{}", Error::PathNotFound(0)));
+}
+
+#[test]
+fn merge_rfc7396_examples() {
+    let cases = vec![
+        (msgpack!({"a": "b"}), msgpack!({"a": "c"}), msgpack!({"a": "c"})),
+        (msgpack!({"a": "b"}), msgpack!({"b": "c"}), msgpack!({"a": "b", "b": "c"})),
+        (msgpack!({"a": "b"}), msgpack!({"a": null}), msgpack!({})),
+        (msgpack!({"a": "b", "b": "c"}), msgpack!({"a": null}), msgpack!({"b": "c"})),
+        (msgpack!({"a": ["b"]}), msgpack!({"a": "c"}), msgpack!({"a": "c"})),
+        (msgpack!({"a": "c"}), msgpack!({"a": ["b"]}), msgpack!({"a": ["b"]})),
+        (msgpack!({"a": {"b": "c"}}),
+         msgpack!({"a": {"b": "d", "c": null}}),
+         msgpack!({"a": {"b": "d"}})),
+        (msgpack!({"a": [{"b": "c"}]}), msgpack!({"a": [1]}), msgpack!({"a": [1]})),
+        (msgpack!(["a", "b"]), msgpack!(["c", "d"]), msgpack!(["c", "d"])),
+        (msgpack!({"a": "b"}), msgpack!(["c"]), msgpack!(["c"])),
+        (msgpack!({"a": "foo"}), msgpack!(null), msgpack!(null)),
+        (msgpack!({"a": "foo"}), msgpack!("bar"), msgpack!("bar")),
+        (msgpack!({"e": null}), msgpack!({"a": 1}), msgpack!({"e": null, "a": 1})),
+        (msgpack!([1, 2]), msgpack!({"a": "b", "c": null}), msgpack!({"a": "b"})),
+        (msgpack!({}), msgpack!({"a": {"bb": {"ccc": null}}}), msgpack!({"a": {"bb": {}}})),
+    ];
+
+    for (mut val, patch, expected) in cases {
+        merge(&mut val, &patch);
+        assert_eq!(expected, val);
+    }
+}
+
+#[test]
+fn merge_non_string_and_duplicate_keys() {
+    let mut val = msgpack!({1: "a", "k": 1, "k": 2, 2: {"x": 1}});
+
+    merge(&mut val, &msgpack!({"k": null, 1: "b", 2: {"y": 2}}));
+
+    assert_eq!(msgpack!({1: "b", 2: {"x": 1, "y": 2}}), val);
+}
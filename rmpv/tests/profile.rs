@@ -0,0 +1,183 @@
+#[macro_use]
+extern crate rmpv;
+
+use rmpv::Value;
+use rmpv::decode::value::Error;
+use rmpv::encode::write_value;
+use rmpv::profile::Profiler;
+
+fn encode(vals: &[Value]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for val in vals {
+        write_value(&mut buf, val).unwrap();
+    }
+    buf
+}
+
+fn orders() -> Vec<Value> {
+    vec![
+        msgpack!({"orders": [{"items": [{"sku": "a1"}, {"sku": "b22"}]}]}),
+        msgpack!({"orders": [{"items": []}, {"items": [{"sku": "c"}]}]}),
+    ]
+}
+
+#[test]
+fn profile_nested_paths() {
+    let buf = encode(&orders());
+
+    let mut profiler = Profiler::new();
+    assert_eq!(2, profiler.profile_stream(&mut &buf[..]).unwrap());
+    assert_eq!(2, profiler.messages());
+    assert_eq!(buf.len() as u64, profiler.bytes());
+
+    let paths: Vec<_> = profiler.paths().map(|(path, _)| &path[..]).collect();
+    assert_eq!(vec!["$",
+                    "$.orders",
+                    "$.orders[*]",
+                    "$.orders[*].items",
+                    "$.orders[*].items[*]",
+                    "$.orders[*].items[*].sku"],
+               paths);
+
+    let sku = profiler.get("$.orders[*].items[*].sku").unwrap();
+    assert_eq!(3, sku.count);
+    assert_eq!(3 + 4 + 2, sku.bytes);
+    assert_eq!(3 * 4, sku.key_bytes);
+    assert_eq!(Some(&3), sku.markers.get("fixstr"));
+
+    let root = profiler.get("$").unwrap();
+    assert_eq!(2, root.count);
+    assert_eq!(buf.len() as u64, root.bytes);
+    assert_eq!(0, root.key_bytes);
+
+    assert_eq!(3, profiler.get("$.orders[*].items").unwrap().count);
+}
+
+#[test]
+fn profile_is_incremental() {
+    let vals = orders();
+
+    let mut profiler = Profiler::new();
+    for val in &vals {
+        profiler.profile(&mut &encode(&[val.clone()])[..]).unwrap();
+    }
+
+    let mut expected = Profiler::new();
+    expected.profile_stream(&mut &encode(&vals)[..]).unwrap();
+
+    assert_eq!(expected, profiler);
+}
+
+#[test]
+fn profile_marker_distribution() {
+    let buf = encode(&[msgpack!([1, 300, -1, -300, 1.5, nil, true, bin([0, 1])])]);
+
+    let mut profiler = Profiler::new();
+    profiler.profile_stream(&mut &buf[..]).unwrap();
+
+    let stats = profiler.get("$[*]").unwrap();
+    assert_eq!(8, stats.count);
+    assert_eq!(Some(&1), stats.markers.get("positive fixint"));
+    assert_eq!(Some(&1), stats.markers.get("uint16"));
+    assert_eq!(Some(&1), stats.markers.get("negative fixint"));
+    assert_eq!(Some(&1), stats.markers.get("int16"));
+    assert_eq!(Some(&1), stats.markers.get("float64"));
+    assert_eq!(Some(&1), stats.markers.get("nil"));
+    assert_eq!(Some(&1), stats.markers.get("true"));
+    assert_eq!(Some(&1), stats.markers.get("bin8"));
+}
+
+#[test]
+fn profile_key_paths() {
+    let val = Value::Map(vec![
+        (Value::from("plain_key-1"), Value::from(1)),
+        (Value::from("it's a key"), Value::from(2)),
+        (Value::from(42), Value::from(3)),
+        (Value::from(-7), Value::from(4)),
+        (Value::Nil, Value::from(5)),
+        (Value::Array(vec![Value::from(1)]), Value::from(6)),
+    ]);
+    let buf = encode(&[val.clone()]);
+
+    let mut profiler = Profiler::new();
+    profiler.profile(&mut &buf[..]).unwrap();
+
+    let paths: Vec<_> = profiler.paths().map(|(path, _)| &path[..]).collect();
+    assert_eq!(vec!["$", "$.plain_key-1", "$['it\\'s a key']", "$[*]", "$[-7]", "$[42]"], paths);
+    assert_eq!(2, profiler.get("$[*]").unwrap().count);
+    assert_eq!(3, profiler.get("$[*]").unwrap().key_bytes);
+
+    // Paths select the profiled values.
+    for (path, stats) in profiler.paths().filter(|&(path, _)| path != "$[*]") {
+        assert_eq!(stats.count as usize, val.query(path).unwrap().len(), "{}", path);
+    }
+}
+
+#[test]
+fn profile_empty_stream() {
+    let mut profiler = Profiler::new();
+
+    assert_eq!(0, profiler.profile_stream(&mut &[][..]).unwrap());
+    assert_eq!(0, profiler.paths().count());
+    assert_eq!("path       count         bytes       %   key bytes  markers\n",
+               profiler.to_string());
+}
+
+#[test]
+fn profile_report() {
+    let buf = encode(&[msgpack!({"id": 1})]);
+
+    let mut profiler = Profiler::new();
+    profiler.profile_stream(&mut &buf[..]).unwrap();
+
+    let expected = "\
+path       count         bytes       %   key bytes  markers
+$              1             5   100.0           0  fixmap: 1
+$.id           1             1    80.0           3  positive fixint: 1
+";
+    assert_eq!(expected, profiler.to_string());
+}
+
+#[test]
+fn profile_fails_on_truncated_input() {
+    let mut profiler = Profiler::new();
+
+    match profiler.profile_stream(&mut &[0x01, 0x92, 0x01][..]) {
+        Err(Error::InvalidMarkerRead(..)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(1, profiler.messages());
+}
+
+#[test]
+fn profile_fails_on_reserved_marker() {
+    let mut profiler = Profiler::new();
+
+    match profiler.profile(&mut &[0x91, 0xc1][..]) {
+        Err(Error::TypeMismatch(..)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn profile_depth_limit() {
+    let mut buf = vec![0x91; 200_000];
+    buf.push(0xc0);
+
+    match Profiler::new().profile(&mut &buf[..]) {
+        Err(Error::DepthLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut profiler = Profiler::new();
+    profiler.set_max_depth(2);
+    profiler.profile(&mut &encode(&[msgpack!([{"a": 1}])])[..]).unwrap();
+
+    // Skipped non-string keys count as nested as well.
+    for val in &[msgpack!([[[1]]]), msgpack!({[[1]]: nil})] {
+        match profiler.profile(&mut &encode(&[val.clone()])[..]) {
+            Err(Error::DepthLimitExceeded) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
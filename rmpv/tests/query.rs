@@ -0,0 +1,134 @@
+#[macro_use]
+extern crate rmpv;
+
+use rmpv::{Value, ValueRef};
+use rmpv::query::{Error, Query};
+
+fn store() -> Value {
+    msgpack!({
+        "items": [
+            {"name": "apple", "price": 10, "status": "ok"},
+            {"name": "pear", "price": 25.5, "status": "failed"},
+            {"name": "plum", "price": 7, "status": "failed", "tags": ["sale"]},
+        ],
+        "owner": {"name": "John"},
+        42: "answer",
+    })
+}
+
+#[test]
+fn select_root() {
+    let val = store();
+
+    assert_eq!(vec![&val], val.query("$").unwrap());
+    assert_eq!(vec![&val], val.query("").unwrap());
+}
+
+#[test]
+fn select_children() {
+    let val = store();
+
+    assert_eq!(vec![&msgpack!("John")], val.query("$.owner.name").unwrap());
+    assert_eq!(vec![&msgpack!("John")], val.query("$['owner'][\"name\"]").unwrap());
+    assert_eq!(vec![&msgpack!("pear")], val.query("$.items[1].name").unwrap());
+    assert_eq!(vec![&msgpack!("plum")], val.query("$.items[-1].name").unwrap());
+    assert_eq!(vec![&msgpack!("answer")], val.query("$[42]").unwrap());
+    assert!(val.query("$.missing.name").unwrap().is_empty());
+    assert!(val.query("$.items[3]").unwrap().is_empty());
+}
+
+#[test]
+fn select_wildcard() {
+    let val = store();
+
+    assert_eq!(vec![&msgpack!(10), &msgpack!(25.5), &msgpack!(7)],
+               val.query("$.items[*].price").unwrap());
+    assert_eq!(vec![&msgpack!("John")], val.query("$.owner.*").unwrap());
+}
+
+#[test]
+fn select_slice() {
+    let val = msgpack!([0, 1, 2, 3, 4, 5]);
+
+    assert_eq!(vec![&msgpack!(1), &msgpack!(2)], val.query("$[1:3]").unwrap());
+    assert_eq!(vec![&msgpack!(4), &msgpack!(5)], val.query("$[-2:]").unwrap());
+    assert_eq!(vec![&msgpack!(0), &msgpack!(2), &msgpack!(4)], val.query("$[::2]").unwrap());
+    assert_eq!(vec![&msgpack!(5), &msgpack!(4), &msgpack!(3)], val.query("$[:2:-1]").unwrap());
+    assert!(val.query("$[4:1]").unwrap().is_empty());
+}
+
+#[test]
+fn select_slice_huge_step() {
+    let val = msgpack!([0, 1, 2, 3, 4, 5]);
+
+    assert_eq!(vec![&msgpack!(1)], val.query("$[1::9223372036854775807]").unwrap());
+    assert_eq!(vec![&msgpack!(4)], val.query("$[4::-9223372036854775808]").unwrap());
+}
+
+#[test]
+fn select_recursive() {
+    let val = store();
+
+    assert_eq!(vec![&msgpack!("apple"), &msgpack!("pear"), &msgpack!("plum"), &msgpack!("John")],
+               val.query("$..name").unwrap());
+    assert_eq!(vec![&val["items"][0], &msgpack!("sale")], val.query("$..[0]").unwrap());
+}
+
+#[test]
+fn select_filter() {
+    let val = store();
+
+    assert_eq!(vec![&msgpack!("pear"), &msgpack!("plum")],
+               val.query("$.items[?(@.status == 'failed')].name").unwrap());
+    assert_eq!(vec![&msgpack!("apple"), &msgpack!("plum")],
+               val.query("$.items[?(@.price < 20)].name").unwrap());
+    assert_eq!(vec![&msgpack!("pear")],
+               val.query("$.items[?@.price >= 25.5].name").unwrap());
+    assert_eq!(vec![&msgpack!("apple")],
+               val.query("$.items[?(@.status != 'failed')].name").unwrap());
+    assert_eq!(vec![&msgpack!("plum")], val.query("$.items[?(@.tags)].name").unwrap());
+    assert_eq!(vec![&msgpack!("plum")],
+               val.query("$.items[?(@.tags[0] == \"sale\")].name").unwrap());
+    assert_eq!(2, val.query("$..[?(@.status == 'failed')]").unwrap().len());
+}
+
+#[test]
+fn select_filter_across_types() {
+    let val = msgpack!([1, "1", -1, 1.0, null, true]);
+
+    assert_eq!(vec![&msgpack!(1), &msgpack!(1.0)], val.query("$[?(@ == 1)]").unwrap());
+    assert_eq!(vec![&msgpack!(-1)], val.query("$[?(@ < 0)]").unwrap());
+    assert_eq!(vec![&Value::Nil], val.query("$[?(@ == null)]").unwrap());
+    assert_eq!(vec![&msgpack!(true)], val.query("$[?(@ == true)]").unwrap());
+}
+
+#[test]
+fn select_ref() {
+    let val = ValueRef::Array(vec![
+        ValueRef::Map(vec![(ValueRef::String("id".into()), ValueRef::from(1))]),
+        ValueRef::Map(vec![(ValueRef::String("id".into()), ValueRef::from(2))]),
+    ]);
+
+    let query = Query::parse("$[?(@.id > 1)].id").unwrap();
+    assert_eq!(vec![&ValueRef::from(2)], query.select_ref(&val));
+    assert_eq!(vec![&ValueRef::from(1), &ValueRef::from(2)], val.query("$..id").unwrap());
+}
+
+#[test]
+fn parse_from_str() {
+    let query: Query = "$.a[0]".parse().unwrap();
+
+    assert_eq!(Query::parse("$['a'][0]").unwrap(), query);
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(Err(Error::UnexpectedEnd), Query::parse("$.a["));
+    assert_eq!(Err(Error::UnexpectedChar('x', 1)), Query::parse("$x"));
+    assert_eq!(Err(Error::UnexpectedChar(']', 2)), Query::parse("$[]"));
+    assert_eq!(Err(Error::ZeroStep(1)), Query::parse("$[::0]"));
+    assert_eq!(Err(Error::InvalidEscape(3)), Query::parse("$['\\x']"));
+    assert_eq!(Err(Error::InvalidNumber(11)), Query::parse("$[?(@.a == 1.2.3)]"));
+    assert_eq!("unexpected character 'x' at position 1",
+               format!("{}", Query::parse("$x").unwrap_err()));
+}
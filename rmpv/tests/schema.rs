@@ -0,0 +1,383 @@
+#[macro_use]
+extern crate rmpv;
+
+use rmpv::{Utf8String, Value, ValueRef};
+use rmpv::schema::{Error, Inference, Schema, Violation, ViolationKind};
+
+fn infer(vals: &[Value]) -> Inference {
+    let mut inference = Inference::new();
+    for val in vals {
+        inference.add(val);
+    }
+    inference
+}
+
+fn users() -> Vec<Value> {
+    vec![
+        msgpack!({"id": 1, "name": "ann", "role": "admin", "tags": ["a", "b"]}),
+        msgpack!({"id": 70000, "name": "bob", "role": "user", "tags": []}),
+        msgpack!({"id": 3, "name": "cy", "role": "user", "tags": ["c"], "age": nil}),
+        msgpack!({"id": 4, "name": "dee", "role": "admin", "tags": [], "age": 30}),
+    ]
+}
+
+#[test]
+fn infer_map_properties() {
+    let inference = infer(&users());
+    assert_eq!(4, inference.samples());
+
+    let schema = inference.to_value();
+    assert_eq!(msgpack!("map"), schema["type"]);
+    assert_eq!(msgpack!(4), schema["count"]);
+    assert_eq!(msgpack!(4), schema["min_len"]);
+    assert_eq!(msgpack!(5), schema["max_len"]);
+    assert_eq!(msgpack!(["id", "name", "role", "tags"]), schema["required"]);
+
+    let id = &schema["properties"]["id"];
+    assert_eq!(msgpack!("int"), id["type"]);
+    assert_eq!(msgpack!(1), id["min"]);
+    assert_eq!(msgpack!(70000), id["max"]);
+
+    let age = &schema["properties"]["age"];
+    assert_eq!(msgpack!(["nil", "int"]), age["type"]);
+    assert_eq!(msgpack!(2), age["count"]);
+    assert_eq!(msgpack!(30), age["max"]);
+
+    let name = &schema["properties"]["name"];
+    assert_eq!(msgpack!("str"), name["type"]);
+    assert_eq!(msgpack!(2), name["min_len"]);
+    assert_eq!(msgpack!(3), name["max_len"]);
+    assert_eq!(Value::Nil, name["enum"]);
+
+    assert_eq!(msgpack!(["admin", "user"]), schema["properties"]["role"]["enum"]);
+}
+
+#[test]
+fn infer_array_items() {
+    let schema = infer(&users()).to_value();
+
+    let tags = &schema["properties"]["tags"];
+    assert_eq!(msgpack!("array"), tags["type"]);
+    assert_eq!(msgpack!(0), tags["min_len"]);
+    assert_eq!(msgpack!(2), tags["max_len"]);
+    assert_eq!(msgpack!(0.75), tags["mean_len"]);
+    assert_eq!(msgpack!("str"), tags["items"]["type"]);
+    assert_eq!(msgpack!(3), tags["items"]["count"]);
+}
+
+#[test]
+fn infer_numeric_bounds() {
+    let schema = infer(&[msgpack!([-5, 2.5, 10]), msgpack!([-7.5])]).to_value();
+
+    let items = &schema["items"];
+    assert_eq!(msgpack!(["int", "float"]), items["type"]);
+    assert_eq!(msgpack!(-7.5), items["min"]);
+    assert_eq!(msgpack!(10), items["max"]);
+}
+
+#[test]
+fn infer_large_integer_and_float_bounds() {
+    let schema = infer(&[Value::U64(u64::max_value()), Value::F64(1.5)]).to_value();
+
+    assert_eq!(msgpack!(1.5), schema["min"]);
+    assert_eq!(Value::U64(u64::max_value()), schema["max"]);
+}
+
+#[test]
+fn infer_no_bounds_with_nan() {
+    // Validation rejects NaNs against any bound, so the schema would reject its own sample.
+    let schema = infer(&[Value::F64(::std::f64::NAN), Value::F64(1.0)]).to_value();
+
+    assert_eq!(Value::Nil, schema["min"]);
+    assert_eq!(Value::Nil, schema["max"]);
+}
+
+#[test]
+fn infer_accepts_samples() {
+    let samples = vec![
+        vec![Value::U64(u64::max_value()), Value::F64(1.5)],
+        vec![Value::I64(i64::min_value()), Value::F64(-1e30), Value::F32(3.5)],
+        vec![Value::U64((1 << 53) + 1), Value::F64((1u64 << 53) as f64)],
+        vec![Value::F64(::std::f64::NAN), Value::I64(-1), Value::F32(::std::f32::NAN)],
+        vec![Value::F64(::std::f64::NEG_INFINITY), Value::U64(0)],
+        vec![msgpack!({"a": [1, 2.5]}), msgpack!({"a": [(u64::max_value())], "b": nil})],
+    ];
+
+    for vals in &samples {
+        let schema = schema(infer(vals).to_value());
+        for val in vals {
+            assert_eq!(Ok(()), schema.validate(val), "{:?} in {:?}", val, vals);
+        }
+    }
+}
+
+#[test]
+fn infer_enum_overflow() {
+    let vals = vec![msgpack!("a"), msgpack!("b"), msgpack!("c"), msgpack!("a")];
+
+    let mut inference = Inference::new();
+    inference.set_max_enum_values(2);
+    for val in &vals {
+        inference.add(val);
+    }
+    assert_eq!(Value::Nil, inference.to_value()["enum"]);
+
+    // Distinct strings are not an enumeration.
+    assert_eq!(Value::Nil, infer(&vals[..3]).to_value()["enum"]);
+    assert_eq!(msgpack!(["a", "b", "c"]), infer(&vals).to_value()["enum"]);
+    assert_eq!(msgpack!([nil, "a"]), infer(&[msgpack!("a"), msgpack!("a"), Value::Nil])
+                                         .to_value()["enum"]);
+}
+
+#[test]
+fn infer_merge_equals_add() {
+    let vals = users();
+
+    let mut merged = infer(&vals[..1]);
+    merged.merge(&infer(&vals[1..]));
+
+    assert_eq!(infer(&vals), merged);
+    assert_eq!(infer(&vals).to_value(), merged.to_value());
+}
+
+#[test]
+fn infer_non_string_keys() {
+    let schema = infer(&[Value::Map(vec![(Value::from(1), Value::from("x")),
+                                         (Value::from("k"), Value::Nil)])])
+        .to_value();
+
+    assert_eq!(msgpack!(["k"]), schema["required"]);
+    assert_eq!(msgpack!("nil"), schema["properties"]["k"]["type"]);
+    assert_eq!(msgpack!("str"), schema["additional"]["type"]);
+}
+
+#[test]
+fn infer_bin_and_ext() {
+    let schema = infer(&[Value::Binary(vec![1, 2]),
+                         Value::Ext(-1, vec![0; 4]),
+                         Value::Ext(5, vec![0; 8])])
+        .to_value();
+
+    assert_eq!(msgpack!(["bin", "ext"]), schema["type"]);
+    assert_eq!(msgpack!([-1, 5]), schema["ext_types"]);
+    assert_eq!(msgpack!(2), schema["min_len"]);
+    assert_eq!(msgpack!(8), schema["max_len"]);
+}
+
+#[test]
+fn infer_empty() {
+    let inference = Inference::new();
+
+    assert_eq!(0, inference.samples());
+    assert_eq!(msgpack!({"count": 0}), inference.to_value());
+}
+
+#[test]
+fn infer_report() {
+    let expected = "\
+$: map len 4..=5 -> struct
+$.age: nil | int 30..=30; optional (2 of 4) -> Option<u8>
+$.id: int 1..=70000 -> u32
+$.name: str len 2..=3 (mean 2.8) -> String
+$.role: str len 4..=5 (mean 4.5); one of \"admin\", \"user\" -> String
+$.tags: array len 0..=2 -> Vec<String>
+$.tags[*]: str len 1..=1 (mean 1.0) -> String
+";
+    assert_eq!(expected, infer(&users()).to_string());
+}
+
+fn schema(val: Value) -> Schema {
+    Schema::from_value(&val).unwrap()
+}
+
+fn violations(schema: &Schema, val: &Value) -> Vec<String> {
+    let res = schema.validate(val);
+    // Borrowed values must produce the same result.
+    assert_eq!(res, schema.validate_ref(&val.as_ref()));
+    res.err().unwrap_or_default().iter().map(Violation::to_string).collect()
+}
+
+#[test]
+fn validate_types() {
+    let schema = schema(msgpack!({"type": ["bin", "ext"]}));
+
+    assert_eq!(Ok(()), schema.validate(&Value::Binary(vec![1])));
+    assert_eq!(Ok(()), schema.validate_ref(&ValueRef::Ext(1, &[0])));
+    assert_eq!(vec![ViolationKind::Type { expected: vec!["bin", "ext"], found: "str" }],
+               schema.validate(&msgpack!("x"))
+                   .unwrap_err()
+                   .into_iter()
+                   .map(|v| v.kind)
+                   .collect::<Vec<_>>());
+
+    // Integers are not floats.
+    let schema = self::schema(msgpack!({"type": "float"}));
+    assert_eq!(Ok(()), schema.validate(&Value::F32(1.0)));
+    assert_eq!(vec!["$: expected float, found int"], violations(&schema, &msgpack!(1)));
+}
+
+#[test]
+fn validate_collects_all_violations() {
+    let schema = schema(msgpack!({
+        "type": "map",
+        "properties": {
+            "id": {"type": "int", "min": 1, "max": 1000},
+            "name": {"type": "str", "min_len": 1, "max_len": 4},
+            "items": {"type": "array", "max_len": 2, "items": {"type": "map", "required": ["sku"]}},
+        },
+        "required": ["id", "name", "email"],
+    }));
+
+    let val = msgpack!({
+        "id": 1001,
+        "name": "",
+        "items": [{"sku": 1}, {"qty": 2}, {}],
+    });
+    assert_eq!(vec![
+        "$: missing required key \"email\"",
+        "$.id: greater than the maximum 1000",
+        "$.name: shorter than the minimum length 1",
+        "$.items: longer than the maximum length 2",
+        "$.items[1]: missing required key \"sku\"",
+        "$.items[2]: missing required key \"sku\"",
+    ], violations(&schema, &val));
+
+    assert_eq!(Vec::<String>::new(),
+               violations(&schema, &msgpack!({"id": 1, "name": "ann", "email": nil})));
+}
+
+#[test]
+fn validate_additional_keys() {
+    let deny = schema(msgpack!({"properties": {"a": {}}, "additional": false}));
+    let val = Value::Map(vec![(Value::from("a"), Value::from(1)),
+                              (Value::from("b c"), Value::from(2)),
+                              (Value::from(-3), Value::from(3)),
+                              (Value::Nil, Value::from(4))]);
+
+    assert_eq!(vec!["$['b c']: key is not allowed",
+                    "$[-3]: key is not allowed",
+                    "$[*]: key is not allowed"],
+               violations(&deny, &val));
+
+    let additional = schema(msgpack!({"properties": {"a": {}}, "additional": {"type": "str"}}));
+    assert_eq!(vec!["$['b c']: expected str, found int",
+                    "$[-3]: expected str, found int",
+                    "$[*]: expected str, found int"],
+               violations(&additional, &val));
+
+    let allow = schema(msgpack!({"properties": {"a": {"type": "str"}}, "additional": true}));
+    assert_eq!(vec!["$.a: expected str, found int"], violations(&allow, &val));
+}
+
+#[test]
+fn validate_numeric_bounds() {
+    let schema = schema(msgpack!({"min": -1.5, "max": 10}));
+
+    for val in &[msgpack!(-1), msgpack!(10), msgpack!(-1.5), msgpack!(9.5), Value::F32(0.0)] {
+        assert_eq!(Ok(()), schema.validate(val), "{}", val);
+    }
+    assert_eq!(vec!["$: less than the minimum -1.5"], violations(&schema, &msgpack!(-2)));
+    assert_eq!(vec!["$: greater than the maximum 10"], violations(&schema, &msgpack!(11)));
+    assert_eq!(vec!["$: greater than the maximum 10"],
+               violations(&schema, &Value::U64(u64::max_value())));
+    assert_eq!(2, violations(&schema, &Value::F64(::std::f64::NAN)).len());
+
+    // Bounds do not constrain other types.
+    assert_eq!(Ok(()), schema.validate(&msgpack!("-100")));
+}
+
+#[test]
+fn validate_lengths() {
+    let schema = schema(msgpack!({"min_len": 1, "max_len": 2}));
+
+    assert_eq!(Ok(()), schema.validate(&msgpack!("ab")));
+    assert_eq!(Ok(()), schema.validate(&msgpack!([1])));
+    assert_eq!(Ok(()), schema.validate(&msgpack!({"a": 1, "b": 2})));
+    assert_eq!(Ok(()), schema.validate(&Value::Ext(1, vec![0])));
+    assert_eq!(Ok(()), schema.validate(&msgpack!(100)));
+
+    // Strings are measured in bytes.
+    assert_eq!(vec!["$: longer than the maximum length 2"],
+               violations(&schema, &msgpack!("ab€")));
+    assert_eq!(vec!["$: shorter than the minimum length 1"],
+               violations(&schema, &Value::Binary(vec![])));
+}
+
+#[test]
+fn validate_pattern() {
+    let schema = schema(msgpack!({"pattern": "id-?*.\\*"}));
+
+    for val in &["id-1.*", "id-12.*", "id-€abc.*"] {
+        assert_eq!(Ok(()), schema.validate(&msgpack!(*val)), "{}", val);
+    }
+    for val in &["id-.*", "id-1.x", "id-1.*x", "xid-1.*"] {
+        assert_eq!(vec![format!("$: does not match the pattern {:?}", "id-?*.\\*")],
+                   violations(&schema, &msgpack!(*val)),
+                   "{}",
+                   val);
+    }
+
+    let any = self::schema(msgpack!({"pattern": "*a*b*"}));
+    assert_eq!(Ok(()), any.validate(&msgpack!("xxaxxbxx")));
+    assert_eq!(Ok(()), any.validate(&msgpack!("ab")));
+    assert!(any.validate(&msgpack!("ba")).is_err());
+}
+
+#[test]
+fn validate_invalid_utf8_string() {
+    let val = Value::String(Utf8String::from_bytes(vec![0x61, 0xff, 0x62]));
+
+    let schema = schema(msgpack!({"type": "str", "max_len": 3}));
+    assert_eq!(Ok(()), schema.validate(&val));
+    assert_eq!(Ok(()), schema.validate_ref(&val.as_ref()));
+
+    let schema = self::schema(msgpack!({"pattern": "*"}));
+    assert_eq!(vec![format!("$: does not match the pattern {:?}", "*")],
+               violations(&schema, &val));
+}
+
+#[test]
+fn validate_enum_and_ext_types() {
+    let schema = schema(msgpack!({"enum": [nil, 1, "a"], "ext_types": [-1]}));
+
+    assert_eq!(Ok(()), schema.validate(&Value::I64(1)));
+    assert_eq!(Ok(()), schema.validate(&msgpack!("a")));
+    assert_eq!(vec!["$: not one of the allowed values"], violations(&schema, &msgpack!("b")));
+
+    let ext = self::schema(msgpack!({"ext_types": [-1, 5]}));
+    assert_eq!(Ok(()), ext.validate(&Value::Ext(5, vec![])));
+    assert_eq!(vec!["$: extension type 4 is not allowed"],
+               violations(&ext, &Value::Ext(4, vec![])));
+}
+
+#[test]
+fn validate_inferred_schema() {
+    let vals = users();
+    let schema = schema(infer(&vals).to_value());
+
+    for val in &vals {
+        assert_eq!(Ok(()), schema.validate(val));
+    }
+    assert_eq!(vec!["$: shorter than the minimum length 4",
+                    "$: missing required key \"tags\"",
+                    "$.role: not one of the allowed values"],
+               violations(&schema, &msgpack!({"id": 2, "name": "ed", "role": "root"})));
+}
+
+#[test]
+fn schema_errors() {
+    assert_eq!(Err(Error::NotAMap("$".to_owned())), Schema::from_value(&msgpack!([])));
+    assert_eq!(Err(Error::NotAMap("$.properties.a.items".to_owned())),
+               Schema::from_value(&msgpack!({"properties": {"a": {"items": 1}}})));
+    assert_eq!(Err(Error::InvalidKeyword("$.min".to_owned())),
+               Schema::from_value(&msgpack!({"min": "1"})));
+    assert_eq!(Err(Error::InvalidKeyword("$.ext_types".to_owned())),
+               Schema::from_value(&msgpack!({"ext_types": [128]})));
+    assert_eq!(Err(Error::InvalidKeyword("$.pattern".to_owned())),
+               Schema::from_value(&msgpack!({"pattern": "a\\"})));
+    assert_eq!(Err(Error::UnknownType("$.type".to_owned(), "number".to_owned())),
+               Schema::from_value(&msgpack!({"type": ["int", "number"]})));
+
+    // Unknown keys are annotations.
+    assert_eq!(Ok(()), schema(msgpack!({"count": 2, "title": "x"})).validate(&msgpack!(1)));
+}
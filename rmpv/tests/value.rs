@@ -1,6 +1,7 @@
+#[macro_use]
 extern crate rmpv;
 
-use rmpv::Value;
+use rmpv::{Utf8String, Value};
 
 #[test]
 fn display_nil() {
@@ -16,24 +17,60 @@ fn display_bool() {
 #[test]
 fn display_int() {
     assert_eq!("42", format!("{}", Value::U64(42)));
-    assert_eq!("42", format!("{}", Value::I64(42)));
+    assert_eq!("-42", format!("{}", Value::I64(-42)));
+    assert_eq!("i64(42)", format!("{}", Value::I64(42)));
 }
 
 #[test]
 fn display_float() {
-    assert_eq!("3.1415", format!("{}", Value::F32(3.1415)));
+    assert_eq!("f32(3.1415)", format!("{}", Value::F32(3.1415)));
     assert_eq!("3.1415", format!("{}", Value::F64(3.1415)));
+    assert_eq!("1.0", format!("{}", Value::F64(1.0)));
+    assert_eq!("-0.0", format!("{}", Value::F64(-0.0)));
+    assert_eq!("1e300", format!("{}", Value::F64(1e300)));
+    assert_eq!("NaN", format!("{}", Value::F64(::std::f64::NAN)));
+    assert_eq!("-Infinity", format!("{}", Value::F64(::std::f64::NEG_INFINITY)));
+    assert_eq!("f32(Infinity)", format!("{}", Value::F32(::std::f32::INFINITY)));
 }
 
 #[test]
 fn display_string() {
-    assert_eq!("\"le string\"", format!("{}", Value::String("le string".to_owned())));
+    assert_eq!("\"le string\"", format!("{}", Value::String("le string".into())));
+    assert_eq!(r#""\"q\" \\ \n\t \u0000 é""#, format!("{}", Value::from("\"q\" \\ \n\t \0 é")));
+}
+
+#[test]
+fn display_invalid_utf8_string() {
+    let val = Value::String(Utf8String::from_bytes(vec![0x61, 0xff, 0x0a, 0xc3, 0xa9, 0xc3]));
+    assert_eq!(r#""a\xff\né\xc3""#, format!("{}", val));
+    assert_eq!(r#""a\xff\né\xc3""#, format!("{}", val.as_ref()));
+}
+
+#[test]
+fn invalid_utf8_string() {
+    use std::convert::TryFrom;
+    use rmpv::TryFromValueError;
+
+    let mut val = Value::String(Utf8String::from_bytes(vec![0x61, 0xff]));
+
+    assert!(!val.is_str());
+    assert_eq!(None, val.as_str());
+    assert_eq!(None, val.as_string_mut());
+    assert_eq!(None, val.as_ref().as_str());
+    assert_eq!(Value::String(Utf8String::from_bytes(vec![0x61, 0xff])), val);
+    assert!(Value::from("a") < val && val < Value::Binary(vec![]));
+    assert_eq!(None, val.clone().into_string());
+    assert_eq!(Err(TryFromValueError::TypeMismatch("string", "invalid UTF-8 string")),
+               String::try_from(val.clone()));
+
+    assert_eq!("a\u{fffd}", Utf8String::from_bytes(vec![0x61, 0xff]).to_string());
+    assert_eq!(Utf8String::from("é"), Utf8String::from_bytes(vec![0xc3, 0xa9]));
 }
 
 #[test]
 fn display_binary() {
-    assert_eq!("[108, 101, 32, 115, 116, 114, 105, 110, 103]", format!("{}",
-        Value::Binary(b"le string".to_vec())));
+    assert_eq!("h'6c6520737472696e67'", format!("{}", Value::Binary(b"le string".to_vec())));
+    assert_eq!("h''", format!("{}", Value::Binary(vec![])));
 }
 
 #[test]
@@ -51,11 +88,20 @@ fn display_map() {
         (Value::Boolean(true), Value::Boolean(false))])));
 }
 
+#[test]
+fn display_pretty() {
+    let val = msgpack!({"a": [1, {}], "b": [], 42: {"c": nil}});
+
+    assert_eq!("{\n  \"a\": [\n    1,\n    {}\n  ],\n  \"b\": [],\n  42: {\n    \"c\": nil\n  }\n}",
+               format!("{:#}", val));
+    assert_eq!("1", format!("{:#}", Value::from(1)));
+}
+
 #[test]
 fn display_ext() {
-    assert_eq!("[1, []]", format!("{}", Value::Ext(1, vec![])));
-    assert_eq!("[1, [100]]", format!("{}", Value::Ext(1, vec![100])));
-    assert_eq!("[1, [100, 42]]", format!("{}", Value::Ext(1, vec![100, 42])));
+    assert_eq!("ext(1, h'')", format!("{}", Value::Ext(1, vec![])));
+    assert_eq!("ext(1, h'64')", format!("{}", Value::Ext(1, vec![100])));
+    assert_eq!("ext(-1, h'642a')", format!("{}", Value::Ext(-1, vec![100, 42])));
 }
 
 #[test]
@@ -148,3 +194,468 @@ fn monadic_index() {
     assert!(val[2].is_nil());
     assert!(val[1][2][3][4][5].is_nil());
 }
+
+#[test]
+fn index_by_str() {
+    let val = Value::Map(vec![
+        (Value::String("user".into()), Value::Map(vec![
+            (Value::String("name".into()), Value::String("John".into())),
+        ])),
+        (Value::U64(42), Value::Nil),
+    ]);
+
+    assert_eq!(Value::String("John".into()), val["user"]["name"]);
+    assert_eq!(Value::Nil, val["user"]["age"]);
+    assert_eq!(Value::Nil, val["missing"]["name"]);
+    assert_eq!(Value::Nil, Value::Array(vec![])["user"]);
+}
+
+#[test]
+fn get_by_str() {
+    let mut val = Value::Map(vec![
+        (Value::String("a".into()), Value::from(1)),
+        (Value::String("a".into()), Value::from(2)),
+    ]);
+
+    assert_eq!(Some(&Value::from(1)), val.get("a"));
+    assert!(val.contains_key("a"));
+    assert!(!val.contains_key("b"));
+
+    *val.get_mut("a").unwrap() = Value::from(3);
+    assert_eq!(Value::from(3), val["a"]);
+    assert!(val.get_mut("b").is_none());
+}
+
+#[test]
+fn key_index() {
+    let mut map: Vec<(Value, Value)> = (0..1000)
+        .map(|i| (Value::String(format!("key{}", i).into()), Value::from(i)))
+        .collect();
+    map.push((Value::U64(1), Value::Nil));
+    map.push((Value::String("key0".into()), Value::Nil));
+    let val = Value::Map(map);
+
+    let index = val.key_index().unwrap();
+    assert_eq!(1000, index.len());
+    assert_eq!(Some(&Value::from(0)), index.get("key0"));
+    assert_eq!(Some(&Value::from(999)), index.get("key999"));
+    assert!(!index.contains_key("key1000"));
+
+    assert!(Value::Nil.key_index().is_none());
+}
+
+#[test]
+fn mutate_array_in_place() {
+    let mut val = Value::Array(vec![Value::from(1), Value::from("a")]);
+
+    val.as_array_mut().unwrap().push(Value::Nil);
+    val.as_array_mut().unwrap()[1].as_string_mut().unwrap().push('b');
+
+    assert_eq!(Value::Array(vec![Value::from(1), Value::from("ab"), Value::Nil]), val);
+    assert!(Value::Nil.as_array_mut().is_none());
+}
+
+#[test]
+fn take_leaves_nil() {
+    let mut val = Value::Binary(vec![1, 2]);
+
+    assert_eq!(Some(vec![1, 2]), val.take().into_bytes());
+    assert_eq!(Value::Nil, val);
+}
+
+#[test]
+fn into_conversions() {
+    assert_eq!(Some("a".to_owned()), Value::from("a").into_string());
+    assert_eq!(Some(vec![Value::Nil]), Value::Array(vec![Value::Nil]).into_array());
+    assert_eq!(Some(vec![(Value::Nil, Value::Nil)]),
+               Value::Map(vec![(Value::Nil, Value::Nil)]).into_map());
+    assert_eq!(Some((1, vec![2])), Value::Ext(1, vec![2]).into_ext());
+    assert_eq!(None, Value::Nil.into_string());
+    assert_eq!(None, Value::from("a").into_bytes());
+}
+
+#[test]
+fn map_insert_remove() {
+    let mut val = Value::Map(vec![
+        (Value::from("a"), Value::from(1)),
+        (Value::from(2), Value::from(2)),
+        (Value::from("a"), Value::from(3)),
+    ]);
+
+    assert_eq!(Some(Value::from(1)), val.insert("a", 4));
+    assert_eq!(None, val.insert("b", 5));
+    assert_eq!(Value::Map(vec![
+        (Value::from("a"), Value::from(4)),
+        (Value::from(2), Value::from(2)),
+        (Value::from("a"), Value::from(3)),
+        (Value::from("b"), Value::from(5)),
+    ]), val);
+
+    assert_eq!(Some(Value::from(4)), val.remove("a"));
+    assert_eq!(Some(Value::from(2)), val.remove(2));
+    assert_eq!(None, val.remove("a"));
+    assert_eq!(Value::Map(vec![(Value::from("b"), Value::from(5))]), val);
+
+    assert_eq!(None, Value::Nil.remove("a"));
+}
+
+#[test]
+#[should_panic]
+fn map_insert_into_non_map() {
+    Value::Nil.insert("a", 1);
+}
+
+#[test]
+fn macro_scalars() {
+    assert_eq!(Value::Nil, msgpack!(nil));
+    assert_eq!(Value::Nil, msgpack!(null));
+    assert_eq!(Value::Boolean(true), msgpack!(true));
+    assert_eq!(Value::U64(42), msgpack!(42));
+    assert_eq!(Value::I64(-42), msgpack!(-42));
+    assert_eq!(Value::F64(2.5), msgpack!(2.5));
+    assert_eq!(Value::F32(2.5), msgpack!(2.5f32));
+    assert_eq!(Value::String("le message".into()), msgpack!("le message"));
+    assert_eq!(Value::Binary(vec![1, 2]), msgpack!(bin([1u8, 2])));
+    assert_eq!(Value::Ext(-1, vec![0xff]), msgpack!(ext(-1, b"\xff")));
+}
+
+#[test]
+fn macro_nested() {
+    let id = 100500u64;
+    let inner = Value::Array(vec![]);
+
+    let val = msgpack!({
+        "a": 1,
+        "b": [true, null, 2.5, [], {}, bin(vec![1u8]), ext(1, vec![2u8])],
+        "c": {"id": id, "inner": inner.clone()},
+        (1 + 1): "two",
+        nil: -1
+    });
+
+    let expected = Value::Map(vec![
+        (Value::from("a"), Value::U64(1)),
+        (Value::from("b"), Value::Array(vec![
+            Value::Boolean(true),
+            Value::Nil,
+            Value::F64(2.5),
+            Value::Array(vec![]),
+            Value::Map(vec![]),
+            Value::Binary(vec![1]),
+            Value::Ext(1, vec![2]),
+        ])),
+        (Value::from("c"), Value::Map(vec![
+            (Value::from("id"), Value::U64(100500)),
+            (Value::from("inner"), inner),
+        ])),
+        (Value::U64(2), Value::from("two")),
+        (Value::Nil, Value::I64(-1)),
+    ]);
+
+    assert_eq!(expected, val);
+}
+
+#[test]
+fn macro_trailing_commas() {
+    assert_eq!(Value::Array(vec![Value::U64(1), Value::U64(2)]), msgpack!([1, 2,]));
+    assert_eq!(Value::Map(vec![(Value::from("a"), Value::Nil)]), msgpack!({"a": nil,}));
+}
+
+#[test]
+fn macro_large_literals() {
+    let val = msgpack!({
+        "k0": nil, "k1": [1], "k2": -2, "k3": bin([3u8]), "k4": nil, "k5": [5], "k6": -6, "k7":
+        bin([7u8]), "k8": nil, "k9": [9], "k10": -10, "k11": bin([11u8]), "k12": nil, "k13":
+        [13], "k14": -14, "k15": bin([15u8]), "k16": nil, "k17": [17], "k18": -18, "k19":
+        bin([19u8]), "k20": nil, "k21": [21], "k22": -22, "k23": bin([23u8]), "k24": nil, "k25":
+        [25], "k26": -26, "k27": bin([27u8]), "k28": nil, "k29": [29], "k30": -30, "k31":
+        bin([31u8]), "k32": nil, "k33": [33], "k34": -34, "k35": bin([35u8]), "k36": nil, "k37":
+        [37], "k38": -38, "k39": bin([39u8]), "k40": nil, "k41": [41], "k42": -42, "k43":
+        bin([43u8]), "k44": nil, "k45": [45], "k46": -46, "k47": bin([47u8]), "k48": nil, "k49":
+        [49], "k50": -50, "k51": bin([51u8]), "k52": nil, "k53": [53], "k54": -54, "k55":
+        bin([55u8]), "k56": nil, "k57": [57], "k58": -58, "k59": bin([59u8])
+    });
+
+    let map = val.as_map().unwrap();
+    assert_eq!(60, map.len());
+    assert_eq!(Value::Nil, val["k0"]);
+    assert_eq!(msgpack!([1]), val["k1"]);
+    assert_eq!(Value::I64(-2), val["k2"]);
+    assert_eq!(Value::Binary(vec![59]), val["k59"]);
+
+    let val = msgpack!([
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+        24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45,
+        46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67,
+        68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89,
+        90, 91, 92, 93, 94, 95, 96, 97, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108,
+        109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 120, 121, 122, 123, 124, 125,
+        126, 127, 128, 129, 130, 131, 132, 133, 134, 135, 136, 137, 138, 139, 140, 141, 142,
+        143, 144, 145, 146, 147, 148, 149, 150, 151, 152, 153, 154, 155, 156, 157, 158, 159,
+        160, 161, 162, 163, 164, 165, 166, 167, 168, 169, 170, 171, 172, 173, 174, 175, 176,
+        177, 178, 179, 180, 181, 182, 183, 184, 185, 186, 187, 188, 189, 190, 191, 192, 193,
+        194, 195, 196, 197, 198, 199
+    ]);
+
+    assert_eq!(200, val.as_array().unwrap().len());
+    assert_eq!(Value::U64(199), val[199]);
+}
+
+#[test]
+fn from_containers() {
+    use std::collections::{BTreeMap, HashMap};
+
+    assert_eq!(Value::Binary(vec![1, 2]), Value::from(vec![1u8, 2]));
+    assert_eq!(Value::Binary(vec![1, 2]), Value::from(&[1u8, 2][..]));
+    assert_eq!(Value::Array(vec![Value::Nil]), Value::from(vec![Value::Nil]));
+    assert_eq!(Value::Nil, Value::from(None::<u32>));
+    assert_eq!(Value::U64(42), Value::from(Some(42)));
+
+    let mut map = BTreeMap::new();
+    map.insert("b", 2);
+    map.insert("a", 1);
+    let expected = Value::Map(vec![
+        (Value::from("a"), Value::from(1)),
+        (Value::from("b"), Value::from(2)),
+    ]);
+    assert_eq!(expected, Value::from(map));
+
+    let mut map = HashMap::new();
+    map.insert(1, "a");
+    assert_eq!(Value::Map(vec![(Value::from(1), Value::from("a"))]), Value::from(map));
+}
+
+#[test]
+fn from_iterator() {
+    let array: Value = vec!["a", "b"].into_iter().collect();
+    assert_eq!(Value::Array(vec![Value::from("a"), Value::from("b")]), array);
+
+    let map: Value = (0..2).map(|i| (i, i * 2)).collect();
+    assert_eq!(Value::Map(vec![(Value::from(0), Value::from(0)), (Value::from(1), Value::from(2))]),
+               map);
+}
+
+#[test]
+fn try_from_scalars() {
+    use std::convert::TryFrom;
+    use rmpv::TryFromValueError;
+
+    assert_eq!(Ok(true), bool::try_from(Value::Boolean(true)));
+    assert_eq!(Ok(42u8), u8::try_from(&Value::U64(42)));
+    assert_eq!(Ok(-42i8), i8::try_from(&Value::I64(-42)));
+    assert_eq!(Ok(1.5f32), f32::try_from(Value::F64(1.5)));
+    assert_eq!(Ok(1.5f64), f64::try_from(Value::F32(1.5)));
+    assert_eq!(Ok("le message"), <&str>::try_from(&Value::from("le message")));
+    assert_eq!(Ok("le message".to_owned()), String::try_from(Value::from("le message")));
+
+    assert_eq!(Err(TryFromValueError::OutOfRange("u8")), u8::try_from(Value::U64(256)));
+    assert_eq!(Err(TryFromValueError::OutOfRange("u64")), u64::try_from(Value::I64(-1)));
+    assert_eq!(Err(TryFromValueError::OutOfRange("f32")), f32::try_from(Value::F64(0.1)));
+    assert_eq!(Err(TryFromValueError::TypeMismatch("u32", "string")),
+               u32::try_from(Value::from("42")));
+    assert_eq!("invalid type: expected u32, found string",
+               format!("{}", u32::try_from(Value::from("42")).unwrap_err()));
+}
+
+#[test]
+fn try_from_containers() {
+    use std::collections::{BTreeMap, HashMap};
+    use std::convert::TryFrom;
+    use rmpv::TryFromValueError;
+
+    assert_eq!(Ok(vec![1u8, 2]), Vec::<u8>::try_from(Value::Binary(vec![1, 2])));
+    assert_eq!(Ok(vec![Value::Nil]), Vec::<Value>::try_from(Value::Array(vec![Value::Nil])));
+    assert_eq!(Ok(&[Value::Nil][..]), <&[Value]>::try_from(&Value::Array(vec![Value::Nil])));
+
+    let val = Value::Map(vec![(Value::from("a"), Value::from(1)), (Value::from("b"), Value::Nil)]);
+
+    let map = HashMap::<String, Value>::try_from(val.clone()).unwrap();
+    assert_eq!(Some(&Value::Nil), map.get("b"));
+
+    assert_eq!(Err(TryFromValueError::TypeMismatch("u32", "nil")),
+               BTreeMap::<String, u32>::try_from(val));
+}
+
+#[test]
+fn eq_floats_totally() {
+    use std::f64;
+
+    assert_eq!(Value::F64(f64::NAN), Value::F64(-f64::NAN));
+    assert_eq!(Value::F64(0.0), Value::F64(-0.0));
+    assert!(Value::F64(f64::NAN) > Value::F64(f64::INFINITY));
+    assert!(Value::F32(1.0) != Value::F64(1.0));
+    assert!(Value::U64(1) != Value::I64(1));
+}
+
+#[test]
+fn cmp_across_kinds() {
+    let mut vals = vec![
+        Value::Ext(1, vec![]),
+        Value::Map(vec![]),
+        Value::Array(vec![]),
+        Value::Binary(vec![]),
+        Value::from("a"),
+        Value::F64(-1.0),
+        Value::F32(0.5),
+        Value::U64(2),
+        Value::I64(1),
+        Value::U64(1),
+        Value::I64(-5),
+        Value::Boolean(false),
+        Value::Nil,
+    ];
+    vals.sort();
+
+    assert_eq!(vec![
+        Value::Nil,
+        Value::Boolean(false),
+        Value::I64(-5),
+        Value::I64(1),
+        Value::U64(1),
+        Value::U64(2),
+        Value::F64(-1.0),
+        Value::F32(0.5),
+        Value::from("a"),
+        Value::Binary(vec![]),
+        Value::Array(vec![]),
+        Value::Map(vec![]),
+        Value::Ext(1, vec![]),
+    ], vals);
+}
+
+#[test]
+fn use_as_hash_key() {
+    use std::collections::{BTreeSet, HashSet};
+    use std::f64;
+
+    let mut set = HashSet::new();
+    assert!(set.insert(Value::F64(f64::NAN)));
+    assert!(!set.insert(Value::F64(-f64::NAN)));
+    assert!(set.insert(Value::F64(0.0)));
+    assert!(!set.insert(Value::F64(-0.0)));
+    assert!(set.insert(Value::Array(vec![Value::from(1)])));
+    assert!(!set.insert(Value::Array(vec![Value::from(1)])));
+    assert_eq!(3, set.len());
+
+    let set: BTreeSet<_> = vec![Value::from("b"), Value::from(1), Value::from("a")]
+        .into_iter()
+        .collect();
+    assert_eq!(vec![Value::from(1), Value::from("a"), Value::from("b")],
+               set.into_iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn pointer() {
+    let val = msgpack!({
+        "users": [{"name": "John"}, {"name": "Jane"}],
+        "a/b": 1,
+        "m~n": 2,
+        "": 3,
+        42: "int",
+        -1: "neg",
+        "7": "str",
+        7: "seven",
+    });
+
+    assert_eq!(Some(&val), val.pointer(""));
+    assert_eq!(Some(&Value::from("Jane")), val.pointer("/users/1/name"));
+    assert_eq!(Some(&Value::from(1)), val.pointer("/a~1b"));
+    assert_eq!(Some(&Value::from(2)), val.pointer("/m~0n"));
+    assert_eq!(Some(&Value::from(3)), val.pointer("/"));
+    assert_eq!(Some(&Value::from("int")), val.pointer("/42"));
+    assert_eq!(Some(&Value::from("neg")), val.pointer("/-1"));
+    assert_eq!(Some(&Value::from("str")), val.pointer("/7"));
+
+    assert_eq!(None, val.pointer("users"));
+    assert_eq!(None, val.pointer("/users/2"));
+    assert_eq!(None, val.pointer("/users/01"));
+    assert_eq!(None, val.pointer("/users/-"));
+    assert_eq!(None, val.pointer("/042"));
+    assert_eq!(None, val.pointer("/a~1b/0"));
+}
+
+#[test]
+fn pointer_mut() {
+    let mut val = msgpack!({"users": [{"name": "John"}]});
+
+    val.pointer_mut("/users/0").unwrap().insert("age", 42);
+    *val.pointer_mut("/users/0/name").unwrap() = Value::from("Jane");
+    assert_eq!(None, val.pointer_mut("/users/1"));
+
+    assert_eq!(msgpack!({"users": [{"name": "Jane", "age": 42}]}), val);
+}
+
+#[test]
+fn parse_roundtrip() {
+    use std::f64;
+
+    let vals = vec![
+        Value::Nil,
+        Value::Boolean(true),
+        Value::U64(u64::max_value()),
+        Value::I64(i64::min_value()),
+        Value::I64(42),
+        Value::F32(3.1415),
+        Value::F32(-0.0),
+        Value::F32(1e-40),
+        Value::F64(0.1),
+        Value::F64(-1e-300),
+        Value::F64(f64::NAN),
+        Value::F64(f64::INFINITY),
+        Value::from("\"q\" \\ \u{1} \u{7f} \u{1F600} é"),
+        Value::String(Utf8String::from_bytes(vec![0x22, 0x80, 0x5c, 0xf0, 0x9f, 0x98])),
+        Value::Binary(vec![0, 1, 254, 255]),
+        Value::Ext(-128, vec![0xde, 0xad]),
+        msgpack!({"a": [1, -2, 3.5, {}], nil: [], [1]: {"b": bin([])}}),
+    ];
+
+    for val in vals {
+        assert_eq!(val, format!("{}", val).parse::<Value>().unwrap());
+        assert_eq!(val, format!("{:#}", val).parse::<Value>().unwrap());
+    }
+}
+
+#[test]
+fn parse_text_notation() {
+    assert_eq!(Ok(Value::Nil), "null".parse());
+    assert_eq!(Ok(Value::I64(-1)), "i64(-1)".parse());
+    assert_eq!(Ok(Value::F32(1.0)), "f32( 1 )".parse());
+    assert_eq!(Ok(Value::Binary(vec![0, 255])), "b64'AP8='".parse());
+    assert_eq!(Ok(Value::Binary(b"le message".to_vec())), "b64'bGUgbWVzc2FnZQ'".parse());
+    assert_eq!(Ok(Value::Ext(2, vec![0, 255])), "ext(2, b64'AP8=')".parse());
+    assert_eq!(Ok(Value::from("\u{1F600}/")), r#""\ud83d\ude00\/""#.parse());
+    assert_eq!(Ok(Value::from("é")), r#""\xc3\xA9""#.parse());
+    assert_eq!(Ok(msgpack!({"a": [1, 2]})), " { \"a\" : [ 1 , 2 ] } ".parse());
+}
+
+#[test]
+fn parse_text_errors() {
+    use rmpv::text::Error;
+
+    assert_eq!(Err(Error::UnexpectedEnd), "[1, 2".parse::<Value>());
+    assert_eq!(Err(Error::UnexpectedChar('x', 2)), "1 x".parse::<Value>());
+    assert_eq!(Err(Error::UnexpectedChar(']', 4)), "[1, ]".parse::<Value>());
+    assert_eq!(Err(Error::InvalidNumber(0)), "18446744073709551616".parse::<Value>());
+    assert_eq!(Err(Error::InvalidNumber(0)), "inf".parse::<Value>());
+    assert_eq!(Err(Error::InvalidNumber(4)), "f32(inf)".parse::<Value>());
+    assert_eq!(Err(Error::InvalidNumber(4)), "f32(1e40)".parse::<Value>());
+    assert_eq!(Err(Error::InvalidNumber(4)), "f32(-3.5e38)".parse::<Value>());
+    assert_eq!(Err(Error::InvalidNumber(0)), "1e309".parse::<Value>());
+    assert_eq!(Err(Error::InvalidEscape(1)), r#""\q""#.parse::<Value>());
+    assert_eq!(Err(Error::InvalidEscape(1)), r#""\ud83d""#.parse::<Value>());
+    assert_eq!(Err(Error::InvalidEscape(1)), r#""\xf""#.parse::<Value>());
+    assert_eq!(Err(Error::InvalidBinary(1)), "h'abc'".parse::<Value>());
+    assert_eq!(Err(Error::InvalidBinary(3)), "b64'A'".parse::<Value>());
+}
+
+#[test]
+fn parse_text_depth_limit() {
+    use rmpv::text::Error;
+
+    let text = "[".repeat(512) + &"]".repeat(512);
+    assert!(text.parse::<Value>().is_ok());
+
+    let text = "{\"a\": ".to_string() + &"[".repeat(512) + &"]".repeat(512) + "}";
+    assert_eq!(Err(Error::DepthLimitExceeded(517)), text.parse::<Value>());
+
+    assert_eq!(Err(Error::DepthLimitExceeded(512)), "[".repeat(200000).parse::<Value>());
+}
@@ -0,0 +1,172 @@
+#[macro_use]
+extern crate rmpv;
+extern crate rmp;
+
+use rmp::Marker;
+use rmpv::Value;
+use rmpv::decode::{read_value, read_wire_value};
+use rmpv::decode::value::Error;
+use rmpv::encode::{write_value, write_wire_value};
+use rmpv::wire::{WireKind, WireValue};
+
+fn encode(val: &WireValue) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_wire_value(&mut buf, val).unwrap();
+    buf
+}
+
+fn reencode(buf: &[u8]) -> Vec<u8> {
+    let mut rd = buf;
+    let val = read_wire_value(&mut rd).unwrap();
+    assert!(rd.is_empty());
+    encode(&val)
+}
+
+#[test]
+fn reencode_non_compact_scalars() {
+    let bufs: &[&[u8]] = &[
+        &[0xcc, 0x01],
+        &[0xcd, 0x00, 0x01],
+        &[0xce, 0x00, 0x00, 0x00, 0x01],
+        &[0xcf, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+        &[0xd0, 0x01],
+        &[0xd0, 0xff],
+        &[0xd1, 0xff, 0xff],
+        &[0xd2, 0x00, 0x00, 0x00, 0x01],
+        &[0xd3, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        &[0xca, 0x3f, 0x80, 0x00, 0x00],
+        &[0xcb, 0x7f, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+        &[0xd9, 0x01, 0x61],
+        &[0xda, 0x00, 0x01, 0x61],
+        &[0xda, 0x00, 0x02, 0xc3, 0x28],
+        &[0xdb, 0x00, 0x00, 0x00, 0x00],
+        &[0xc5, 0x00, 0x01, 0x00],
+        &[0xc6, 0x00, 0x00, 0x00, 0x00],
+        &[0xc7, 0x01, 0x05, 0x00],
+        &[0xc8, 0x00, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00],
+        &[0xc9, 0x00, 0x00, 0x00, 0x00, 0x05],
+        &[0xd4, 0x05, 0x00],
+    ];
+
+    for buf in bufs {
+        assert_eq!(buf.to_vec(), reencode(buf), "{:02x?}", buf);
+        assert!(encode(&WireValue::from(read_value(&mut &buf[..]).unwrap())).len() <= buf.len());
+    }
+}
+
+#[test]
+fn reencode_non_compact_containers() {
+    // [1 as uint16, {"a" as str8: []}] with array16, map32 and array32.
+    let buf = [0xdc, 0x00, 0x02,
+               0xcd, 0x00, 0x01,
+               0xdf, 0x00, 0x00, 0x00, 0x01,
+               0xd9, 0x01, 0x61,
+               0xdd, 0x00, 0x00, 0x00, 0x00];
+
+    let val = read_wire_value(&mut &buf[..]).unwrap();
+    assert_eq!(Some(Marker::Array16), val.marker);
+    assert_eq!(msgpack!([1, {"a": []}]), val.to_value());
+    assert_eq!(buf.to_vec(), encode(&val));
+}
+
+#[test]
+fn modified_nodes_keep_width() {
+    // {"n": 1 as uint16, "s": "a" as str16, "l": [] as array16}
+    let buf = [0x83,
+               0xa1, 0x6e, 0xcd, 0x00, 0x01,
+               0xa1, 0x73, 0xda, 0x00, 0x01, 0x61,
+               0xa1, 0x6c, 0xdc, 0x00, 0x00];
+    let mut val = read_wire_value(&mut &buf[..]).unwrap();
+
+    val.get_mut("n").unwrap().kind = WireKind::U64(0x1234);
+    val.get_mut("s").unwrap().kind = WireKind::String("abc".into());
+    if let WireKind::Array(ref mut vals) = val.get_mut("l").unwrap().kind {
+        vals.push(WireValue::from(Value::from(7)));
+    }
+
+    assert_eq!(vec![0x83,
+                    0xa1, 0x6e, 0xcd, 0x12, 0x34,
+                    0xa1, 0x73, 0xda, 0x00, 0x03, 0x61, 0x62, 0x63,
+                    0xa1, 0x6c, 0xdc, 0x00, 0x01, 0x07],
+               encode(&val));
+}
+
+#[test]
+fn modified_nodes_fall_back_to_compact_form() {
+    let mut val = read_wire_value(&mut &[0xcc, 0x01][..]).unwrap();
+    val.kind = WireKind::U64(300);
+    assert_eq!(vec![0xcd, 0x01, 0x2c], encode(&val));
+
+    val.kind = WireKind::I64(-1);
+    assert_eq!(vec![0xff], encode(&val));
+
+    val.kind = WireKind::String("a".into());
+    assert_eq!(vec![0xa1, 0x61], encode(&val));
+
+    let mut val = read_wire_value(&mut &[0xa1, 0x61][..]).unwrap();
+    val.kind = WireKind::String("a".repeat(32).into());
+    assert_eq!(&[0xd9, 0x20], &encode(&val)[..2]);
+
+    let mut val = read_wire_value(&mut &[0xd4, 0x05, 0x00][..]).unwrap();
+    val.kind = WireKind::Ext(5, vec![0, 0]);
+    assert_eq!(vec![0xd5, 0x05, 0x00, 0x00], encode(&val));
+
+    let mut val = read_wire_value(&mut &[0xca, 0x3f, 0x80, 0x00, 0x00][..]).unwrap();
+    val.kind = WireKind::F64(1.0);
+    assert_eq!(vec![0xcb, 0x3f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], encode(&val));
+}
+
+#[test]
+fn integers_keep_width_across_signedness() {
+    let mut val = read_wire_value(&mut &[0xd1, 0xff, 0xfe][..]).unwrap();
+    assert_eq!(WireKind::I64(-2), val.kind);
+
+    val.kind = WireKind::U64(5);
+    assert_eq!(vec![0xd1, 0x00, 0x05], encode(&val));
+
+    val.kind = WireKind::U64(u64::max_value());
+    assert_eq!(vec![0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff], encode(&val));
+
+    let mut val = read_wire_value(&mut &[0xcd, 0x00, 0x01][..]).unwrap();
+    val.kind = WireKind::I64(2);
+    assert_eq!(vec![0xcd, 0x00, 0x02], encode(&val));
+
+    let mut val = read_wire_value(&mut &[0xcb, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0][..]).unwrap();
+    val.kind = WireKind::F32(0.5);
+    assert_eq!(vec![0xcb, 0x3f, 0xe0, 0, 0, 0, 0, 0, 0], encode(&val));
+}
+
+#[test]
+fn new_nodes_are_written_compactly() {
+    let val = msgpack!({"a": [1, -1, 300, "x", 1.5], "b": bin([1, 2])});
+
+    let mut expected = Vec::new();
+    write_value(&mut expected, &val).unwrap();
+
+    let wire = WireValue::from(val.clone());
+    assert_eq!(None, wire.marker);
+    assert_eq!(expected, encode(&wire));
+    assert_eq!(val, Value::from(wire));
+}
+
+#[test]
+fn read_wire_value_matches_read_value() {
+    let buf = [0x92, 0xcd, 0x00, 0x2a, 0x81, 0xa1, 0x61, 0xc3];
+
+    let val = read_wire_value(&mut &buf[..]).unwrap();
+    assert_eq!(read_value(&mut &buf[..]).unwrap(), val.to_value());
+    assert_eq!(Some(Marker::FixArray(2)), val.marker);
+    assert_eq!(None, val.get("a"));
+}
+
+#[test]
+fn read_wire_value_fails() {
+    match read_wire_value(&mut &[0x92, 0x01][..]) {
+        Err(Error::InvalidMarkerRead(..)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match read_wire_value(&mut &[0xc1][..]) {
+        Err(Error::TypeMismatch(Marker::Reserved)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
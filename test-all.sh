@@ -15,3 +15,6 @@ cd rmp-serde-tests && cargo test --features=with-syntex --no-default-features --
 echo "RMP VALUE"
 cd rmpv && cargo test -- -q && cd .. && \
 cd rmpv-tests && cargo test --features=with-serde -- -q && cd ..
+
+echo "RMP CLI"
+cd rmp-cli && cargo test -- -q && cd ..